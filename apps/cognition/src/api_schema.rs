@@ -3,7 +3,7 @@ use bioma_llm::prelude::*;
 use bioma_rag::{
     indexer::{ImagesContent, TextsContent},
     prelude::{GlobsContent, Index, IndexContent, RetrieveContext, RetrieveQuery, TextChunkConfig},
-    retriever::{default_retriever_limit, default_retriever_sources, default_retriever_threshold},
+    retriever::{default_retriever_limit, default_retriever_sources},
 };
 use ollama_rs::{generation::tools::ToolInfo, models::ModelOptions};
 use serde::{Deserialize, Serialize};
@@ -293,10 +293,9 @@ pub struct RetrieveContextRequest {
     #[serde(default = "default_retriever_limit")]
     pub limit: usize,
 
-    /// The threshold for the similarity score
-    #[schema(default = default_retriever_threshold)]
-    #[serde(default = "default_retriever_threshold")]
-    pub threshold: f32,
+    /// The minimum similarity score of the contexts, none are filtered out when unset
+    #[serde(default)]
+    pub threshold: Option<f32>,
 
     /// A list of sources to filter the search
     #[schema(default = default_retriever_sources)]
@@ -322,7 +321,7 @@ impl From<RetrieveContextRequest> for RetrieveContext {
     fn from(request: RetrieveContextRequest) -> Self {
         RetrieveContext::builder()
             .query(RetrieveQuery::Text(request.query))
-            .threshold(request.threshold)
+            .maybe_threshold(request.threshold)
             .limit(request.limit)
             .sources(request.sources)
             .build()
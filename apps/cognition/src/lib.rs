@@ -69,13 +69,17 @@ pub async fn chat_with_tools(
 ) -> Result<(), ChatToolError> {
     // Make chat request with current messages and tools
     let chat_request = ChatMessages {
-        messages: messages.to_owned(),
+        messages: ChatEntry::numbered(messages.to_owned()),
+        next_id: 0,
         restart: true,
         persist: false,
         stream,
         format: format.clone(),
         tools: if tools.is_empty() { None } else { Some(tools.clone()) },
         options: options.clone(),
+        template_vars: Default::default(),
+        summarization: None,
+        generation: None,
     };
 
     info!("chat_with_tools: {} tools, {} messages, actor: {}", tools.len(), messages.len(), chat_actor);
@@ -117,7 +121,7 @@ pub async fn chat_with_tools(
 
                     // Stream the response chunk
                     let response = ChatResponse {
-                        response: message_response,
+                        response: message_response.response,
                         context: if is_first_message { messages.clone() } else { vec![] },
                         ttft_ms,
                     };
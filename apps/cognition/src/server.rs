@@ -94,16 +94,19 @@ impl AppState {
         let ulid = ulid::Ulid::new();
         let actor_id = ActorId::of::<Chat>(format!("{}/{}", prefix, ulid.to_string()));
 
+        let chat = Chat::builder()
+            .model(model)
+            .endpoint(self.config.chat_endpoint.clone())
+            .messages_number_limit(messages_limit)
+            .max_context_length(max_context_length)
+            .build()
+            .map_err(|e| HttpResponse::BadRequest().body(format!("Invalid chat configuration: {}", e)))?;
+
         // Initialize the chat actor
         let (mut ctx, mut actor) = match Actor::spawn(
             self.engine.clone(),
             actor_id.clone(),
-            Chat::builder()
-                .model(model.into())
-                .endpoint(self.config.chat_endpoint.clone())
-                .messages_number_limit(messages_limit)
-                .max_context_length(max_context_length)
-                .build(),
+            chat,
             SpawnOptions::builder().exists(SpawnExistsOptions::Reset).build(),
         )
         .await
@@ -173,6 +176,18 @@ async fn hello() -> impl Responder {
     HttpResponse::Ok().json("Hello world!")
 }
 
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    description = "Actor metrics in the Prometheus text format, empty unless built with the `metrics` feature.",
+    responses(
+        (status = 200, description = "Ok", body = String, content_type = "text/plain"),
+    )
+)]
+async fn metrics() -> impl Responder {
+    HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(bioma_llm::metrics::render())
+}
+
 #[utoipa::path(
     get,
     path = "/reset",
@@ -808,8 +823,14 @@ async fn chat(body: web::Json<ChatQuery>, data: web::Data<AppState>) -> HttpResp
     let retrieve_context = RetrieveContext {
         query: RetrieveQuery::Text(query.clone()),
         limit: data.config.retrieve_limit,
-        threshold: 0.0,
+        threshold: None,
         sources: body.sources.clone(),
+        min_results: None,
+        paginate: false,
+        cursor: None,
+        empty_query: EmptyQueryPolicy::Error,
+        max_per_source: None,
+        merge_adjacent: false,
     };
 
     let context = user_actor
@@ -943,13 +964,17 @@ async fn chat(body: web::Json<ChatQuery>, data: web::Data<AppState>) -> HttpResp
                     let chat_response = user_actor
                         .send_and_wait_reply::<Chat, ChatMessages>(
                             ChatMessages {
-                                messages: conversation.clone(),
+                                messages: ChatEntry::numbered(conversation.clone()),
+                                next_id: 0,
                                 restart: true,
                                 persist: false,
                                 stream: false,
                                 format: body.format.clone(),
                                 tools: Some(client_tools),
                                 options: body.options,
+                                template_vars: Default::default(),
+                                summarization: None,
+                                generation: None,
                             },
                             &chat_id,
                             SendOptions::builder().timeout(std::time::Duration::from_secs(600)).build(),
@@ -966,13 +991,17 @@ async fn chat(body: web::Json<ChatQuery>, data: web::Data<AppState>) -> HttpResp
 
                                 // Add TTFT to the response
                                 let response = ChatResponse {
-                                    response: response.clone(),
+                                    response: response.response.clone(),
                                     context: conversation.clone(),
                                     ttft_ms: Some(ttft.as_millis() as u64),
                                 };
                                 let _ = tx.send(Ok(Json(response))).await;
                             } else {
-                                let response = ChatResponse { response, context: conversation.clone(), ttft_ms: None };
+                                let response = ChatResponse {
+                                    response: response.response,
+                                    context: conversation.clone(),
+                                    ttft_ms: None,
+                                };
                                 let _ = tx.send(Ok(Json(response))).await;
                             }
                             return Ok(());
@@ -1244,8 +1273,14 @@ async fn think(body: web::Json<ThinkQuery>, data: web::Data<AppState>) -> HttpRe
     let retrieve_context = RetrieveContext {
         query: RetrieveQuery::Text(query.clone()),
         limit: data.config.retrieve_limit,
-        threshold: 0.0,
+        threshold: None,
         sources: body.sources.clone(),
+        min_results: None,
+        paginate: false,
+        cursor: None,
+        empty_query: EmptyQueryPolicy::Error,
+        max_per_source: None,
+        merge_adjacent: false,
     };
 
     let mut retrieved = match user_actor
@@ -1437,13 +1472,17 @@ async fn think(body: web::Json<ThinkQuery>, data: web::Data<AppState>) -> HttpRe
     // Spawn task to handle chat processing
     tokio::spawn(async move {
         let chat_request = ChatMessages {
-            messages: conversation.clone(),
+            messages: ChatEntry::numbered(conversation.clone()),
+            next_id: 0,
             restart: true,
             persist: false,
             stream: body.stream,
             format: body.format.clone(),
             tools: None,
             options: body.options.clone(),
+            template_vars: Default::default(),
+            summarization: None,
+            generation: None,
         };
 
         let mut chat_response = match user_actor
@@ -1478,7 +1517,7 @@ async fn think(body: web::Json<ThinkQuery>, data: web::Data<AppState>) -> HttpRe
 
                     // Create response with context only on first message
                     let response = ChatResponse {
-                        response: message_response,
+                        response: message_response.response,
                         context: if is_first_message { conversation.clone() } else { vec![] },
                         ttft_ms,
                     };
@@ -1667,8 +1706,14 @@ async fn ask(body: web::Json<AskQuery>, data: web::Data<AppState>) -> HttpRespon
     let retrieve_context = RetrieveContext {
         query: RetrieveQuery::Text(query.clone()),
         limit: data.config.retrieve_limit,
-        threshold: 0.0,
+        threshold: None,
         sources: body.sources.clone(),
+        min_results: None,
+        paginate: false,
+        cursor: None,
+        empty_query: EmptyQueryPolicy::Error,
+        max_per_source: None,
+        merge_adjacent: false,
     };
 
     let retrieved = user_actor
@@ -1782,13 +1827,17 @@ async fn ask(body: web::Json<AskQuery>, data: web::Data<AppState>) -> HttpRespon
             let ask_response = user_actor
                 .send_and_wait_reply::<Chat, ChatMessages>(
                     ChatMessages {
-                        messages: conversation.clone(),
+                        messages: ChatEntry::numbered(conversation.clone()),
+                        next_id: 0,
                         restart: true,
                         persist: false,
                         stream: false,
                         format: body.format.clone(),
                         tools: None,
                         options: body.options,
+                        template_vars: Default::default(),
+                        summarization: None,
+                        generation: None,
                     },
                     &chat_id,
                     SendOptions::builder().timeout(std::time::Duration::from_secs(600)).build(),
@@ -1798,7 +1847,7 @@ async fn ask(body: web::Json<AskQuery>, data: web::Data<AppState>) -> HttpRespon
             match ask_response {
                 Ok(response) => {
                     info!("Ask response: {:#?}", &response);
-                    HttpResponse::Ok().json(AskResponse { response, context: conversation })
+                    HttpResponse::Ok().json(AskResponse { response: response.response, context: conversation })
                 }
                 Err(e) => {
                     error!("Error fetching ask response: {:?}", e);
@@ -1959,7 +2008,11 @@ async fn embed(body: web::Json<EmbeddingsQuery>, data: web::Data<AppState>) -> H
             for chunk in texts.chunks(CHUNK_SIZE) {
                 match user_actor
                     .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-                        GenerateEmbeddings { content: EmbeddingContent::Text(chunk.to_vec()) },
+                        GenerateEmbeddings {
+                            content: EmbeddingContent::Text(chunk.to_vec()),
+                            input: InputKind::Passage,
+                            normalize: false,
+                        },
                         &data.embeddings,
                         SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
                     )
@@ -1977,7 +2030,7 @@ async fn embed(body: web::Json<EmbeddingsQuery>, data: web::Data<AppState>) -> H
             // Process all images in a single request
             match user_actor
                 .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-                    GenerateEmbeddings { content: embedding_content },
+                    GenerateEmbeddings { content: embedding_content, input: InputKind::Passage, normalize: false },
                     &data.embeddings,
                     SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
                 )
@@ -2188,6 +2241,7 @@ async fn list_sources(data: web::Data<AppState>) -> HttpResponse {
     paths(
         health,
         hello,
+        metrics,
         reset,
         index,
         retrieve,
@@ -2231,7 +2285,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let indexer_id = ActorId::of::<Indexer>("/rag/indexer");
     let mut indexer = Indexer::default();
     indexer.summary = Summary::builder()
-        .chat(Chat::builder().model(config.chat_model.clone()).endpoint(config.chat_endpoint.clone()).build())
+        .chat(Chat::builder().model(config.chat_model.clone()).endpoint(config.chat_endpoint.clone()).build()?)
         .text_prompt(config.summary_text_prompt.clone())
         .build();
 
@@ -2334,6 +2388,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .route("/", web::get().to(dashboard))
             .route("/health", web::get().to(health))
             .route("/hello", web::get().to(hello))
+            .route("/metrics", web::get().to(metrics))
             .route("/reset", web::post().to(reset))
             .route("/index", web::post().to(index))
             .route("/retrieve", web::post().to(retrieve))
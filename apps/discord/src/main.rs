@@ -217,7 +217,7 @@ impl Handler {
             .await?;
 
         // Get the response content
-        let response_message = chat_response.message.content;
+        let response_message = chat_response.response.message.content;
 
         // Convert the response to ShouldRespond
         let should_respond = serde_json::from_str::<ShouldRespond>(&response_message)
@@ -260,7 +260,7 @@ impl Handler {
         // tokio::fs::write(debug_file, chat_response.message.content.clone()).await.unwrap();
 
         // Get the response content
-        let mut response_message = chat_response.message.content;
+        let mut response_message = chat_response.response.message.content;
 
         // Remove <think></think> tags and their content using regex
         let think_tag_regex = Regex::new(r"<think>[\s\S]*?</think>").unwrap();
@@ -407,8 +407,14 @@ impl Handler {
         let retrieve_context = RetrieveContext {
             query: RetrieveQuery::Text(query),
             limit: 5,
-            threshold: 0.0,
+            threshold: None,
             sources: vec!["/bioma".to_string()],
+            min_results: None,
+            paginate: false,
+            cursor: None,
+            empty_query: EmptyQueryPolicy::Error,
+            max_per_source: None,
+            merge_adjacent: false,
         };
 
         let retrieved = author_ctx
@@ -540,7 +546,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .model(config.chat_model.clone())
             .endpoint(config.chat_endpoint.clone())
             // .generation_options(ollama_rs::generation::options::GenerationOptions::default().num_predict(500))
-            .build(),
+            .build()?,
         SpawnOptions::builder().exists(SpawnExistsOptions::Reset).build(),
     )
     .await?;
@@ -1,4 +1,5 @@
 use crate::engine::{Engine, Record};
+use crate::supervisor::SupervisorLink;
 use futures::{future, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -21,7 +22,15 @@ const DB_TABLE_REPLY: &str = "reply";
 const DB_TABLE_HEALTH: &str = "health";
 
 /// Implement this trait to define custom actor error types
-pub trait ActorError: std::error::Error + Debug + Send + Sync + From<SystemActorError> {}
+pub trait ActorError: std::error::Error + Debug + Send + Sync + From<SystemActorError> {
+    /// Whether the error comes from the external service the actor depends on, rather than from the request itself.
+    ///
+    /// A `Supervisor` restarts an actor after too many consecutive backend failures. Errors are not backend failures
+    /// unless the actor says otherwise.
+    fn is_backend_failure(&self) -> bool {
+        false
+    }
+}
 
 /// Enumerates the types of errors that can occur in Actor framework
 #[derive(thiserror::Error, Debug)]
@@ -440,6 +449,9 @@ where
             // Process message and store result
             let result = self.handle(ctx, message).await;
 
+            // Let the supervisor, if any, know how the message went
+            ctx.report_outcome(&result);
+
             // If error, send error to client
             if let Err(e) = &result {
                 ctx.error(e).await?;
@@ -663,6 +675,13 @@ impl Default for SpawnOptions {
     }
 }
 
+impl SpawnOptions {
+    /// The same options, restoring the actor's persisted state if it exists
+    pub(crate) fn restoring(&self) -> Self {
+        Self { exists: SpawnExistsOptions::Restore, health_config: self.health_config.clone() }
+    }
+}
+
 /// Options for handling existing actors during spawn.
 #[derive(Clone)]
 pub enum SpawnExistsOptions {
@@ -837,6 +856,14 @@ pub trait Actor: Sized + Serialize + for<'de> Deserialize<'de> + Debug + Send +
     /// - `Err(Self::Error)` if an error occurs during the actor's execution.
     fn start(&mut self, ctx: &mut ActorContext<Self>) -> impl Future<Output = Result<(), Self::Error>>;
 
+    /// Whether the actor keeps receiving messages while the engine is draining, until shutdown is aborted.
+    ///
+    /// Actors stop receiving once draining starts by default. Actors serving other actors, such as embeddings, keep
+    /// receiving so work already in flight elsewhere can complete.
+    fn serves_while_draining() -> bool {
+        false
+    }
+
     /// Saves the current state of the actor in the system.
     ///
     /// This function updates the actor's state in the database.
@@ -971,6 +998,8 @@ pub struct ActorContext<T: Actor> {
     tx: Option<mpsc::UnboundedSender<Result<Value, Value>>>,
     /// Handle to health update task
     health_task: Option<tokio::task::JoinHandle<()>>,
+    /// Where message outcomes are reported when the actor runs under a `Supervisor`
+    supervisor: Option<SupervisorLink>,
     /// Type marker for the actor
     _marker: std::marker::PhantomData<T>,
 }
@@ -987,7 +1016,19 @@ impl<T: Actor> ActorContext<T> {
     /// Create a new actor context
     fn new(engine: Engine, id: ActorId) -> Self {
         debug!("[{}] ctx-new", id.record_id());
-        Self { engine, id, tx: None, health_task: None, _marker: std::marker::PhantomData }
+        Self { engine, id, tx: None, health_task: None, supervisor: None, _marker: std::marker::PhantomData }
+    }
+
+    /// Reports message outcomes to a supervisor from now on
+    pub(crate) fn set_supervisor(&mut self, link: SupervisorLink) {
+        self.supervisor = Some(link);
+    }
+
+    /// Tells the supervisor whether a message succeeded or failed because of the actor's backend
+    fn report_outcome(&self, result: &Result<(), T::Error>) {
+        if let Some(link) = &self.supervisor {
+            link.report(result);
+        }
     }
 
     async fn unreplied_messages(&self) -> Result<Vec<FrameMessage>, SystemActorError> {
@@ -1194,6 +1235,18 @@ impl<T: Actor> ActorContext<T> {
         let unreplied_stream = futures::stream::iter(unreplied_messages).map(Ok);
         let chained_stream = unreplied_stream.chain(live_query);
 
+        // Once shutting down, end the stream: the message being handled completes, later ones stay unreplied and are
+        // received again when the actor restarts
+        let shutdown = self.engine().shutdown_signal().clone();
+        let serves_while_draining = T::serves_while_draining();
+        let chained_stream = chained_stream.take_until(async move {
+            if serves_while_draining {
+                shutdown.aborted().await
+            } else {
+                shutdown.draining().await
+            }
+        });
+
         Ok(Box::pin(chained_stream))
     }
 
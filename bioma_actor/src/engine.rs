@@ -1,5 +1,6 @@
 use crate::actor::SystemActorError;
 use crate::factory::ActorTagRegistry;
+use crate::shutdown::ShutdownSignal;
 use crate::util::find_project_root;
 use derive_more::Display;
 use object_store::local::LocalFileSystem;
@@ -126,6 +127,7 @@ pub struct Engine {
     db: Arc<Mutex<Surreal<Any>>>,
     options: EngineOptions,
     registry: ActorTagRegistry,
+    shutdown: ShutdownSignal,
 }
 
 impl Engine {
@@ -157,7 +159,12 @@ impl Engine {
         db.signin(Root { username: &options.username, password: &options.password }).await?;
         db.use_ns(options.namespace.clone()).use_db(options.database.clone()).await?;
         Engine::define(&db).await?;
-        Ok(Engine { db: Arc::new(Mutex::new(db)), options: options.clone(), registry: ActorTagRegistry::default() })
+        Ok(Engine {
+            db: Arc::new(Mutex::new(db)),
+            options: options.clone(),
+            registry: ActorTagRegistry::default(),
+            shutdown: ShutdownSignal::default(),
+        })
     }
 
     pub async fn test() -> Result<Engine, SystemActorError> {
@@ -167,7 +174,12 @@ impl Engine {
         db.connect("memory").await?;
         db.use_ns(options.namespace.clone()).use_db(options.database.clone()).await?;
         Engine::define(&db).await?;
-        Ok(Engine { db: Arc::new(Mutex::new(db)), options, registry: ActorTagRegistry::default() })
+        Ok(Engine {
+            db: Arc::new(Mutex::new(db)),
+            options,
+            registry: ActorTagRegistry::default(),
+            shutdown: ShutdownSignal::default(),
+        })
     }
 
     pub async fn reset(&self) -> Result<(), SystemActorError> {
@@ -224,6 +236,11 @@ impl Engine {
     pub fn registry(&self) -> &ActorTagRegistry {
         &self.registry
     }
+
+    /// The signal shared by the actors of this engine, they stop receiving messages once it is draining
+    pub fn shutdown_signal(&self) -> &ShutdownSignal {
+        &self.shutdown
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,67 @@
+use crate::actor::{Actor, ActorContext, ActorId, SendOptions};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Asks an actor whether the service it depends on is usable.
+///
+/// Actors backed by an external service (a model server, a conversion endpoint, the database) handle this
+/// message by probing that service and replying with a `HealthStatus`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck;
+
+/// The outcome of a `HealthCheck`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthStatus {
+    /// Whether the service is usable
+    pub ok: bool,
+    /// How long the probe took
+    pub latency: Duration,
+    /// What was checked and, on failure, what went wrong
+    pub detail: String,
+}
+
+impl HealthStatus {
+    pub fn healthy(latency: Duration, detail: impl Into<String>) -> Self {
+        Self { ok: true, latency, detail: detail.into() }
+    }
+
+    pub fn unhealthy(latency: Duration, detail: impl Into<String>) -> Self {
+        Self { ok: false, latency, detail: detail.into() }
+    }
+}
+
+/// The health of a set of actors, as returned by `check_health`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// The status of each actor, in the order they were given
+    pub statuses: Vec<(ActorId, HealthStatus)>,
+}
+
+impl HealthReport {
+    /// Whether every actor reported a usable service
+    pub fn ok(&self) -> bool {
+        self.statuses.iter().all(|(_, status)| status.ok)
+    }
+
+    /// The actors that did not report a usable service
+    pub fn failures(&self) -> impl Iterator<Item = &(ActorId, HealthStatus)> {
+        self.statuses.iter().filter(|(_, status)| !status.ok)
+    }
+}
+
+/// Sends a `HealthCheck` to every actor concurrently and collects their statuses.
+///
+/// An actor that does not reply within `timeout`, or cannot be reached at all, is reported as unhealthy.
+pub async fn check_health<T: Actor>(ctx: &ActorContext<T>, actors: &[ActorId], timeout: Duration) -> HealthReport {
+    let checks = actors.iter().map(|actor_id| async move {
+        let start = Instant::now();
+        let options = SendOptions::builder().timeout(timeout).build();
+        let reply = ctx.send_as_and_wait_reply::<HealthCheck, HealthStatus>(HealthCheck, actor_id.clone(), options);
+        let status = match reply.await {
+            Ok(status) => status,
+            Err(e) => HealthStatus::unhealthy(start.elapsed(), format!("No health reply from {}: {}", actor_id, e)),
+        };
+        (actor_id.clone(), status)
+    });
+    HealthReport { statuses: futures::future::join_all(checks).await }
+}
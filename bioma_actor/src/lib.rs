@@ -1,6 +1,9 @@
 mod actor;
 mod engine;
 mod factory;
+mod health;
+mod shutdown;
+mod supervisor;
 mod util;
 
 pub use crate::actor::{
@@ -9,6 +12,9 @@ pub use crate::actor::{
 };
 pub use crate::engine::{Engine, EngineOptions, Record};
 pub use crate::factory::{ActorFactory, ActorHandle, ActorTagRegistry};
+pub use crate::health::{check_health, HealthCheck, HealthReport, HealthStatus};
+pub use crate::shutdown::{DrainOutcome, ShutdownCoordinator, ShutdownReport, ShutdownSignal};
+pub use crate::supervisor::{run_supervised, LifecycleEvent, RestartPolicy, Supervised, Supervisor, SupervisorLink};
 pub use crate::util::Relay;
 pub use futures::{Future, StreamExt};
 
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Tells actors and transports that the process is shutting down.
+///
+/// Shutdown happens in two stages: once draining, components stop taking new work and finish what they are doing;
+/// once aborted, the deadline has passed and remaining work should stop right away. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal {
+    drain: CancellationToken,
+    abort: CancellationToken,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts draining
+    pub fn trigger(&self) {
+        self.drain.cancel();
+    }
+
+    /// Stops remaining work, draining too if not already
+    pub fn abort(&self) {
+        self.drain.cancel();
+        self.abort.cancel();
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.drain.is_cancelled()
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.abort.is_cancelled()
+    }
+
+    /// Completes once draining starts
+    pub async fn draining(&self) {
+        self.drain.cancelled().await
+    }
+
+    /// Completes once remaining work must stop
+    pub async fn aborted(&self) {
+        self.abort.cancelled().await
+    }
+
+    /// The token cancelled when draining starts, for components that take a `CancellationToken`
+    pub fn token(&self) -> CancellationToken {
+        self.drain.clone()
+    }
+}
+
+/// How a component ended its shutdown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DrainOutcome {
+    /// The component finished its work and stopped on its own
+    Drained { elapsed: Duration },
+    /// The component was still running at the deadline and was aborted
+    Aborted,
+    /// The component stopped with a panic or was cancelled elsewhere
+    Failed(String),
+}
+
+/// The drain outcome of each component, in the order they were tracked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    pub outcomes: Vec<(String, DrainOutcome)>,
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    /// Whether every component drained before the deadline
+    pub fn clean(&self) -> bool {
+        self.outcomes.iter().all(|(_, outcome)| matches!(outcome, DrainOutcome::Drained { .. }))
+    }
+}
+
+type Completion = Pin<Box<dyn Future<Output = Result<(), JoinError>> + Send>>;
+
+/// Shuts the tracked components down together, within a deadline.
+///
+/// Components are the tasks running actors, servers or transports. They watch the coordinator's `ShutdownSignal`,
+/// the one of the `Engine` for actors, and end their task once drained. Actors serving while draining are stopped
+/// once every component is done, they need not be tracked.
+///
+/// ```rust,ignore
+/// let mut coordinator = ShutdownCoordinator::new(engine.shutdown_signal().clone());
+/// coordinator.track("indexer", indexer_handle);
+/// tokio::signal::ctrl_c().await?;
+/// let report = coordinator.shutdown(Duration::from_secs(30)).await;
+/// ```
+pub struct ShutdownCoordinator {
+    signal: ShutdownSignal,
+    components: Vec<(String, AbortHandle, Completion)>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(signal: ShutdownSignal) -> Self {
+        Self { signal, components: Vec::new() }
+    }
+
+    pub fn signal(&self) -> &ShutdownSignal {
+        &self.signal
+    }
+
+    /// Tracks the task of a component, which must end once the signal is draining
+    pub fn track<T: Send + 'static>(&mut self, name: impl Into<String>, handle: JoinHandle<T>) {
+        let abort = handle.abort_handle();
+        let completion = Box::pin(async move { handle.await.map(|_| ()) });
+        self.components.push((name.into(), abort, completion));
+    }
+
+    /// Drains every component, aborting those still running once `deadline` has passed, then aborts the signal
+    pub async fn shutdown(self, deadline: Duration) -> ShutdownReport {
+        info!("Shutting down {} components, deadline {:?}", self.components.len(), deadline);
+        let start = Instant::now();
+        let deadline = tokio::time::Instant::now() + deadline;
+        self.signal.trigger();
+
+        let signal = self.signal.clone();
+        let drains = self.components.into_iter().map(|(name, abort, completion)| {
+            let signal = signal.clone();
+            async move {
+                let outcome = match tokio::time::timeout_at(deadline, completion).await {
+                    Ok(Ok(())) => DrainOutcome::Drained { elapsed: start.elapsed() },
+                    Ok(Err(e)) => DrainOutcome::Failed(e.to_string()),
+                    Err(_) => {
+                        signal.abort();
+                        abort.abort();
+                        warn!("{} did not drain before the deadline, aborted", name);
+                        DrainOutcome::Aborted
+                    }
+                };
+                (name, outcome)
+            }
+        });
+        let outcomes = futures::future::join_all(drains).await;
+        self.signal.abort();
+
+        let report = ShutdownReport { outcomes, elapsed: start.elapsed() };
+        info!("Shutdown complete in {:?}, clean: {}", report.elapsed, report.clean());
+        report
+    }
+}
+
+impl std::fmt::Debug for ShutdownCoordinator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownCoordinator").field("components", &self.components.len()).finish()
+    }
+}
@@ -0,0 +1,351 @@
+use crate::actor::{Actor, ActorError, ActorId, SpawnOptions, SystemActorError};
+use crate::engine::Engine;
+use crate::factory::ActorHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// How a `Supervisor` decides when to restart an actor, and how often it may do so
+#[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    /// Consecutive backend failures after which the actor is restarted
+    #[builder(default = default_failure_threshold())]
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Delay before the first restart, doubled for each further restart within `restart_window`
+    #[builder(default = default_initial_backoff())]
+    #[serde(default = "default_initial_backoff", with = "humantime_serde")]
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between restarts
+    #[builder(default = default_max_backoff())]
+    #[serde(default = "default_max_backoff", with = "humantime_serde")]
+    pub max_backoff: Duration,
+    /// Restarts allowed within `restart_window` before the supervisor gives up on the actor
+    #[builder(default = default_max_restarts())]
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    /// Period over which restarts are counted
+    #[builder(default = default_restart_window())]
+    #[serde(default = "default_restart_window", with = "humantime_serde")]
+    pub restart_window: Duration,
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_initial_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_max_backoff() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_restart_window() -> Duration {
+    Duration::from_secs(600)
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl RestartPolicy {
+    /// The delay before the given restart, counting from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// What happened to a supervised actor, so callers can pause work that depends on it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LifecycleEvent {
+    /// The actor is being restarted after `backoff`, it does not answer until `Restarted`
+    Restarting { id: ActorId, attempt: u32, backoff: Duration, reason: String },
+    /// The actor is running again
+    Restarted { id: ActorId, attempt: u32 },
+    /// The actor failed again after `restarts` restarts within the window and was stopped
+    GaveUp { id: ActorId, restarts: u32, reason: String },
+}
+
+/// An actor that can run under a `Supervisor`
+pub trait Supervised: Actor + Clone + 'static {
+    /// Builds the actor to run after a restart from its original configuration and the state last persisted.
+    ///
+    /// The configuration is used as is by default, discarding the persisted state.
+    fn restore(config: Self, _persisted: Self) -> Self {
+        config
+    }
+
+    /// Spawns the actor on its own task, which is usually `tokio::spawn(run_supervised(engine, id, self, options,
+    /// link))`
+    fn spawn_supervised(self, engine: Engine, id: ActorId, options: SpawnOptions, link: SupervisorLink) -> ActorHandle;
+}
+
+/// Spawns and runs a supervised actor, reporting to the supervisor when it exits
+pub async fn run_supervised<A: Supervised>(
+    engine: Engine,
+    id: ActorId,
+    config: A,
+    options: SpawnOptions,
+    link: SupervisorLink,
+) -> Result<(), SystemActorError> {
+    let (mut ctx, persisted) = match A::spawn(engine, id.clone(), config.clone(), options).await {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            link.send(Outcome::Exited(e.to_string()));
+            return Ok(());
+        }
+    };
+    ctx.set_supervisor(link.clone());
+
+    let mut actor = A::restore(config, persisted);
+    let reason = match actor.start(&mut ctx).await {
+        Ok(()) => "Actor finished".to_string(),
+        Err(e) => e.to_string(),
+    };
+    error!("{} Supervised actor exited: {}", id, reason);
+    link.send(Outcome::Exited(reason));
+    Ok(())
+}
+
+/// What a supervised actor reports to its supervisor
+#[derive(Debug)]
+enum Outcome {
+    Success,
+    BackendFailure(String),
+    Exited(String),
+}
+
+#[derive(Debug)]
+struct Report {
+    id: ActorId,
+    generation: u64,
+    outcome: Outcome,
+}
+
+/// Connects a running actor to its supervisor
+#[derive(Debug, Clone)]
+pub struct SupervisorLink {
+    id: ActorId,
+    generation: u64,
+    tx: mpsc::UnboundedSender<Report>,
+}
+
+impl SupervisorLink {
+    pub(crate) fn report<E: ActorError>(&self, result: &Result<(), E>) {
+        match result {
+            Ok(()) => self.send(Outcome::Success),
+            Err(e) if e.is_backend_failure() => self.send(Outcome::BackendFailure(e.to_string())),
+            Err(_) => {}
+        }
+    }
+
+    fn send(&self, outcome: Outcome) {
+        let _ = self.tx.send(Report { id: self.id.clone(), generation: self.generation, outcome });
+    }
+}
+
+type StartFn = Box<dyn Fn(Engine, ActorId, SpawnOptions, SupervisorLink) -> ActorHandle + Send + Sync>;
+
+struct Child {
+    start: StartFn,
+    options: SpawnOptions,
+    handle: ActorHandle,
+    /// Incremented on each restart, so reports from a replaced actor are ignored
+    generation: u64,
+    failures: u32,
+    restarting: bool,
+    restarts: VecDeque<Instant>,
+}
+
+struct Shared {
+    engine: Engine,
+    policy: RestartPolicy,
+    children: Mutex<HashMap<ActorId, Child>>,
+    events: broadcast::Sender<LifecycleEvent>,
+    tx: mpsc::UnboundedSender<Report>,
+}
+
+impl Shared {
+    fn link(&self, id: &ActorId, generation: u64) -> SupervisorLink {
+        SupervisorLink { id: id.clone(), generation, tx: self.tx.clone() }
+    }
+
+    fn emit(&self, event: LifecycleEvent) {
+        // No subscribers is fine
+        let _ = self.events.send(event);
+    }
+}
+
+/// Owns a set of actors and restarts them when their backend keeps failing.
+///
+/// Actors report the outcome of every message they handle. After `failure_threshold` consecutive backend failures,
+/// or if the actor exits, it is stopped and spawned again from its original configuration, restoring its persisted
+/// state. Restarts are delayed with exponential backoff, and the supervisor gives up on an actor that needs more than
+/// `max_restarts` restarts within `restart_window`. Dropping the supervisor stops its actors.
+pub struct Supervisor {
+    shared: Arc<Shared>,
+    monitor: JoinHandle<()>,
+}
+
+impl Supervisor {
+    pub fn new(engine: Engine, policy: RestartPolicy) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(64);
+        let shared = Arc::new(Shared { engine, policy, children: Mutex::new(HashMap::new()), events, tx });
+        let monitor = tokio::spawn(monitor(Arc::downgrade(&shared), rx));
+        Self { shared, monitor }
+    }
+
+    /// Spawns an actor under supervision
+    pub fn supervise<A: Supervised>(
+        &self,
+        id: ActorId,
+        actor: A,
+        options: SpawnOptions,
+    ) -> Result<(), SystemActorError> {
+        let mut children = self.shared.children.lock().unwrap();
+        if children.contains_key(&id) {
+            return Err(SystemActorError::ActorAlreadyExists(id));
+        }
+
+        let start: StartFn =
+            Box::new(move |engine, id, options, link| actor.clone().spawn_supervised(engine, id, options, link));
+        let handle = start(self.shared.engine.clone(), id.clone(), options.clone(), self.shared.link(&id, 0));
+        let child =
+            Child { start, options, handle, generation: 0, failures: 0, restarting: false, restarts: VecDeque::new() };
+        children.insert(id, child);
+        Ok(())
+    }
+
+    /// Stops a supervised actor, returning whether it was supervised
+    pub fn stop(&self, id: &ActorId) -> bool {
+        match self.shared.children.lock().unwrap().remove(id) {
+            Some(child) => {
+                child.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the actor is supervised, it is no longer once the supervisor gave up on it
+    pub fn is_supervising(&self, id: &ActorId) -> bool {
+        self.shared.children.lock().unwrap().contains_key(id)
+    }
+
+    /// Receives the lifecycle events of every supervised actor
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.shared.events.subscribe()
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.monitor.abort();
+        for (_, child) in self.shared.children.lock().unwrap().drain() {
+            child.handle.abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Supervisor")
+    }
+}
+
+/// Counts the failures reported by the actors, restarting those that reach the threshold
+async fn monitor(shared: Weak<Shared>, mut rx: mpsc::UnboundedReceiver<Report>) {
+    while let Some(report) = rx.recv().await {
+        let Some(shared) = shared.upgrade() else {
+            break;
+        };
+
+        let reason = {
+            let mut children = shared.children.lock().unwrap();
+            let Some(child) = children.get_mut(&report.id) else {
+                continue;
+            };
+            if child.generation != report.generation || child.restarting {
+                continue;
+            }
+            match report.outcome {
+                Outcome::Success => {
+                    child.failures = 0;
+                    None
+                }
+                Outcome::BackendFailure(reason) => {
+                    child.failures += 1;
+                    let threshold = shared.policy.failure_threshold;
+                    warn!("{} Backend failure {}/{}: {}", report.id, child.failures, threshold, reason);
+                    (child.failures >= threshold).then_some(reason)
+                }
+                Outcome::Exited(reason) => Some(reason),
+            }
+        };
+
+        if let Some(reason) = reason {
+            tokio::spawn(restart(shared, report.id, reason));
+        }
+    }
+}
+
+/// Replaces a failing actor after the backoff delay, or stops it if it was restarted too often
+async fn restart(shared: Arc<Shared>, id: ActorId, reason: String) {
+    let (attempt, backoff) = {
+        let mut children = shared.children.lock().unwrap();
+        let Some(child) = children.get_mut(&id) else {
+            return;
+        };
+        child.handle.abort();
+
+        let now = Instant::now();
+        while child.restarts.front().is_some_and(|at| now.duration_since(*at) > shared.policy.restart_window) {
+            child.restarts.pop_front();
+        }
+        let restarts = child.restarts.len() as u32;
+        if restarts >= shared.policy.max_restarts {
+            children.remove(&id);
+            error!("{} Giving up after {} restarts: {}", id, restarts, reason);
+            shared.emit(LifecycleEvent::GaveUp { id, restarts, reason });
+            return;
+        }
+
+        child.restarts.push_back(now);
+        child.restarting = true;
+        child.generation += 1;
+        let attempt = restarts + 1;
+        (attempt, shared.policy.backoff(attempt))
+    };
+
+    warn!("{} Restarting in {:?} (attempt {}): {}", id, backoff, attempt, reason);
+    shared.emit(LifecycleEvent::Restarting { id: id.clone(), attempt, backoff, reason });
+    tokio::time::sleep(backoff).await;
+
+    {
+        let mut children = shared.children.lock().unwrap();
+        let Some(child) = children.get_mut(&id) else {
+            return;
+        };
+        let link = shared.link(&id, child.generation);
+        child.handle = (child.start)(shared.engine.clone(), id.clone(), child.options.restoring(), link);
+        child.failures = 0;
+        child.restarting = false;
+    }
+
+    info!("{} Restarted (attempt {})", id, attempt);
+    shared.emit(LifecycleEvent::Restarted { id, attempt });
+}
@@ -0,0 +1,193 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 4096;
+
+/// Runs a subprocess, then succeeds if it exits with code 0 and fails otherwise.
+///
+/// The `ExecCommand` action spawns `program` with `args` passed as discrete arguments, so they are never
+/// interpreted by a shell. Setting `shell` runs `program` as a `sh -c` script instead, with `args` available
+/// as the positional parameters `$1`, `$2`, and so on. The exit code and the captured stdout and stderr are
+/// logged. Only the first `max_output_bytes` of each are kept, the rest is read as it comes and discarded.
+/// The process is killed if it outlives `timeout` or if the node is dropped while waiting for it.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct ExecCommand {
+    pub program: String,
+    #[serde(default)]
+    #[builder(default)]
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    #[serde(default)]
+    #[builder(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    #[builder(default)]
+    pub shell: bool,
+    #[serde(default = "default_max_output_bytes")]
+    #[builder(default = default_max_output_bytes())]
+    pub max_output_bytes: usize,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Action,
+}
+
+fn default_max_output_bytes() -> usize {
+    DEFAULT_MAX_OUTPUT_BYTES
+}
+
+impl ExecCommand {
+    fn command(&self) -> Command {
+        let mut command = if self.shell {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.program).arg("sh");
+            command
+        } else {
+            Command::new(&self.program)
+        };
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(working_dir) = &self.working_dir {
+            command.current_dir(working_dir);
+        }
+        command
+    }
+}
+
+/// Reads a pipe to its end, keeping up to `max_bytes` of it. Reading on past the cap keeps the process from
+/// blocking on a full pipe.
+async fn read_capped(pipe: Option<impl AsyncRead + Unpin>, max_bytes: usize) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let Some(mut pipe) = pipe else {
+        return Ok(output);
+    };
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = pipe.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(output);
+        }
+        let kept = n.min(max_bytes - output.len());
+        output.extend_from_slice(&buf[..kept]);
+    }
+}
+
+impl Behavior for ExecCommand {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Action(&self.node)
+    }
+}
+
+pub struct ExecCommandFactory;
+
+impl ActorFactory for ExecCommandFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let engine = engine.clone();
+        let node: tree::ActionNode = serde_json::from_value(config.clone()).unwrap();
+        let config: ExecCommand = serde_json::from_value(node.data.config.clone())?;
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("ExecCommandFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("ExecCommandFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for ExecCommand {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let mut child = match self.command().spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("ExecCommand {} failed to spawn {}: {}", ctx.id(), self.program, e);
+                ctx.reply(BehaviorStatus::Failure).await?;
+                return Ok(());
+            }
+        };
+
+        // Dropping the output future on timeout drops the child, which kills the process
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        let max_output_bytes = self.max_output_bytes;
+        let output = async move {
+            let (status, stdout, stderr) = tokio::try_join!(
+                child.wait(),
+                read_capped(stdout, max_output_bytes),
+                read_capped(stderr, max_output_bytes)
+            )?;
+            Ok::<_, std::io::Error>((status, stdout, stderr))
+        };
+        let output = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, output).await {
+                Ok(output) => output,
+                Err(_) => {
+                    warn!("ExecCommand {} killed {} after {:?}", ctx.id(), self.program, timeout);
+                    ctx.reply(BehaviorStatus::Failure).await?;
+                    return Ok(());
+                }
+            },
+            None => output.await,
+        };
+
+        let status = match output {
+            Ok((status, stdout, stderr)) => {
+                info!(
+                    "ExecCommand {} exited with {:?}, stdout: {:?}, stderr: {:?}",
+                    ctx.id(),
+                    status.code(),
+                    String::from_utf8_lossy(&stdout),
+                    String::from_utf8_lossy(&stderr)
+                );
+                if status.success() {
+                    BehaviorStatus::Success
+                } else {
+                    BehaviorStatus::Failure
+                }
+            }
+            Err(e) => {
+                warn!("ExecCommand {} failed to wait for {}: {}", ctx.id(), self.program, e);
+                BehaviorStatus::Failure
+            }
+        };
+
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
+impl Actor for ExecCommand {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
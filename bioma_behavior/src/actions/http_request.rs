@@ -0,0 +1,196 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use url::Url;
+
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Performs an HTTP request, then succeeds if the response has an expected status and fails otherwise.
+///
+/// The `HttpRequest` action sends `body`, if any, as JSON along with `headers`. Unless `expected_status` lists
+/// the accepted codes, any 2xx status succeeds. Transport errors, including the request outliving `timeout`,
+/// fail the action. The status, headers and JSON body of the response are logged. Redirects are followed up to
+/// `max_redirects` times, and `accept_invalid_certs` disables TLS certificate validation. A single client is
+/// shared by all the requests of the node.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct HttpRequest {
+    #[serde(default)]
+    #[builder(default)]
+    pub method: HttpMethod,
+    pub url: Url,
+    #[serde(default)]
+    #[builder(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<serde_json::Value>,
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    #[builder(default)]
+    pub expected_status: Vec<u16>,
+    #[serde(default = "default_max_redirects")]
+    #[builder(default = default_max_redirects())]
+    pub max_redirects: usize,
+    #[serde(default)]
+    #[builder(default)]
+    pub accept_invalid_certs: bool,
+    #[serde(skip)]
+    #[builder(skip)]
+    client: OnceLock<reqwest::Client>,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Action,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Head,
+}
+
+impl From<HttpMethod> for reqwest::Method {
+    fn from(method: HttpMethod) -> Self {
+        match method {
+            HttpMethod::Get => reqwest::Method::GET,
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Patch => reqwest::Method::PATCH,
+            HttpMethod::Delete => reqwest::Method::DELETE,
+            HttpMethod::Head => reqwest::Method::HEAD,
+        }
+    }
+}
+
+fn default_max_redirects() -> usize {
+    DEFAULT_MAX_REDIRECTS
+}
+
+impl HttpRequest {
+    fn client(&self) -> Result<&reqwest::Client, reqwest::Error> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let redirect = match self.max_redirects {
+            0 => reqwest::redirect::Policy::none(),
+            max_redirects => reqwest::redirect::Policy::limited(max_redirects),
+        };
+        let client = reqwest::Client::builder()
+            .redirect(redirect)
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .build()?;
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    async fn send(&self) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self.client()?.request(self.method.into(), self.url.clone());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &self.body {
+            request = request.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.to_string());
+        }
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        request.send().await
+    }
+
+    fn is_expected(&self, status: reqwest::StatusCode) -> bool {
+        if self.expected_status.is_empty() {
+            status.is_success()
+        } else {
+            self.expected_status.contains(&status.as_u16())
+        }
+    }
+}
+
+impl Behavior for HttpRequest {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Action(&self.node)
+    }
+}
+
+pub struct HttpRequestFactory;
+
+impl ActorFactory for HttpRequestFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let engine = engine.clone();
+        let node: tree::ActionNode = serde_json::from_value(config.clone()).unwrap();
+        let config: HttpRequest = serde_json::from_value(node.data.config.clone())?;
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("HttpRequestFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("HttpRequestFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for HttpRequest {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let response = match self.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("HttpRequest {} {:?} {} failed: {}", ctx.id(), self.method, self.url, e);
+                ctx.reply(BehaviorStatus::Failure).await?;
+                return Ok(());
+            }
+        };
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = match response.bytes().await {
+            Ok(bytes) => serde_json::from_slice::<serde_json::Value>(&bytes).ok(),
+            Err(e) => {
+                warn!("HttpRequest {} failed to read the response body: {}", ctx.id(), e);
+                None
+            }
+        };
+        info!(
+            "HttpRequest {} {:?} {} returned {}, headers: {:?}, body: {:?}",
+            ctx.id(),
+            self.method,
+            self.url,
+            status,
+            headers,
+            body
+        );
+
+        let status = if self.is_expected(status) { BehaviorStatus::Success } else { BehaviorStatus::Failure };
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
+impl Actor for HttpRequest {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
+mod exec_command;
+mod http_request;
 pub mod log;
 mod wait;
 
+pub use exec_command::{ExecCommand, ExecCommandFactory};
+pub use http_request::{HttpMethod, HttpRequest, HttpRequestFactory};
 pub use log::{Log, LogFactory};
 pub use wait::{Wait, WaitFactory};
@@ -39,6 +39,19 @@ pub struct BehaviorTick;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BehaviorCancel;
 
+/// Ticks a child node and waits for its status.
+///
+/// Within a tree, the tick counts against the tree's `max_ticks`. Once they are exceeded the child is not ticked and
+/// fails, while the tree aborts the run.
+pub async fn tick<T: Actor>(ctx: &ActorContext<T>, child: ActorId) -> Result<BehaviorStatus, SystemActorError> {
+    if let Some(run) = tree::TreeRun::of(ctx.id()) {
+        if !run.tick() {
+            return Ok(BehaviorStatus::Failure);
+        }
+    }
+    ctx.send_as_and_wait_reply(BehaviorTick, child, SendOptions::default()).await
+}
+
 /// Represents the final status of a behavior after execution.
 ///
 /// Due to the asynchronous nature of behavior execution, behaviors that haven't
@@ -164,6 +177,9 @@ impl Decorator {
                 let child_tag = child_data.data().tag.clone();
                 let child_config = child_data.value();
                 let child_handle = registry.spawn(child_tag, engine, child_config, child_id.clone(), options).await?;
+                if let Some(run) = tree::TreeRun::of(&child_id) {
+                    run.abort_on_finish(&child_handle);
+                }
                 self.child_handle = Some(child_handle);
                 Ok(Some(child_id))
             } else {
@@ -185,6 +201,17 @@ impl Decorator {
         self.child_handle = None;
         self.child = None;
     }
+
+    /// Aborts the child of this decorator node.
+    ///
+    /// Unlike `child_stop`, this method does not rely on the runtime to stop the child once its handle is
+    /// released: the child's task is aborted right away, cancelling any work it is still doing.
+    pub fn child_abort(&mut self) {
+        if let Some(child_handle) = self.child_handle.take() {
+            child_handle.abort();
+        }
+        self.child = None;
+    }
 }
 
 /// Represents a Composite node in a behavior tree.
@@ -253,6 +280,9 @@ impl Composite {
                     let child_handle = registry
                         .spawn(child_tag, engine.clone(), child_config, child_id.clone(), options.clone())
                         .await?;
+                    if let Some(run) = tree::TreeRun::of(&child_id) {
+                        run.abort_on_finish(&child_handle);
+                    }
                     self.children_handles.push(child_handle);
                     result.push(child_id);
                 }
@@ -53,10 +53,7 @@ impl Message<BehaviorTick> for Any {
         let children = self.node.children(ctx, SpawnOptions::default()).await?;
 
         // Create a future for each child and pin it
-        let futures = children
-            .iter()
-            .map(|child| Box::pin(ctx.send_as_and_wait_reply(BehaviorTick, child.clone(), SendOptions::default())))
-            .collect::<Vec<_>>();
+        let futures = children.iter().map(|child| Box::pin(behavior::tick(ctx, child.clone()))).collect::<Vec<_>>();
 
         // Use futures::future::select_all to run all futures concurrently
         let mut remaining_futures = futures;
@@ -2,20 +2,29 @@ use crate::prelude::*;
 use bioma_actor::prelude::*;
 use bon::Builder;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, info};
 
 /// Executes child nodes sequentially until one succeeds or all fail.
 ///
 /// The `Fallback` composite node processes its children one by one in order. It returns success as soon as one
 /// child node succeeds. If a child fails, it proceeds to the next one. If all children fail,
 /// then the `Fallback` node fails.
+///
+/// With no children the `Fallback` node returns `empty_status`, which defaults to failure.
 #[derive(Builder, Debug, Serialize, Deserialize)]
 pub struct Fallback {
+    #[serde(default = "default_empty_status")]
+    #[builder(default = default_empty_status())]
+    pub empty_status: BehaviorStatus,
     #[serde(skip)]
     #[builder(skip)]
     pub node: behavior::Composite,
 }
 
+fn default_empty_status() -> BehaviorStatus {
+    BehaviorStatus::Failure
+}
+
 impl Behavior for Fallback {
     fn node(&self) -> behavior::Node {
         behavior::Node::Composite(&self.node)
@@ -50,9 +59,16 @@ impl Message<BehaviorTick> for Fallback {
     type Response = BehaviorStatus;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let children = self.node.children(ctx, SpawnOptions::default()).await?;
+        if children.is_empty() {
+            info!("Fallback {} has no children, returning {:?}", ctx.id(), self.empty_status);
+            ctx.reply(self.empty_status.clone()).await?;
+            return Ok(());
+        }
+
         // Iterate over all children until one succeeds
-        for child in self.node.children(ctx, SpawnOptions::default()).await? {
-            let status = ctx.send_as_and_wait_reply(BehaviorTick, child, SendOptions::default()).await;
+        for child in children {
+            let status = behavior::tick(ctx, child).await;
             match status {
                 Ok(BehaviorStatus::Success) => {
                     ctx.reply(BehaviorStatus::Success).await?;
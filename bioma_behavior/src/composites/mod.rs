@@ -1,9 +1,13 @@
 mod all;
 mod any;
 mod fallback;
+mod random_selector;
 mod sequence;
+mod weighted_selector;
 
 pub use all::{All, AllFactory};
 pub use any::{Any, AnyFactory};
 pub use fallback::{Fallback, FallbackFactory};
+pub use random_selector::{RandomSelector, RandomSelectorFactory};
 pub use sequence::{Sequence, SequenceFactory};
+pub use weighted_selector::{WeightedSelector, WeightedSelectorFactory};
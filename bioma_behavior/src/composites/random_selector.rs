@@ -0,0 +1,100 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// Executes child nodes in a random order until one succeeds or all fail.
+///
+/// The `RandomSelector` composite node shuffles its children on every tick and processes them in that order. It
+/// returns success as soon as one child node succeeds. If a child fails, it proceeds to the next untried one. If all
+/// children fail, then the `RandomSelector` node fails. When a `seed` is configured the order of picks is
+/// reproducible across runs.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct RandomSelector {
+    pub seed: Option<u64>,
+    #[serde(skip)]
+    #[builder(skip)]
+    rng: Option<StdRng>,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Composite,
+}
+
+impl Behavior for RandomSelector {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Composite(&self.node)
+    }
+}
+
+pub struct RandomSelectorFactory;
+
+impl ActorFactory for RandomSelectorFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let engine = engine.clone();
+        let node: tree::CompositeNode = serde_json::from_value(config.clone()).unwrap();
+        let mut config: RandomSelector = serde_json::from_value(node.data.config.clone())?;
+        config.node.copy_children(&node);
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("RandomSelectorFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("RandomSelectorFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for RandomSelector {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let children = self.node.children(ctx, SpawnOptions::default()).await?;
+
+        // The generator lives across ticks so a seeded sequence of picks stays reproducible
+        let seed = self.seed;
+        let rng = self.rng.get_or_insert_with(|| match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        });
+        let mut order: Vec<usize> = (0..children.len()).collect();
+        order.shuffle(rng);
+
+        // Iterate over the shuffled children until one succeeds
+        for idx in order {
+            info!("RandomSelector {} picked child {}", ctx.id(), idx);
+            let status = behavior::tick(ctx, children[idx].clone()).await;
+            match status {
+                Ok(BehaviorStatus::Success) => {
+                    ctx.reply(BehaviorStatus::Success).await?;
+                    return Ok(());
+                }
+                Ok(BehaviorStatus::Failure) => continue,
+                Err(_e) => continue,
+            }
+        }
+        ctx.reply(BehaviorStatus::Failure).await?;
+        Ok(())
+    }
+}
+
+impl Actor for RandomSelector {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
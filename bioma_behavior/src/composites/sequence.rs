@@ -2,20 +2,29 @@ use crate::prelude::*;
 use bioma_actor::prelude::*;
 use bon::Builder;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, info};
 
 /// Executes child nodes sequentially until one fails or all succeed.
 ///
 /// The `Sequence` composite node processes its children one by one in order. It returns success only if
 /// all child nodes succeed. If a child fails, the `Sequence` node immediately fails. If a child
 /// returns running, the `Sequence` node also returns running.
+///
+/// With no children the `Sequence` node returns `empty_status`, which defaults to success.
 #[derive(Builder, Debug, Serialize, Deserialize)]
 pub struct Sequence {
+    #[serde(default = "default_empty_status")]
+    #[builder(default = default_empty_status())]
+    pub empty_status: BehaviorStatus,
     #[serde(skip)]
     #[builder(skip)]
     pub node: behavior::Composite,
 }
 
+fn default_empty_status() -> BehaviorStatus {
+    BehaviorStatus::Success
+}
+
 impl Behavior for Sequence {
     fn node(&self) -> behavior::Node {
         behavior::Node::Composite(&self.node)
@@ -50,9 +59,16 @@ impl Message<BehaviorTick> for Sequence {
     type Response = BehaviorStatus;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let children = self.node.children(ctx, SpawnOptions::default()).await?;
+        if children.is_empty() {
+            info!("Sequence {} has no children, returning {:?}", ctx.id(), self.empty_status);
+            ctx.reply(self.empty_status.clone()).await?;
+            return Ok(());
+        }
+
         // Iterate over all children until one fails
-        for child in self.node.children(ctx, SpawnOptions::default()).await? {
-            let status = ctx.send_as_and_wait_reply(BehaviorTick, child, SendOptions::default()).await;
+        for child in children {
+            let status = behavior::tick(ctx, child).await;
             match status {
                 Ok(BehaviorStatus::Success) => continue,
                 Ok(BehaviorStatus::Failure) => {
@@ -0,0 +1,122 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+use serde::{de::Error as _, Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// Executes child nodes drawn at random, proportionally to their weights, until one succeeds or all fail.
+///
+/// The `WeightedSelector` composite node draws a child according to `weights`, which are given in the same order
+/// as the children. It returns success as soon as one child node succeeds. If a child fails, its weight is excluded
+/// and another child is drawn among the remaining ones. If all children fail, then the `WeightedSelector` node fails.
+/// When a `seed` is configured the sequence of draws is reproducible across runs.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct WeightedSelector {
+    pub weights: Vec<f64>,
+    pub seed: Option<u64>,
+    #[serde(skip)]
+    #[builder(skip)]
+    rng: Option<StdRng>,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Composite,
+}
+
+impl Behavior for WeightedSelector {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Composite(&self.node)
+    }
+}
+
+pub struct WeightedSelectorFactory;
+
+impl ActorFactory for WeightedSelectorFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let engine = engine.clone();
+        let node: tree::CompositeNode = serde_json::from_value(config.clone()).unwrap();
+        let mut config: WeightedSelector = serde_json::from_value(node.data.config.clone())?;
+
+        // Validate the weights against the children before spawning
+        if node.children.is_empty() {
+            return Err(serde_json::Error::custom("WeightedSelector requires at least one child").into());
+        }
+        if config.weights.len() != node.children.len() {
+            return Err(serde_json::Error::custom(format!(
+                "WeightedSelector has {} weights for {} children",
+                config.weights.len(),
+                node.children.len()
+            ))
+            .into());
+        }
+        if let Some(weight) = config.weights.iter().find(|weight| !(weight.is_finite() && **weight > 0.0)) {
+            return Err(serde_json::Error::custom(format!("WeightedSelector weight {} is not positive", weight)).into());
+        }
+
+        config.node.copy_children(&node);
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("WeightedSelectorFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("WeightedSelectorFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for WeightedSelector {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let children = self.node.children(ctx, SpawnOptions::default()).await?;
+
+        // The generator lives across ticks so a seeded sequence of draws stays reproducible
+        let seed = self.seed;
+        let rng = self.rng.get_or_insert_with(|| match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        });
+
+        // Draw among the children not yet tried in this activation until one succeeds
+        let mut remaining: Vec<usize> = (0..children.len()).collect();
+        while !remaining.is_empty() {
+            let Ok(distribution) = WeightedIndex::new(remaining.iter().map(|&idx| self.weights[idx])) else {
+                break;
+            };
+            let idx = remaining.remove(distribution.sample(rng));
+            info!("WeightedSelector {} picked child {}", ctx.id(), idx);
+
+            let status = behavior::tick(ctx, children[idx].clone()).await;
+            match status {
+                Ok(BehaviorStatus::Success) => {
+                    ctx.reply(BehaviorStatus::Success).await?;
+                    return Ok(());
+                }
+                Ok(BehaviorStatus::Failure) => continue,
+                Err(_e) => continue,
+            }
+        }
+        ctx.reply(BehaviorStatus::Failure).await?;
+        Ok(())
+    }
+}
+
+impl Actor for WeightedSelector {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -54,8 +54,7 @@ impl Message<BehaviorTick> for Always {
             return Ok(());
         };
         // Execute the child node but ignore its result
-        let _status: Result<BehaviorStatus, SystemActorError> =
-            ctx.send_as_and_wait_reply(BehaviorTick, child.clone(), SendOptions::default()).await;
+        let _status = behavior::tick(ctx, child.clone()).await;
         // Return the configured status
         ctx.reply(self.get_configured_status()).await?;
         Ok(())
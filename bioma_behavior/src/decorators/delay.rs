@@ -58,10 +58,7 @@ impl Message<BehaviorTick> for Delay {
             return Ok(());
         };
 
-        match ctx
-            .send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(BehaviorTick, child.clone(), SendOptions::default())
-            .await
-        {
+        match behavior::tick(ctx, child.clone()).await {
             Ok(status) => ctx.reply(status).await?,
             Err(_) => ctx.reply(BehaviorStatus::Failure).await?,
         }
@@ -0,0 +1,95 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Ticks its child node without waiting for it, then succeeds.
+///
+/// The `Detach` decorator node is meant for fire-and-forget actions: the child is ticked and left running in the
+/// background, while the decorator returns success right away. Later ticks tick the same child again. The child is
+/// aborted when the `Detach` node itself is stopped, so it does not outlive the tree it belongs to.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct Detach {
+    #[serde(skip)]
+    #[builder(skip)]
+    detached: Option<ActorId>,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Decorator,
+}
+
+impl Behavior for Detach {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Decorator(&self.node)
+    }
+}
+
+impl Drop for Detach {
+    fn drop(&mut self) {
+        self.node.child_abort();
+    }
+}
+
+pub struct DetachFactory;
+
+impl ActorFactory for DetachFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let engine = engine.clone();
+        let node: crate::tree::DecoratorNode = serde_json::from_value(config.clone()).unwrap();
+        let mut config: Detach = serde_json::from_value(node.data.config.clone())?;
+        config.node.copy_child(&node);
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("DetachFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("DetachFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for Detach {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let child = match self.detached.clone() {
+            Some(child) => child,
+            None => {
+                let Some(child) = self.node.child(ctx, SpawnOptions::default()).await? else {
+                    ctx.reply(BehaviorStatus::Success).await?;
+                    return Ok(());
+                };
+                self.detached = Some(child.clone());
+                child
+            }
+        };
+
+        // Tick the child without waiting for its status
+        ctx.do_send_as(BehaviorTick, &child).await?;
+        debug!("Detach {} detached {}", ctx.id(), child);
+
+        ctx.reply(BehaviorStatus::Success).await?;
+        Ok(())
+    }
+}
+
+impl Actor for Detach {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -54,10 +54,7 @@ impl Message<BehaviorTick> for Invert {
         };
 
         // Execute the child node and invert its result
-        let status = match ctx
-            .send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(BehaviorTick, child.clone(), SendOptions::default())
-            .await
-        {
+        let status = match behavior::tick(ctx, child.clone()).await {
             Ok(BehaviorStatus::Success) => BehaviorStatus::Failure,
             Ok(BehaviorStatus::Failure) => BehaviorStatus::Success,
             Err(_) => BehaviorStatus::Failure,
@@ -0,0 +1,89 @@
+use crate::prelude::*;
+use bioma_actor::prelude::*;
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Re-runs its child node until the child fails.
+///
+/// The `KeepRunningUntilFailure` decorator node ticks its child again every time it succeeds. When the child fails it
+/// returns success, or failure if `propagate_failure` is set. A child that never fails loops forever, unless the
+/// tree running it sets `max_ticks`.
+#[derive(Builder, Debug, Serialize, Deserialize)]
+pub struct KeepRunningUntilFailure {
+    #[serde(default)]
+    #[builder(default)]
+    pub propagate_failure: bool,
+    #[serde(skip)]
+    #[builder(skip)]
+    pub node: behavior::Decorator,
+}
+
+impl Behavior for KeepRunningUntilFailure {
+    fn node(&self) -> behavior::Node {
+        behavior::Node::Decorator(&self.node)
+    }
+}
+
+pub struct KeepRunningUntilFailureFactory;
+
+impl ActorFactory for KeepRunningUntilFailureFactory {
+    fn spawn(
+        &self,
+        engine: Engine,
+        config: serde_json::Value,
+        id: ActorId,
+        options: SpawnOptions,
+    ) -> Result<ActorHandle, SystemActorError> {
+        let node: crate::tree::DecoratorNode = serde_json::from_value(config.clone()).unwrap();
+        let mut config: KeepRunningUntilFailure = serde_json::from_value(node.data.config.clone())?;
+        config.node.copy_child(&node);
+        Ok(tokio::spawn(async move {
+            let (mut ctx, mut actor) = Actor::spawn(engine, id, config, options).await?;
+            debug!("KeepRunningUntilFailureFactory::spawn: start {}", ctx.id());
+            actor.start(&mut ctx).await?;
+            debug!("KeepRunningUntilFailureFactory::spawn: end {}", ctx.id());
+            Ok(())
+        }))
+    }
+}
+
+impl Message<BehaviorTick> for KeepRunningUntilFailure {
+    type Response = BehaviorStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &BehaviorTick) -> Result<(), Self::Error> {
+        let Some(child) = self.node.child(ctx, SpawnOptions::default()).await? else {
+            ctx.reply(BehaviorStatus::Failure).await?;
+            return Ok(());
+        };
+
+        let mut iterations = 0;
+        loop {
+            iterations += 1;
+            let status = behavior::tick(ctx, child.clone()).await;
+            match status {
+                Ok(BehaviorStatus::Success) => continue,
+                Ok(BehaviorStatus::Failure) | Err(_) => break,
+            }
+        }
+
+        debug!("KeepRunningUntilFailure {} child failed after {} iterations", ctx.id(), iterations);
+        let status = if self.propagate_failure { BehaviorStatus::Failure } else { BehaviorStatus::Success };
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
+impl Actor for KeepRunningUntilFailure {
+    type Error = SystemActorError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(BehaviorTick) = frame.is::<BehaviorTick>() {
+                self.reply(ctx, &BehaviorTick, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
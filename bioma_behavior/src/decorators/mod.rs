@@ -1,9 +1,13 @@
 mod always;
 mod delay;
+mod detach;
 mod invert;
+mod keep_running_until_failure;
 mod timeout;
 
 pub use always::{Always, AlwaysFactory};
 pub use delay::{Delay, DelayFactory};
+pub use detach::{Detach, DetachFactory};
 pub use invert::{Invert, InvertFactory};
+pub use keep_running_until_failure::{KeepRunningUntilFailure, KeepRunningUntilFailureFactory};
 pub use timeout::{Timeout, TimeoutFactory};
@@ -58,16 +58,7 @@ impl Message<BehaviorTick> for Timeout {
             return Ok(());
         };
 
-        let status = match timeout(
-            self.duration,
-            ctx.send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(
-                BehaviorTick,
-                child.clone(),
-                SendOptions::default(),
-            ),
-        )
-        .await
-        {
+        let status = match timeout(self.duration, behavior::tick(ctx, child.clone())).await {
             Ok(Ok(status)) => status,
             Ok(Err(_)) => BehaviorStatus::Failure,
             Err(_) => BehaviorStatus::Failure,
@@ -6,6 +6,10 @@ use bioma_actor::{ActorError, SystemActorError};
 pub enum BehaviorError {
     #[error("System error: {0}")]
     System(#[from] SystemActorError),
+    #[error("Deadline of {0:?} exceeded")]
+    DeadlineExceeded(std::time::Duration),
+    #[error("Max ticks of {0} exceeded")]
+    MaxTicksExceeded(u64),
 }
 
 impl ActorError for BehaviorError {}
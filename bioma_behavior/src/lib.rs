@@ -20,16 +20,22 @@ pub async fn register_behaviors(registry: &bioma_actor::ActorTagRegistry) -> Res
     use crate::behavior::Behavior;
 
     // Actions
+    registry.add(actions::ExecCommand::tag(), actions::ExecCommandFactory).await?;
+    registry.add(actions::HttpRequest::tag(), actions::HttpRequestFactory).await?;
     registry.add(actions::Wait::tag(), actions::WaitFactory).await?;
     registry.add(actions::Log::tag(), actions::LogFactory).await?;
 
     // Decorators
     registry.add(decorators::Delay::tag(), decorators::DelayFactory).await?;
+    registry.add(decorators::Detach::tag(), decorators::DetachFactory).await?;
+    registry.add(decorators::KeepRunningUntilFailure::tag(), decorators::KeepRunningUntilFailureFactory).await?;
 
     // Composites
     registry.add(composites::All::tag(), composites::AllFactory).await?;
     registry.add(composites::Any::tag(), composites::AnyFactory).await?;
     registry.add(composites::Fallback::tag(), composites::FallbackFactory).await?;
+    registry.add(composites::RandomSelector::tag(), composites::RandomSelectorFactory).await?;
     registry.add(composites::Sequence::tag(), composites::SequenceFactory).await?;
+    registry.add(composites::WeightedSelector::tag(), composites::WeightedSelectorFactory).await?;
     Ok(())
 }
@@ -1,10 +1,20 @@
 use crate::behavior::{self, Behavior, BehaviorTick};
 use crate::error::BehaviorError;
 use bioma_actor::prelude::*;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::oneshot;
-use tracing::debug;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+lazy_static! {
+    static ref RUNS: Mutex<HashMap<String, Arc<TreeRun>>> = Mutex::new(HashMap::new());
+}
 
 /// Behavior tree node type designed to be ergonomic and easy to view and edit in json.
 /// Any weirdness is due to the need to serialize/deserialize the node type as part of the node definition.
@@ -169,10 +179,78 @@ impl Node {
     }
 }
 
+/// State shared by the nodes of a running tree.
+///
+/// Nodes find the run of their tree from their id, which is prefixed by the id of the tree.
+#[derive(Debug)]
+pub struct TreeRun {
+    max_ticks: Option<u64>,
+    ticks: AtomicU64,
+    /// Cancelled when the run ends, aborting the nodes still running
+    cancel: CancellationToken,
+}
+
+impl TreeRun {
+    /// Registers the run of the tree `tree_id`, replacing a previous run of the same tree.
+    fn start(tree_id: &ActorId, max_ticks: Option<u64>) -> Arc<Self> {
+        let run = Arc::new(Self { max_ticks, ticks: AtomicU64::new(0), cancel: CancellationToken::new() });
+        RUNS.lock().unwrap().insert(tree_id.name().to_string(), run.clone());
+        run
+    }
+
+    /// Unregisters the run of the tree `tree_id` and aborts its nodes.
+    fn finish(&self, tree_id: &ActorId) {
+        RUNS.lock().unwrap().remove(tree_id.name());
+        self.cancel.cancel();
+    }
+
+    /// Returns the run of the tree the node `node_id` belongs to, if it was spawned by a running tree.
+    pub fn of(node_id: &ActorId) -> Option<Arc<Self>> {
+        let runs = RUNS.lock().unwrap();
+        let name = node_id.name();
+        name.match_indices('/').find_map(|(idx, _)| runs.get(&name[..idx]).cloned())
+    }
+
+    /// Counts a tick sent within the run, cancelling it when the tick exceeds `max_ticks`.
+    ///
+    /// Returns whether the tick may be sent.
+    pub fn tick(&self) -> bool {
+        let ticks = self.ticks.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.max_ticks.is_some_and(|max_ticks| ticks > max_ticks) {
+            self.cancel.cancel();
+            return false;
+        }
+        !self.cancel.is_cancelled()
+    }
+
+    /// Number of ticks sent so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::SeqCst)
+    }
+
+    /// Aborts the task of a node once the run ends, so that no node outlives the run.
+    pub fn abort_on_finish(&self, handle: &ActorHandle) {
+        let abort = handle.abort_handle();
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            cancel.cancelled().await;
+            abort.abort();
+        });
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BehaviorTree {
     pub root: Node,
     pub logs: Vec<String>,
+    /// Maximum duration of a whole run, after which every node still running is aborted.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<Duration>,
+    /// Maximum number of ticks sent between the nodes of a run, after which the run is aborted.
+    ///
+    /// Guards against accidental infinite loops, such as `KeepRunningUntilFailure` around a child that always succeeds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ticks: Option<u64>,
     #[serde(skip)]
     pub root_handle: Option<ActorHandle>,
 }
@@ -198,6 +276,9 @@ impl Actor for BehaviorTree {
 
         debug!("BehaviorTree::start {}", ctx.id());
 
+        // Every node of the run is aborted when it ends, however it ends
+        let run = TreeRun::start(ctx.id(), self.max_ticks);
+        run.abort_on_finish(&root_handle);
         let root_handle: tokio::task::JoinHandle<Result<(), SystemActorError>> = tokio::spawn(async move {
             let res = root_handle.await?;
             let _ = tx.send(res);
@@ -209,7 +290,17 @@ impl Actor for BehaviorTree {
         let _ = ctx.do_send_as(BehaviorTick, &root_id).await;
 
         let mut stream = ctx.recv().await?;
-        let result = Ok(());
+        let mut result = Ok(());
+
+        // Arm the deadline timer, if any, for the whole run
+        let deadline = self.deadline;
+        let deadline_timer = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(deadline_timer);
 
         loop {
             tokio::select! {
@@ -222,10 +313,22 @@ impl Actor for BehaviorTree {
                         Err(_) => break,
                     }
                 }
+                _ = &mut deadline_timer => {
+                    warn!("BehaviorTree::start: deadline exceeded {}", ctx.id());
+                    result = Err(BehaviorError::DeadlineExceeded(deadline.unwrap_or_default()));
+                    break;
+                }
+                _ = run.cancel.cancelled() => {
+                    let max_ticks = self.max_ticks.unwrap_or_default();
+                    warn!("BehaviorTree::start: max ticks of {} exceeded {}", max_ticks, ctx.id());
+                    result = Err(BehaviorError::MaxTicksExceeded(max_ticks));
+                    break;
+                }
             }
         }
+        run.finish(ctx.id());
 
-        debug!("BehaviorTree::start: end {}", ctx.id());
+        debug!("BehaviorTree::start: end {} after {} ticks", ctx.id(), run.ticks());
 
         result
     }
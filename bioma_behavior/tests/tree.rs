@@ -43,6 +43,8 @@ async fn test_tree_roundtrip() -> Result<(), SystemActorError> {
     let tree = BehaviorTree {
         root: all_0,
         logs: vec!["Log 0".to_string(), "Log 1".to_string(), "Log 2".to_string()],
+        deadline: None,
+        max_ticks: None,
         root_handle: None,
     };
 
@@ -107,6 +109,508 @@ async fn test_tree_from_file() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test(tokio::test)]
+async fn test_tree_deadline() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    // Two chained delays take longer than the deadline
+    let delay_0 = decorators::Delay::builder().duration(Duration::from_millis(500)).build();
+    let delay_1 = decorators::Delay::builder().duration(Duration::from_millis(500)).build();
+    let sequence_0 = composites::Sequence::builder().build();
+
+    let delay_0 = Node::from("delay_0", delay_0, vec![]).unwrap();
+    let delay_1 = Node::from("delay_1", delay_1, vec![]).unwrap();
+    let sequence_0 = Node::from("sequence_0", sequence_0, vec![delay_0, delay_1]).unwrap();
+
+    let tree = BehaviorTree {
+        root: sequence_0,
+        logs: vec![],
+        deadline: Some(Duration::from_millis(600)),
+        max_ticks: None,
+        root_handle: None,
+    };
+
+    let tree_id = ActorId::of::<BehaviorTree>("tree_deadline");
+    let (mut tree_ctx, mut tree_actor) =
+        Actor::spawn(engine.clone(), tree_id.clone(), tree, SpawnOptions::default()).await?;
+
+    let start = std::time::Instant::now();
+    let result = tree_actor.start(&mut tree_ctx).await;
+
+    assert!(matches!(result, Err(BehaviorError::DeadlineExceeded(_))), "Expected the deadline to be exceeded");
+    assert!(start.elapsed() < Duration::from_millis(1000), "Expected the tree to be aborted before completing");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tree_deadline_stops_children() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+    let output_dir = engine.output_dir().join("debug").join("test_tree_deadline_stops_children");
+    std::fs::create_dir_all(&output_dir)?;
+    let marker = output_dir.join("too_late");
+    let _ = std::fs::remove_file(&marker);
+
+    // A child of the root leaves a marker after waiting past the deadline
+    let exec = actions::ExecCommand::builder()
+        .program(r#"touch "$1""#.to_string())
+        .args(vec![marker.to_string_lossy().to_string()])
+        .shell(true)
+        .build();
+    let exec = Node::from("exec_0", exec, vec![]).unwrap();
+    let delay = decorators::Delay::builder().duration(Duration::from_millis(500)).build();
+    let delay = Node::from("delay_0", delay, vec![exec]).unwrap();
+    let sequence = composites::Sequence::builder().build();
+    let sequence = Node::from("sequence_0", sequence, vec![delay]).unwrap();
+
+    let tree = BehaviorTree {
+        root: sequence,
+        logs: vec![],
+        deadline: Some(Duration::from_millis(100)),
+        max_ticks: None,
+        root_handle: None,
+    };
+    let tree_id = ActorId::of::<BehaviorTree>("tree_deadline_children");
+    let (mut tree_ctx, mut tree_actor) = Actor::spawn(engine.clone(), tree_id, tree, SpawnOptions::default()).await?;
+    let result = tree_actor.start(&mut tree_ctx).await;
+    assert!(matches!(result, Err(BehaviorError::DeadlineExceeded(_))), "Expected the deadline to be exceeded");
+
+    // The child is aborted along with the root, so it never gets to leave the marker
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(!marker.exists(), "Expected the child to be stopped at the deadline");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_random_selector_seeded() -> Result<(), Box<dyn std::error::Error>> {
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    let seed = 42;
+    let selector = composites::RandomSelector::builder().seed(seed).build();
+    let selector = Node::from("random_0", selector, log_children(3)).unwrap();
+
+    // All children succeed, so each activation ticks only its first pick
+    let picks = tick_and_collect_picks(selector, 3).await?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let expected: Vec<usize> = (0..3)
+        .map(|_| {
+            let mut order: Vec<usize> = (0..3).collect();
+            order.shuffle(&mut rng);
+            order[0]
+        })
+        .collect();
+
+    assert_eq!(picks, expected, "Seeded picks should be reproducible");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weighted_selector_seeded() -> Result<(), Box<dyn std::error::Error>> {
+    use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+
+    let seed = 7;
+    let weights = vec![0.7, 0.2, 0.1];
+    let selector = composites::WeightedSelector::builder().weights(weights.clone()).seed(seed).build();
+    let selector = Node::from("weighted_0", selector, log_children(3)).unwrap();
+
+    // Weights round-trip through the node config
+    let config: composites::WeightedSelector = serde_json::from_value(selector.data().config.clone())?;
+    assert_eq!(config.weights, weights);
+
+    let picks = tick_and_collect_picks(selector, 5).await?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let distribution = WeightedIndex::new(&weights)?;
+    let expected: Vec<usize> = (0..5).map(|_| distribution.sample(&mut rng)).collect();
+
+    assert_eq!(picks, expected, "Seeded picks should be reproducible");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weighted_selector_distribution() -> Result<(), Box<dyn std::error::Error>> {
+    let selector = composites::WeightedSelector::builder().weights(vec![0.7, 0.2, 0.1]).seed(1).build();
+    let selector = Node::from("weighted_0", selector, log_children(3)).unwrap();
+
+    let activations = 300;
+    let picks = tick_and_collect_picks(selector, activations).await?;
+    assert_eq!(picks.len(), activations);
+
+    // Each child is picked roughly in proportion to its weight
+    let share = |idx: usize| picks.iter().filter(|&&pick| pick == idx).count() as f64 / activations as f64;
+    assert!((share(0) - 0.7).abs() < 0.1, "Child 0 picked {:.2} of the time", share(0));
+    assert!((share(1) - 0.2).abs() < 0.1, "Child 1 picked {:.2} of the time", share(1));
+    assert!((share(2) - 0.1).abs() < 0.1, "Child 2 picked {:.2} of the time", share(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weighted_selector_invalid_weights() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    let selector = composites::WeightedSelector::builder().weights(vec![1.0, -1.0]).build();
+    let selector = Node::from("weighted_0", selector, log_children(2)).unwrap();
+
+    let selector_tag = selector.data().tag.clone();
+    let result = engine
+        .registry()
+        .spawn(selector_tag, engine.clone(), selector.value(), selector.id(None), SpawnOptions::default())
+        .await;
+    assert!(result.is_err(), "Negative weights should be rejected");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_keep_running_until_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+    engine.registry().add(decorators::Always::tag(), decorators::AlwaysFactory).await?;
+
+    let failing_child = |uid: &'static str| {
+        let always = decorators::Always::builder().success(false).build();
+        Node::from(uid, always, log_children(1)).unwrap()
+    };
+
+    // A failing child ends the loop with success by default
+    let repeat = decorators::KeepRunningUntilFailure::builder().build();
+    let repeat = Node::from("repeat_0", repeat, vec![failing_child("always_0")]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, repeat).await?, BehaviorStatus::Success);
+
+    // Or with failure when configured to propagate it
+    let repeat = decorators::KeepRunningUntilFailure::builder().propagate_failure(true).build();
+    let repeat = Node::from("repeat_1", repeat, vec![failing_child("always_1")]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, repeat).await?, BehaviorStatus::Failure);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tree_max_ticks() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+    engine.registry().add(decorators::Always::tag(), decorators::AlwaysFactory).await?;
+
+    // Runs `repeat` under a sequence, which ends the run once it replies
+    let run = |name: &'static str, repeat: Node| {
+        let engine = engine.clone();
+        async move {
+            let sequence = composites::Sequence::builder().build();
+            let root = Node::from("sequence_0", sequence, vec![repeat]).unwrap();
+            let tree = BehaviorTree { root, logs: vec![], deadline: None, max_ticks: Some(20), root_handle: None };
+            let tree_id = ActorId::of::<BehaviorTree>(name);
+            let (mut tree_ctx, mut tree_actor) = Actor::spawn(engine, tree_id, tree, SpawnOptions::default()).await?;
+            Ok::<_, Box<dyn std::error::Error>>(tree_actor.start(&mut tree_ctx).await)
+        }
+    };
+
+    // A loop ending within the limit completes the run
+    let always = decorators::Always::builder().success(false).build();
+    let always = Node::from("always_0", always, log_children(1)).unwrap();
+    let repeat = decorators::KeepRunningUntilFailure::builder().build();
+    let repeat = Node::from("repeat_0", repeat, vec![always]).unwrap();
+    assert!(run("tree_max_ticks_0", repeat).await?.is_ok());
+
+    // An always-succeeding child would loop forever without the guard
+    let repeat = decorators::KeepRunningUntilFailure::builder().build();
+    let repeat = Node::from("repeat_1", repeat, log_children(1)).unwrap();
+    let result = run("tree_max_ticks_1", repeat).await?;
+    assert!(matches!(result, Err(BehaviorError::MaxTicksExceeded(20))), "Unexpected result: {:?}", result);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exec_command() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    // Exit code 0 maps to success, anything else to failure
+    let exec = actions::ExecCommand::builder().program("true".to_string()).build();
+    let exec = Node::from("exec_true", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Success);
+
+    let exec = actions::ExecCommand::builder().program("false".to_string()).build();
+    let exec = Node::from("exec_false", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Failure);
+
+    // A program that cannot be spawned fails
+    let exec = actions::ExecCommand::builder().program("bioma-no-such-program".to_string()).build();
+    let exec = Node::from("exec_missing", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Failure);
+
+    // Arguments are passed as-is, never through a shell
+    let exec = actions::ExecCommand::builder().program("test".to_string()).args(vec!["$HOME".to_string()]).build();
+    let exec = Node::from("exec_args", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Success);
+
+    // Unless the shell is opted into, with the arguments as positional parameters
+    let exec = actions::ExecCommand::builder()
+        .program(r#"test "$1" = "$GREETING""#.to_string())
+        .args(vec!["hello; exit 1".to_string()])
+        .env([("GREETING".to_string(), "hello; exit 1".to_string())].into())
+        .shell(true)
+        .build();
+    let exec = Node::from("exec_shell", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Success);
+
+    // Output past the cap is read and discarded, so a chatty process runs to its end
+    let exec = actions::ExecCommand::builder()
+        .program("head -c 8000000 /dev/zero; head -c 8000000 /dev/zero >&2".to_string())
+        .shell(true)
+        .max_output_bytes(16)
+        .timeout(Duration::from_secs(10))
+        .build();
+    let exec = Node::from("exec_chatty", exec, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Success);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exec_command_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    let exec = actions::ExecCommand::builder()
+        .program("sleep".to_string())
+        .args(vec!["30".to_string()])
+        .timeout(Duration::from_millis(200))
+        .build();
+    let exec = Node::from("exec_sleep", exec, vec![]).unwrap();
+
+    // The sleeping process is killed once the timeout elapses
+    let start = std::time::Instant::now();
+    assert_eq!(spawn_and_tick(&engine, exec).await?, BehaviorStatus::Failure);
+    assert!(start.elapsed() < Duration::from_secs(5), "Expected the process to be killed, took {:?}", start.elapsed());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_detach() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+    let output_dir = engine.output_dir().join("debug").join("test_detach");
+    std::fs::create_dir_all(&output_dir)?;
+    let started = output_dir.join("started");
+    let finished = output_dir.join("finished");
+    let _ = std::fs::remove_file(&started);
+    let _ = std::fs::remove_file(&finished);
+
+    // A long-running child that leaves a marker when it starts and another one when it finishes
+    let exec = actions::ExecCommand::builder()
+        .program(r#"touch "$1"; sleep 2; touch "$2""#.to_string())
+        .args(vec![started.to_string_lossy().to_string(), finished.to_string_lossy().to_string()])
+        .shell(true)
+        .build();
+    let exec = Node::from("exec_0", exec, vec![]).unwrap();
+    let detach = decorators::Detach::builder().build();
+    let detach = Node::from("detach_0", detach, vec![exec]).unwrap();
+
+    let detach_id = detach.id(None);
+    let detach_handle = engine
+        .registry()
+        .spawn(detach.data().tag.clone(), engine.clone(), detach.value(), detach_id.clone(), SpawnOptions::default())
+        .await?;
+    let relay_id = ActorId::of::<Relay>("/relay/detach_0");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine.clone(), relay_id, Relay, SpawnOptions::default()).await?;
+
+    // The decorator succeeds without waiting for its child
+    let start = std::time::Instant::now();
+    let status = relay_ctx
+        .send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(BehaviorTick, detach_id, SendOptions::default())
+        .await?;
+    assert_eq!(status, BehaviorStatus::Success);
+    assert!(start.elapsed() < Duration::from_secs(2), "Expected a prompt reply, took {:?}", start.elapsed());
+
+    // While the child keeps running in the background
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !started.exists() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await?;
+
+    // Until the decorator is shut down, which aborts the child before it finishes
+    detach_handle.abort();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(!finished.exists(), "Expected the detached child to be shut down with the decorator");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_request() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    let mut server = mockito::Server::new_async().await;
+    let ok = server
+        .mock("POST", "/hook")
+        .match_header("x-token", "secret")
+        .match_body(mockito::Matcher::Json(serde_json::json!({"event": "done"})))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"ok":true}"#)
+        .create_async()
+        .await;
+    let error = server.mock("GET", "/status").with_status(500).expect(2).create_async().await;
+    let url = |path: &str| url::Url::parse(&server.url()).unwrap().join(path).unwrap();
+
+    // A 2xx status succeeds
+    let request = actions::HttpRequest::builder()
+        .method(actions::HttpMethod::Post)
+        .url(url("/hook"))
+        .headers([("x-token".to_string(), "secret".to_string())].into())
+        .body(serde_json::json!({"event": "done"}))
+        .build();
+    let request = Node::from("http_ok", request, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, request).await?, BehaviorStatus::Success);
+    ok.assert_async().await;
+
+    // An unexpected status fails
+    let request = actions::HttpRequest::builder().url(url("/status")).build();
+    let request = Node::from("http_error", request, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, request).await?, BehaviorStatus::Failure);
+
+    // Unless it is listed as expected
+    let request = actions::HttpRequest::builder().url(url("/status")).expected_status(vec![500]).build();
+    let request = Node::from("http_expected", request, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, request).await?, BehaviorStatus::Success);
+    error.assert_async().await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_request_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    // A server that accepts connections but never responds
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = url::Url::parse(&format!("http://{}/slow", listener.local_addr()?))?;
+    tokio::spawn(async move {
+        let mut sockets = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            sockets.push(socket);
+        }
+    });
+
+    let request = actions::HttpRequest::builder().url(url).timeout(Duration::from_millis(200)).build();
+    let request = Node::from("http_timeout", request, vec![]).unwrap();
+
+    let start = std::time::Instant::now();
+    assert_eq!(spawn_and_tick(&engine, request).await?, BehaviorStatus::Failure);
+    assert!(start.elapsed() < Duration::from_secs(5), "Expected the request to time out, took {:?}", start.elapsed());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_empty_composites() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    // An empty sequence succeeds and an empty fallback fails by default
+    let sequence = Node::from("sequence_0", composites::Sequence::builder().build(), vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, sequence).await?, BehaviorStatus::Success);
+
+    let fallback = Node::from("fallback_0", composites::Fallback::builder().build(), vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, fallback).await?, BehaviorStatus::Failure);
+
+    // Both can be configured otherwise
+    let sequence = composites::Sequence::builder().empty_status(BehaviorStatus::Failure).build();
+    let sequence = Node::from("sequence_1", sequence, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, sequence).await?, BehaviorStatus::Failure);
+
+    let fallback = composites::Fallback::builder().empty_status(BehaviorStatus::Success).build();
+    let fallback = Node::from("fallback_1", fallback, vec![]).unwrap();
+    assert_eq!(spawn_and_tick(&engine, fallback).await?, BehaviorStatus::Success);
+
+    Ok(())
+}
+
+/// Spawns a node directly and ticks it once through a relay.
+async fn spawn_and_tick(engine: &Engine, node: Node) -> Result<BehaviorStatus, SystemActorError> {
+    let node_id = node.id(None);
+    let node_tag = node.data().tag.clone();
+    engine.registry().spawn(node_tag, engine.clone(), node.value(), node_id.clone(), SpawnOptions::default()).await?;
+
+    let relay_id = ActorId::of::<Relay>(format!("/relay/{}", node.data().uid));
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine.clone(), relay_id, Relay, SpawnOptions::default()).await?;
+
+    relay_ctx
+        .send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(BehaviorTick, node_id, SendOptions::default())
+        .await
+}
+
+/// Creates `count` log actions named after their index.
+fn log_children(count: usize) -> Vec<Node> {
+    (0..count)
+        .map(|i| {
+            let log = actions::Log::builder().level(Info).text(format!("Child {}", i)).build();
+            Node::from(format!("log_{}", i), log, vec![]).unwrap()
+        })
+        .collect()
+}
+
+/// Spawns a selector node, ticks it `ticks` times and returns the child indices it logged as picked.
+async fn tick_and_collect_picks(selector: Node, ticks: usize) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    bioma_behavior::register_behaviors(&engine.registry()).await?;
+
+    // Set up a custom layer that captures log messages
+    let (log_sender, mut log_receiver) = tokio::sync::mpsc::channel::<String>(4096);
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(move || TestWriter(log_sender.clone()))
+        .with_filter(tracing_subscriber::filter::filter_fn(|metadata| metadata.target().starts_with("bioma_behavior")))
+        .with_filter(tracing_subscriber::filter::LevelFilter::INFO);
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    // Spawn the selector directly and tick it
+    let selector_id = selector.id(None);
+    let selector_tag = selector.data().tag.clone();
+    let _selector_handle = engine
+        .registry()
+        .spawn(selector_tag, engine.clone(), selector.value(), selector_id.clone(), SpawnOptions::default())
+        .await?;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    for _ in 0..ticks {
+        let status = relay_ctx
+            .send_as_and_wait_reply::<BehaviorTick, BehaviorStatus>(
+                BehaviorTick,
+                selector_id.clone(),
+                SendOptions::default(),
+            )
+            .await?;
+        assert_eq!(status, BehaviorStatus::Success);
+    }
+
+    let mut picks = Vec::new();
+    while let Ok(message) = log_receiver.try_recv() {
+        if let Some((_, idx)) = message.trim().rsplit_once("picked child ") {
+            picks.push(idx.parse::<usize>()?);
+        }
+    }
+
+    Ok(picks)
+}
+
 struct TestWriter(tokio::sync::mpsc::Sender<String>);
 
 impl Write for TestWriter {
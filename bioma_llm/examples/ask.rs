@@ -31,7 +31,7 @@ impl Actor for MainActor {
 
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
         let ask_id = ActorId::of::<Chat>("/chat");
-        let ask = Chat::builder().model("llama3.2".into()).build();
+        let ask = Chat::builder().model("llama3.2").build()?;
 
         // Spawn the chat actor
         info!("{} Spawning chat actor", ctx.id());
@@ -50,7 +50,7 @@ impl Actor for MainActor {
         let format = chat::Schema::new::<RustPrinciples>();
         info!("{} Format: {}", ctx.id(), format.schema_json());
 
-        let response: ChatMessageResponse = ctx
+        let response: ChatReply = ctx
             .send_and_wait_reply::<Chat, ChatMessages>(
                 ChatMessages::builder().messages(vec![chat_message]).format(format).tools(vec![]).build(),
                 &ask_id,
@@ -59,7 +59,7 @@ impl Actor for MainActor {
             .await?;
 
         // First try to parse as RustPrinciples
-        let assistant_message = response.message;
+        let assistant_message = response.response.message;
         match serde_json::from_str::<RustPrinciples>(&assistant_message.content) {
             Ok(principles) => {
                 info!(
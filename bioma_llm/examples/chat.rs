@@ -15,7 +15,7 @@ impl Actor for MainActor {
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
         let chat_id = ActorId::of::<Chat>("/llm");
 
-        let chat = Chat::builder().model("llama3.2".into()).build();
+        let chat = Chat::builder().model("llama3.2").build()?;
 
         // Spawn the chat actor
         let (mut chat_ctx, mut chat_actor) =
@@ -1,10 +1,12 @@
+use crate::metrics::{self, Counter, Histogram};
 use bioma_actor::prelude::*;
+use futures::Stream;
 use ollama_rs::{
     error::OllamaError,
     generation::{
-        chat::{request::ChatMessageRequest, ChatMessage, ChatMessageResponse},
+        chat::{request::ChatMessageRequest, ChatMessage, ChatMessageResponse, MessageRole},
         parameters::{FormatType, JsonStructure},
-        tools::ToolInfo,
+        tools::{ToolCall, ToolInfo},
     },
     models::ModelOptions,
     Ollama,
@@ -12,7 +14,12 @@ use ollama_rs::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use tracing::{error, info};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{error, info, warn};
 use url::Url;
 
 /// Enumerates the types of errors that can occur in LLM
@@ -30,8 +37,24 @@ pub enum ChatError {
     OllamaInternal(String),
     #[error("Ollama other error: {0}")]
     OllamaOther(String),
+    #[error("Ollama returned {status}: {message}")]
+    OllamaStatus { status: reqwest::StatusCode, message: String },
     #[error("Ollama not initialized")]
     OllamaNotInitialized,
+    #[error("Invalid chat configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Template error: {0}")]
+    TemplateError(String),
+    #[error("Missing template variable: {0}")]
+    MissingTemplateVar(String),
+    #[error("Context overflow: {used} tokens (limit: {limit})")]
+    ContextOverflow { used: usize, limit: usize },
+    #[error("Chat request failed after {attempts} attempts: {source}")]
+    RetryFailed { attempts: u32, source: Box<ChatError> },
+    #[error("Response stream ended without any chunk")]
+    EmptyStream,
+    #[error("Invalid generation options: {0}")]
+    InvalidOptions(String),
 }
 
 impl From<OllamaError> for ChatError {
@@ -46,11 +69,411 @@ impl From<OllamaError> for ChatError {
     }
 }
 
-impl ActorError for ChatError {}
+impl ActorError for ChatError {
+    fn is_backend_failure(&self) -> bool {
+        matches!(
+            self,
+            ChatError::ReqwestError(_)
+                | ChatError::OllamaInternal(_)
+                | ChatError::OllamaOther(_)
+                | ChatError::OllamaStatus { .. }
+                | ChatError::OllamaNotInitialized
+                | ChatError::RetryFailed { .. }
+        )
+    }
+}
+
+/// A request to a chat backend: Ollama's request, along with the tool call answered by each of its tool messages
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub request: ChatMessageRequest,
+    /// The id of the tool call answered by each message of `request`, in order, `None` for the other messages
+    pub tool_call_ids: Vec<Option<String>>,
+}
+
+impl ChatRequest {
+    pub fn new(request: ChatMessageRequest) -> Self {
+        Self { request, tool_call_ids: vec![] }
+    }
+
+    /// The JSON body of the request, linking the tool calls and their results by id.
+    ///
+    /// The tool calls of an assistant message get the ids of `ToolCallRequest`, `call_0`, `call_1`... in order. Each
+    /// tool message after it gets the `tool_call_id` it was pushed with, or else the id of the call at its position.
+    pub fn body(&self, stream: bool) -> Result<serde_json::Value, ChatError> {
+        let mut body = serde_json::to_value(&self.request).map_err(ChatError::JsonError)?;
+        body["stream"] = serde_json::Value::Bool(stream);
+
+        let Some(messages) = body.get_mut("messages").and_then(serde_json::Value::as_array_mut) else {
+            return Ok(body);
+        };
+        let mut results = 0;
+        for (index, message) in messages.iter_mut().enumerate() {
+            if let Some(calls) = message.get_mut("tool_calls").and_then(serde_json::Value::as_array_mut) {
+                if !calls.is_empty() {
+                    for (position, call) in calls.iter_mut().enumerate() {
+                        call["id"] = serde_json::Value::String(tool_call_id(position));
+                    }
+                    results = 0;
+                }
+            }
+            if message["role"] == "tool" {
+                let id = self.tool_call_ids.get(index).cloned().flatten().unwrap_or_else(|| tool_call_id(results));
+                message["tool_call_id"] = serde_json::Value::String(id);
+                results += 1;
+            }
+        }
+        Ok(body)
+    }
+}
+
+impl std::ops::Deref for ChatRequest {
+    type Target = ChatMessageRequest;
+
+    fn deref(&self) -> &ChatMessageRequest {
+        &self.request
+    }
+}
+
+impl std::ops::DerefMut for ChatRequest {
+    fn deref_mut(&mut self) -> &mut ChatMessageRequest {
+        &mut self.request
+    }
+}
+
+/// Id of the tool call at `position` among the calls of a reply
+fn tool_call_id(position: usize) -> String {
+    format!("call_{}", position)
+}
+
+/// Stream of response chunks returned by a `ChatBackend`
+pub type ChatResponseStream = Pin<Box<dyn Stream<Item = Result<ChatMessageResponse, ChatError>> + Send>>;
+
+/// A model provider that answers chat requests
+///
+/// `Chat` talks to Ollama at its `endpoint` by default; other providers, such as OpenAI-compatible
+/// endpoints, can be used by setting a different backend.
+pub trait ChatBackend: Send + Sync + std::fmt::Debug {
+    /// Sends a request and waits for the complete response
+    fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>>;
+
+    /// Sends a request and returns the response as a stream of chunks, the last one being marked as done
+    fn chat_stream<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>>;
+
+    /// Checks that the provider can be reached, assumed healthy unless the backend overrides it
+    fn health<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<(), ChatError>> + Send + 'a>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Chat backend for an Ollama server
+///
+/// Requests are posted as built by `ChatRequest::body`, so tool results keep the id of the call they answer.
+#[derive(Debug, Clone)]
+pub struct OllamaBackend {
+    ollama: Ollama,
+    endpoint: Url,
+    client: reqwest::Client,
+}
+
+impl Default for OllamaBackend {
+    fn default() -> Self {
+        Self::new(default_endpoint())
+    }
+}
+
+impl OllamaBackend {
+    pub fn new(endpoint: Url) -> Self {
+        Self { ollama: Ollama::from_url(endpoint.clone()), endpoint, client: reqwest::Client::new() }
+    }
+
+    /// Posts the request to `/api/chat`, failing with the error returned by Ollama if it refuses it
+    async fn post(&self, request: &ChatRequest, stream: bool) -> Result<reqwest::Response, ChatError> {
+        let url = self.endpoint.join("api/chat").map_err(|e| ChatError::OllamaOther(e.to_string()))?;
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(request.body(stream)?.to_string())
+            .send()
+            .await
+            .map_err(ChatError::ReqwestError)?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.map_err(ChatError::ReqwestError)?;
+            return Err(ChatError::OllamaStatus { status, message });
+        }
+        Ok(response)
+    }
+}
+
+impl ChatBackend for OllamaBackend {
+    fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let bytes = self.post(&request, false).await?.bytes().await.map_err(ChatError::ReqwestError)?;
+            serde_json::from_slice(&bytes).map_err(ChatError::JsonError)
+        })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let response = self.post(&request, true).await?;
+
+            // Each line of the body is a chunk
+            let lines = futures::stream::unfold(
+                (response.bytes_stream(), Vec::new()),
+                |(mut bytes, mut buffer): (_, Vec<u8>)| async move {
+                    loop {
+                        if let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                            let line: Vec<u8> = buffer.drain(..=end).collect();
+                            return Some((Ok(line), (bytes, buffer)));
+                        }
+                        match bytes.next().await {
+                            Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                            Some(Err(e)) => return Some((Err(ChatError::ReqwestError(e)), (bytes, buffer))),
+                            None if buffer.is_empty() => return None,
+                            None => return Some((Ok(std::mem::take(&mut buffer)), (bytes, buffer))),
+                        }
+                    }
+                },
+            );
+            let stream = lines
+                .filter(|line| {
+                    let blank = line.as_ref().is_ok_and(|line| line.iter().all(u8::is_ascii_whitespace));
+                    futures::future::ready(!blank)
+                })
+                .map(|line| line.and_then(|line| parse_chunk(&line)));
+            Ok(Box::pin(stream) as ChatResponseStream)
+        })
+    }
+
+    fn health<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<(), ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            self.ollama.list_local_models().await?;
+            Ok(())
+        })
+    }
+}
+
+/// Parses a streamed chunk, or the error Ollama sent in its place
+fn parse_chunk(line: &[u8]) -> Result<ChatMessageResponse, ChatError> {
+    #[derive(Deserialize)]
+    struct StreamError {
+        error: String,
+    }
+
+    match serde_json::from_slice::<StreamError>(line) {
+        Ok(StreamError { error }) => Err(ChatError::OllamaOther(error)),
+        Err(_) => serde_json::from_slice(line).map_err(ChatError::JsonError),
+    }
+}
+
+/// Tokens added to every message for its role and delimiters
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// Tokens counted for every attached image
+const IMAGE_TOKENS: usize = 576;
+
+/// Counts the tokens of a text as the model would
+pub trait Tokenizer: Send + Sync + std::fmt::Debug {
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Estimates a token per four characters of each word, used when no tokenizer is set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count(&self, text: &str) -> usize {
+        text.split_whitespace().map(|word| word.chars().count().div_ceil(4)).sum()
+    }
+}
+
+/// Compresses the oldest history into a summary once a request would exceed `max_tokens` or `max_messages`.
+///
+/// The summary is written by a separate chat call and replaces the summarized messages as a single system message.
+/// System prompts and the last two turns are never summarized.
+#[derive(bon::Builder, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummarizationPolicy {
+    /// Estimated prompt tokens, history included, above which the history is summarized
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Messages, history included, above which the history is summarized
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    /// Most recent turns of the history kept verbatim, a turn starting at a user message, at least 2
+    #[builder(default = default_keep_turns())]
+    #[serde(default = "default_keep_turns")]
+    pub keep_turns: usize,
+    /// Instructions given to the model writing the summary
+    #[builder(default = default_summary_prompt(), into)]
+    #[serde(default = "default_summary_prompt")]
+    pub prompt: String,
+    /// Model writing the summary, the chat model if not set
+    #[builder(into)]
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+fn default_keep_turns() -> usize {
+    4
+}
+
+/// Turns always kept out of a summary
+const MIN_KEEP_TURNS: usize = 2;
+
+/// Start of the system message holding the summary, which a later summary folds in
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+fn default_summary_prompt() -> String {
+    "Summarize the following conversation in a few sentences, keeping names, facts and decisions that later \
+     messages may refer to."
+        .to_string()
+}
+
+/// Token counts and timings reported by the backend for a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Time the backend spent on the whole request
+    #[serde(with = "humantime_serde")]
+    pub total_duration: Duration,
+    /// Time the backend spent generating the completion
+    #[serde(with = "humantime_serde")]
+    pub eval_duration: Duration,
+}
+
+impl Usage {
+    /// The usage reported with a response, found on the final chunk when streaming
+    pub fn of(response: &ChatMessageResponse) -> Option<Self> {
+        response.final_data.as_ref().map(|data| Self {
+            prompt_tokens: u64::from(data.prompt_eval_count),
+            completion_tokens: u64::from(data.eval_count),
+            total_duration: Duration::from_nanos(data.total_duration),
+            eval_duration: Duration::from_nanos(data.eval_duration),
+        })
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_duration += other.total_duration;
+        self.eval_duration += other.eval_duration;
+    }
+}
+
+/// Ask a chat actor for the usage accumulated since it started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetChatStats;
+
+/// Usage accumulated by a chat actor, summaries of its history included
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatStats {
+    /// Responses returned to callers
+    pub responses: u64,
+    /// Summaries written of the oldest history
+    pub summaries: u64,
+    pub usage: Usage,
+}
+
+/// How requests failing on a transient backend error are retried, with exponential backoff
+#[derive(bon::Builder, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Retries after the first attempt
+    #[builder(default = default_max_retries())]
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry
+    #[builder(default = default_base_delay())]
+    #[serde(default = "default_base_delay", with = "humantime_serde")]
+    pub base_delay: Duration,
+    /// Factor applied to the delay for each further retry
+    #[builder(default = default_multiplier())]
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// Upper bound of the delay between retries
+    #[builder(default = default_max_delay())]
+    #[serde(default = "default_max_delay", with = "humantime_serde")]
+    pub max_delay: Duration,
+    /// Fraction between 0 and 1 by which each delay is shortened at random, spreading out concurrent retries
+    #[builder(default = default_jitter())]
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_base_delay() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_max_delay() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl RetryConfig {
+    /// The delay before the given retry, counting from 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(retry.saturating_sub(1).min(64) as i32);
+        let delay = self.base_delay.as_secs_f64() * factor;
+        let delay = if delay < self.max_delay.as_secs_f64() { Duration::from_secs_f64(delay) } else { self.max_delay };
+        delay.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * rand::random::<f64>())
+    }
+}
+
+/// Whether an error may go away on retrying: the backend being unreachable, timing out, busy or failing on its side.
+/// Other errors, such as a bad request or an invalid reply, fail at once.
+fn is_transient(error: &ChatError) -> bool {
+    match error {
+        ChatError::ReqwestError(e) => e.is_connect() || e.is_timeout() || e.status().is_some_and(is_transient_status),
+        ChatError::OllamaStatus { status, .. } => is_transient_status(*status),
+        _ => false,
+    }
+}
+
+/// Server errors, including a failing gateway or a model still loading, and rate limiting
+fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
 
 #[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct Chat {
-    #[builder(default = default_model_name())]
+    #[builder(default = default_model_name(), into)]
     pub model: Cow<'static, str>,
     #[builder(default = default_endpoint())]
     pub endpoint: Url,
@@ -60,9 +483,115 @@ pub struct Chat {
     pub history: Vec<ChatMessage>,
     #[builder(default = default_max_context_length())]
     pub max_context_length: u64,
-    #[serde(skip)]
+    /// Maximum number of requests sent to Ollama at once, further calls wait for a free slot.
+    ///
+    /// An actor handles one message at a time, so the slots are shared by the clones of a chat: actors spawned from
+    /// the same chat, along with their `send_raw` and `stream` calls, stay within the limit together.
+    #[builder(default = default_max_concurrent_requests())]
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Sequences that end generation, used unless the request options set their own
     #[builder(default)]
-    ollama: Ollama,
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Requests estimated to need more prompt tokens are refused before reaching the backend
+    #[serde(default)]
+    pub max_context_tokens: Option<usize>,
+    /// Asks for the log probability of each generated token, only returned by `send_raw`
+    #[builder(default)]
+    #[serde(default)]
+    pub logprobs: bool,
+    /// Drops the oldest history to fit `max_context_tokens` instead of refusing the request
+    #[builder(default)]
+    #[serde(default)]
+    pub trim_history: bool,
+    /// System prompt sent before the messages, its `{{key}}` placeholders filled from the `template_vars` of each
+    /// request, see `render_template`
+    #[builder(into)]
+    #[serde(default)]
+    pub system_template: Option<String>,
+    /// Retries requests failing on a transient backend error, requests fail on the first error if not set
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+    /// Summarizes the oldest history of long conversations, unless the request sets its own policy
+    #[serde(default)]
+    pub summarization: Option<SummarizationPolicy>,
+    /// Counts the prompt tokens of requests, `HeuristicTokenizer` if not set
+    #[serde(skip)]
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    /// The provider requests are sent to, Ollama at `endpoint` if not set
+    #[serde(skip)]
+    backend: Option<Arc<dyn ChatBackend>>,
+    /// Shared by the clones of the chat, see `max_concurrent_requests`
+    #[serde(skip)]
+    #[builder(skip)]
+    request_slots: Arc<OnceLock<Arc<Semaphore>>>,
+    #[serde(skip)]
+    #[builder(skip)]
+    metrics: OnceLock<ChatMetrics>,
+    #[serde(skip)]
+    #[builder(skip)]
+    stats: ChatStats,
+}
+
+/// Request metrics of a chat model
+#[derive(Debug, Clone)]
+struct ChatMetrics {
+    requests: Counter,
+    errors: Counter,
+    duration: Histogram,
+    prompt_tokens: Counter,
+    completion_tokens: Counter,
+    summarizations: Counter,
+    summarization_tokens: Counter,
+    retries: Counter,
+}
+
+impl ChatMetrics {
+    fn new(model: &str) -> Self {
+        let registry = metrics::registry();
+        let labels = [("model", model)];
+        Self {
+            requests: registry.counter("bioma_chat_requests_total", "Chat requests sent to the backend", &labels),
+            errors: registry.counter("bioma_chat_errors_total", "Chat requests that failed", &labels),
+            duration: registry.histogram(
+                "bioma_chat_request_duration_seconds",
+                "Time until the complete chat response",
+                &labels,
+                metrics::DURATION_BUCKETS,
+            ),
+            prompt_tokens: registry.counter("bioma_chat_prompt_tokens_total", "Tokens in chat prompts", &labels),
+            completion_tokens: registry.counter(
+                "bioma_chat_completion_tokens_total",
+                "Tokens generated in chat responses",
+                &labels,
+            ),
+            summarizations: registry.counter(
+                "bioma_chat_summarizations_total",
+                "Times the oldest chat history was replaced by a summary",
+                &labels,
+            ),
+            summarization_tokens: registry.counter(
+                "bioma_chat_summarization_tokens_total",
+                "Prompt and generated tokens of history summaries",
+                &labels,
+            ),
+            retries: registry.counter(
+                "bioma_chat_retries_total",
+                "Chat requests retried after a transient error",
+                &labels,
+            ),
+        }
+    }
+
+    /// Records a complete response
+    fn record(&self, response: &ChatMessageResponse, elapsed: std::time::Duration) {
+        self.duration.observe_duration(elapsed);
+        if let Some(data) = &response.final_data {
+            self.prompt_tokens.inc_by(u64::from(data.prompt_eval_count));
+            self.completion_tokens.inc_by(u64::from(data.eval_count));
+        }
+    }
 }
 
 fn default_model_name() -> Cow<'static, str> {
@@ -81,9 +610,22 @@ fn default_max_context_length() -> u64 {
     4096
 }
 
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
 impl Default for Chat {
     fn default() -> Self {
-        Self::builder().build()
+        Self::builder().build_unchecked()
+    }
+}
+
+impl<S: chat_builder::IsComplete> ChatBuilder<S> {
+    /// Builds the chat actor, failing if the configuration is invalid
+    pub fn build(self) -> Result<Chat, ChatError> {
+        let chat = self.build_unchecked();
+        chat.validate()?;
+        Ok(chat)
     }
 }
 
@@ -110,62 +652,435 @@ impl Schema {
     }
 }
 
+/// Identifier of a message within `ChatMessages`
+pub type MessageId = u64;
+
+/// Id of a message received without one, replaced by `ChatMessages::assign_missing_ids`
+pub const UNASSIGNED_ID: MessageId = MessageId::MAX;
+
+fn unassigned_id() -> MessageId {
+    UNASSIGNED_ID
+}
+
+/// A message of `ChatMessages` along with its id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatEntry {
+    /// Left `UNASSIGNED_ID` when missing from the JSON, as sent by clients predating message ids
+    #[serde(default = "unassigned_id")]
+    pub id: MessageId,
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    /// For a tool result, the id of the tool call it answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatEntry {
+    pub fn new(id: MessageId, message: ChatMessage) -> Self {
+        Self { id, message, tool_call_id: None }
+    }
+
+    /// Numbers `messages` in order, from 0
+    pub fn numbered(messages: Vec<ChatMessage>) -> Vec<ChatEntry> {
+        messages.into_iter().zip(0..).map(|(message, id)| ChatEntry::new(id, message)).collect()
+    }
+}
+
+impl std::ops::Deref for ChatEntry {
+    type Target = ChatMessage;
+
+    fn deref(&self) -> &ChatMessage {
+        &self.message
+    }
+}
+
+impl std::ops::DerefMut for ChatEntry {
+    fn deref_mut(&mut self) -> &mut ChatMessage {
+        &mut self.message
+    }
+}
+
 #[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessages {
-    pub messages: Vec<ChatMessage>,
+    /// The messages with their ids, numbered in order when built from plain messages
+    #[builder(with = |messages: Vec<ChatMessage>| ChatEntry::numbered(messages))]
+    pub messages: Vec<ChatEntry>,
+    /// The id given to the next message added, only ever increasing so that ids are never reused
+    #[builder(default)]
+    #[serde(default)]
+    pub next_id: MessageId,
     #[builder(default)]
     pub restart: bool,
     #[builder(default)]
     pub persist: bool,
+    /// Reply with the content in chunks as it is generated, the last chunk being marked as done
     #[builder(default)]
     pub stream: bool,
     pub format: Option<Schema>,
     pub tools: Option<Vec<ToolInfo>>,
     pub options: Option<ModelOptions>,
+    /// Values of the placeholders in the system template of the chat
+    #[builder(default)]
+    #[serde(default)]
+    pub template_vars: HashMap<String, String>,
+    /// Overrides the summarization policy of the chat for this request
+    pub summarization: Option<SummarizationPolicy>,
+    /// Sampling options for this request, applied over `options` and the stop sequences of the chat
+    pub generation: Option<GenerationOptions>,
+}
+
+/// Generation options of a single request, left to the model defaults when unset
+#[derive(bon::Builder, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    /// Sequences that end generation, replacing the stop sequences of the chat
+    pub stop: Option<Vec<String>>,
+    /// Maximum number of tokens to generate, -1 for no limit and -2 to fill the context
+    pub num_predict: Option<i32>,
+    /// Context window of the request, capped at the max context length of the chat
+    pub num_ctx: Option<u64>,
+    pub repeat_penalty: Option<f32>,
+}
+
+impl GenerationOptions {
+    /// Checks every option set is within the range the backend accepts
+    pub fn validate(&self) -> Result<(), ChatError> {
+        let invalid = |message: String| Err(ChatError::InvalidOptions(message));
+        if let Some(temperature) = self.temperature.filter(|t| !(t.is_finite() && *t >= 0.0)) {
+            return invalid(format!("temperature must be a non-negative number, got {}", temperature));
+        }
+        if let Some(top_p) = self.top_p.filter(|p| !(0.0..=1.0).contains(p)) {
+            return invalid(format!("top_p must be between 0 and 1, got {}", top_p));
+        }
+        if self.top_k == Some(0) {
+            return invalid("top_k must be at least 1".to_string());
+        }
+        if self.stop.as_ref().is_some_and(|stop| stop.iter().any(String::is_empty)) {
+            return invalid("stop sequences must not be empty".to_string());
+        }
+        if let Some(num_predict) = self.num_predict.filter(|n| *n < -2 || *n == 0) {
+            return invalid(format!(
+                "num_predict must be at least 1, or -1 for no limit and -2 to fill the context, got {}",
+                num_predict
+            ));
+        }
+        if self.num_ctx == Some(0) {
+            return invalid("num_ctx must be at least 1".to_string());
+        }
+        if let Some(repeat_penalty) = self.repeat_penalty.filter(|p| !(p.is_finite() && *p > 0.0)) {
+            return invalid(format!("repeat_penalty must be a positive number, got {}", repeat_penalty));
+        }
+        Ok(())
+    }
+
+    /// Sets the options of `options` that are set here
+    fn apply(&self, mut options: ModelOptions) -> ModelOptions {
+        if let Some(temperature) = self.temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            options = options.top_p(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            options = options.top_k(top_k);
+        }
+        if let Some(stop) = &self.stop {
+            options = options.stop(stop.clone());
+        }
+        if let Some(num_predict) = self.num_predict {
+            options = options.num_predict(num_predict);
+        }
+        if let Some(num_ctx) = self.num_ctx {
+            options = options.num_ctx(num_ctx);
+        }
+        if let Some(repeat_penalty) = self.repeat_penalty {
+            options = options.repeat_penalty(repeat_penalty);
+        }
+        options
+    }
+}
+
+impl ChatMessages {
+    /// Appends a message, returning the id assigned to it
+    pub fn push(&mut self, message: ChatMessage) -> MessageId {
+        let id = self.next_id();
+        self.messages.push(ChatEntry::new(id, message));
+        id
+    }
+
+    /// Appends an assistant message, returning the id assigned to it
+    pub fn push_assistant(&mut self, content: impl Into<String>) -> MessageId {
+        self.push(ChatMessage::assistant(content.into()))
+    }
+
+    /// Appends the result of the tool call `tool_call_id` made by the model, returning the id assigned to it
+    ///
+    /// Sent after the reply holding the tool calls, it lets the model continue the conversation with the result. The
+    /// id is the one of the `ToolCallRequest`, and is sent along with the result.
+    pub fn push_tool_result(&mut self, tool_call_id: impl Into<String>, content: impl Into<String>) -> MessageId {
+        let id = self.next_id();
+        let message = ChatMessage::tool(content.into());
+        self.messages.push(ChatEntry { id, message, tool_call_id: Some(tool_call_id.into()) });
+        id
+    }
+
+    /// Appends user and assistant example pairs for few-shot prompting, returning the ids assigned to them
+    ///
+    /// Examples are appended after the messages already present, so a system prompt pushed first stays first.
+    pub fn push_examples(&mut self, examples: Vec<(String, String)>) -> Vec<MessageId> {
+        let mut ids = Vec::with_capacity(examples.len() * 2);
+        for (user, assistant) in examples {
+            ids.push(self.push(ChatMessage::user(user)));
+            ids.push(self.push_assistant(assistant));
+        }
+        ids
+    }
+
+    /// Removes the message with the given id, returning it if found
+    pub fn remove(&mut self, id: MessageId) -> Option<ChatMessage> {
+        let index = self.position(id)?;
+        Some(self.messages.remove(index).message)
+    }
+
+    /// Replaces the content of the message with the given id, keeping its role and id
+    pub fn replace(&mut self, id: MessageId, content: impl Into<String>) -> bool {
+        let Some(index) = self.position(id) else {
+            return false;
+        };
+        self.messages[index].content = content.into();
+        true
+    }
+
+    /// The ids of the messages, in order
+    pub fn ids(&self) -> impl Iterator<Item = MessageId> + '_ {
+        self.messages.iter().map(|entry| entry.id)
+    }
+
+    /// Numbers the messages received without an id in order, after the ids already present
+    pub fn assign_missing_ids(&mut self) {
+        let missing = |entry: &ChatEntry| entry.id == UNASSIGNED_ID;
+        if !self.messages.iter().any(missing) {
+            return;
+        }
+        for index in 0..self.messages.len() {
+            if missing(&self.messages[index]) {
+                self.messages[index].id = self.next_id();
+            }
+        }
+    }
+
+    fn position(&self, id: MessageId) -> Option<usize> {
+        self.messages.iter().position(|entry| entry.id == id)
+    }
+
+    /// Takes the next id from the counter, kept past the ids of messages that were given theirs directly
+    fn next_id(&mut self) -> MessageId {
+        let id = self
+            .messages
+            .iter()
+            .filter(|entry| entry.id != UNASSIGNED_ID)
+            .map(|entry| entry.id + 1)
+            .fold(self.next_id, MessageId::max);
+        self.next_id = id + 1;
+        id
+    }
+
+    /// Inserts a system prompt rendered from `template` before the messages, see `render_template`
+    ///
+    /// Placeholders missing from `vars` are left verbatim.
+    pub fn with_template(self, template: &str, vars: HashMap<String, String>) -> Self {
+        let prompt = render_template(template, &vars, false).unwrap_or_else(|_| template.to_string());
+        self.with_system(prompt)
+    }
+
+    /// Inserts a system prompt rendered from `template` before the messages, failing on a placeholder missing from
+    /// `vars`
+    pub fn with_strict_template(self, template: &str, vars: HashMap<String, String>) -> Result<Self, ChatError> {
+        Ok(self.with_system(render_template(template, &vars, true)?))
+    }
+
+    fn with_system(mut self, prompt: String) -> Self {
+        let id = self.next_id();
+        self.messages.insert(0, ChatEntry::new(id, ChatMessage::system(prompt)));
+        self
+    }
+}
+
+/// Substitutes the `{{key}}` placeholders of a template with their value in `vars`.
+///
+/// Whitespace around keys is ignored, and `\{{` is a literal `{{`. In strict mode a placeholder missing from `vars` or
+/// left unclosed is a `ChatError::TemplateError`, otherwise it is kept verbatim.
+/// Values are inserted as is, so placeholders in them are not substituted.
+pub fn render_template(template: &str, vars: &HashMap<String, String>, strict: bool) -> Result<String, ChatError> {
+    render(template, vars, strict, |key| ChatError::TemplateError(format!("unresolved placeholder {:?}", key)))
+}
+
+/// Escapes the placeholders of `text`, so it renders to itself when included in a template
+pub fn escape_template(text: &str) -> String {
+    text.replace("{{", "\\{{")
+}
+
+fn render(
+    template: &str,
+    vars: &HashMap<String, String>,
+    strict: bool,
+    missing: impl Fn(&str) -> ChatError,
+) -> Result<String, ChatError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            rendered.push_str(&rest[..start - 1]);
+            rendered.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest[2..].find("}}").map(|end| end + 2) else {
+            if strict {
+                return Err(ChatError::TemplateError(format!("unclosed placeholder in {:?}", rest)));
+            }
+            break;
+        };
+        let key = rest[2..end].trim();
+        match vars.get(key) {
+            Some(value) => rendered.push_str(value),
+            None if strict => return Err(missing(key)),
+            None => rendered.push_str(&rest[..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Whether Ollama refused a request because the model cannot call tools
+fn tools_unsupported(error: &ChatError) -> bool {
+    match error {
+        ChatError::OllamaStatus { status: reqwest::StatusCode::BAD_REQUEST, message }
+        | ChatError::OllamaOther(message)
+        | ChatError::OllamaInternal(message) => message.contains("does not support tools"),
+        _ => false,
+    }
+}
+
+/// Index at which the last `turns` turns of a history start, a turn starting at a user message
+fn turns_start(history: &[ChatMessage], turns: usize) -> usize {
+    if turns == 0 {
+        return history.len();
+    }
+    history
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, message)| message.role == MessageRole::User)
+        .nth(turns - 1)
+        .map_or(0, |(index, _)| index)
+}
+
+fn role_name(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "user",
+        MessageRole::Assistant => "assistant",
+        MessageRole::System => "system",
+        MessageRole::Tool => "tool",
+    }
+}
+
+/// Position of the first stop sequence in a reply
+fn stop_position(content: &str, stop: &[String]) -> Option<usize> {
+    stop.iter().filter(|stop| !stop.is_empty()).filter_map(|stop| content.find(stop.as_str())).min()
+}
+
+/// Ends a response stream at the first stop sequence, in case the backend generates past it
+fn end_at_stop(stream: ChatResponseStream, stop: Vec<String>) -> ChatResponseStream {
+    let stream = stream.scan((String::new(), false), move |(accumulated, ended), chunk| {
+        if *ended {
+            return futures::future::ready(None);
+        }
+        let chunk = chunk.map(|mut chunk| {
+            accumulated.push_str(&chunk.message.content);
+            if let Some(position) = stop_position(accumulated, &stop) {
+                let sent = accumulated.len() - chunk.message.content.len();
+                chunk.message.content = accumulated[sent.min(position)..position].to_string();
+                accumulated.truncate(position);
+                chunk.done = true;
+            }
+            chunk
+        });
+        *ended = match &chunk {
+            Ok(chunk) => chunk.done,
+            Err(_) => true,
+        };
+        futures::future::ready(Some(chunk))
+    });
+    Box::pin(stream)
+}
+
+/// Collects a response stream into a single response, as if it had not been streamed.
+///
+/// The content, thinking and tool calls of the chunks are concatenated, while the done flag and final statistics come
+/// from the last chunk. The first error in the stream is returned as is.
+pub async fn collect_stream(mut stream: ChatResponseStream) -> Result<ChatMessageResponse, ChatError> {
+    let mut collected: Option<ChatMessageResponse> = None;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let Some(response) = collected.as_mut() else {
+            collected = Some(chunk);
+            continue;
+        };
+
+        response.message.content.push_str(&chunk.message.content);
+        if let Some(thinking) = chunk.message.thinking {
+            response.message.thinking.get_or_insert_with(String::new).push_str(&thinking);
+        }
+        response.message.tool_calls.extend(chunk.message.tool_calls);
+        response.model = chunk.model;
+        response.created_at = chunk.created_at;
+        response.done = chunk.done;
+        if chunk.final_data.is_some() {
+            response.final_data = chunk.final_data;
+        }
+    }
+    collected.ok_or(ChatError::EmptyStream)
 }
 
 impl Message<ChatMessages> for Chat {
-    type Response = ChatMessageResponse;
+    type Response = ChatReply;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, request: &ChatMessages) -> Result<(), ChatError> {
         // Get stream flag, may be changed by tools
         let mut stream = request.stream;
 
-        if request.restart {
-            self.history.clear();
-        }
-
-        // Add new messages to history
-        for message in &request.messages {
-            self.history.push(message.clone());
-            if self.history.len() > self.messages_number_limit {
-                self.history.drain(..self.history.len() - self.messages_number_limit);
+        // Compress the oldest history before it outgrows the policy
+        if let Some(policy) = request.summarization.clone().or_else(|| self.summarization.clone()) {
+            if !request.restart {
+                self.summarize_history(&policy, request).await?;
             }
         }
 
-        // Prepare chat request
-        let mut chat_message_request = ChatMessageRequest::new(self.model.to_string(), self.history.clone());
-
-        // Add tools
-        if let Some(tools) = &request.tools {
-            chat_message_request.tools = tools.clone();
-            stream = false;
+        // Refuse requests that would not fit in the context window, unless trimming the history makes them fit
+        let fitted = self.fit_context(request)?;
+        let dropped = fitted.dropped.len();
+        if dropped > 0 {
+            warn!("Dropped the {} oldest messages of the {} history to fit the context", dropped, self.model);
         }
 
-        // Add generation options
-        if let Some(options) = &request.options {
-            chat_message_request = chat_message_request.options(
-                options
-                    .num_ctx
-                    .map(|num_ctx| std::cmp::min(num_ctx, self.max_context_length))
-                    .map_or_else(|| options.clone(), |context_length| options.clone().num_ctx(context_length)),
-            );
+        if self.logprobs {
+            warn!("Log probabilities are not returned through the actor, use send_raw");
         }
 
-        // Add format
-        if let Some(format) = &request.format {
-            chat_message_request = chat_message_request
-                .format(FormatType::StructuredJson(JsonStructure::from_schema(format.schema.clone())));
+        // Add new messages to history
+        self.history = fitted.messages;
+
+        // Prepare chat request, tools are not streamed
+        let (chat_message_request, stop) = self.chat_request(self.history.clone(), request)?;
+        if request.tools.is_some() {
+            stream = false;
         }
 
         // // Save chat request to debug file
@@ -184,23 +1099,43 @@ impl Message<ChatMessages> for Chat {
         //     error!("Failed to write chat request debug file: {}", e);
         // }
 
+        // Hold a request slot until the response is complete
+        let _slot = self.acquire_request_slot().await?;
+
+        let metrics = self.metrics().clone();
+        metrics.requests.inc();
+        let start = std::time::Instant::now();
+
         if stream {
-            // Get streaming response from Ollama
-            let mut stream = self.ollama.send_chat_messages_stream(chat_message_request).await?;
+            // Get streaming response from the backend
+            let backend = self.backend()?;
+            let result = self.retrying(|| backend.chat_stream(chat_message_request.clone())).await;
+            let (mut stream, attempts) = match result {
+                Ok((stream, attempts)) => (end_at_stop(stream, stop), attempts),
+                Err(e) => {
+                    metrics.errors.inc();
+                    return Err(e);
+                }
+            };
             let mut accumulated_content = String::new();
 
             // Stream responses back to caller
             while let Some(response) = stream.next().await {
                 match response {
                     Ok(chunk) => {
-                        // Send chunk through actor's reply mechanism
-                        ctx.reply(chunk.clone()).await?;
-
                         // Accumulate message content
                         accumulated_content.push_str(&chunk.message.content);
 
+                        // Send chunk through actor's reply mechanism
+                        let usage = Usage::of(&chunk);
+                        let reply = ChatReply { response: chunk.clone(), dropped, attempts, usage, tool_calls: vec![] };
+                        ctx.reply(reply).await?;
+
                         // If this is the final message, add the complete message to history
                         if chunk.done {
+                            metrics.record(&chunk, start.elapsed());
+                            self.stats.responses += 1;
+                            self.stats.usage += usage.unwrap_or_default();
                             if !accumulated_content.is_empty() {
                                 self.history.push(ChatMessage::assistant(accumulated_content.clone()));
                             }
@@ -210,49 +1145,105 @@ impl Message<ChatMessages> for Chat {
                                 self.history = self
                                     .history
                                     .iter()
-                                    .filter(|msg| msg.role != ollama_rs::generation::chat::MessageRole::System)
+                                    .filter(|msg| msg.role != MessageRole::System)
                                     .cloned()
                                     .collect();
                                 self.save(ctx).await?;
                             }
                         }
                     }
-                    Err(_) => {
-                        error!("Error in chat stream");
+                    Err(e) => {
+                        error!("Error in chat stream: {}", e);
+                        metrics.errors.inc();
                         break;
                     }
                 }
             }
         } else {
-            // Send the messages to the ollama client
-            let result = self.ollama.send_chat_messages(chat_message_request).await?;
+            // Send the messages to the backend
+            let (mut result, attempts) = match self.send_chat(chat_message_request).await {
+                Ok(result) => result,
+                Err(e) => {
+                    metrics.errors.inc();
+                    return Err(e);
+                }
+            };
+            metrics.record(&result, start.elapsed());
+            let usage = Usage::of(&result);
+            self.stats.responses += 1;
+            self.stats.usage += usage.unwrap_or_default();
+
+            if let Some(position) = stop_position(&result.message.content, &stop) {
+                result.message.content.truncate(position);
+            }
+
+            // Malformed tool calls fail the request rather than reach the caller
+            let tools = request.tools.as_deref().unwrap_or_default();
+            let parsed: Result<Vec<_>, _> = result
+                .message
+                .tool_calls
+                .iter()
+                .enumerate()
+                .map(|(position, call)| ToolCallRequest::parse(position, call, tools))
+                .collect();
+            let tool_calls = match parsed {
+                Ok(tool_calls) => tool_calls,
+                Err(e) => {
+                    metrics.errors.inc();
+                    return Err(e);
+                }
+            };
 
             // Add the response message to the history only if its an assistant message
-            if result.message.role == ollama_rs::generation::chat::MessageRole::Assistant {
+            if result.message.role == MessageRole::Assistant {
                 self.history.push(result.message.clone());
             }
 
             if request.persist {
                 // Filter out system messages before saving
-                self.history = self
-                    .history
-                    .iter()
-                    .filter(|msg| msg.role != ollama_rs::generation::chat::MessageRole::System)
-                    .cloned()
-                    .collect();
+                self.history = self.history.iter().filter(|msg| msg.role != MessageRole::System).cloned().collect();
                 self.save(ctx).await?;
             }
 
-            ctx.reply(result).await?;
+            ctx.reply(ChatReply { response: result, dropped, attempts, usage, tool_calls }).await?;
         }
 
         Ok(())
     }
 }
 
+impl Message<GetChatStats> for Chat {
+    type Response = ChatStats;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _request: &GetChatStats) -> Result<(), ChatError> {
+        ctx.reply(self.stats.clone()).await?;
+        Ok(())
+    }
+}
+
+impl Message<HealthCheck> for Chat {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _request: &HealthCheck) -> Result<(), ChatError> {
+        let start = std::time::Instant::now();
+        let result = self.backend()?.health().await;
+        let latency = start.elapsed();
+        let status = match result {
+            Ok(()) => HealthStatus::healthy(latency, format!("Chat backend at {} is reachable", self.endpoint)),
+            Err(e) => HealthStatus::unhealthy(latency, format!("Chat backend at {} failed: {}", self.endpoint, e)),
+        };
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
 impl Actor for Chat {
     type Error = ChatError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), ChatError> {
         info!("{} Started", ctx.id());
 
@@ -260,11 +1251,23 @@ impl Actor for Chat {
 
         let mut stream = ctx.recv().await?;
         while let Some(Ok(frame)) = stream.next().await {
-            if let Some(chat_messages) = frame.is::<ChatMessages>() {
+            if let Some(mut chat_messages) = frame.is::<ChatMessages>() {
+                // Clients predating message ids send none, number them on receipt
+                chat_messages.assign_missing_ids();
                 let response = self.reply(ctx, &chat_messages, &frame).await;
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(get_stats) = frame.is::<GetChatStats>() {
+                let response = self.reply(ctx, &get_stats, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
+            } else if let Some(health_check) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &health_check, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
         info!("{} Finished", ctx.id());
@@ -272,9 +1275,479 @@ impl Actor for Chat {
     }
 }
 
+impl Supervised for Chat {
+    /// Keeps the configured backend, restoring the history persisted before the restart
+    fn restore(config: Self, persisted: Self) -> Self {
+        Self { history: persisted.history, ..config }
+    }
+
+    fn spawn_supervised(self, engine: Engine, id: ActorId, options: SpawnOptions, link: SupervisorLink) -> ActorHandle {
+        tokio::spawn(run_supervised(engine, id, self, options, link))
+    }
+}
+
+/// The messages of a request that fit in the context window, see `Chat::fit_context`
+#[derive(Debug, Clone, Default)]
+pub struct FittedMessages {
+    /// The messages sent, in order
+    pub messages: Vec<ChatMessage>,
+    /// The oldest messages left out to make room, in order
+    pub dropped: Vec<ChatMessage>,
+}
+
+/// The reply of a chat actor to `ChatMessages`, one per chunk when streaming
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatReply {
+    #[serde(flatten)]
+    pub response: ChatMessageResponse,
+    /// Number of the oldest history messages left out to fit the context window
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped: usize,
+    /// Number of requests sent to the backend before it answered, retries included
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub attempts: u32,
+    /// Tokens used by the request, on the final chunk when streaming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// The tool calls of the reply, checked against the tools of the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCallRequest>,
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl std::ops::Deref for ChatReply {
+    type Target = ChatMessageResponse;
+
+    fn deref(&self) -> &ChatMessageResponse {
+        &self.response
+    }
+}
+
+/// A call of the model to one of the tools it was given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRequest {
+    /// Id of the call among the calls of its reply, to push its result with
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ToolCallRequest {
+    /// Parses the tool call at `position` among the calls of a reply, failing with `ChatError::ToolCall` if it names a
+    /// tool missing from `tools` or its arguments are not a JSON object.
+    ///
+    /// Arguments sent as a string holding a JSON object, as some models do, are parsed.
+    pub fn parse(position: usize, call: &ToolCall, tools: &[ToolInfo]) -> Result<Self, ChatError> {
+        let name = &call.function.name;
+        if !tools.iter().any(|tool| &tool.function.name == name) {
+            return Err(ChatError::ToolCall(format!("model called the unknown tool {:?}", name)));
+        }
+        let arguments = match &call.function.arguments {
+            serde_json::Value::Object(arguments) => Some(arguments.clone()),
+            serde_json::Value::String(arguments) => serde_json::from_str(arguments).ok(),
+            _ => None,
+        };
+        let Some(arguments) = arguments else {
+            return Err(ChatError::ToolCall(format!(
+                "arguments of the call to {:?} are not a JSON object: {}",
+                name, call.function.arguments
+            )));
+        };
+        Ok(Self { id: tool_call_id(position), name: name.clone(), arguments })
+    }
+
+    /// Deserializes the arguments into the parameters type of the tool
+    pub fn arguments<T: serde::de::DeserializeOwned>(&self) -> Result<T, ChatError> {
+        serde_json::from_value(serde_json::Value::Object(self.arguments.clone()))
+            .map_err(|e| ChatError::ToolCall(format!("invalid arguments of the call to {:?}: {}", self.name, e)))
+    }
+}
+
+/// A chat response together with the exact JSON returned by Ollama
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawChatResponse {
+    pub response: ChatMessageResponse,
+    pub raw: serde_json::Value,
+    /// Log probabilities of the generated tokens, when requested and returned by the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+}
+
+/// The log probability of a generated token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    /// The most likely alternatives at this position
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TokenLogprob>,
+}
+
 impl Chat {
+    fn validate(&self) -> Result<(), ChatError> {
+        if self.model.trim().is_empty() {
+            return Err(ChatError::InvalidConfig("model name is empty".to_string()));
+        }
+        if !matches!(self.endpoint.scheme(), "http" | "https") {
+            return Err(ChatError::InvalidConfig(format!("endpoint {} is not an HTTP URL", self.endpoint)));
+        }
+        if self.messages_number_limit == 0 {
+            return Err(ChatError::InvalidConfig("messages number limit must be at least 1".to_string()));
+        }
+        if self.max_context_length == 0 {
+            return Err(ChatError::InvalidConfig("max context length must be at least 1".to_string()));
+        }
+        if self.max_concurrent_requests == 0 {
+            return Err(ChatError::InvalidConfig("max concurrent requests must be at least 1".to_string()));
+        }
+        if self.max_context_tokens == Some(0) {
+            return Err(ChatError::InvalidConfig("max context tokens must be at least 1".to_string()));
+        }
+        if self.retry.as_ref().is_some_and(|retry| !(0.0..=1.0).contains(&retry.jitter)) {
+            return Err(ChatError::InvalidConfig("retry jitter must be between 0 and 1".to_string()));
+        }
+        if self.retry.as_ref().is_some_and(|retry| !(1.0..).contains(&retry.multiplier)) {
+            return Err(ChatError::InvalidConfig("retry multiplier must be at least 1".to_string()));
+        }
+        if self
+            .summarization
+            .as_ref()
+            .is_some_and(|policy| policy.max_tokens.is_none() && policy.max_messages.is_none())
+        {
+            return Err(ChatError::InvalidConfig("summarization needs max tokens or max messages".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sends messages to Ollama without streaming, returning the raw JSON alongside the parsed response.
+    ///
+    /// This bypasses the history and is meant for debugging what the model returns.
+    pub async fn send_raw(&self, messages: Vec<ChatMessage>) -> Result<RawChatResponse, ChatError> {
+        let request = ChatMessageRequest::new(self.model.to_string(), messages);
+        let mut body = serde_json::to_value(&request).map_err(ChatError::JsonError)?;
+        body["stream"] = serde_json::Value::Bool(false);
+        if self.logprobs {
+            body["logprobs"] = serde_json::Value::Bool(true);
+        }
+
+        let url = self.endpoint.join("api/chat").map_err(|e| ChatError::OllamaOther(e.to_string()))?;
+        let _slot = self.acquire_request_slot().await?;
+        let bytes = reqwest::Client::new()
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(ChatError::ReqwestError)?
+            .bytes()
+            .await
+            .map_err(ChatError::ReqwestError)?;
+
+        let raw: serde_json::Value = serde_json::from_slice(&bytes).map_err(ChatError::JsonError)?;
+        let response = serde_json::from_value(raw.clone()).map_err(ChatError::JsonError)?;
+        let logprobs = match raw.get("logprobs") {
+            Some(logprobs) if self.logprobs && !logprobs.is_null() => {
+                Some(serde_json::from_value(logprobs.clone()).map_err(ChatError::JsonError)?)
+            }
+            _ => {
+                if self.logprobs {
+                    warn!("Model {} did not return log probabilities", self.model);
+                }
+                None
+            }
+        };
+        Ok(RawChatResponse { response, raw, logprobs })
+    }
+
+    /// Streams the reply to the history followed by `request`, without recording the exchange in the history.
+    ///
+    /// Each chunk carries the next part of the content, and the last one is marked as done with the final statistics.
+    /// Dropping the stream closes the connection to the backend, which stops generating, and frees the request slot.
+    /// Unlike streaming through the actor, the reply can be consumed without a relay.
+    pub async fn stream(&self, request: &ChatMessages) -> Result<ChatResponseStream, ChatError> {
+        let (chat_message_request, stop) = self.chat_request(self.fit_context(request)?.messages, request)?;
+
+        let slot = self.acquire_request_slot().await?;
+        let metrics = self.metrics().clone();
+        metrics.requests.inc();
+        let start = std::time::Instant::now();
+
+        // Not initialized by an actor, talk to Ollama at `endpoint` directly
+        let backend = self.backend.clone().unwrap_or_else(|| Arc::new(OllamaBackend::new(self.endpoint.clone())));
+        let stream = match self.retrying(|| backend.chat_stream(chat_message_request.clone())).await {
+            Ok((stream, _)) => end_at_stop(stream, stop),
+            Err(e) => {
+                metrics.errors.inc();
+                return Err(e);
+            }
+        };
+
+        // The slot is held by the stream until it is dropped
+        let stream = stream.inspect(move |chunk| {
+            let _slot = &slot;
+            match chunk {
+                Ok(chunk) if chunk.done => metrics.record(chunk, start.elapsed()),
+                Ok(_) => {}
+                Err(_) => metrics.errors.inc(),
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    /// Estimates the prompt tokens of `request`, including the system prompt and the history sent along with it.
+    ///
+    /// Every message adds a fixed overhead for its role, and every attached image a fixed number of tokens.
+    pub fn estimate_tokens(&self, request: &ChatMessages) -> usize {
+        let prompt = self.system_template.as_ref().map(|template| {
+            let prompt = render_template(template, &request.template_vars, false).unwrap_or_else(|_| template.clone());
+            self.message_tokens(&ChatMessage::system(prompt))
+        });
+        prompt.unwrap_or(0)
+            + self.request_messages(request).iter().map(|message| self.message_tokens(message)).sum::<usize>()
+    }
+
+    /// The system prompt rendered from `system_template`, failing on a variable missing from the request
+    fn system_prompt(&self, request: &ChatMessages) -> Result<Option<ChatMessage>, ChatError> {
+        self.system_template
+            .as_ref()
+            .map(|template| {
+                render(template, &request.template_vars, true, |key| ChatError::MissingTemplateVar(key.to_string()))
+                    .map(ChatMessage::system)
+            })
+            .transpose()
+    }
+
+    fn message_tokens(&self, message: &ChatMessage) -> usize {
+        let tokenizer = self.tokenizer.as_deref().unwrap_or(&HeuristicTokenizer);
+        let images = message.images.as_ref().map_or(0, |images| images.len());
+        MESSAGE_OVERHEAD_TOKENS + tokenizer.count(&message.content) + images * IMAGE_TOKENS
+    }
+
+    /// Replaces the history before the last `keep_turns` turns with a summary if `request` exceeds the policy
+    async fn summarize_history(
+        &mut self,
+        policy: &SummarizationPolicy,
+        request: &ChatMessages,
+    ) -> Result<(), ChatError> {
+        let used = self.estimate_tokens(request);
+        let messages = self.request_messages(request).len();
+        let over_tokens = policy.max_tokens.is_some_and(|max_tokens| used > max_tokens);
+        let over_messages = policy.max_messages.is_some_and(|max_messages| messages > max_messages);
+        if !over_tokens && !over_messages {
+            return Ok(());
+        }
+
+        // System prompts stay as they are, earlier summaries are folded into the new one
+        let split = turns_start(&self.history, policy.keep_turns.max(MIN_KEEP_TURNS));
+        let (kept, summarized): (Vec<_>, Vec<_>) = self.history[..split]
+            .iter()
+            .cloned()
+            .partition(|message| message.role == MessageRole::System && !message.content.starts_with(SUMMARY_PREFIX));
+        if summarized.is_empty() {
+            return Ok(());
+        }
+
+        let transcript = summarized
+            .iter()
+            .map(|message| format!("{}: {}", role_name(&message.role), message.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let model = policy.model.clone().unwrap_or_else(|| self.model.to_string());
+        let summary_request = ChatRequest::new(ChatMessageRequest::new(
+            model,
+            vec![ChatMessage::system(policy.prompt.clone()), ChatMessage::user(transcript)],
+        ));
+        let (summary, _) = {
+            let _slot = self.acquire_request_slot().await?;
+            self.send_chat(summary_request).await?
+        };
+
+        let usage = Usage::of(&summary).unwrap_or_default();
+        let metrics = self.metrics();
+        metrics.summarizations.inc();
+        metrics.summarization_tokens.inc_by(usage.total_tokens());
+        self.stats.summaries += 1;
+        self.stats.usage += usage;
+
+        let summary = format!("{}{}", SUMMARY_PREFIX, summary.message.content.trim());
+        self.history.splice(..split, kept.into_iter().chain([ChatMessage::system(summary)]));
+        info!(
+            "Summarized {} messages of the {} history, from {} to {} prompt tokens",
+            summarized.len(),
+            self.model,
+            used,
+            self.estimate_tokens(request)
+        );
+        Ok(())
+    }
+
+    /// The messages sent for `request`, fitted within `max_context_tokens`.
+    ///
+    /// With `trim_history` set, the oldest messages are dropped until the rest fits, except system prompts and the
+    /// latest user message. Fails with `ChatError::ContextOverflow` if the messages still do not fit.
+    pub fn fit_context(&self, request: &ChatMessages) -> Result<FittedMessages, ChatError> {
+        let prompt = self.system_prompt(request)?;
+        let messages = self.request_messages(request);
+        let Some(limit) = self.max_context_tokens else {
+            return Ok(FittedMessages { messages, dropped: vec![] });
+        };
+
+        let tokens: Vec<usize> = messages.iter().map(|message| self.message_tokens(message)).collect();
+        let mut used = prompt.map_or(0, |prompt| self.message_tokens(&prompt)) + tokens.iter().sum::<usize>();
+        let mut dropped = vec![false; messages.len()];
+        if self.trim_history {
+            let latest_user = messages.iter().rposition(|message| message.role == MessageRole::User);
+            for (index, message) in messages.iter().enumerate() {
+                if used <= limit {
+                    break;
+                }
+                if message.role != MessageRole::System && Some(index) != latest_user {
+                    used -= tokens[index];
+                    dropped[index] = true;
+                }
+            }
+        }
+        if used > limit {
+            return Err(ChatError::ContextOverflow { used, limit });
+        }
+
+        let (dropped, kept): (Vec<_>, Vec<_>) = messages.into_iter().zip(dropped).partition(|(_, dropped)| *dropped);
+        Ok(FittedMessages {
+            messages: kept.into_iter().map(|(message, _)| message).collect(),
+            dropped: dropped.into_iter().map(|(message, _)| message).collect(),
+        })
+    }
+
+    /// The messages sent for `request`: the history unless restarting, then its messages, within the limit
+    fn request_messages(&self, request: &ChatMessages) -> Vec<ChatMessage> {
+        let mut messages = if request.restart { vec![] } else { self.history.clone() };
+        messages.extend(request.messages.iter().map(|entry| entry.message.clone()));
+        messages.drain(..messages.len().saturating_sub(self.messages_number_limit));
+        messages
+    }
+
+    /// Builds the request sending `messages` after the system prompt, with the tools, options and format of `request`.
+    ///
+    /// The messages of `request` end `messages`, so their tool call ids are matched from the end. Returns the request
+    /// along with the stop sequences the reply ends at.
+    fn chat_request(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        request: &ChatMessages,
+    ) -> Result<(ChatRequest, Vec<String>), ChatError> {
+        if let Some(prompt) = self.system_prompt(request)? {
+            messages.insert(0, prompt);
+        }
+        let sent = &request.messages[request.messages.len().saturating_sub(messages.len())..];
+        let mut tool_call_ids = vec![None; messages.len() - sent.len()];
+        tool_call_ids.extend(sent.iter().map(|entry| entry.tool_call_id.clone()));
+        let mut chat_message_request = ChatMessageRequest::new(self.model.to_string(), messages);
+
+        // Add tools
+        if let Some(tools) = &request.tools {
+            chat_message_request.tools = tools.clone();
+        }
+
+        // Add generation options
+        let mut options = request.options.clone();
+        if let Some(generation) = &request.generation {
+            generation.validate()?;
+            options = Some(generation.apply(options.unwrap_or_default()));
+        }
+        if !self.stop.is_empty() && !options.as_ref().is_some_and(|options| options.stop.is_some()) {
+            options = Some(options.unwrap_or_default().stop(self.stop.clone()));
+        }
+        let stop = options.as_ref().and_then(|options| options.stop.clone()).unwrap_or_default();
+        if let Some(options) = &options {
+            chat_message_request = chat_message_request.options(
+                options
+                    .num_ctx
+                    .map(|num_ctx| std::cmp::min(num_ctx, self.max_context_length))
+                    .map_or_else(|| options.clone(), |context_length| options.clone().num_ctx(context_length)),
+            );
+        }
+
+        // Add format
+        if let Some(format) = &request.format {
+            chat_message_request = chat_message_request
+                .format(FormatType::StructuredJson(JsonStructure::from_schema(format.schema.clone())));
+        }
+
+        Ok((ChatRequest { request: chat_message_request, tool_call_ids }, stop))
+    }
+
+    /// Sends `request` to the backend, again without its tools if the model does not support them.
+    ///
+    /// The reply then has no tool calls, as for a model choosing not to call any. The number of attempts counts the
+    /// refused request too.
+    async fn send_chat(&self, mut request: ChatRequest) -> Result<(ChatMessageResponse, u32), ChatError> {
+        let backend = self.backend()?;
+        let result = self.retrying(|| backend.chat(request.clone())).await;
+        match result {
+            Err(e) if !request.tools.is_empty() && tools_unsupported(&e) => {
+                warn!("Model {} does not support tools, sending the request without them", request.model_name);
+                request.tools.clear();
+                let (response, attempts) = self.retrying(|| backend.chat(request.clone())).await?;
+                Ok((response, attempts + 1))
+            }
+            result => result,
+        }
+    }
+
+    /// Runs `send` until it succeeds, retrying transient failures as configured by `retry`.
+    ///
+    /// Returns the value along with the number of attempts it took. An error after retries is a
+    /// `ChatError::RetryFailed` telling the number of attempts.
+    async fn retrying<T, F, Fut>(&self, mut send: F) -> Result<(T, u32), ChatError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ChatError>>,
+    {
+        let mut attempts = 1;
+        loop {
+            let error = match send().await {
+                Ok(value) => return Ok((value, attempts)),
+                Err(error) => error,
+            };
+            match &self.retry {
+                Some(retry) if attempts <= retry.max_retries && is_transient(&error) => {
+                    let delay = retry.delay(attempts);
+                    warn!(
+                        "Chat request to {} failed (attempt {}), retrying in {:?}: {}",
+                        self.model, attempts, delay, error
+                    );
+                    self.metrics().retries.inc();
+                    tokio::time::sleep(delay).await;
+                    attempts += 1;
+                }
+                _ if attempts > 1 => return Err(ChatError::RetryFailed { attempts, source: Box::new(error) }),
+                _ => return Err(error),
+            }
+        }
+    }
+
+    /// Waits for a free request slot, limiting concurrent requests to `max_concurrent_requests`
+    async fn acquire_request_slot(&self) -> Result<OwnedSemaphorePermit, ChatError> {
+        let slots = self.request_slots.get_or_init(|| Arc::new(Semaphore::new(self.max_concurrent_requests.max(1))));
+        slots.clone().acquire_owned().await.map_err(|e| ChatError::OllamaOther(e.to_string()))
+    }
+
+    fn metrics(&self) -> &ChatMetrics {
+        self.metrics.get_or_init(|| ChatMetrics::new(&self.model))
+    }
+
+    fn backend(&self) -> Result<Arc<dyn ChatBackend>, ChatError> {
+        self.backend.clone().ok_or(ChatError::OllamaNotInitialized)
+    }
+
     pub async fn init(&mut self, _ctx: &mut ActorContext<Self>) -> Result<(), ChatError> {
-        self.ollama = Ollama::from_url(self.endpoint.clone());
+        if self.backend.is_none() {
+            self.backend = Some(Arc::new(OllamaBackend::new(self.endpoint.clone())));
+        }
         Ok(())
     }
 }
@@ -0,0 +1,198 @@
+//! Configuration loading shared by the bioma actors.
+//!
+//! Documents are read from TOML or JSON files and overridden by environment variables named after the key path,
+//! for example `BIOMA__CHAT__MODEL` for `chat.model`. Every error names the key it was found at.
+//!
+//! This module holds the loading and the sections of the actors of this crate. The sections of the RAG actors, and
+//! the `BiomaConfig` gathering all of them, are in `bioma_rag::config`, as this crate cannot depend on the actors
+//! defined there.
+
+use crate::chat::Chat;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Prefix of the environment variables overriding configuration keys
+const ENV_PREFIX: &str = "BIOMA__";
+/// Separator between the keys of a nested environment variable override
+const ENV_SEPARATOR: &str = "__";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {1}", .0.display())]
+    Io(PathBuf, std::io::Error),
+    #[error("Failed to parse {}: {1}", .0.display())]
+    Parse(PathBuf, String),
+    #[error("Unsupported config format: {}", .0.display())]
+    UnsupportedFormat(PathBuf),
+    #[error("Invalid value for `{key}`: {message}")]
+    Invalid { key: String, message: String },
+}
+
+/// Ollama server shared by the actors that talk to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaConfig {
+    #[serde(default = "default_ollama_endpoint")]
+    pub endpoint: Url,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self { endpoint: default_ollama_endpoint() }
+    }
+}
+
+fn default_ollama_endpoint() -> Url {
+    Chat::default().endpoint
+}
+
+/// The `chat` section, building the chat actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatConfig {
+    #[serde(default = "default_chat_model")]
+    pub model: Cow<'static, str>,
+    /// Endpoint of the chat model, the shared Ollama endpoint if not set
+    #[serde(default)]
+    pub endpoint: Option<Url>,
+    #[serde(default = "default_chat_messages_number_limit")]
+    pub messages_number_limit: usize,
+    #[serde(default = "default_chat_max_context_length")]
+    pub max_context_length: u64,
+    #[serde(default = "default_chat_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            model: default_chat_model(),
+            endpoint: None,
+            messages_number_limit: default_chat_messages_number_limit(),
+            max_context_length: default_chat_max_context_length(),
+            max_concurrent_requests: default_chat_max_concurrent_requests(),
+        }
+    }
+}
+
+fn default_chat_model() -> Cow<'static, str> {
+    Chat::default().model
+}
+
+fn default_chat_messages_number_limit() -> usize {
+    Chat::default().messages_number_limit
+}
+
+fn default_chat_max_context_length() -> u64 {
+    Chat::default().max_context_length
+}
+
+fn default_chat_max_concurrent_requests() -> usize {
+    Chat::default().max_concurrent_requests
+}
+
+impl ChatConfig {
+    /// Checks every value the chat builder would reject, reporting the key it was set at
+    pub fn validate(&self, ollama: &OllamaConfig) -> Result<(), ConfigError> {
+        ensure(!self.model.trim().is_empty(), "chat.model", "model name is empty")?;
+        match &self.endpoint {
+            Some(endpoint) => ensure_http_url(endpoint, "chat.endpoint")?,
+            None => ensure_http_url(&ollama.endpoint, "ollama.endpoint")?,
+        }
+        ensure(self.messages_number_limit > 0, "chat.messages_number_limit", "must be at least 1")?;
+        ensure(self.max_context_length > 0, "chat.max_context_length", "must be at least 1")?;
+        ensure(self.max_concurrent_requests > 0, "chat.max_concurrent_requests", "must be at least 1")
+    }
+
+    /// Builds the chat actor, talking to the shared Ollama server unless the section sets its own endpoint
+    pub fn chat(&self, ollama: &OllamaConfig) -> Result<Chat, ConfigError> {
+        self.validate(ollama)?;
+        Chat::builder()
+            .model(self.model.clone())
+            .endpoint(self.endpoint.clone().unwrap_or_else(|| ollama.endpoint.clone()))
+            .messages_number_limit(self.messages_number_limit)
+            .max_context_length(self.max_context_length)
+            .max_concurrent_requests(self.max_concurrent_requests)
+            .build()
+            .map_err(invalid("chat"))
+    }
+}
+
+/// Reads a TOML or JSON document, picking the format from the file extension
+pub fn read_file(path: &Path) -> Result<Value, ConfigError> {
+    let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => {
+            let value: toml::Value =
+                toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))?;
+            serde_json::to_value(value).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))
+        }
+        Some("json") => {
+            serde_json::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))
+        }
+        _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+/// Applies the `BIOMA__` prefixed overrides of `env` to a parsed document, other variables are ignored.
+///
+/// Override values are parsed as JSON when possible, so numbers and booleans keep their type, and are taken as
+/// plain strings otherwise.
+pub fn apply_env(value: &mut Value, env: impl IntoIterator<Item = (String, String)>) -> Result<(), ConfigError> {
+    for (name, raw) in env {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split(ENV_SEPARATOR).map(|key| key.to_lowercase()).collect();
+        let override_value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+        set_path(value, &keys, override_value)
+            .map_err(|message| ConfigError::Invalid { key: keys.join("."), message })?;
+    }
+    Ok(())
+}
+
+/// Deserializes a document, reporting the key of the first value that does not fit
+pub fn deserialize<T: DeserializeOwned>(value: Value) -> Result<T, ConfigError> {
+    serde_path_to_error::deserialize(value)
+        .map_err(|e| ConfigError::Invalid { key: e.path().to_string(), message: e.inner().to_string() })
+}
+
+/// Fails with an invalid value at `key` unless the condition holds
+pub fn ensure(condition: bool, key: &str, message: &str) -> Result<(), ConfigError> {
+    if condition {
+        return Ok(());
+    }
+    Err(ConfigError::Invalid { key: key.to_string(), message: message.to_string() })
+}
+
+/// Fails with an invalid value at `key` unless the URL is an HTTP URL with a host
+pub fn ensure_http_url(url: &Url, key: &str) -> Result<(), ConfigError> {
+    ensure(matches!(url.scheme(), "http" | "https") && url.has_host(), key, &format!("{} is not an HTTP URL", url))
+}
+
+/// Maps an actor build error not covered by the key checks to an invalid value of its section
+pub fn invalid<E: std::fmt::Display>(key: &str) -> impl FnOnce(E) -> ConfigError + '_ {
+    move |e| ConfigError::Invalid { key: key.to_string(), message: e.to_string() }
+}
+
+/// Sets the value at the given key path, creating the intermediate tables as needed
+fn set_path(value: &mut Value, keys: &[String], new_value: Value) -> Result<(), String> {
+    let Some((last, parents)) = keys.split_last() else {
+        return Err("empty key".to_string());
+    };
+    let mut current = value;
+    for key in parents {
+        let Value::Object(table) = current else {
+            return Err("overrides a value that is not a table".to_string());
+        };
+        current = table.entry(key.clone()).or_insert_with(|| Value::Object(Default::default()));
+    }
+    let Value::Object(table) = current else {
+        return Err("overrides a value that is not a table".to_string());
+    };
+    table.insert(last.clone(), new_value);
+    Ok(())
+}
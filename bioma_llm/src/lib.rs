@@ -1,9 +1,18 @@
 pub mod chat;
+pub mod config;
+pub mod metrics;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub mod prelude {
-    pub use crate::chat::{self, Chat, ChatError, ChatMessages};
+    pub use crate::chat::{
+        self, Chat, ChatBackend, ChatEntry, ChatError, ChatMessages, ChatReply, ChatRequest, ChatResponseStream,
+        ChatStats, FittedMessages, GenerationOptions, GetChatStats, HeuristicTokenizer, MessageId, OllamaBackend,
+        RawChatResponse, RetryConfig, SummarizationPolicy, TokenLogprob, Tokenizer, ToolCallRequest, Usage,
+    };
+    pub use crate::metrics::{self, Counter, Histogram};
     pub use ollama_rs::generation::{
-        chat::{ChatMessage, ChatMessageResponse, MessageRole},
+        chat::{request::ChatMessageRequest, ChatMessage, ChatMessageResponse, MessageRole},
         images::Image,
         tools::{ToolCall, ToolInfo},
     };
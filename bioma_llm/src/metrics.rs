@@ -0,0 +1,245 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Histogram buckets for durations, in seconds
+pub const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Histogram buckets for batch sizes
+pub const SIZE_BUCKETS: &[f64] = &[1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0];
+
+/// The registry actors record their metrics into
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Renders every registered metric in the Prometheus text exposition format, empty if metrics are disabled
+pub fn render() -> String {
+    registry().render()
+}
+
+/// A monotonically increasing count
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    #[cfg(feature = "metrics")]
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, value: u64) {
+        #[cfg(feature = "metrics")]
+        self.value.fetch_add(value, Ordering::Relaxed);
+        #[cfg(not(feature = "metrics"))]
+        let _ = value;
+    }
+}
+
+/// Observations counted in buckets, along with their sum
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    #[cfg(feature = "metrics")]
+    cells: Arc<HistogramCells>,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+struct HistogramCells {
+    bounds: Vec<f64>,
+    /// Observations per bucket, made cumulative when rendered
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    /// Bits of the `f64` sum
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: f64) {
+        #[cfg(feature = "metrics")]
+        {
+            let cells = &self.cells;
+            if let Some(bucket) = cells.bounds.iter().position(|bound| value <= *bound) {
+                cells.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+            }
+            cells.count.fetch_add(1, Ordering::Relaxed);
+            let _ = cells.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = value;
+    }
+
+    pub fn observe_duration(&self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+}
+
+/// Metrics registered by name and labels.
+///
+/// Registering takes a lock and is meant to be done once, when an actor starts; the returned handles record with
+/// atomic operations only. Without the `metrics` feature, handles record nothing and the registry stays empty.
+#[derive(Debug, Default)]
+pub struct Registry {
+    #[cfg(feature = "metrics")]
+    families: Mutex<Vec<Family>>,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+struct Family {
+    name: String,
+    help: String,
+    series: Vec<(Vec<(String, String)>, Series)>,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+enum Series {
+    Counter(Counter),
+    Histogram(Histogram),
+}
+
+impl Registry {
+    /// Returns the counter with the given name and labels, registering it on first use
+    pub fn counter(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Counter {
+        #[cfg(feature = "metrics")]
+        {
+            match self.series(name, help, labels, || Series::Counter(Counter::default())) {
+                Series::Counter(counter) => counter,
+                Series::Histogram(_) => {
+                    tracing::warn!("Metric {} is already registered as a histogram", name);
+                    Counter::default()
+                }
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = (name, help, labels);
+            Counter::default()
+        }
+    }
+
+    /// Returns the histogram with the given name and labels, registering it with `buckets` on first use
+    pub fn histogram(&self, name: &str, help: &str, labels: &[(&str, &str)], buckets: &[f64]) -> Histogram {
+        #[cfg(feature = "metrics")]
+        {
+            let new = || {
+                let cells = HistogramCells {
+                    bounds: buckets.to_vec(),
+                    buckets: buckets.iter().map(|_| AtomicU64::new(0)).collect(),
+                    ..Default::default()
+                };
+                Series::Histogram(Histogram { cells: Arc::new(cells) })
+            };
+            match self.series(name, help, labels, new) {
+                Series::Histogram(histogram) => histogram,
+                Series::Counter(_) => {
+                    tracing::warn!("Metric {} is already registered as a counter", name);
+                    Histogram::default()
+                }
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = (name, help, labels, buckets);
+            Histogram::default()
+        }
+    }
+
+    /// Renders every registered metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        #[cfg(feature = "metrics")]
+        {
+            let mut output = String::new();
+            for family in self.families.lock().unwrap().iter() {
+                let kind = match family.series.first() {
+                    Some((_, Series::Histogram(_))) => "histogram",
+                    _ => "counter",
+                };
+                let _ = writeln!(output, "# HELP {} {}", family.name, family.help);
+                let _ = writeln!(output, "# TYPE {} {}", family.name, kind);
+                for (labels, series) in &family.series {
+                    render_series(&mut output, &family.name, labels, series);
+                }
+            }
+            output
+        }
+        #[cfg(not(feature = "metrics"))]
+        String::new()
+    }
+
+    #[cfg(feature = "metrics")]
+    fn series(&self, name: &str, help: &str, labels: &[(&str, &str)], new: impl FnOnce() -> Series) -> Series {
+        let labels: Vec<(String, String)> = labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut families = self.families.lock().unwrap();
+        let index = match families.iter().position(|family| family.name == name) {
+            Some(index) => index,
+            None => {
+                families.push(Family { name: name.to_string(), help: help.to_string(), series: vec![] });
+                families.len() - 1
+            }
+        };
+        let family = &mut families[index];
+        if let Some((_, series)) = family.series.iter().find(|(existing, _)| *existing == labels) {
+            return series.clone();
+        }
+        let series = new();
+        family.series.push((labels, series.clone()));
+        series
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn render_series(output: &mut String, name: &str, labels: &[(String, String)], series: &Series) {
+    match series {
+        Series::Counter(counter) => {
+            let value = counter.value.load(Ordering::Relaxed);
+            let _ = writeln!(output, "{}{} {}", name, format_labels(labels, None), value);
+        }
+        Series::Histogram(histogram) => {
+            let cells = &histogram.cells;
+            let mut cumulative = 0;
+            for (bound, bucket) in cells.bounds.iter().zip(&cells.buckets) {
+                cumulative += bucket.load(Ordering::Relaxed);
+                let le = format_labels(labels, Some(&bound.to_string()));
+                let _ = writeln!(output, "{}_bucket{} {}", name, le, cumulative);
+            }
+            let count = cells.count.load(Ordering::Relaxed);
+            let sum = f64::from_bits(cells.sum.load(Ordering::Relaxed));
+            let _ = writeln!(output, "{}_bucket{} {}", name, format_labels(labels, Some("+Inf")), count);
+            let _ = writeln!(output, "{}_sum{} {}", name, format_labels(labels, None), sum);
+            let _ = writeln!(output, "{}_count{} {}", name, format_labels(labels, None), count);
+        }
+    }
+}
+
+/// Formats labels as `{name="value",...}`, with an optional `le` bucket bound
+#[cfg(feature = "metrics")]
+fn format_labels(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels.iter().map(|(name, value)| format!("{}=\"{}\"", name, escape(value))).collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,413 @@
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::debug;
+use url::Url;
+
+/// Size of the embeddings generated when no response is programmed
+pub const MOCK_EMBEDDING_SIZE: usize = 8;
+
+const CREATED_AT: &str = "2024-01-01T00:00:00Z";
+/// Pause between streamed chunks, so clients receive them one at a time as from a real server
+const CHUNK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A request received by a `MockOllama`
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub method: String,
+    pub path: String,
+    /// The JSON body, `Null` if the request had none
+    pub body: Value,
+}
+
+/// What a `MockOllama` answers to a request
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    kind: ResponseKind,
+    latency: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum ResponseKind {
+    Chat(String),
+    Embeddings(Vec<Vec<f32>>),
+    Json(Value),
+    Error { status: u16, message: String },
+    Disconnect { after: usize },
+}
+
+impl MockResponse {
+    /// A chat reply with the given content, streamed word by word when the request asks for streaming
+    pub fn chat(content: impl Into<String>) -> Self {
+        Self::new(ResponseKind::Chat(content.into()))
+    }
+
+    /// An embed reply with the given embeddings, whatever the inputs
+    pub fn embeddings(embeddings: Vec<Vec<f32>>) -> Self {
+        Self::new(ResponseKind::Embeddings(embeddings))
+    }
+
+    /// A successful reply with the given body
+    pub fn json(body: Value) -> Self {
+        Self::new(ResponseKind::Json(body))
+    }
+
+    /// An error status with an Ollama error body, such as 503 for an overloaded server
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::new(ResponseKind::Error { status, message: message.into() })
+    }
+
+    /// Closes the connection after streaming `chunks` chunks, before the final one
+    pub fn disconnect_after(chunks: usize) -> Self {
+        Self::new(ResponseKind::Disconnect { after: chunks })
+    }
+
+    /// Waits before replying
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    fn new(kind: ResponseKind) -> Self {
+        Self { kind, latency: Duration::ZERO }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queued: HashMap<String, VecDeque<MockResponse>>,
+    defaults: HashMap<String, MockResponse>,
+    requests: Vec<CapturedRequest>,
+    /// Models requested so far, reported as running by `/api/ps`
+    models: Vec<String>,
+}
+
+/// An in-process server speaking the part of the Ollama API used by the actors.
+///
+/// It serves `/api/chat`, with and without streaming, `/api/embed`, `/api/show`, `/api/ps` and `/api/tags` on a free
+/// local port. Every request is captured. Responses are programmed per path: queued responses are used once, in order,
+/// then the default of the path if set, then a built-in reply.
+///
+/// ```rust,ignore
+/// let ollama = MockOllama::start().await?;
+/// ollama.enqueue("/api/chat", MockResponse::error(503, "server busy"));
+/// ollama.set_default("/api/chat", MockResponse::chat("Paris"));
+/// let chat = Chat::builder().endpoint(ollama.url().clone()).build()?;
+/// ```
+#[derive(Debug)]
+pub struct MockOllama {
+    url: Url,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+}
+
+impl MockOllama {
+    /// Starts the server on `127.0.0.1`, binding port 0
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}", listener.local_addr()?)).map_err(std::io::Error::other)?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server = tokio::spawn({
+            let state = state.clone();
+            async move {
+                // Connections are dropped along with the server
+                let mut connections = JoinSet::new();
+                while let Ok((socket, _)) = listener.accept().await {
+                    while connections.try_join_next().is_some() {}
+                    connections.spawn(handle_connection(socket, state.clone()));
+                }
+            }
+        });
+
+        Ok(Self { url, state, server })
+    }
+
+    /// The base URL of the server, to use as the endpoint of the actors
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Answers the next request to `path` that has no earlier queued response
+    pub fn enqueue(&self, path: &str, response: MockResponse) {
+        self.state.lock().unwrap().queued.entry(path.to_string()).or_default().push_back(response);
+    }
+
+    /// Answers requests to `path` once its queued responses are used up
+    pub fn set_default(&self, path: &str, response: MockResponse) {
+        self.state.lock().unwrap().defaults.insert(path.to_string(), response);
+    }
+
+    /// Every request received so far, in order
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The requests received so far on `path`, in order
+    pub fn requests_to(&self, path: &str) -> Vec<CapturedRequest> {
+        self.requests().into_iter().filter(|request| request.path == path).collect()
+    }
+}
+
+impl Drop for MockOllama {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle_connection(mut socket: TcpStream, state: Arc<Mutex<State>>) {
+    let Some(request) = read_request(&mut socket).await else {
+        return;
+    };
+    debug!("Mock Ollama {} {}", request.method, request.path);
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        if let Some(model) = request.body.get("model").and_then(Value::as_str) {
+            if !state.models.iter().any(|known| known == model) {
+                state.models.push(model.to_string());
+            }
+        }
+        state.requests.push(request.clone());
+
+        let queued = state.queued.get_mut(&request.path).and_then(VecDeque::pop_front);
+        match queued.or_else(|| state.defaults.get(&request.path).cloned()) {
+            Some(response) => response,
+            None => builtin_response(&request, &state.models),
+        }
+    };
+
+    tokio::time::sleep(response.latency).await;
+    let _ = write_response(&mut socket, &request, response.kind).await;
+}
+
+/// Reads the request line, headers and the body announced by content-length
+async fn read_request(socket: &mut TcpStream) -> Option<CapturedRequest> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let (head, body_start, length) = loop {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&data[..end]).to_string();
+            let length = head
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(str::to_string))
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            break (head, end + 4, length);
+        }
+    };
+    while data.len() < body_start + length {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+
+    let mut request_line = head.lines().next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.split('?').next()?.to_string();
+    let body = serde_json::from_slice(&data[body_start..body_start + length]).unwrap_or(Value::Null);
+    Some(CapturedRequest { method, path, body })
+}
+
+/// The reply used when nothing is programmed for the path
+fn builtin_response(request: &CapturedRequest, models: &[String]) -> MockResponse {
+    let model = request.body.get("model").or_else(|| request.body.get("name")).and_then(Value::as_str);
+    let details = json!({
+        "format": "gguf",
+        "family": "mock",
+        "families": ["mock"],
+        "parameter_size": "1B",
+        "quantization_level": "Q4_0"
+    });
+    match request.path.as_str() {
+        "/api/chat" => MockResponse::chat("Mock response"),
+        "/api/embed" => {
+            let inputs = match request.body.get("input") {
+                Some(Value::Array(inputs)) => inputs.iter().map(input_text).collect(),
+                Some(input) => vec![input_text(input)],
+                None => vec![],
+            };
+            MockResponse::embeddings(inputs.iter().map(|input| mock_embedding(input)).collect())
+        }
+        "/api/show" => MockResponse::json(json!({
+            "modelfile": "",
+            "parameters": "",
+            "template": "{{ .Prompt }}",
+            "details": details,
+            "model_info": {"general.architecture": "mock", "mock.context_length": 4096},
+            "capabilities": ["completion"],
+        })),
+        "/api/ps" => MockResponse::json(json!({
+            "models": models.iter().map(|name| json!({
+                "name": name,
+                "model": name,
+                "size": 1_000_000,
+                "digest": "mock",
+                "details": details,
+                "expires_at": "2099-01-01T00:00:00Z",
+                "size_vram": 1_000_000,
+            })).collect::<Vec<_>>()
+        })),
+        "/api/tags" => MockResponse::json(json!({
+            "models": models.iter().map(|name| json!({
+                "name": name,
+                "model": name,
+                "modified_at": CREATED_AT,
+                "size": 1_000_000,
+                "digest": "mock",
+                "details": details,
+            })).collect::<Vec<_>>()
+        })),
+        _ => match model {
+            Some(model) => MockResponse::error(404, format!("model '{}' not found", model)),
+            None => MockResponse::error(404, "not found"),
+        },
+    }
+}
+
+fn input_text(input: &Value) -> String {
+    input.as_str().map_or_else(|| input.to_string(), str::to_string)
+}
+
+/// A unit length embedding derived from the input, so equal inputs get equal embeddings
+pub fn mock_embedding(input: &str) -> Vec<f32> {
+    let values: Vec<f32> = (0..MOCK_EMBEDDING_SIZE)
+        .map(|i| {
+            let mut hasher = DefaultHasher::new();
+            (input, i).hash(&mut hasher);
+            (hasher.finish() % 2000) as f32 / 1000.0 - 1.0
+        })
+        .collect();
+    let norm = values.iter().map(|value| value * value).sum::<f32>().sqrt().max(f32::EPSILON);
+    values.into_iter().map(|value| value / norm).collect()
+}
+
+async fn write_response(socket: &mut TcpStream, request: &CapturedRequest, kind: ResponseKind) -> std::io::Result<()> {
+    let model = request.body.get("model").and_then(Value::as_str).unwrap_or("mock");
+    let stream = request.body.get("stream").and_then(Value::as_bool).unwrap_or(true);
+    match kind {
+        ResponseKind::Chat(content) if stream => {
+            let chunks = chat_chunks(model, &content, prompt_words(&request.body));
+            write_chunked(socket, &chunks).await
+        }
+        ResponseKind::Chat(content) => {
+            let mut response = chat_chunk(model, &content);
+            finish_chat(&mut response, prompt_words(&request.body), content.split_whitespace().count());
+            write_json(socket, 200, &response).await
+        }
+        ResponseKind::Embeddings(embeddings) => {
+            write_json(socket, 200, &json!({"model": model, "embeddings": embeddings})).await
+        }
+        ResponseKind::Json(body) => write_json(socket, 200, &body).await,
+        ResponseKind::Error { status, message } => write_json(socket, status, &json!({"error": message})).await,
+        ResponseKind::Disconnect { after } => {
+            if stream {
+                let chunks = chat_chunks(model, "Mock response cut off midway", prompt_words(&request.body));
+                let head = "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\ntransfer-encoding: chunked\r\n\
+                            connection: close\r\n\r\n";
+                socket.write_all(head.as_bytes()).await?;
+                for chunk in chunks.iter().take(after.min(chunks.len() - 1)) {
+                    socket.write_all(encode_chunk(chunk).as_bytes()).await?;
+                    socket.flush().await?;
+                    tokio::time::sleep(CHUNK_INTERVAL).await;
+                }
+            }
+            // Close without the final chunk, nor the end of the chunked body
+            socket.shutdown().await
+        }
+    }
+}
+
+/// The streamed chunks of a chat reply, one per word, then the final chunk with the statistics
+fn chat_chunks(model: &str, content: &str, prompt_words: usize) -> Vec<Value> {
+    let mut chunks: Vec<Value> = content.split_inclusive(' ').map(|word| chat_chunk(model, word)).collect();
+    let mut last = chat_chunk(model, "");
+    finish_chat(&mut last, prompt_words, chunks.len());
+    chunks.push(last);
+    chunks
+}
+
+fn chat_chunk(model: &str, content: &str) -> Value {
+    json!({
+        "model": model,
+        "created_at": CREATED_AT,
+        "message": {"role": "assistant", "content": content},
+        "done": false,
+    })
+}
+
+/// Marks a chat reply as done, counting words as tokens
+fn finish_chat(response: &mut Value, prompt_words: usize, completion_words: usize) {
+    response["done"] = json!(true);
+    response["done_reason"] = json!("stop");
+    response["total_duration"] = json!(1_000_000);
+    response["load_duration"] = json!(1_000);
+    response["prompt_eval_count"] = json!(prompt_words);
+    response["prompt_eval_duration"] = json!(100_000);
+    response["eval_count"] = json!(completion_words);
+    response["eval_duration"] = json!(500_000);
+}
+
+fn prompt_words(body: &Value) -> usize {
+    let Some(messages) = body.get("messages").and_then(Value::as_array) else {
+        return 0;
+    };
+    messages
+        .iter()
+        .filter_map(|message| message.get("content").and_then(Value::as_str))
+        .map(|content| content.split_whitespace().count())
+        .sum()
+}
+
+async fn write_json(socket: &mut TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+async fn write_chunked(socket: &mut TcpStream, chunks: &[Value]) -> std::io::Result<()> {
+    let head = "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\ntransfer-encoding: chunked\r\n\
+                connection: close\r\n\r\n";
+    socket.write_all(head.as_bytes()).await?;
+    for chunk in chunks {
+        socket.write_all(encode_chunk(chunk).as_bytes()).await?;
+        socket.flush().await?;
+        tokio::time::sleep(CHUNK_INTERVAL).await;
+    }
+    socket.write_all(b"0\r\n\r\n").await?;
+    socket.shutdown().await
+}
+
+/// Encodes a JSON line as a chunk of a chunked body
+fn encode_chunk(chunk: &Value) -> String {
+    let line = format!("{}\n", chunk);
+    format!("{:x}\r\n{}\r\n", line.len(), line)
+}
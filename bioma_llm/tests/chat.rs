@@ -0,0 +1,1368 @@
+use bioma_actor::prelude::*;
+use bioma_llm::prelude::*;
+use bioma_llm::testing::{MockOllama, MockResponse};
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[test]
+fn test_chat_messages_ids() {
+    let mut messages = ChatMessages::builder().messages(vec![]).build();
+
+    let first = messages.push(ChatMessage::user("Hello".to_string()));
+    let second = messages.push(ChatMessage::assistant("Hi, how can I help?".to_string()));
+    let third = messages.push(ChatMessage::user("Tell me a joke".to_string()));
+
+    // Remove the middle turn by id
+    let removed = messages.remove(second).expect("Expected the second turn to be removed");
+    assert_eq!(removed.content, "Hi, how can I help?");
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![first, third], "Remaining turns should keep their ids");
+    assert!(messages.remove(second).is_none(), "Removed id should no longer be found");
+
+    // Ids survive a serialization round-trip
+    let json = serde_json::to_string(&messages).unwrap();
+    let mut messages: ChatMessages = serde_json::from_str(&json).unwrap();
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![first, third]);
+
+    // Replace a turn's content, keeping its id
+    assert!(messages.replace(third, "Tell me a story"));
+    assert_eq!(messages.messages[1].content, "Tell me a story");
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![first, third]);
+
+    // The id of a removed last turn is not given again
+    assert!(messages.remove(third).is_some());
+    let fourth = messages.push(ChatMessage::user("Another one".to_string()));
+    assert!(fourth > third, "Expected a new id, got {}", fourth);
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![first, fourth]);
+}
+
+#[test]
+fn test_chat_messages_assigns_missing_ids() {
+    let mut messages = ChatMessages::builder()
+        .messages(vec![ChatMessage::user("First".to_string()), ChatMessage::user("Second".to_string())])
+        .build();
+
+    let third = messages.push(ChatMessage::user("Third".to_string()));
+
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(third, 2);
+}
+
+#[test]
+fn test_chat_messages_without_ids() {
+    // Messages sent before ids existed still deserialize, and are numbered on receipt after the ids present
+    let json = serde_json::json!({
+        "messages": [
+            {"role": "user", "content": "First"},
+            {"id": 4, "role": "assistant", "content": "Second"},
+            {"role": "user", "content": "Third"}
+        ],
+        "restart": false,
+        "persist": false,
+        "stream": false,
+        "format": null,
+        "tools": null,
+        "options": null,
+        "summarization": null,
+        "generation": null
+    });
+    let mut messages: ChatMessages = serde_json::from_value(json).unwrap();
+    messages.assign_missing_ids();
+
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![5, 4, 6]);
+    assert_eq!(messages.push(ChatMessage::user("Fourth".to_string())), 7);
+}
+
+#[test]
+fn test_chat_messages_template() {
+    let vars: HashMap<String, String> = [("user_name", "Ada"), ("today", "2024-01-01")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let messages = ChatMessages::builder().messages(vec![ChatMessage::user("Hello".to_string())]).build();
+
+    // Placeholders are substituted into a system prompt inserted first
+    let rendered = messages.clone().with_template("You are helping {{user_name}} on {{ today }}.", vars.clone());
+    assert_eq!(rendered.messages[0].role, MessageRole::System);
+    assert_eq!(rendered.messages[0].content, "You are helping Ada on 2024-01-01.");
+    assert_eq!(rendered.messages[1].content, "Hello");
+    assert_eq!(rendered.ids().collect::<Vec<_>>(), vec![1, 0]);
+
+    // Unknown placeholders are kept verbatim, unless strict
+    let rendered = messages.clone().with_template("Hi {{user_name}}, see {{unknown}}", vars.clone());
+    assert_eq!(rendered.messages[0].content, "Hi Ada, see {{unknown}}");
+    let result = messages.clone().with_strict_template("Hi {{user_name}}, see {{unknown}}", vars.clone());
+    assert!(matches!(result, Err(ChatError::TemplateError(_))));
+    let result = messages.clone().with_strict_template("Hi {{user_name", vars.clone());
+    assert!(matches!(result, Err(ChatError::TemplateError(_))));
+
+    // Escaped braces are literal, even in strict mode
+    let rendered = chat::render_template(r"Use \{{user_name}} for {{user_name}}", &vars, true).unwrap();
+    assert_eq!(rendered, "Use {{user_name}} for Ada");
+}
+
+#[tokio::test]
+async fn test_chat_system_template() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    let chat = Chat::builder()
+        .model("mock")
+        .endpoint(ollama.url().clone())
+        .system_template("You are helping {{user}}. Reply in {{ language }}.")
+        .build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/system_template").await?;
+    let ask = |vars: &[(&str, &str)]| {
+        ChatMessages::builder()
+            .messages(vec![ChatMessage::user("Hello".to_string())])
+            .template_vars(vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect())
+            .build()
+    };
+
+    // The variables are substituted before the request is sent
+    relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(
+            ask(&[("user", "Ada"), ("language", "French")]),
+            &chat_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let sent = &ollama.requests_to("/api/chat")[0].body["messages"];
+    assert_eq!(sent[0]["role"], "system");
+    assert_eq!(sent[0]["content"], "You are helping Ada. Reply in French.");
+    assert_eq!(sent[1]["content"], "Hello");
+
+    // Values are inserted as is, and the prompt is not kept in the history
+    relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(
+            ask(&[("user", "{{language}}"), ("language", "Spanish")]),
+            &chat_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let sent = ollama.requests_to("/api/chat")[1].body["messages"].clone();
+    assert_eq!(sent[0]["content"], "You are helping {{language}}. Reply in Spanish.");
+    let system_prompts = sent.as_array().unwrap().iter().filter(|message| message["role"] == "system").count();
+    assert_eq!(system_prompts, 1);
+
+    // A missing variable fails the request before it reaches the server
+    let result = relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(ask(&[("user", "Ada")]), &chat_id, SendOptions::default())
+        .await;
+    let error = result.expect_err("Expected the missing variable to fail the request");
+    assert!(error.to_string().contains("Missing template variable: language"), "Unexpected error: {}", error);
+    assert_eq!(ollama.requests_to("/api/chat").len(), 2);
+
+    // Escaped text renders to itself
+    let user_content = r"Type {{language}} or \{{user}}";
+    let template = format!("Repeat: {}", chat::escape_template(user_content));
+    let rendered = chat::render_template(&template, &HashMap::new(), true)?;
+    assert_eq!(rendered, format!("Repeat: {}", user_content));
+
+    handle.abort();
+    Ok(())
+}
+
+#[test]
+fn test_chat_messages_few_shot() {
+    let mut messages = ChatMessages::builder().messages(vec![]).build();
+
+    let system = messages.push(ChatMessage::system("Translate English to French".to_string()));
+    let examples = messages.push_examples(vec![
+        ("cheese".to_string(), "fromage".to_string()),
+        ("bread".to_string(), "pain".to_string()),
+    ]);
+    let question = messages.push(ChatMessage::user("apple".to_string()));
+
+    assert_eq!(system, 0);
+    assert_eq!(examples, vec![1, 2, 3, 4]);
+    assert_eq!(question, 5);
+    assert_eq!(messages.ids().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5]);
+
+    // The system prompt stays first, followed by the examples in order and the actual question
+    let turns: Vec<(MessageRole, &str)> =
+        messages.messages.iter().map(|message| (message.role.clone(), message.content.as_str())).collect();
+    assert_eq!(
+        turns,
+        vec![
+            (MessageRole::System, "Translate English to French"),
+            (MessageRole::User, "cheese"),
+            (MessageRole::Assistant, "fromage"),
+            (MessageRole::User, "bread"),
+            (MessageRole::Assistant, "pain"),
+            (MessageRole::User, "apple"),
+        ]
+    );
+
+    // A single assistant turn can also be appended on its own
+    let answer = messages.push_assistant("pomme");
+    assert_eq!(answer, 6);
+    assert_eq!(messages.messages[6].content, "pomme");
+}
+
+/// Backend answering every request with the same response
+#[derive(Debug)]
+struct StubBackend {
+    response: ChatMessageResponse,
+}
+
+impl ChatBackend for StubBackend {
+    fn chat<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        Box::pin(async move { Ok(self.response.clone()) })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let stream = futures::stream::iter([Ok::<_, ChatError>(self.response.clone())]);
+            Ok(Box::pin(stream) as ChatResponseStream)
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_chat_backend() -> Result<(), Box<dyn std::error::Error>> {
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Canned answer"},"done":true}"#;
+    let response: ChatMessageResponse = serde_json::from_str(body).unwrap();
+
+    let engine = Engine::test().await?;
+    let chat = Chat::builder().backend(Arc::new(StubBackend { response: response.clone() })).build()?;
+    let chat_id = ActorId::of::<Chat>("/chat");
+    let (mut chat_ctx, mut chat_actor) =
+        Actor::spawn(engine.clone(), chat_id.clone(), chat, SpawnOptions::default()).await?;
+    let chat_handle = tokio::spawn(async move { chat_actor.start(&mut chat_ctx).await });
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine.clone(), relay_id, Relay, SpawnOptions::default()).await?;
+
+    // The response of the backend is returned unchanged
+    let messages = ChatMessages::builder().messages(vec![ChatMessage::user("Hi".to_string())]).build();
+    let reply = relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, &chat_id, SendOptions::default()).await?;
+    assert_eq!(serde_json::to_value(&reply.response).unwrap(), serde_json::to_value(&response).unwrap());
+
+    // Nothing was dropped, so the serialized reply carries no count, next to the attempts it took
+    assert_eq!((reply.dropped, reply.attempts), (0, 1));
+    let value = serde_json::to_value(&reply).unwrap();
+    assert!(value.get("dropped").is_none());
+    assert_eq!(value["attempts"], 1);
+
+    // Including when streaming
+    let messages = ChatMessages::builder().messages(vec![ChatMessage::user("Hi".to_string())]).stream(true).build();
+    let reply = relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, &chat_id, SendOptions::default()).await?;
+    assert_eq!(reply.message.content, "Canned answer");
+    assert_eq!(reply.attempts, 1);
+
+    chat_handle.abort();
+    Ok(())
+}
+
+/// Backend that fails while `alive` is false, like an Ollama server being restarted, keeping the requests answered
+#[derive(Debug)]
+struct FlakyBackend {
+    response: ChatMessageResponse,
+    alive: Arc<AtomicBool>,
+    requests: Arc<Mutex<Vec<ChatRequest>>>,
+}
+
+impl FlakyBackend {
+    fn answer(&self, request: ChatRequest) -> Result<ChatMessageResponse, ChatError> {
+        if !self.alive.load(Ordering::SeqCst) {
+            return Err(ChatError::OllamaOther("Connection refused".to_string()));
+        }
+        self.requests.lock().unwrap().push(request);
+        Ok(self.response.clone())
+    }
+}
+
+impl ChatBackend for FlakyBackend {
+    fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        Box::pin(async move { self.answer(request) })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let response = self.answer(request)?;
+            Ok(Box::pin(futures::stream::iter([Ok::<_, ChatError>(response)])) as ChatResponseStream)
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_chat_supervised_restart() -> Result<(), Box<dyn std::error::Error>> {
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Canned answer"},"done":true}"#;
+    let alive = Arc::new(AtomicBool::new(true));
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let response = serde_json::from_str(body).unwrap();
+    let backend = FlakyBackend { response, alive: alive.clone(), requests: requests.clone() };
+
+    let engine = Engine::test().await?;
+    let policy = RestartPolicy::builder().failure_threshold(2).initial_backoff(Duration::from_millis(100)).build();
+    let supervisor = Supervisor::new(engine.clone(), policy);
+    let mut events = supervisor.subscribe();
+
+    let chat_id = ActorId::of::<Chat>("/supervised/chat");
+    let chat = Chat::builder().backend(Arc::new(backend)).build()?;
+    supervisor.supervise(chat_id.clone(), chat, SpawnOptions::default())?;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine.clone(), relay_id, Relay, SpawnOptions::default()).await?;
+
+    // A persisted turn, to be restored after the restart
+    ask_chat(&relay_ctx, &chat_id, "Remember the number 42", true).await?;
+
+    // The backend goes away, every request fails until the threshold is reached
+    alive.store(false, Ordering::SeqCst);
+    assert!(ask_chat(&relay_ctx, &chat_id, "Are you there?", false).await.is_err());
+    assert!(ask_chat(&relay_ctx, &chat_id, "Hello?", false).await.is_err());
+    alive.store(true, Ordering::SeqCst);
+
+    // The supervisor restarts the actor on its own
+    let event = tokio::time::timeout(Duration::from_secs(10), events.recv()).await??;
+    assert!(matches!(event, LifecycleEvent::Restarting { attempt: 1, .. }), "Unexpected event: {:?}", event);
+    let event = tokio::time::timeout(Duration::from_secs(10), events.recv()).await??;
+    assert!(matches!(event, LifecycleEvent::Restarted { attempt: 1, .. }), "Unexpected event: {:?}", event);
+
+    // Requests succeed again, with the persisted history and without the failed turns
+    let reply = ask_chat(&relay_ctx, &chat_id, "What was the number?", false).await?;
+    assert_eq!(reply.message.content, "Canned answer");
+    let request = requests.lock().unwrap().pop().expect("Expected a request after the restart");
+    let turns: Vec<&str> = request.messages.iter().map(|message| message.content.as_str()).collect();
+    assert_eq!(turns, vec!["Remember the number 42", "Canned answer", "What was the number?"]);
+    assert!(supervisor.is_supervising(&chat_id));
+
+    Ok(())
+}
+
+/// Sends a single user message to the chat actor
+async fn ask_chat(
+    relay_ctx: &ActorContext<Relay>,
+    chat_id: &ActorId,
+    content: &str,
+    persist: bool,
+) -> Result<ChatReply, SystemActorError> {
+    let messages =
+        ChatMessages::builder().messages(vec![ChatMessage::user(content.to_string())]).persist(persist).build();
+    relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, chat_id, SendOptions::default()).await
+}
+
+#[tokio::test]
+async fn test_chat_send_raw() {
+    let body = r#"{"model":"llama3.2:3b","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Hello!"},"done":true,"total_duration":1000,"eval_count":3}"#;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/api/chat")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let chat = Chat::builder().endpoint(url::Url::parse(&server.url()).unwrap()).build().unwrap();
+    let raw = chat.send_raw(vec![ChatMessage::user("Hi".to_string())]).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(raw.raw, serde_json::from_str::<serde_json::Value>(body).unwrap(), "Raw payload should be unchanged");
+    assert_eq!(raw.response.message.content, "Hello!");
+}
+
+#[tokio::test]
+async fn test_chat_max_concurrent_requests() {
+    let body = r#"{"model":"llama3.2:3b","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Hello!"},"done":true,"total_duration":1000,"eval_count":3}"#;
+
+    let peak = Arc::new(AtomicUsize::new(0));
+    let endpoint = spawn_slow_chat_server(body, peak.clone()).await;
+
+    let chat = Chat::builder().endpoint(endpoint).max_concurrent_requests(2).build().unwrap();
+    let calls = (0..10).map(|i| chat.send_raw(vec![ChatMessage::user(format!("Message {}", i))]));
+    let results = futures::future::join_all(calls).await;
+
+    assert!(results.iter().all(|result| result.is_ok()), "All queued calls should complete");
+    assert!(peak.load(Ordering::SeqCst) <= 2, "Peak concurrency {} exceeded the limit", peak.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_chat_max_concurrent_requests_actors() -> Result<(), Box<dyn std::error::Error>> {
+    let body = r#"{"model":"llama3.2:3b","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Hello!"},"done":true,"total_duration":1000,"eval_count":3}"#;
+
+    let peak = Arc::new(AtomicUsize::new(0));
+    let endpoint = spawn_slow_chat_server(body, peak.clone()).await;
+
+    // Each actor handles one message at a time, actors spawned from clones of the same chat share its limit
+    let engine = Engine::test().await?;
+    let chat = Chat::builder().endpoint(endpoint).max_concurrent_requests(2).build()?;
+    let mut chat_ids = vec![];
+    for i in 0..5 {
+        let chat_id = ActorId::of::<Chat>(format!("/chat/limited/{}", i));
+        let (mut chat_ctx, mut chat_actor) =
+            Actor::spawn(engine.clone(), chat_id.clone(), chat.clone(), SpawnOptions::default()).await?;
+        tokio::spawn(async move {
+            let _ = chat_actor.start(&mut chat_ctx).await;
+        });
+        chat_ids.push(chat_id);
+    }
+
+    let relay_id = ActorId::of::<Relay>("/relay/limited");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine, relay_id, Relay, SpawnOptions::default()).await?;
+
+    let calls = chat_ids.iter().cycle().take(10).enumerate().map(|(i, chat_id)| {
+        let messages = ChatMessages::builder().messages(vec![ChatMessage::user(format!("Message {}", i))]).build();
+        relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, chat_id, SendOptions::default())
+    });
+    let results = futures::future::join_all(calls).await;
+
+    assert!(results.iter().all(|result| result.is_ok()), "All queued messages should be answered");
+    assert_eq!(peak.load(Ordering::SeqCst), 2, "The actors should send up to the limit at once, not more");
+
+    Ok(())
+}
+
+/// Serves `body` to every request after a delay, recording the peak number of requests handled at once
+async fn spawn_slow_chat_server(body: &'static str, peak: Arc<AtomicUsize>) -> url::Url {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = url::Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let active = Arc::new(AtomicUsize::new(0));
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let active = active.clone();
+            let peak = peak.clone();
+            tokio::spawn(async move {
+                peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+
+                // Read the headers and the body announced by content-length
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let Ok(n) = socket.read(&mut buf).await else { break };
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((headers, received)) = text.split_once("\r\n\r\n") {
+                        let length = headers
+                            .lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(str::to_string))
+                            .and_then(|value| value.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if received.len() >= length {
+                            break;
+                        }
+                    }
+                }
+
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+
+    endpoint
+}
+
+/// Spawns a chat actor talking to the mock server, along with a relay to reach it
+async fn spawn_mock_chat(
+    ollama: &MockOllama,
+    name: &str,
+) -> Result<(ActorContext<Relay>, ActorId, tokio::task::JoinHandle<()>), Box<dyn std::error::Error>> {
+    spawn_chat(Chat::builder().model("mock").endpoint(ollama.url().clone()).build()?, name).await
+}
+
+/// Spawns a chat actor along with a relay to reach it
+async fn spawn_chat(
+    chat: Chat,
+    name: &str,
+) -> Result<(ActorContext<Relay>, ActorId, tokio::task::JoinHandle<()>), Box<dyn std::error::Error>> {
+    let engine = Engine::test().await?;
+    let chat_id = ActorId::of::<Chat>(name);
+    let (mut chat_ctx, mut chat_actor) =
+        Actor::spawn(engine.clone(), chat_id.clone(), chat, SpawnOptions::default()).await?;
+    let handle = tokio::spawn(async move {
+        let _ = chat_actor.start(&mut chat_ctx).await;
+    });
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine, relay_id, Relay, SpawnOptions::default()).await?;
+    Ok((relay_ctx, chat_id, handle))
+}
+
+#[tokio::test]
+async fn test_chat_mock_ollama() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.set_default("/api/chat", MockResponse::chat("Paris is the capital of France."));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat").await?;
+
+    let reply = ask_chat(&relay_ctx, &chat_id, "What is the capital of France?", false).await?;
+    assert_eq!(reply.message.content, "Paris is the capital of France.");
+    assert_eq!((reply.dropped, reply.attempts), (0, 1));
+    let data = reply.response.final_data.expect("Expected the final statistics");
+    assert_eq!((data.prompt_eval_count, data.eval_count), (6, 6));
+
+    // The request reached the server as Ollama expects it
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].body["model"], "mock");
+    assert_eq!(requests[0].body["stream"], false);
+    assert_eq!(requests[0].body["messages"][0]["content"], "What is the capital of France?");
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_mock_ollama_streaming() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("One two three"));
+    ollama.enqueue("/api/chat", MockResponse::disconnect_after(2));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/stream").await?;
+    let ask = |content: &str| {
+        ChatMessages::builder().messages(vec![ChatMessage::user(content.to_string())]).stream(true).build()
+    };
+
+    // Every word arrives as its own chunk, followed by the final chunk
+    let chunks = relay_ctx
+        .send_and_collect::<Chat, ChatMessages>(ask("Count to three"), &chat_id, SendOptions::default())
+        .await?;
+    let content: String = chunks.iter().map(|chunk| chunk.message.content.as_str()).collect();
+    assert_eq!(content, "One two three");
+    assert_eq!(chunks.len(), 4);
+    assert!(chunks.last().unwrap().done);
+    assert_eq!(ollama.requests_to("/api/chat")[0].body["stream"], true);
+
+    // A stream cut off by the server ends without its final chunk
+    let chunks = relay_ctx
+        .send_and_collect::<Chat, ChatMessages>(ask("Keep going"), &chat_id, SendOptions::default())
+        .await?;
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks.iter().all(|chunk| !chunk.done));
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_usage_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("Paris is the capital of France."));
+    ollama.enqueue("/api/chat", MockResponse::chat("One two three"));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/usage").await?;
+
+    // The usage of a response comes from its final statistics, and is attached to the reply
+    let reply = ask_chat(&relay_ctx, &chat_id, "What is the capital of France?", false).await?;
+    let usage = reply.usage.expect("Expected the usage of the reply");
+    assert_eq!(Some(usage), Usage::of(&reply));
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (6, 6));
+    assert_eq!(usage.total_duration, Duration::from_millis(1));
+    assert_eq!(usage.eval_duration, Duration::from_micros(500));
+
+    // A streamed reply reports its usage on the final chunk
+    let request =
+        ChatMessages::builder().messages(vec![ChatMessage::user("Count to three".to_string())]).stream(true).build();
+    let chunks = relay_ctx.send_and_collect::<Chat, ChatMessages>(request, &chat_id, SendOptions::default()).await?;
+    assert!(chunks[..chunks.len() - 1].iter().all(|chunk| chunk.usage.is_none()));
+    let streamed = chunks.last().unwrap().usage.expect("Expected the usage on the final chunk");
+    assert_eq!((streamed.prompt_tokens, streamed.completion_tokens), (15, 3));
+
+    // The actor totals the usage of both replies
+    let stats =
+        relay_ctx.send_and_wait_reply::<Chat, GetChatStats>(GetChatStats, &chat_id, SendOptions::default()).await?;
+    assert_eq!(stats.responses, 2);
+    assert_eq!(stats.summaries, 0);
+    assert_eq!((stats.usage.prompt_tokens, stats.usage.completion_tokens), (21, 9));
+    assert_eq!(stats.usage.total_tokens(), 30);
+    assert_eq!(stats.usage.total_duration, Duration::from_millis(2));
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_stream_method() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("One two three"));
+    ollama.enqueue("/api/chat", MockResponse::chat("A reply far too long to wait for"));
+    ollama.enqueue("/api/chat", MockResponse::chat("Again"));
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).max_concurrent_requests(1).build()?;
+    let ask = |content: &str| ChatMessages::builder().messages(vec![ChatMessage::user(content.to_string())]).build();
+
+    // The three word chunks concatenate to the full message, the final chunk carries the statistics
+    let chunks: Vec<_> = chat.stream(&ask("Count to three")).await?.collect().await;
+    let chunks = chunks.into_iter().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(chunks.len(), 4);
+    let content: String = chunks.iter().map(|chunk| chunk.message.content.as_str()).collect();
+    assert_eq!(content, "One two three");
+    assert!(chunks[..3].iter().all(|chunk| !chunk.done));
+    let last = chunks.last().unwrap();
+    assert!(last.done);
+    let data = last.final_data.as_ref().expect("Expected the final statistics");
+    assert_eq!(data.eval_count, 3);
+    assert!(data.total_duration > 0);
+
+    // Dropping a stream midway frees its request slot for the next request
+    let mut stream = chat.stream(&ask("Talk for a while")).await?;
+    assert!(!stream.next().await.unwrap()?.done);
+    drop(stream);
+    let next = tokio::time::timeout(Duration::from_secs(5), chat.stream(&ask("Again?"))).await?;
+    let chunks: Vec<_> = next?.collect().await;
+    assert!(chunks.last().is_some_and(|chunk| chunk.as_ref().is_ok_and(|chunk| chunk.done)));
+
+    // The history is left untouched
+    assert!(chat.history.is_empty());
+    assert_eq!(ollama.requests_to("/api/chat")[0].body["stream"], true);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_collect_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("One two three"));
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).build()?;
+    let request = ChatMessages::builder().messages(vec![ChatMessage::user("Count to three".to_string())]).build();
+
+    // The chunks collapse into one response carrying the final statistics
+    let response = chat::collect_stream(chat.stream(&request).await?).await?;
+    assert_eq!(response.message.content, "One two three");
+    assert!(response.done);
+    let data = response.final_data.as_ref().expect("Expected the final statistics");
+    assert_eq!(data.eval_count, 3);
+
+    // The first error ends the collection
+    let chunks: Vec<Result<ChatMessageResponse, ChatError>> = vec![
+        Ok(ChatMessageResponse { done: false, final_data: None, ..response.clone() }),
+        Err(ChatError::OllamaOther("connection reset".to_string())),
+        Ok(response),
+    ];
+    let result = chat::collect_stream(Box::pin(futures::stream::iter(chunks))).await;
+    assert!(matches!(result, Err(ChatError::OllamaOther(message)) if message == "connection reset"));
+
+    // A stream without chunks has nothing to collect
+    let result =
+        chat::collect_stream(Box::pin(futures::stream::empty::<Result<ChatMessageResponse, ChatError>>())).await;
+    assert!(matches!(result, Err(ChatError::EmptyStream)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_stop_sequences() -> Result<(), Box<dyn std::error::Error>> {
+    // A server that ignores the stop option and keeps generating
+    let ollama = MockOllama::start().await?;
+    ollama.set_default("/api/chat", MockResponse::chat("Paris is the capital.\n\nUser: And of Spain?"));
+    let chat = Chat::builder()
+        .model("mock")
+        .endpoint(ollama.url().clone())
+        .stop(vec!["\n\nUser:".to_string()])
+        .build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/stop").await?;
+
+    let reply = ask_chat(&relay_ctx, &chat_id, "What is the capital of France?", false).await?;
+    assert_eq!(reply.message.content, "Paris is the capital.");
+    assert_eq!(ollama.requests_to("/api/chat")[0].body["options"]["stop"], serde_json::json!(["\n\nUser:"]));
+
+    // Streaming ends at the delimiter too
+    let ask = ChatMessages::builder().messages(vec![ChatMessage::user("And again?".to_string())]).stream(true).build();
+    let chunks = relay_ctx.send_and_collect::<Chat, ChatMessages>(ask, &chat_id, SendOptions::default()).await?;
+    let content: String = chunks.iter().map(|chunk| chunk.message.content.as_str()).collect();
+    assert_eq!(content, "Paris is the capital.");
+    assert!(chunks.last().unwrap().done);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_generation_options() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.set_default("/api/chat", MockResponse::chat("Madrid. END And more"));
+    let chat =
+        Chat::builder().model("mock").endpoint(ollama.url().clone()).stop(vec!["\n\nUser:".to_string()]).build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/generation").await?;
+    let ask = |generation: Option<GenerationOptions>| {
+        ChatMessages::builder()
+            .messages(vec![ChatMessage::user("What is the capital of Spain?".to_string())])
+            .maybe_generation(generation)
+            .build()
+    };
+
+    // The options of a request apply to it alone, replacing the stop sequences of the chat
+    let generation =
+        GenerationOptions::builder().temperature(0.5).top_k(5).stop(vec!["END".to_string()]).num_predict(64).build();
+    let reply = relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(ask(Some(generation)), &chat_id, SendOptions::default())
+        .await?;
+    assert_eq!(reply.message.content, "Madrid. ");
+    let options = &ollama.requests_to("/api/chat")[0].body["options"];
+    assert_eq!(options["temperature"], 0.5);
+    assert_eq!(options["top_k"], 5);
+    assert_eq!(options["num_predict"], 64);
+    assert_eq!(options["stop"], serde_json::json!(["END"]));
+
+    // Later requests are back to the defaults
+    relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(ask(None), &chat_id, SendOptions::default()).await?;
+    let options = &ollama.requests_to("/api/chat")[1].body["options"];
+    assert!(options["temperature"].is_null());
+    assert_eq!(options["stop"], serde_json::json!(["\n\nUser:"]));
+
+    // Invalid options are refused before reaching the backend
+    let invalid = GenerationOptions::builder().temperature(-0.5).build();
+    let result = invalid.validate();
+    assert!(matches!(result, Err(ChatError::InvalidOptions(message)) if message.contains("temperature")));
+    let result =
+        relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(ask(Some(invalid)), &chat_id, SendOptions::default()).await;
+    assert!(result.is_err());
+    assert_eq!(ollama.requests_to("/api/chat").len(), 2);
+
+    handle.abort();
+    Ok(())
+}
+
+/// Counts a token per word
+#[derive(Debug)]
+struct WordTokenizer;
+
+impl Tokenizer for WordTokenizer {
+    fn count(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+}
+
+#[tokio::test]
+async fn test_chat_context_overflow() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    let ask = |content: &str| {
+        ChatMessages::builder().messages(vec![ChatMessage::user(content.to_string())]).restart(true).build()
+    };
+
+    // Every message counts its words and a fixed overhead for its role
+    let chat =
+        Chat::builder().model("mock").endpoint(ollama.url().clone()).tokenizer(Arc::new(WordTokenizer)).build()?;
+    let fits = chat.estimate_tokens(&ask("one two three"));
+    assert!(fits > 3);
+    let system = ChatMessages::builder()
+        .messages(vec![ChatMessage::system("Be brief".to_string()), ChatMessage::user("one two three".to_string())])
+        .build();
+    assert!(chat.estimate_tokens(&system) > fits + 2);
+    let image = ChatMessage::user("one two three".to_string()).with_images(vec![Image::from_base64("aGVsbG8=")]);
+    assert!(chat.estimate_tokens(&ChatMessages::builder().messages(vec![image]).build()) > fits);
+
+    // A request at the limit is sent, one more token overflows before reaching the server
+    let chat = Chat::builder()
+        .model("mock")
+        .endpoint(ollama.url().clone())
+        .tokenizer(Arc::new(WordTokenizer))
+        .max_context_tokens(fits)
+        .build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/overflow").await?;
+    let reply = relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(ask("one two three"), &chat_id, SendOptions::default())
+        .await?;
+    assert_eq!(reply.message.content, "Mock response");
+
+    let result = relay_ctx
+        .send_and_wait_reply::<Chat, ChatMessages>(ask("one two three four"), &chat_id, SendOptions::default())
+        .await;
+    let error = result.expect_err("Expected the request to overflow the context");
+    assert!(error.to_string().contains("Context overflow"), "Unexpected error: {}", error);
+    assert_eq!(ollama.requests_to("/api/chat").len(), 1);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_trim_history() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.set_default("/api/chat", MockResponse::chat("Fine"));
+
+    // Ten turns of five words each, a system prompt and the question, with room for about half of them
+    let mut history = vec![ChatMessage::system("You are a helpful assistant".to_string())];
+    for i in 0..10 {
+        history.push(ChatMessage::user(format!("question {} about the weather", i)));
+        history.push(ChatMessage::assistant(format!("answer {} about the weather", i)));
+    }
+    let chat = Chat::builder()
+        .model("mock")
+        .endpoint(ollama.url().clone())
+        .messages_number_limit(100)
+        .history(history.clone())
+        .tokenizer(Arc::new(WordTokenizer))
+        .max_context_tokens(100)
+        .trim_history(true)
+        .build()?;
+    let question = ChatMessages::builder().messages(vec![ChatMessage::user("Is it raining?".to_string())]).build();
+
+    // The oldest turns are dropped first, keeping the system prompt
+    let fitted = chat.fit_context(&question)?;
+    assert!(!fitted.dropped.is_empty());
+    assert_eq!(fitted.dropped[0].content, "question 0 about the weather");
+    assert_eq!(fitted.messages[0].content, "You are a helpful assistant");
+    assert_eq!(fitted.messages.last().map(|message| message.content.as_str()), Some("Is it raining?"));
+    assert_eq!(fitted.messages.len() + fitted.dropped.len(), history.len() + 1);
+    let fitted_request = ChatMessages::builder().messages(fitted.messages.clone()).restart(true).build();
+    assert!(chat.estimate_tokens(&fitted_request) <= 100);
+
+    // The actor sends the same messages, telling how many it dropped
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/trim").await?;
+    let reply =
+        relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(question.clone(), &chat_id, SendOptions::default()).await?;
+    assert_eq!(reply.message.content, "Fine");
+    assert_eq!(reply.dropped, fitted.dropped.len());
+    let sent = ollama.requests_to("/api/chat")[0].body["messages"].clone();
+    assert_eq!(sent.as_array().map(Vec::len), Some(fitted.messages.len()));
+    assert_eq!(sent[0]["role"], "system");
+
+    // The system prompt and the latest user message are kept even if they alone do not fit
+    let chat = Chat::builder()
+        .model("mock")
+        .endpoint(ollama.url().clone())
+        .tokenizer(Arc::new(WordTokenizer))
+        .max_context_tokens(5)
+        .trim_history(true)
+        .build()?;
+    let result = chat.fit_context(&question.with_template("Answer in one word", HashMap::new()));
+    assert!(matches!(result, Err(ChatError::ContextOverflow { .. })));
+
+    handle.abort();
+    Ok(())
+}
+
+/// A tool looking up the weather of a city
+fn weather_tool() -> Result<ToolInfo, serde_json::Error> {
+    serde_json::from_value(serde_json::json!({
+        "type": "function",
+        "function": {
+            "name": "get_weather",
+            "description": "Current weather of a city",
+            "parameters": {
+                "type": "object",
+                "properties": {"city": {"type": "string"}},
+                "required": ["city"]
+            }
+        }
+    }))
+}
+
+#[tokio::test]
+async fn test_chat_tool_call_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    // The model is forced to call the only tool it is given, then answers from its result
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue(
+        "/api/chat",
+        MockResponse::json(serde_json::json!({
+            "model": "mock",
+            "created_at": "2024-01-01T00:00:00Z",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "get_weather", "arguments": {"city": "Paris"}}}]
+            },
+            "done": true
+        })),
+    );
+    ollama.enqueue("/api/chat", MockResponse::chat("It is sunny in Paris."));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/tools").await?;
+
+    let mut messages = ChatMessages::builder()
+        .messages(vec![ChatMessage::user("What is the weather in Paris?".to_string())])
+        .tools(vec![weather_tool()?])
+        .stream(true)
+        .build();
+    let reply =
+        relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages.clone(), &chat_id, SendOptions::default()).await?;
+
+    // The reply carries the tool call instead of text, parsed against the tools given
+    assert_eq!(reply.message.tool_calls.len(), 1);
+    assert_eq!(reply.tool_calls.len(), 1);
+    let call = &reply.tool_calls[0];
+    assert_eq!(call.id, "call_0");
+    assert_eq!(call.name, "get_weather");
+    let arguments: HashMap<String, String> = call.arguments()?;
+    assert_eq!(arguments["city"], "Paris");
+
+    // The tools reached the server as Ollama expects them, and tool calls are never streamed
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests[0].body["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(requests[0].body["stream"], false);
+
+    // The tool result continues the conversation, and keeps the id of the call it answers
+    messages.messages.clear();
+    let id = messages.push_tool_result(&call.id, r#"{"forecast": "sunny"}"#);
+    assert_eq!(messages.messages[0].id, id);
+    assert_eq!(messages.messages[0].tool_call_id.as_deref(), Some("call_0"));
+    let reply = relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, &chat_id, SendOptions::default()).await?;
+    assert_eq!(reply.message.content, "It is sunny in Paris.");
+    assert!(reply.message.tool_calls.is_empty());
+
+    let sent = &ollama.requests_to("/api/chat")[1].body["messages"];
+    assert_eq!(sent[0]["role"], "user");
+    assert_eq!(sent[1]["role"], "assistant");
+    assert_eq!(sent[1]["tool_calls"][0]["function"]["name"], "get_weather");
+    assert_eq!(sent[2]["role"], "tool");
+    assert_eq!(sent[2]["content"], r#"{"forecast": "sunny"}"#);
+
+    // The call and its result are linked by id on the wire
+    assert_eq!(sent[1]["tool_calls"][0]["id"], "call_0");
+    assert_eq!(sent[2]["tool_call_id"], "call_0");
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_malformed_tool_calls() -> Result<(), Box<dyn std::error::Error>> {
+    let tool_call = |name: &str, arguments: serde_json::Value| {
+        MockResponse::json(serde_json::json!({
+            "model": "mock",
+            "created_at": "2024-01-01T00:00:00Z",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": name, "arguments": arguments}}]
+            },
+            "done": true
+        }))
+    };
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", tool_call("get_weather", serde_json::json!(r#"{"city": "Paris"}"#)));
+    ollama.enqueue("/api/chat", tool_call("get_time", serde_json::json!({"city": "Paris"})));
+    ollama.enqueue("/api/chat", tool_call("get_weather", serde_json::json!(["Paris"])));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/tools/malformed").await?;
+    let ask = ChatMessages::builder()
+        .messages(vec![ChatMessage::user("What is the weather in Paris?".to_string())])
+        .tools(vec![weather_tool()?])
+        .restart(true)
+        .build();
+
+    // Arguments encoded as a JSON string are parsed
+    let reply =
+        relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(ask.clone(), &chat_id, SendOptions::default()).await?;
+    assert_eq!(reply.tool_calls[0].arguments.get("city"), Some(&serde_json::json!("Paris")));
+
+    // A call to a tool that was not given, or with arguments that are not an object, fails the request
+    for expected in ["unknown tool \"get_time\"", "not a JSON object"] {
+        let result =
+            relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(ask.clone(), &chat_id, SendOptions::default()).await;
+        let error = result.expect_err("Expected the tool call to be refused");
+        assert!(error.to_string().contains(expected), "Unexpected error: {}", error);
+    }
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_tools_unsupported() -> Result<(), Box<dyn std::error::Error>> {
+    // Ollama refuses tools for models that cannot call them
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::error(400, "registry.ollama.ai/library/mock does not support tools"));
+    ollama.enqueue("/api/chat", MockResponse::chat("I cannot check the weather."));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/tools/unsupported").await?;
+
+    let messages = ChatMessages::builder()
+        .messages(vec![ChatMessage::user("What is the weather in Paris?".to_string())])
+        .tools(vec![weather_tool()?])
+        .build();
+    let reply = relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, &chat_id, SendOptions::default()).await?;
+
+    // The request is sent again without tools, and answered without tool calls
+    assert_eq!(reply.message.content, "I cannot check the weather.");
+    assert!(reply.message.tool_calls.is_empty());
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].body.get("tools").is_some());
+    assert!(requests[1].body.get("tools").is_none());
+    assert_eq!(requests[1].body["messages"], requests[0].body["messages"]);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_summarization() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("The user asked about twenty topics."));
+    ollama.set_default("/api/chat", MockResponse::chat("Noted."));
+
+    // Twenty synthetic turns, well over the policy budget
+    let history: Vec<ChatMessage> = (0..20)
+        .flat_map(|i| {
+            [
+                ChatMessage::user(format!("Tell me about topic number {}", i)),
+                ChatMessage::assistant(format!("Topic number {} is an interesting subject", i)),
+            ]
+        })
+        .collect();
+    let chat = Chat::builder()
+        .model("mock-summarization")
+        .endpoint(ollama.url().clone())
+        .messages_number_limit(100)
+        .history(history.clone())
+        .summarization(SummarizationPolicy::builder().max_tokens(200).keep_turns(3).build())
+        .build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/summarization").await?;
+
+    let reply = ask_chat(&relay_ctx, &chat_id, "And what about the last one?", false).await?;
+    assert_eq!(reply.message.content, "Noted.");
+
+    // The oldest turns went to a separate summarization call
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    let transcript = requests[0].body["messages"][1]["content"].as_str().unwrap_or_default();
+    assert!(transcript.contains("user: Tell me about topic number 0"));
+    assert!(!transcript.contains("topic number 17"), "The kept turns should not be summarized");
+
+    // The history shrank to the summary followed by the last turns verbatim
+    let sent = requests[1].body["messages"].as_array().unwrap();
+    assert_eq!(sent.len(), 1 + 6 + 1);
+    assert_eq!(sent[0]["role"], "system");
+    assert!(sent[0]["content"].as_str().unwrap_or_default().contains("The user asked about twenty topics."));
+    for (sent, kept) in sent[1..7].iter().zip(&history[34..]) {
+        assert_eq!(sent["content"], kept.content);
+    }
+    assert_eq!(sent[7]["content"], "And what about the last one?");
+
+    // The compacted history fits, so the next request is not summarized again
+    ask_chat(&relay_ctx, &chat_id, "Thanks", false).await?;
+    assert_eq!(ollama.requests_to("/api/chat").len(), 3);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_summarization_max_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::chat("The first four topics were covered."));
+    ollama.enqueue("/api/chat", MockResponse::chat("Noted."));
+    ollama.enqueue("/api/chat", MockResponse::chat("Five topics were covered."));
+    ollama.set_default("/api/chat", MockResponse::chat("Noted."));
+
+    // A system prompt followed by six turns
+    let mut history = vec![ChatMessage::system("You are a concise tutor.".to_string())];
+    history.extend((0..6).flat_map(|i| {
+        [
+            ChatMessage::user(format!("Tell me about topic number {}", i)),
+            ChatMessage::assistant(format!("Topic number {} is an interesting subject", i)),
+        ]
+    }));
+    let chat = Chat::builder()
+        .model("mock-summarization-messages")
+        .endpoint(ollama.url().clone())
+        .messages_number_limit(100)
+        .history(history.clone())
+        .summarization(SummarizationPolicy::builder().max_messages(8).keep_turns(1).build())
+        .build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/summarization/messages").await?;
+
+    ask_chat(&relay_ctx, &chat_id, "And what about the last one?", false).await?;
+
+    // The system prompt is not summarized, and at least two turns are kept
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    let transcript = requests[0].body["messages"][1]["content"].as_str().unwrap_or_default();
+    assert!(!transcript.contains("concise tutor"), "The system prompt should not be summarized");
+    assert!(transcript.contains("topic number 3") && !transcript.contains("topic number 4"));
+
+    // The system prompt, the summary, the last two turns and the new message are sent
+    let sent = requests[1].body["messages"].as_array().unwrap();
+    assert_eq!(sent.len(), 1 + 1 + 4 + 1);
+    assert_eq!(sent[0]["content"], "You are a concise tutor.");
+    assert!(sent[1]["content"].as_str().unwrap_or_default().contains("The first four topics were covered."));
+    for (sent, kept) in sent[2..6].iter().zip(&history[9..]) {
+        assert_eq!(sent["content"], kept.content);
+    }
+
+    // The next summary folds in the previous one
+    ask_chat(&relay_ctx, &chat_id, "Thanks", false).await?;
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 4);
+    let transcript = requests[2].body["messages"][1]["content"].as_str().unwrap_or_default();
+    assert!(transcript.contains("The first four topics were covered."));
+    let sent = requests[3].body["messages"].as_array().unwrap();
+    assert_eq!(sent[0]["content"], "You are a concise tutor.");
+    assert!(sent[1]["content"].as_str().unwrap_or_default().contains("Five topics were covered."));
+    assert_eq!(sent.iter().filter(|message| message["role"] == "system").count(), 2);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_logprobs() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.set_default(
+        "/api/chat",
+        MockResponse::json(serde_json::json!({
+            "model": "mock",
+            "created_at": "2024-01-01T00:00:00Z",
+            "message": {"role": "assistant", "content": "Yes."},
+            "done": true,
+            "logprobs": [
+                {"token": "Yes", "logprob": -0.25, "top_logprobs": [{"token": "No", "logprob": -1.5}]},
+                {"token": ".", "logprob": -0.01}
+            ]
+        })),
+    );
+
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).logprobs(true).build()?;
+    let raw = chat.send_raw(vec![ChatMessage::user("Is the sky blue?".to_string())]).await?;
+    assert_eq!(ollama.requests_to("/api/chat")[0].body["logprobs"], true);
+
+    let logprobs = raw.logprobs.expect("Expected log probabilities on the response");
+    assert_eq!(logprobs.len(), 2);
+    assert_eq!((logprobs[0].token.as_str(), logprobs[0].logprob), ("Yes", -0.25));
+    assert_eq!(logprobs[0].top_logprobs[0].token, "No");
+    assert!(logprobs[1].top_logprobs.is_empty());
+
+    // Not asked for, not parsed
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).build()?;
+    let raw = chat.send_raw(vec![ChatMessage::user("Is the sky blue?".to_string())]).await?;
+    assert!(ollama.requests_to("/api/chat")[1].body.get("logprobs").is_none());
+    assert!(raw.logprobs.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_mock_ollama_failures() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy"));
+    ollama.enqueue("/api/chat", MockResponse::chat("Finally").with_latency(Duration::from_millis(300)));
+    let (relay_ctx, chat_id, handle) = spawn_mock_chat(&ollama, "/mock/chat/failures").await?;
+
+    // An overloaded server fails the request
+    assert!(ask_chat(&relay_ctx, &chat_id, "Hello", false).await.is_err());
+
+    // A slow server is waited for
+    let start = std::time::Instant::now();
+    let reply = ask_chat(&relay_ctx, &chat_id, "Hello again", false).await?;
+    assert_eq!(reply.message.content, "Finally");
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert_eq!(ollama.requests_to("/api/chat").len(), 2);
+
+    handle.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_retry() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    let retry = RetryConfig::builder().max_retries(2).base_delay(Duration::from_millis(20)).build();
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).retry(retry).build()?;
+    let (relay_ctx, chat_id, handle) = spawn_chat(chat, "/mock/chat/retry").await?;
+
+    // A busy server is retried until it answers, and the reply tells how many attempts it took
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy, please try again"));
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy, please try again"));
+    ollama.enqueue("/api/chat", MockResponse::chat("Finally"));
+    let reply = ask_chat(&relay_ctx, &chat_id, "Hello", false).await?;
+    assert_eq!(reply.message.content, "Finally");
+    assert_eq!(reply.attempts, 3);
+    assert_eq!(ollama.requests_to("/api/chat").len(), 3);
+
+    // A server answering the second time, streamed or not
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy, please try again"));
+    ollama.enqueue("/api/chat", MockResponse::chat("Second"));
+    let reply = ask_chat(&relay_ctx, &chat_id, "Hello", false).await?;
+    assert_eq!(reply.message.content, "Second");
+    assert_eq!(reply.attempts, 2);
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy, please try again"));
+    ollama.enqueue("/api/chat", MockResponse::chat("Second"));
+    let request = ChatMessages::builder().messages(vec![ChatMessage::user("Hello".to_string())]).stream(true).build();
+    let chunks = relay_ctx.send_and_collect::<Chat, ChatMessages>(request, &chat_id, SendOptions::default()).await?;
+    assert!(chunks.iter().all(|chunk| chunk.attempts == 2));
+    assert_eq!(ollama.requests_to("/api/chat").len(), 7);
+
+    // A bad request fails on the first attempt
+    ollama.enqueue("/api/chat", MockResponse::error(400, "invalid options"));
+    assert!(ask_chat(&relay_ctx, &chat_id, "Hello", false).await.is_err());
+    assert_eq!(ollama.requests_to("/api/chat").len(), 8);
+
+    // Retries run out, and the error tells how many attempts were made
+    for _ in 0..3 {
+        ollama.enqueue("/api/chat", MockResponse::error(503, "server busy, please try again"));
+    }
+    let error = ask_chat(&relay_ctx, &chat_id, "Hello", false).await.expect_err("Expected the retries to run out");
+    assert!(error.to_string().contains("failed after 3 attempts"), "Unexpected error: {}", error);
+    assert_eq!(ollama.requests_to("/api/chat").len(), 11);
+
+    // Delays double up to the maximum, shortened by the jitter
+    let retry = RetryConfig::builder()
+        .base_delay(Duration::from_millis(100))
+        .max_delay(Duration::from_millis(300))
+        .jitter(0.5)
+        .build();
+    for (attempt, full) in [(1, 100), (2, 200), (3, 300), (10, 300)] {
+        let delay = retry.delay(attempt);
+        assert!(delay <= Duration::from_millis(full) && delay >= Duration::from_millis(full / 2), "{:?}", delay);
+    }
+    let invalid = RetryConfig::builder().jitter(1.5).build();
+    assert!(matches!(Chat::builder().retry(invalid).build(), Err(ChatError::InvalidConfig(_))));
+
+    handle.abort();
+    Ok(())
+}
+
+/// Backend failing with the queued errors, one per request, before answering
+#[derive(Debug)]
+struct ScriptedBackend {
+    response: ChatMessageResponse,
+    errors: Mutex<VecDeque<ChatError>>,
+    attempts: AtomicUsize,
+}
+
+impl ScriptedBackend {
+    fn answer(&self) -> Result<ChatMessageResponse, ChatError> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        match self.errors.lock().unwrap().pop_front() {
+            Some(error) => Err(error),
+            None => Ok(self.response.clone()),
+        }
+    }
+}
+
+impl ChatBackend for ScriptedBackend {
+    fn chat<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        Box::pin(async move { self.answer() })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let response = self.answer()?;
+            Ok(Box::pin(futures::stream::iter([Ok::<_, ChatError>(response)])) as ChatResponseStream)
+        })
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_chat_retry_backoff() -> Result<(), Box<dyn std::error::Error>> {
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Loaded"},"done":true}"#;
+    let backend = Arc::new(ScriptedBackend {
+        response: serde_json::from_str(body)?,
+        errors: Mutex::new(VecDeque::from([
+            ChatError::OllamaStatus { status: reqwest::StatusCode::BAD_GATEWAY, message: "Bad Gateway".to_string() },
+            ChatError::OllamaStatus {
+                status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                message: "request timed out while loading the model".to_string(),
+            },
+        ])),
+        attempts: AtomicUsize::new(0),
+    });
+    let retry = RetryConfig::builder()
+        .max_retries(3)
+        .base_delay(Duration::from_millis(100))
+        .multiplier(3.0)
+        .jitter(0.0)
+        .build();
+    let chat = Chat::builder().model("stub").backend(backend.clone()).retry(retry).build()?;
+    let messages = ChatMessages::builder().messages(vec![ChatMessage::user("Hi".to_string())]).build();
+
+    // Two gateway errors are retried after 100ms then 300ms
+    let start = tokio::time::Instant::now();
+    let mut stream = chat.stream(&messages).await?;
+    let elapsed = start.elapsed();
+    assert!(elapsed.abs_diff(Duration::from_millis(400)) < Duration::from_millis(1), "Waited {:?}", elapsed);
+    assert_eq!(stream.next().await.transpose()?.map(|chunk| chunk.message.content), Some("Loaded".to_string()));
+    assert_eq!(backend.attempts.load(Ordering::SeqCst), 3);
+
+    // An invalid reply fails at once
+    let invalid = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    backend.errors.lock().unwrap().push_back(ChatError::JsonError(invalid));
+    let start = tokio::time::Instant::now();
+    assert!(matches!(chat.stream(&messages).await, Err(ChatError::JsonError(_))));
+    assert_eq!(start.elapsed(), Duration::ZERO);
+    assert_eq!(backend.attempts.load(Ordering::SeqCst), 4);
+
+    // Errors are classified by their status, not by their message
+    let refused = ChatError::OllamaStatus {
+        status: reqwest::StatusCode::BAD_REQUEST,
+        message: "server busy, please try again".to_string(),
+    };
+    backend.errors.lock().unwrap().push_back(refused);
+    assert!(matches!(chat.stream(&messages).await, Err(ChatError::OllamaStatus { .. })));
+    assert_eq!(start.elapsed(), Duration::ZERO);
+    assert_eq!(backend.attempts.load(Ordering::SeqCst), 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chat_mock_ollama_supervised_restart() -> Result<(), Box<dyn std::error::Error>> {
+    let ollama = MockOllama::start().await?;
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy"));
+    ollama.enqueue("/api/chat", MockResponse::error(503, "server busy"));
+    ollama.set_default("/api/chat", MockResponse::chat("Back online"));
+
+    let engine = Engine::test().await?;
+    let policy = RestartPolicy::builder().failure_threshold(2).initial_backoff(Duration::from_millis(100)).build();
+    let supervisor = Supervisor::new(engine.clone(), policy);
+    let mut events = supervisor.subscribe();
+
+    let chat_id = ActorId::of::<Chat>("/mock/supervised/chat");
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).build()?;
+    supervisor.supervise(chat_id.clone(), chat, SpawnOptions::default())?;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) = Actor::spawn(engine.clone(), relay_id, Relay, SpawnOptions::default()).await?;
+
+    // Service errors from the server count as backend failures
+    assert!(ask_chat(&relay_ctx, &chat_id, "Are you there?", false).await.is_err());
+    assert!(ask_chat(&relay_ctx, &chat_id, "Hello?", false).await.is_err());
+    let event = tokio::time::timeout(Duration::from_secs(10), events.recv()).await??;
+    assert!(matches!(event, LifecycleEvent::Restarting { attempt: 1, .. }), "Unexpected event: {:?}", event);
+    let event = tokio::time::timeout(Duration::from_secs(10), events.recv()).await??;
+    assert!(matches!(event, LifecycleEvent::Restarted { attempt: 1, .. }), "Unexpected event: {:?}", event);
+
+    let reply = ask_chat(&relay_ctx, &chat_id, "Hello now", false).await?;
+    assert_eq!(reply.message.content, "Back online");
+
+    Ok(())
+}
+
+#[test]
+fn test_chat_builder() -> Result<(), ChatError> {
+    let chat = Chat::builder().build()?;
+    assert_eq!(chat.model, "llama3.2:3b");
+    assert_eq!(chat.endpoint.as_str(), "http://localhost:11434/");
+    assert_eq!(chat.messages_number_limit, 10);
+    assert_eq!(chat.max_concurrent_requests, 4);
+
+    let endpoint = url::Url::parse("http://ollama:11434").unwrap();
+    let chat = Chat::builder().model("llama3").endpoint(endpoint.clone()).messages_number_limit(2).build()?;
+    assert_eq!(chat.model, "llama3");
+    assert_eq!(chat.endpoint, endpoint);
+    assert_eq!(chat.messages_number_limit, 2);
+
+    assert!(matches!(Chat::builder().model("").build(), Err(ChatError::InvalidConfig(_))));
+    assert!(matches!(Chat::builder().max_concurrent_requests(0).build(), Err(ChatError::InvalidConfig(_))));
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use bioma_llm::config::{self, ChatConfig, ConfigError, OllamaConfig};
+use serde_json::json;
+
+#[test]
+fn test_chat_config_with_env_overrides() -> Result<(), ConfigError> {
+    let mut value = json!({"model": "llama3.2:3b", "max_context_length": 4096});
+    let overrides = vec![
+        ("BIOMA__MESSAGES_NUMBER_LIMIT".to_string(), "20".to_string()),
+        ("OTHER__MODEL".to_string(), "ignored".to_string()),
+    ];
+    config::apply_env(&mut value, overrides)?;
+    let chat_config: ChatConfig = config::deserialize(value)?;
+
+    // The shared Ollama endpoint is used unless the section sets its own
+    let ollama: OllamaConfig = config::deserialize(json!({"endpoint": "http://ollama.internal:11434"}))?;
+    let chat = chat_config.chat(&ollama)?;
+    assert_eq!(chat.model, "llama3.2:3b");
+    assert_eq!(chat.messages_number_limit, 20);
+    assert_eq!(chat.max_context_length, 4096);
+    assert_eq!(chat.endpoint.as_str(), "http://ollama.internal:11434/");
+
+    Ok(())
+}
+
+#[test]
+fn test_chat_config_invalid_key() {
+    let error = config::deserialize::<ChatConfig>(json!({"max_context_length": "large"})).unwrap_err();
+    assert!(error.to_string().contains("`max_context_length`"), "Unexpected error: {}", error);
+
+    let chat_config = ChatConfig { max_concurrent_requests: 0, ..Default::default() };
+    let error = chat_config.chat(&OllamaConfig::default()).unwrap_err();
+    assert!(error.to_string().contains("`chat.max_concurrent_requests`"), "Unexpected error: {}", error);
+}
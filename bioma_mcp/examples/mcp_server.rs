@@ -12,6 +12,7 @@ use bioma_mcp::{
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, Level};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::fmt::format::FmtSpan;
@@ -134,7 +135,17 @@ async fn main() -> Result<()> {
 
     let server = ExampleMcpServer { transport_config, capabilities, base_dir: args.base_dir };
 
-    let mcp_server = Server::new(server);
+    // Notify clients and flush the transport on Ctrl-C instead of exiting mid-request
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let _ = tokio::signal::ctrl_c().await;
+            shutdown.cancel();
+        }
+    });
+
+    let mcp_server = Server::new_with_shutdown(server, shutdown);
 
     let _ = mcp_server.start().await;
 
@@ -6,6 +6,7 @@ use crate::schema::{
     ListToolsResult, ReadResourceRequestParams, ReadResourceResult, Root, RootsListChangedNotificationParams,
     ServerCapabilities,
 };
+use crate::transport::interceptor::{InterceptorChain, TransportInterceptor};
 use crate::transport::sse::SseTransport;
 use crate::transport::ws::WsTransport;
 use crate::transport::{stdio::StdioTransport, Transport, TransportSender, TransportType};
@@ -122,11 +123,21 @@ pub struct Client<T: ModelContextProtocolClient> {
     #[allow(unused)]
     on_close_rx: mpsc::Receiver<()>,
     conn_id: ConnectionId,
+    interceptors: InterceptorChain,
 }
 
 impl<T: ModelContextProtocolClient> Client<T> {
     pub async fn new(client: T) -> Result<Self, ClientError> {
+        Self::new_with_interceptors(client, vec![]).await
+    }
+
+    /// Creates a client applying the interceptors, in order, to every message sent and received
+    pub async fn new_with_interceptors(
+        client: T,
+        interceptors: Vec<Arc<dyn TransportInterceptor>>,
+    ) -> Result<Self, ClientError> {
         let client = Arc::new(RwLock::new(client));
+        let interceptors = InterceptorChain::new(interceptors);
 
         let (on_message_tx, mut on_message_rx) = mpsc::channel::<JsonRpcMessage>(1);
         let (on_error_tx, on_error_rx) = mpsc::channel::<Error>(1);
@@ -216,8 +227,10 @@ impl<T: ModelContextProtocolClient> Client<T> {
 
         let message_handler = tokio::spawn({
             let pending_requests = pending_requests_clone;
+            let interceptors = interceptors.clone();
             async move {
                 while let Some(message) = on_message_rx.recv().await {
+                    let message = interceptors.inbound(message);
                     match &message {
                         JsonRpcMessage::Response(jsonrpc_core::Response::Single(output)) => match output {
                             jsonrpc_core::Output::Success(success) => {
@@ -246,9 +259,8 @@ impl<T: ModelContextProtocolClient> Client<T> {
                                     return;
                                 };
 
-                                if let Err(e) =
-                                    transport_sender_clone.send(response.into(), conn_id_clone.clone()).await
-                                {
+                                let response = interceptors.outbound(response.into(), &conn_id_clone);
+                                if let Err(e) = transport_sender_clone.send(response, conn_id_clone.clone()).await {
                                     error!("Failed to send response: {}", e);
                                 }
                             }
@@ -277,6 +289,7 @@ impl<T: ModelContextProtocolClient> Client<T> {
             on_error_rx,
             on_close_rx,
             conn_id,
+            interceptors,
         })
     }
 
@@ -442,8 +455,9 @@ impl<T: ModelContextProtocolClient> Client<T> {
         }
 
         let conn_id = self.conn_id.clone();
+        let request = self.interceptors.outbound(request.into(), &conn_id);
 
-        if let Err(e) = self.transport_sender.send(request.into(), conn_id).await {
+        if let Err(e) = self.transport_sender.send(request, conn_id).await {
             let mut pending = self.pending_requests.lock().await;
             pending.remove(&id);
             return Err(ClientError::Transport(format!("Send: {}", e).into()));
@@ -475,9 +489,10 @@ impl<T: ModelContextProtocolClient> Client<T> {
         };
 
         let conn_id = self.conn_id.clone();
+        let notification = self.interceptors.outbound(notification.into(), &conn_id);
 
         self.transport_sender
-            .send(notification.into(), conn_id)
+            .send(notification, conn_id)
             .await
             .map_err(|e| ClientError::Transport(format!("Send: {}", e).into()))
     }
@@ -19,7 +19,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 #[derive(Clone)]
@@ -57,12 +60,23 @@ pub struct SseConfig {
     pub endpoint: String,
     #[builder(default = default_channel_capacity())]
     pub channel_capacity: usize,
+    /// Disconnect clients that post no message for this long
+    #[serde(default, with = "humantime_serde")]
+    pub idle_timeout: Option<Duration>,
+    /// Reject posted messages that parse but break the JSON-RPC 2.0 structure with `400 Bad Request`
+    #[builder(default = default_validate_json_rpc())]
+    #[serde(default = "default_validate_json_rpc")]
+    pub validate_json_rpc: bool,
 }
 
 fn default_server_url() -> String {
     "127.0.0.1:8090".to_string()
 }
 
+fn default_validate_json_rpc() -> bool {
+    true
+}
+
 fn default_channel_capacity() -> usize {
     32
 }
@@ -236,15 +250,23 @@ pub struct Server<T: ModelContextProtocolServer> {
     sessions: Arc<RwLock<HashMap<ConnectionId, Session>>>,
     pending_requests: PendingRequests,
     request_counter: RequestCounter,
+    shutdown: CancellationToken,
 }
 
 impl<T: ModelContextProtocolServer> Server<T> {
     pub fn new(server: T) -> Self {
+        Self::new_with_shutdown(server, CancellationToken::new())
+    }
+
+    /// Creates a server that shuts down once `shutdown` is cancelled: it stops reading requests, answers those in
+    /// flight, then closes its transport, notifying SSE clients and flushing stdio
+    pub fn new_with_shutdown(server: T, shutdown: CancellationToken) -> Self {
         Server {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             server: Arc::new(RwLock::new(server)),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
             request_counter: Arc::new(RwLock::new(0)),
+            shutdown,
         }
     }
 
@@ -782,22 +804,36 @@ impl<T: ModelContextProtocolServer> Server<T> {
             }
         });
 
-        {
+        let transport_handle = {
             let mut transport_lock = transport.lock().await;
-            if let Err(e) = transport_lock.start().await {
-                error!("Transport error: {}", e);
-                return Err(ServerError::Transport(e.to_string()));
+            match transport_lock.start().await {
+                Ok(handle) => handle,
+                Err(e) => {
+                    error!("Transport error: {}", e);
+                    return Err(ServerError::Transport(e.to_string()));
+                }
             }
-        }
+        };
 
         let pending_requests = self.pending_requests.clone();
+        let mut tasks = JoinSet::new();
+
+        loop {
+            let message = tokio::select! {
+                message = on_client_rx.recv() => message,
+                _ = self.shutdown.cancelled() => None,
+            };
+            let Some(message) = message else {
+                break;
+            };
+            // Reap finished requests so the set only holds those in flight
+            while tasks.try_join_next().is_some() {}
 
-        while let Some(message) = on_client_rx.recv().await {
             let io_handler_clone = io_handler.clone();
             let transport_sender_clone = transport_sender.clone();
             let pending_requests = pending_requests.clone();
 
-            tokio::spawn(async move {
+            tasks.spawn(async move {
                 match &message.message {
                     JsonRpcMessage::Request(request) => match request {
                         jsonrpc_core::Request::Single(jsonrpc_core::Call::MethodCall(_call)) => {
@@ -848,6 +884,15 @@ impl<T: ModelContextProtocolServer> Server<T> {
             });
         }
 
+        if self.shutdown.is_cancelled() {
+            info!("Shutting down, waiting for {} requests in flight", tasks.len());
+        }
+        while tasks.join_next().await.is_some() {}
+        if let Err(e) = transport.lock().await.close().await {
+            error!("Failed to close transport: {}", e);
+        }
+        transport_handle.abort();
+
         Ok(())
     }
 }
@@ -0,0 +1,90 @@
+use crate::{ConnectionId, JsonRpcMessage};
+use jsonrpc_core::{Call, Params, Request};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// Metadata attached to an outbound message by the interceptors.
+///
+/// The entries of `meta` are merged into the `_meta` field of the request parameters once every interceptor has run,
+/// which is how MCP carries out-of-band data such as authentication tokens.
+#[derive(Debug, Clone)]
+pub struct MessageMetadata {
+    pub conn_id: ConnectionId,
+    pub meta: Map<String, Value>,
+}
+
+/// A hook applied to every JSON-RPC message crossing the transport, regardless of its method.
+///
+/// Interceptors run in the order they were added on both outbound and inbound messages. Both hooks do nothing by
+/// default, so an interceptor only implements the direction it cares about.
+pub trait TransportInterceptor: Send + Sync {
+    /// Called before a message is handed to the transport
+    fn on_send(&self, _message: &mut JsonRpcMessage, _metadata: &mut MessageMetadata) {}
+
+    /// Called when a message is received, before it is dispatched
+    fn on_receive(&self, _message: &mut JsonRpcMessage) {}
+}
+
+/// Interceptors applied in order around a transport
+#[derive(Clone, Default)]
+pub struct InterceptorChain {
+    interceptors: Vec<Arc<dyn TransportInterceptor>>,
+}
+
+impl InterceptorChain {
+    pub fn new(interceptors: Vec<Arc<dyn TransportInterceptor>>) -> Self {
+        Self { interceptors }
+    }
+
+    pub fn push(&mut self, interceptor: Arc<dyn TransportInterceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+
+    /// Runs the outbound hooks, then merges the collected metadata into the message
+    pub fn outbound(&self, mut message: JsonRpcMessage, conn_id: &ConnectionId) -> JsonRpcMessage {
+        if self.is_empty() {
+            return message;
+        }
+
+        let mut metadata = MessageMetadata { conn_id: conn_id.clone(), meta: Map::new() };
+        for interceptor in &self.interceptors {
+            interceptor.on_send(&mut message, &mut metadata);
+        }
+        if !metadata.meta.is_empty() {
+            merge_meta(&mut message, metadata.meta);
+        }
+        message
+    }
+
+    /// Runs the inbound hooks
+    pub fn inbound(&self, mut message: JsonRpcMessage) -> JsonRpcMessage {
+        for interceptor in &self.interceptors {
+            interceptor.on_receive(&mut message);
+        }
+        message
+    }
+}
+
+/// Merges entries into the `_meta` of a request or notification, responses carry no metadata
+fn merge_meta(message: &mut JsonRpcMessage, meta: Map<String, Value>) {
+    let params = match message {
+        JsonRpcMessage::Request(Request::Single(Call::MethodCall(call))) => &mut call.params,
+        JsonRpcMessage::Request(Request::Single(Call::Notification(notification))) => &mut notification.params,
+        _ => return,
+    };
+
+    if matches!(params, Params::None) {
+        *params = Params::Map(Map::new());
+    }
+    let Params::Map(params) = params else {
+        return;
+    };
+    let Value::Object(existing) = params.entry("_meta").or_insert_with(|| Value::Object(Map::new())) else {
+        return;
+    };
+    existing.extend(meta);
+}
@@ -1,3 +1,4 @@
+pub mod interceptor;
 pub mod sse;
 pub mod stdio;
 pub mod ws;
@@ -5,6 +6,7 @@ pub mod ws;
 use crate::ConnectionId;
 use crate::JsonRpcMessage;
 use anyhow::Result;
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 use tokio::task::JoinHandle;
@@ -20,6 +22,17 @@ pub trait Transport {
 
     fn send(&mut self, message: JsonRpcMessage, conn_id: ConnectionId) -> impl Future<Output = Result<()>>;
 
+    /// Sends an already serialized JSON-RPC message.
+    ///
+    /// The default implementation deserializes the bytes and calls `send`; transports that can write the bytes
+    /// directly override it to skip the round-trip.
+    fn send_raw(&mut self, bytes: Bytes, conn_id: ConnectionId) -> impl Future<Output = Result<()>> {
+        async move {
+            let message: JsonRpcMessage = serde_json::from_slice(&bytes)?;
+            self.send(message, conn_id).await
+        }
+    }
+
     fn close(&mut self) -> impl Future<Output = Result<()>>;
 
     fn sender(&self) -> TransportSender;
@@ -99,6 +112,14 @@ impl Transport for TransportType {
         }
     }
 
+    async fn send_raw(&mut self, bytes: Bytes, conn_id: ConnectionId) -> Result<()> {
+        match self {
+            TransportType::Stdio(t) => t.send_raw(bytes, conn_id).await,
+            TransportType::Sse(t) => t.send_raw(bytes, conn_id).await,
+            TransportType::Ws(t) => t.send_raw(bytes, conn_id).await,
+        }
+    }
+
     async fn close(&mut self) -> Result<()> {
         match self {
             TransportType::Stdio(t) => t.close().await,
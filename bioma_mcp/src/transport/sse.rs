@@ -7,7 +7,7 @@ use super::{SendMessage, Transport, TransportSender};
 use anyhow::{Context, Error, Result};
 use bytes::Bytes;
 use futures_util::StreamExt;
-use http_body_util::{BodyExt, Empty};
+use http_body_util::{BodyExt, Full};
 use hyper::{body::Frame, header, service::service_fn, Method, Request, Response, StatusCode};
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder as HyperServerBuilder;
@@ -16,9 +16,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, Mutex, Notify};
 use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
@@ -38,6 +39,10 @@ pub enum SseEvent {
 
     #[serde(rename = "shutdown")]
     Shutdown(Shutdown),
+
+    /// An already serialized JSON-RPC message, written to the frame as is
+    #[serde(skip)]
+    RawMessage(Bytes),
 }
 
 impl SseEvent {
@@ -47,12 +52,23 @@ impl SseEvent {
 
     pub fn to_sse_string(&self) -> Result<String> {
         let event_type = match self {
-            SseEvent::Message(_) => Self::EVENT_TYPE_MESSAGE,
+            SseEvent::Message(_) | SseEvent::RawMessage(_) => Self::EVENT_TYPE_MESSAGE,
             SseEvent::Endpoint(_) => Self::EVENT_TYPE_ENDPOINT,
             SseEvent::Shutdown(_) => Self::EVENT_TYPE_SHUTDOWN,
         };
 
-        let data = serde_json::to_string(self)?;
+        let data = match self {
+            SseEvent::RawMessage(bytes) => {
+                let data = std::str::from_utf8(bytes).context("Raw message is not valid UTF-8")?;
+                // A data line cannot span lines, so multi-line JSON is compacted first
+                if data.contains(['\n', '\r']) {
+                    serde_json::from_str::<serde_json::Value>(data)?.to_string()
+                } else {
+                    data.to_string()
+                }
+            }
+            _ => serde_json::to_string(self)?,
+        };
 
         Ok(format!("event: {}\ndata: {}\n\n", event_type, data))
     }
@@ -121,13 +137,76 @@ enum SseError {
     Other(String),
 }
 
-type ClientRegistry = Arc<Mutex<HashMap<ConnectionId, mpsc::Sender<SseEvent>>>>;
+/// Checks that a parsed message follows the JSON-RPC 2.0 structure, describing the first violation found
+fn check_json_rpc(message: &JsonRpcMessage) -> std::result::Result<(), String> {
+    use jsonrpc_core::{Call, Output, Request as RpcRequest, Response as RpcResponse, Version};
+
+    let check_version = |version: Option<Version>| match version {
+        Some(Version::V2) => Ok(()),
+        None => Err("Missing or unsupported \"jsonrpc\" version, expected \"2.0\"".to_string()),
+    };
+
+    match message {
+        JsonRpcMessage::Request(request) => {
+            let calls = match request {
+                RpcRequest::Single(call) => std::slice::from_ref(call),
+                RpcRequest::Batch(calls) => calls.as_slice(),
+            };
+            if calls.is_empty() {
+                return Err("Empty batch request".to_string());
+            }
+            for call in calls {
+                match call {
+                    Call::MethodCall(jsonrpc_core::MethodCall { jsonrpc, method, .. })
+                    | Call::Notification(jsonrpc_core::Notification { jsonrpc, method, .. }) => {
+                        check_version(*jsonrpc)?;
+                        if method.is_empty() {
+                            return Err("Request \"method\" must not be empty".to_string());
+                        }
+                    }
+                    Call::Invalid { .. } => {
+                        return Err("Request is neither a method call nor a notification".to_string());
+                    }
+                }
+            }
+        }
+        JsonRpcMessage::Response(response) => {
+            let outputs = match response {
+                RpcResponse::Single(output) => std::slice::from_ref(output),
+                RpcResponse::Batch(outputs) => outputs.as_slice(),
+            };
+            if outputs.is_empty() {
+                return Err("Empty batch response".to_string());
+            }
+            for output in outputs {
+                match output {
+                    Output::Success(success) => check_version(success.jsonrpc)?,
+                    Output::Failure(failure) => check_version(failure.jsonrpc)?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A connected client, with the signal raised each time it posts a message
+struct SseClient {
+    events: mpsc::Sender<SseEvent>,
+    activity: Arc<Notify>,
+    /// Stops the idle watcher, and releases its sender, once the client leaves the registry
+    _idle_watcher: Option<DropGuard>,
+}
+
+type ClientRegistry = Arc<Mutex<HashMap<ConnectionId, SseClient>>>;
 
 enum SseMode {
     Server {
         clients: ClientRegistry,
         endpoint: String,
         channel_capacity: usize,
+        idle_timeout: Option<Duration>,
+        validate_json_rpc: bool,
         on_message: mpsc::Sender<Message>,
     },
 
@@ -162,6 +241,8 @@ impl SseTransport {
                 clients,
                 endpoint: config.endpoint,
                 channel_capacity: config.channel_capacity,
+                idle_timeout: config.idle_timeout,
+                validate_json_rpc: config.validate_json_rpc,
                 on_message,
             }),
             on_error,
@@ -198,8 +279,8 @@ impl SseTransport {
     async fn send_to_client(clients: &ClientRegistry, conn_id: &ConnectionId, event: SseEvent) -> Result<()> {
         let clients_map = clients.lock().await;
 
-        if let Some(tx) = clients_map.get(conn_id) {
-            if tx.send(event).await.is_err() {
+        if let Some(client) = clients_map.get(conn_id) {
+            if client.events.send(event).await.is_err() {
                 debug!("Client {} disconnected", conn_id.to_string());
             }
         } else {
@@ -272,6 +353,8 @@ impl SseTransport {
                             info!("Received shutdown event from server: {}", shutdown.reason);
                             return Ok(());
                         }
+
+                        SseEvent::RawMessage(_) => {}
                     }
                 }
             }
@@ -279,6 +362,40 @@ impl SseTransport {
 
         Err(SseError::Connection("SSE connection closed unexpectedly".to_string()).into())
     }
+
+    /// Disconnects a client once it has gone `idle_timeout` without posting a message
+    async fn watch_idle(
+        clients: ClientRegistry,
+        conn_id: ConnectionId,
+        events: mpsc::Sender<SseEvent>,
+        activity: Arc<Notify>,
+        idle_timeout: Duration,
+        removed: CancellationToken,
+    ) {
+        loop {
+            tokio::select! {
+                // The client went away on its own
+                _ = events.closed() => return,
+                // The client was removed, so its stream ends once the registry drops its sender
+                _ = removed.cancelled() => return,
+                active = tokio::time::timeout(idle_timeout, activity.notified()) => {
+                    if active.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if clients.lock().await.remove(&conn_id).is_none() {
+            return;
+        }
+        info!("Disconnecting client {} after {:?} idle", conn_id.to_string(), idle_timeout);
+
+        let shutdown_event = SseEvent::Shutdown(Shutdown { reason: "Idle timeout".to_string() });
+        if events.send(shutdown_event).await.is_err() {
+            debug!("Client {} already disconnected", conn_id.to_string());
+        }
+    }
 }
 
 impl Transport for SseTransport {
@@ -287,7 +404,14 @@ impl Transport for SseTransport {
 
         async move {
             match *mode {
-                SseMode::Server { ref clients, ref endpoint, channel_capacity, ref on_message } => {
+                SseMode::Server {
+                    ref clients,
+                    ref endpoint,
+                    channel_capacity,
+                    idle_timeout,
+                    validate_json_rpc,
+                    ref on_message,
+                } => {
                     let clients = clients.clone();
                     let on_message = on_message.clone();
                     let endpoint = endpoint.clone();
@@ -326,10 +450,31 @@ impl Transport for SseTransport {
 
                                                 let (client_tx, mut client_rx) = mpsc::channel::<SseEvent>(capacity);
                                                 let conn_id = ConnectionId::new();
+                                                let activity = Arc::new(Notify::new());
+
+                                                let idle_watcher = idle_timeout.map(|idle_timeout| {
+                                                    let removed = CancellationToken::new();
+                                                    tokio::spawn(Self::watch_idle(
+                                                        clients.clone(),
+                                                        conn_id.clone(),
+                                                        client_tx.clone(),
+                                                        activity.clone(),
+                                                        idle_timeout,
+                                                        removed.clone(),
+                                                    ));
+                                                    removed.drop_guard()
+                                                });
 
                                                 {
                                                     let mut clients_map = clients.lock().await;
-                                                    clients_map.insert(conn_id.clone(), client_tx);
+                                                    clients_map.insert(
+                                                        conn_id.clone(),
+                                                        SseClient {
+                                                            events: client_tx,
+                                                            activity,
+                                                            _idle_watcher: idle_watcher,
+                                                        },
+                                                    );
                                                 }
 
                                                 let (response_tx, response_rx) =
@@ -395,14 +540,14 @@ impl Transport for SseTransport {
                                                     } else {
                                                         let response = Response::builder()
                                                             .status(StatusCode::BAD_REQUEST)
-                                                            .body(http_body_util::Either::Right(Empty::new()))
+                                                            .body(http_body_util::Either::Right(Full::default()))
                                                             .map_err(|e| SseError::HttpBuilderError(e))?;
                                                         return Ok(response);
                                                     }
                                                 } else {
                                                     let response = Response::builder()
                                                         .status(StatusCode::NOT_FOUND)
-                                                        .body(http_body_util::Either::Right(Empty::new()))
+                                                        .body(http_body_util::Either::Right(Full::default()))
                                                         .map_err(|e| SseError::HttpBuilderError(e))?;
                                                     return Ok(response);
                                                 };
@@ -423,6 +568,27 @@ impl Transport for SseTransport {
 
                                                 match serde_json::from_str::<JsonRpcMessage>(&message_str) {
                                                     Ok(json_rpc_message) => {
+                                                        let checked = if validate_json_rpc {
+                                                            check_json_rpc(&json_rpc_message)
+                                                        } else {
+                                                            Ok(())
+                                                        };
+                                                        if let Err(reason) = checked {
+                                                            error!("Rejecting invalid message: {}", reason);
+                                                            let response = Response::builder()
+                                                                .status(StatusCode::BAD_REQUEST)
+                                                                .body(http_body_util::Either::Right(Full::new(
+                                                                    Bytes::from(reason),
+                                                                )))
+                                                                .map_err(|e| SseError::HttpBuilderError(e))?;
+                                                            return Ok(response);
+                                                        }
+
+                                                        // Any valid message, keepalives included, resets the idle timer
+                                                        if let Some(client) = clients.lock().await.get(&conn_id) {
+                                                            client.activity.notify_one();
+                                                        }
+
                                                         if on_message
                                                             .send(Message { message: json_rpc_message, conn_id })
                                                             .await
@@ -438,7 +604,7 @@ impl Transport for SseTransport {
 
                                                 let response = Response::builder()
                                                     .status(StatusCode::OK)
-                                                    .body(http_body_util::Either::Right(Empty::new()))
+                                                    .body(http_body_util::Either::Right(Full::default()))
                                                     .map_err(|e| SseError::HttpBuilderError(e))?;
 
                                                 Ok::<_, SseError>(response)
@@ -447,7 +613,7 @@ impl Transport for SseTransport {
                                             _ => {
                                                 let response = Response::builder()
                                                     .status(StatusCode::NOT_FOUND)
-                                                    .body(http_body_util::Either::Right(Empty::new()))
+                                                    .body(http_body_util::Either::Right(Full::default()))
                                                     .map_err(|e| SseError::HttpBuilderError(e))?;
 
                                                 Ok::<_, SseError>(response)
@@ -579,6 +745,44 @@ impl Transport for SseTransport {
         }
     }
 
+    fn send_raw(&mut self, bytes: Bytes, conn_id: ConnectionId) -> impl std::future::Future<Output = Result<()>> {
+        let mode = self.mode.clone();
+
+        async move {
+            match &*mode {
+                SseMode::Server { clients, .. } => {
+                    debug!("Server sending [sse] raw JsonRpcMessage");
+
+                    Self::send_to_client(clients, &conn_id, SseEvent::RawMessage(bytes)).await
+                }
+                SseMode::Client { message_endpoint, http_client, .. } => {
+                    debug!("Client sending [sse] raw JsonRpcMessage");
+
+                    let Some(url) = message_endpoint.lock().await.clone() else {
+                        return Err(SseError::Other(
+                            "No endpoint URL available yet. Wait for the SSE connection to establish.".to_string(),
+                        )
+                        .into());
+                    };
+
+                    let response = http_client
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .body(bytes)
+                        .send()
+                        .await
+                        .context("Failed to send message")?;
+
+                    if !response.status().is_success() {
+                        return Err(SseError::HttpError(response.status()).into());
+                    }
+
+                    Ok(())
+                }
+            }
+        }
+    }
+
     fn close(&mut self) -> impl std::future::Future<Output = Result<()>> {
         let mode = self.mode.clone();
 
@@ -589,13 +793,13 @@ impl Transport for SseTransport {
 
                     let mut clients_map = clients.lock().await;
 
-                    for (conn_id, tx) in clients_map.drain() {
+                    for (conn_id, client) in clients_map.drain() {
                         debug!("Sending shutdown event to client {}", conn_id.to_string());
 
                         let shutdown_event =
                             SseEvent::Shutdown(Shutdown { reason: "Server is shutting down".to_string() });
 
-                        if tx.send(shutdown_event).await.is_err() {
+                        if client.events.send(shutdown_event).await.is_err() {
                             debug!("Client {} already disconnected", conn_id.to_string());
                         }
                     }
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use bioma_mcp::client::{
+    Client, ModelContextProtocolClient, ServerConfig, SseConfig as SseClientConfig, TransportConfig,
+};
+use bioma_mcp::schema::{ClientCapabilities, CreateMessageRequestParams, CreateMessageResult, Root};
+use bioma_mcp::server::SseConfig as SseServerConfig;
+use bioma_mcp::transport::interceptor::{MessageMetadata, TransportInterceptor};
+use bioma_mcp::transport::sse::SseTransport;
+use bioma_mcp::transport::{Message, Transport};
+use bioma_mcp::JsonRpcMessage;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+struct TestClient {
+    server_config: ServerConfig,
+}
+
+impl ModelContextProtocolClient for TestClient {
+    async fn get_server_config(&self) -> ServerConfig {
+        self.server_config.clone()
+    }
+
+    async fn get_capabilities(&self) -> ClientCapabilities {
+        ClientCapabilities::default()
+    }
+
+    async fn get_roots(&self) -> Vec<Root> {
+        vec![]
+    }
+
+    async fn on_create_message(&self, _params: CreateMessageRequestParams) -> CreateMessageResult {
+        todo!()
+    }
+}
+
+/// Records every frame it sees, tagged with the name of the interceptor
+struct LoggingInterceptor {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl TransportInterceptor for LoggingInterceptor {
+    fn on_send(&self, message: &mut JsonRpcMessage, _metadata: &mut MessageMetadata) {
+        self.log.lock().unwrap().push(format!("{} send {}", self.name, serde_json::to_string(message).unwrap()));
+    }
+
+    fn on_receive(&self, message: &mut JsonRpcMessage) {
+        self.log.lock().unwrap().push(format!("{} receive {}", self.name, serde_json::to_string(message).unwrap()));
+    }
+}
+
+struct AuthInterceptor;
+
+impl TransportInterceptor for AuthInterceptor {
+    fn on_send(&self, _message: &mut JsonRpcMessage, metadata: &mut MessageMetadata) {
+        metadata.meta.insert("authorization".to_string(), json!("Bearer token"));
+    }
+}
+
+#[tokio::test]
+async fn test_client_interceptors() -> Result<()> {
+    let endpoint = "127.0.0.1:49160".to_string();
+
+    // A server answering every request with an empty tool list
+    let (tx, mut rx) = mpsc::channel::<Message>(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+    let server_config = SseServerConfig::builder().endpoint(endpoint.clone()).build();
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let server_handle = server.start().await?;
+
+    let received = Arc::new(Mutex::new(Vec::<Value>::new()));
+    let responder = tokio::spawn({
+        let received = received.clone();
+        let mut server = server.clone();
+        async move {
+            while let Some(Message { conn_id, message }) = rx.recv().await {
+                received.lock().unwrap().push(serde_json::to_value(&message).unwrap());
+                let JsonRpcMessage::Request(jsonrpc_core::Request::Single(jsonrpc_core::Call::MethodCall(call))) =
+                    message
+                else {
+                    continue;
+                };
+                let response = jsonrpc_core::Response::Single(jsonrpc_core::Output::Success(jsonrpc_core::Success {
+                    jsonrpc: Some(jsonrpc_core::Version::V2),
+                    result: json!({"tools": []}),
+                    id: call.id,
+                }));
+                server.send(response.into(), conn_id).await.unwrap();
+            }
+        }
+    });
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let server_config = ServerConfig::builder()
+        .name("interceptor".to_string())
+        .transport(TransportConfig::Sse(SseClientConfig::builder().endpoint(format!("http://{}", endpoint)).build()))
+        .build();
+    let interceptors: Vec<Arc<dyn TransportInterceptor>> = vec![
+        Arc::new(LoggingInterceptor { name: "first", log: log.clone() }),
+        Arc::new(AuthInterceptor),
+        Arc::new(LoggingInterceptor { name: "second", log: log.clone() }),
+    ];
+    let mut client = Client::new_with_interceptors(TestClient { server_config }, interceptors).await?;
+
+    // Wait for the endpoint event before sending
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let tools = client.list_tools(None).await?;
+    assert!(tools.tools.is_empty());
+
+    // Both directions are observed, by each interceptor in order
+    let log = log.lock().unwrap().clone();
+    assert_eq!(log.len(), 4, "Unexpected log: {:?}", log);
+    assert!(log[0].starts_with("first send") && log[0].contains("tools/list"));
+    assert!(log[1].starts_with("second send") && log[1].contains("tools/list"));
+    assert!(log[2].starts_with("first receive") && log[2].contains("\"tools\":[]"));
+    assert!(log[3].starts_with("second receive"));
+
+    // Metadata added by an interceptor reaches the server, after the interceptors ran
+    assert!(!log[1].contains("authorization"));
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received[0]["params"]["_meta"]["authorization"], "Bearer token");
+
+    client.close().await?;
+    responder.abort();
+    server_handle.abort();
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use bioma_mcp::prompts::PromptGetHandler;
+use bioma_mcp::resources::ResourceReadHandler;
+use bioma_mcp::schema::ServerCapabilities;
+use bioma_mcp::server::{Context, ModelContextProtocolServer, Server, SseConfig, TransportConfig};
+use bioma_mcp::tools::{self, ToolCallHandler};
+use bioma_mcp::transport::sse::SseEvent;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+struct TestServer {
+    transport_config: TransportConfig,
+}
+
+impl ModelContextProtocolServer for TestServer {
+    async fn get_transport_config(&self) -> TransportConfig {
+        self.transport_config.clone()
+    }
+
+    async fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities::default()
+    }
+
+    async fn new_resources(&self, _context: Context) -> Vec<Arc<dyn ResourceReadHandler>> {
+        vec![]
+    }
+
+    async fn new_prompts(&self, _context: Context) -> Vec<Arc<dyn PromptGetHandler>> {
+        vec![]
+    }
+
+    async fn new_tools(&self, _context: Context) -> Vec<Arc<dyn ToolCallHandler>> {
+        vec![Arc::new(tools::echo::Echo)]
+    }
+
+    async fn on_error(&self, _error: anyhow::Error) {}
+}
+
+#[tokio::test]
+async fn test_server_shutdown_notifies_sse_clients() -> Result<()> {
+    let endpoint = "127.0.0.1:49161".to_string();
+    let transport_config = TransportConfig::Sse(SseConfig::builder().endpoint(endpoint.clone()).build());
+
+    let shutdown = CancellationToken::new();
+    let server = Server::new_with_shutdown(TestServer { transport_config }, shutdown.clone());
+    let server_handle = tokio::spawn(async move { server.start().await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // An open client stream, connected once the endpoint event arrives
+    let response = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await?;
+    let mut stream = Box::pin(response.bytes_stream());
+    let mut buffer = String::new();
+    let endpoint_event = next_event(&mut stream, &mut buffer).await?;
+    assert!(matches!(SseEvent::from_sse_string(&endpoint_event)?, Some(SseEvent::Endpoint(_))));
+
+    shutdown.cancel();
+
+    // The client is told about the shutdown and the server stops well within the deadline
+    let shutdown_event = tokio::time::timeout(Duration::from_secs(5), next_event(&mut stream, &mut buffer)).await??;
+    let Some(SseEvent::Shutdown(event)) = SseEvent::from_sse_string(&shutdown_event)? else {
+        panic!("Expected a shutdown event, got {}", shutdown_event);
+    };
+    assert_eq!(event.reason, "Server is shutting down");
+
+    let result = tokio::time::timeout(Duration::from_secs(5), server_handle).await?;
+    assert!(result?.is_ok(), "Server should stop cleanly");
+
+    // The listener is closed
+    let reconnect = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await;
+    assert!(reconnect.is_err(), "Listener should no longer accept connections");
+
+    Ok(())
+}
+
+/// Reads the next complete SSE event from a response stream.
+async fn next_event(
+    stream: &mut (impl futures_util::Stream<Item = reqwest::Result<Bytes>> + Unpin),
+    buffer: &mut String,
+) -> Result<String> {
+    while !buffer.contains("\n\n") {
+        let chunk = stream.next().await.expect("SSE stream ended")?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let pos = buffer.find("\n\n").unwrap() + 2;
+    Ok(buffer.drain(..pos).collect())
+}
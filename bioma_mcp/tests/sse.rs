@@ -1,9 +1,11 @@
 use anyhow::Result;
+use bytes::Bytes;
 use bioma_mcp::client::SseConfig as SseClientConfig;
 use bioma_mcp::server::SseConfig as SseServerConfig;
 use bioma_mcp::transport::sse::{SseEvent, SseTransport};
 use bioma_mcp::transport::Transport;
 use bioma_mcp::{ConnectionId, JsonRpcMessage};
+use futures_util::StreamExt;
 use serde_json::json;
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -97,6 +99,7 @@ async fn test_server_config() {
     let default_config = SseServerConfig::default();
     assert_eq!(default_config.endpoint, "127.0.0.1:8090");
     assert_eq!(default_config.channel_capacity, 32);
+    assert!(default_config.validate_json_rpc, "JSON-RPC validation should be on by default");
 
     let custom_addr: SocketAddr = "127.0.0.1:9090".parse().unwrap();
     let custom_config = SseServerConfig::builder().endpoint(custom_addr.to_string()).channel_capacity(64).build();
@@ -148,3 +151,225 @@ async fn test_error_handling() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_server_rejects_invalid_json_rpc() -> Result<()> {
+    let endpoint = "127.0.0.1:49154".to_string();
+    let server_config = SseServerConfig::builder().endpoint(endpoint.clone()).build();
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let handle = server.start().await?;
+
+    // Parseable as a request but missing the "jsonrpc" field
+    let message = json!({"method": "test", "params": {}, "id": 1});
+    let url = format!("http://{}/sse/{}", endpoint, ConnectionId::new());
+    let response = reqwest::Client::new().post(&url).body(message.to_string()).send().await?;
+
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST, "Invalid message should be rejected");
+    assert!(response.text().await?.contains("jsonrpc"), "Response should describe the violation");
+    assert!(rx.try_recv().is_err(), "Invalid message should not be forwarded");
+
+    // A well-formed message is still forwarded
+    let message = json!({"jsonrpc": "2.0", "method": "test", "params": {}, "id": 1});
+    let response = reqwest::Client::new().post(&url).body(message.to_string()).send().await?;
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(rx.recv().await.is_some(), "Valid message should be forwarded");
+
+    handle.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_without_json_rpc_validation() -> Result<()> {
+    let endpoint = "127.0.0.1:49158".to_string();
+    let server_config = SseServerConfig::builder().endpoint(endpoint.clone()).validate_json_rpc(false).build();
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let handle = server.start().await?;
+
+    // Missing the "jsonrpc" field, but forwarded as is with validation off
+    let message = json!({"method": "test", "params": {}, "id": 1});
+    let url = format!("http://{}/sse/{}", endpoint, ConnectionId::new());
+    let response = reqwest::Client::new().post(&url).body(message.to_string()).send().await?;
+
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(rx.recv().await.is_some(), "Message should be forwarded without validation");
+
+    handle.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sse_raw_message_event() -> Result<()> {
+    let message = json!({"jsonrpc": "2.0", "method": "test", "params": {"text": "hello"}, "id": 1});
+    let json_rpc_message: JsonRpcMessage = serde_json::from_value(message)?;
+
+    // Raw bytes are framed exactly like the serialized message
+    let bytes = Bytes::from(serde_json::to_vec(&json_rpc_message)?);
+    let raw_str = SseEvent::RawMessage(bytes).to_sse_string()?;
+    let serialized_str = SseEvent::Message(json_rpc_message.clone()).to_sse_string()?;
+    assert_eq!(raw_str, serialized_str, "Raw and serialized events should be identical");
+
+    // Multi-line JSON is compacted so the event stays parseable
+    let pretty = Bytes::from(serde_json::to_vec_pretty(&json_rpc_message)?);
+    let pretty_str = SseEvent::RawMessage(pretty).to_sse_string()?;
+    let parsed = SseEvent::from_sse_string(&pretty_str)?;
+    assert!(matches!(parsed, Some(SseEvent::Message(parsed)) if parsed == json_rpc_message));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_send_raw() -> Result<()> {
+    let endpoint = "127.0.0.1:49155".to_string();
+    let server_config = SseServerConfig::builder().endpoint(endpoint.clone()).build();
+
+    let (tx, _) = mpsc::channel(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let handle = server.start().await?;
+
+    // Connect a client and read the connection id from the endpoint event
+    let response = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await?;
+    let mut stream = Box::pin(response.bytes_stream());
+    let mut buffer = String::new();
+
+    let endpoint_event = next_event(&mut stream, &mut buffer).await?;
+    let Some(SseEvent::Endpoint(endpoint_url)) = SseEvent::from_sse_string(&endpoint_event)? else {
+        panic!("Expected an endpoint event");
+    };
+    let conn_id: ConnectionId = serde_json::from_value(json!(endpoint_url.rsplit('/').next().unwrap()))?;
+
+    let message = json!({"jsonrpc": "2.0", "method": "test", "params": {}, "id": 1});
+    let json_rpc_message: JsonRpcMessage = serde_json::from_value(message)?;
+
+    server.send_raw(Bytes::from(serde_json::to_vec(&json_rpc_message)?), conn_id.clone()).await?;
+    let raw_event = next_event(&mut stream, &mut buffer).await?;
+
+    server.send(json_rpc_message, conn_id).await?;
+    let serialized_event = next_event(&mut stream, &mut buffer).await?;
+
+    assert_eq!(raw_event, serialized_event, "Raw send should produce the same event body");
+
+    handle.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_idle_timeout() -> Result<()> {
+    let endpoint = "127.0.0.1:49156".to_string();
+    let server_config =
+        SseServerConfig::builder().endpoint(endpoint.clone()).idle_timeout(Duration::from_millis(300)).build();
+
+    let (tx, _) = mpsc::channel(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let handle = server.start().await?;
+
+    // Connect a client that never posts anything
+    let response = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await?;
+    let mut stream = Box::pin(response.bytes_stream());
+    let mut buffer = String::new();
+
+    let endpoint_event = next_event(&mut stream, &mut buffer).await?;
+    let Some(SseEvent::Endpoint(endpoint_url)) = SseEvent::from_sse_string(&endpoint_event)? else {
+        panic!("Expected an endpoint event");
+    };
+    let conn_id: ConnectionId = serde_json::from_value(json!(endpoint_url.rsplit('/').next().unwrap()))?;
+
+    let shutdown_event = tokio::time::timeout(Duration::from_secs(5), next_event(&mut stream, &mut buffer)).await??;
+    assert!(
+        matches!(SseEvent::from_sse_string(&shutdown_event)?, Some(SseEvent::Shutdown(_))),
+        "Idle client should receive a shutdown event"
+    );
+
+    // The client was removed from the registry
+    let message: JsonRpcMessage = serde_json::from_value(json!({"jsonrpc": "2.0", "method": "test", "id": 1}))?;
+    assert!(server.send(message, conn_id).await.is_err(), "Idle client should no longer be registered");
+
+    handle.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_close_ends_idle_watched_stream() -> Result<()> {
+    let endpoint = "127.0.0.1:49157".to_string();
+    let server_config =
+        SseServerConfig::builder().endpoint(endpoint.clone()).idle_timeout(Duration::from_millis(600)).build();
+
+    let (tx, _) = mpsc::channel(32);
+    let (err_tx, _) = mpsc::channel(32);
+    let (close_tx, _) = mpsc::channel(32);
+
+    let mut server = SseTransport::new_server(server_config, tx, err_tx, close_tx);
+    let handle = server.start().await?;
+
+    let response = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await?;
+    let mut stream = Box::pin(response.bytes_stream());
+    let mut buffer = String::new();
+
+    let endpoint_event = next_event(&mut stream, &mut buffer).await?;
+    let Some(SseEvent::Endpoint(endpoint_url)) = SseEvent::from_sse_string(&endpoint_event)? else {
+        panic!("Expected an endpoint event");
+    };
+
+    // Invalid messages are rejected without resetting the idle timer
+    let message = json!({"method": "test", "params": {}, "id": 1});
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let _ = reqwest::Client::new().post(&endpoint_url).body(message.to_string()).send().await?;
+    }
+    let shutdown_event =
+        tokio::time::timeout(Duration::from_millis(100), next_event(&mut stream, &mut buffer)).await??;
+    assert!(
+        matches!(SseEvent::from_sse_string(&shutdown_event)?, Some(SseEvent::Shutdown(_))),
+        "Client posting only invalid messages should be disconnected as idle"
+    );
+
+    // A closed server ends the stream right away, even though the idle watcher still runs
+    let response = reqwest::Client::new().get(format!("http://{}/", endpoint)).send().await?;
+    let mut stream = Box::pin(response.bytes_stream());
+    let mut buffer = String::new();
+    next_event(&mut stream, &mut buffer).await?;
+
+    server.close().await?;
+
+    let shutdown_event = next_event(&mut stream, &mut buffer).await?;
+    assert!(matches!(SseEvent::from_sse_string(&shutdown_event)?, Some(SseEvent::Shutdown(_))));
+    let end = tokio::time::timeout(Duration::from_millis(300), stream.next()).await?;
+    assert!(end.is_none(), "Stream should end after the shutdown event");
+
+    handle.abort();
+
+    Ok(())
+}
+
+/// Reads the next complete SSE event from a response stream.
+async fn next_event(
+    stream: &mut (impl futures_util::Stream<Item = reqwest::Result<Bytes>> + Unpin),
+    buffer: &mut String,
+) -> Result<String> {
+    while !buffer.contains("\n\n") {
+        let chunk = stream.next().await.expect("SSE stream ended")?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let pos = buffer.find("\n\n").unwrap() + 2;
+    Ok(buffer.drain(..pos).collect())
+}
@@ -47,6 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             StoreEmbeddings {
                 content: EmbeddingContent::Image(image_paths.iter().map(|p| ImageData::Path(p.clone())).collect()),
                 metadata: None,
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -80,6 +81,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
             GenerateEmbeddings {
                 content: EmbeddingContent::Image(vec![ImageData::Path("assets/images/rust-pet.png".to_string())]),
+                input: InputKind::Passage,
+                normalize: false,
             },
             &embeddings_id,
             SendOptions::default(),
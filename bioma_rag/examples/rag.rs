@@ -46,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create chat conversation
     let mut conversation = vec![];
 
-    let ask = Chat::builder().model("llama3.2".into()).messages_number_limit(10).history(conversation.clone()).build();
+    let ask = Chat::builder().model("llama3.2").messages_number_limit(10).history(conversation.clone()).build()?;
 
     let ask_id = ActorId::of::<Chat>("/ask");
     let (mut ask_ctx, mut ask_actor) =
@@ -140,7 +140,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     for message in &conversation {
         ask_content.push_str(&format!("{:?}: {}\n\n", message.role, message.content));
     }
-    let response = ask_response.message;
+    let response = ask_response.response.message;
     ask_content.push_str(&format!("{:?}: {}\n\n", &response.role, &response.content));
     tokio::fs::write(output_dir.join("debug").join("rag_ask.md"), ask_content).await?;
 
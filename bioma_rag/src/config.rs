@@ -0,0 +1,280 @@
+use crate::embeddings::{self, Embeddings, ImageModel};
+use crate::indexer::Indexer;
+use crate::markitdown::MarkitDown;
+use crate::pdf_analyzer::PdfAnalyzer;
+use crate::rerank::{self, Rerank};
+use crate::retriever::{QueryCacheConfig, Retriever};
+use crate::summary::Summary;
+use bioma_llm::config::{apply_env, deserialize, ensure, ensure_http_url, invalid, read_file};
+use bioma_llm::prelude::Chat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+pub use bioma_llm::config::{ChatConfig, ConfigError, OllamaConfig};
+
+/// Configuration of the whole RAG pipeline, from which each actor can be built.
+///
+/// Every section is optional and defaults to the same values as the actors' own defaults. Endpoints shared by
+/// several actors, such as the Ollama server, are configured once. Any key can be overridden by an environment
+/// variable named after its path, for example `BIOMA__CHAT__MODEL` for `chat.model`.
+///
+/// The loading and the `ollama` and `chat` sections come from `bioma_llm::config`, this adds the sections of the
+/// actors of this crate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BiomaConfig {
+    #[serde(default)]
+    pub ollama: OllamaConfig,
+    #[serde(default)]
+    pub chat: ChatConfig,
+    #[serde(default)]
+    pub embeddings: EmbeddingsConfig,
+    #[serde(default)]
+    pub rerank: RerankConfig,
+    #[serde(default)]
+    pub markitdown: MarkitDownConfig,
+    #[serde(default)]
+    pub pdf_analyzer: PdfAnalyzerConfig,
+    #[serde(default)]
+    pub indexer: IndexerConfig,
+    #[serde(default)]
+    pub retriever: RetrieverConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbeddingsConfig {
+    #[serde(default = "default_embeddings_model")]
+    pub model: embeddings::Model,
+    #[serde(default = "default_embeddings_image_model")]
+    pub image_model: ImageModel,
+    #[serde(default)]
+    pub table_name_prefix: Option<String>,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            model: default_embeddings_model(),
+            image_model: default_embeddings_image_model(),
+            table_name_prefix: None,
+        }
+    }
+}
+
+fn default_embeddings_model() -> embeddings::Model {
+    Embeddings::default().model
+}
+
+fn default_embeddings_image_model() -> ImageModel {
+    Embeddings::default().image_model
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RerankConfig {
+    #[serde(default = "default_rerank_model")]
+    pub model: rerank::Model,
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        Self { model: default_rerank_model() }
+    }
+}
+
+fn default_rerank_model() -> rerank::Model {
+    Rerank::default().model
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkitDownConfig {
+    #[serde(default = "default_markitdown_url")]
+    pub url: Url,
+}
+
+impl Default for MarkitDownConfig {
+    fn default() -> Self {
+        Self { url: default_markitdown_url() }
+    }
+}
+
+fn default_markitdown_url() -> Url {
+    MarkitDown::default().markitdown_url
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PdfAnalyzerConfig {
+    #[serde(default = "default_pdf_analyzer_url")]
+    pub url: Url,
+}
+
+impl Default for PdfAnalyzerConfig {
+    fn default() -> Self {
+        Self { url: default_pdf_analyzer_url() }
+    }
+}
+
+fn default_pdf_analyzer_url() -> Url {
+    PdfAnalyzer::default().pdf_analyzer_url
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerConfig {
+    /// Number of chunk batches embedded in parallel, stored in the order of the chunks, see `Indexer`
+    #[serde(default)]
+    pub embedding_concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetrieverConfig {
+    /// Caches the contexts of repeated identical queries, disabled if not set
+    #[serde(default)]
+    pub query_cache: Option<RetrieverQueryCacheConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetrieverQueryCacheConfig {
+    #[serde(default = "default_query_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "default_query_cache_capacity")]
+    pub capacity: usize,
+}
+
+impl Default for RetrieverQueryCacheConfig {
+    fn default() -> Self {
+        Self { ttl_secs: default_query_cache_ttl_secs(), capacity: default_query_cache_capacity() }
+    }
+}
+
+fn default_query_cache_ttl_secs() -> u64 {
+    QueryCacheConfig::default().ttl.as_secs()
+}
+
+fn default_query_cache_capacity() -> usize {
+    QueryCacheConfig::default().capacity
+}
+
+impl BiomaConfig {
+    /// Loads the configuration from a TOML or JSON file, then applies the environment variable overrides
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_value(read_file(path.as_ref())?, std::env::vars())
+    }
+
+    /// Builds the configuration from its defaults and the environment variable overrides
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_value(Value::Object(Default::default()), std::env::vars())
+    }
+
+    /// Builds the configuration from a parsed document and a set of `BIOMA__` prefixed overrides.
+    ///
+    /// Override values are parsed as JSON when possible, so numbers and booleans keep their type, and are
+    /// taken as plain strings otherwise.
+    pub fn from_value(mut value: Value, env: impl IntoIterator<Item = (String, String)>) -> Result<Self, ConfigError> {
+        apply_env(&mut value, env)?;
+        let config: Self = deserialize(value)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks every value the actor builders would reject, reporting the key it was set at
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.chat.validate(&self.ollama)?;
+        self.validate_embeddings()?;
+        ensure_http_url(&self.markitdown.url, "markitdown.url")?;
+        ensure_http_url(&self.pdf_analyzer.url, "pdf_analyzer.url")?;
+        self.validate_indexer()?;
+        self.validate_retriever()
+    }
+
+    fn validate_embeddings(&self) -> Result<(), ConfigError> {
+        let Some(prefix) = &self.embeddings.table_name_prefix else {
+            return Ok(());
+        };
+        ensure(
+            !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "embeddings.table_name_prefix",
+            "must be non-empty and contain only letters, digits and underscores",
+        )
+    }
+
+    fn validate_indexer(&self) -> Result<(), ConfigError> {
+        ensure(self.indexer.embedding_concurrency != Some(0), "indexer.embedding_concurrency", "must be at least 1")
+    }
+
+    fn validate_retriever(&self) -> Result<(), ConfigError> {
+        let Some(query_cache) = &self.retriever.query_cache else {
+            return Ok(());
+        };
+        ensure(query_cache.ttl_secs > 0, "retriever.query_cache.ttl_secs", "must be at least 1")?;
+        ensure(query_cache.capacity > 0, "retriever.query_cache.capacity", "must be at least 1")
+    }
+
+    pub fn chat(&self) -> Result<Chat, ConfigError> {
+        self.chat.chat(&self.ollama)
+    }
+
+    pub fn embeddings(&self) -> Result<Embeddings, ConfigError> {
+        self.validate_embeddings()?;
+        Embeddings::builder()
+            .model(self.embeddings.model.clone())
+            .image_model(self.embeddings.image_model.clone())
+            .maybe_table_name_prefix(self.embeddings.table_name_prefix.clone())
+            .build()
+            .map_err(invalid("embeddings"))
+    }
+
+    pub fn rerank(&self) -> Rerank {
+        Rerank::builder().model(self.rerank.model.clone()).build()
+    }
+
+    pub fn markitdown(&self) -> Result<MarkitDown, ConfigError> {
+        ensure_http_url(&self.markitdown.url, "markitdown.url")?;
+        MarkitDown::builder().markitdown_url(self.markitdown.url.clone()).build().map_err(invalid("markitdown"))
+    }
+
+    pub fn pdf_analyzer(&self) -> Result<PdfAnalyzer, ConfigError> {
+        ensure_http_url(&self.pdf_analyzer.url, "pdf_analyzer.url")?;
+        PdfAnalyzer::builder().pdf_analyzer_url(self.pdf_analyzer.url.clone()).build().map_err(invalid("pdf_analyzer"))
+    }
+
+    pub fn summary(&self) -> Result<Summary, ConfigError> {
+        Ok(Summary::builder().chat(self.chat()?).build())
+    }
+
+    pub fn indexer(&self) -> Result<Indexer, ConfigError> {
+        self.validate_indexer()?;
+        Indexer::builder()
+            .embeddings(self.embeddings()?)
+            .pdf_analyzer(self.pdf_analyzer()?)
+            .markitdown(self.markitdown()?)
+            .summary(self.summary()?)
+            .maybe_embedding_concurrency(self.indexer.embedding_concurrency)
+            .build()
+            .map_err(invalid("indexer"))
+    }
+
+    pub fn retriever(&self) -> Result<Retriever, ConfigError> {
+        self.validate_retriever()?;
+        let query_cache = self.retriever.query_cache.as_ref().map(|query_cache| {
+            QueryCacheConfig::builder()
+                .ttl(Duration::from_secs(query_cache.ttl_secs))
+                .capacity(query_cache.capacity)
+                .build()
+        });
+        Retriever::builder()
+            .embeddings(self.embeddings()?)
+            .rerank(self.rerank())
+            .maybe_query_cache(query_cache)
+            .build()
+            .map_err(invalid("retriever"))
+    }
+}
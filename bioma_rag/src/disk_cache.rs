@@ -0,0 +1,194 @@
+use crate::embeddings::DiskCacheConfig;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const ENTRY_EXTENSION: &str = "f32";
+const PARTIAL_EXTENSION: &str = "partial";
+
+lazy_static! {
+    static ref OPEN_CACHES: Mutex<HashMap<PathBuf, Weak<DiskCache>>> = Mutex::new(HashMap::new());
+}
+
+/// Embeddings stored one per file, evicting the least recently used beyond a size cap
+///
+/// Caches opened on the same directory within a process share one instance, so their
+/// index and size accounting stay consistent.
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<DiskIndex>,
+}
+
+#[derive(Debug, Default)]
+struct DiskIndex {
+    entries: HashMap<String, DiskEntry>,
+    total_bytes: u64,
+    /// Incremented on every use, ordering the entries from least to most recently used
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct DiskEntry {
+    bytes: u64,
+    last_used: u64,
+}
+
+impl DiskIndex {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = self.clock;
+        }
+    }
+
+    fn insert(&mut self, key: String, bytes: u64) {
+        self.clock += 1;
+        let previous = self.entries.insert(key, DiskEntry { bytes, last_used: self.clock });
+        self.total_bytes = self.total_bytes + bytes - previous.map_or(0, |entry| entry.bytes);
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.total_bytes -= entry.bytes;
+        }
+    }
+
+    /// Keys to evict, least recently used first, to bring the cache under `max_bytes`
+    fn overflow(&self, max_bytes: u64) -> Vec<String> {
+        let mut entries: Vec<(&String, &DiskEntry)> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| entry.last_used);
+        let mut total_bytes = self.total_bytes;
+        entries
+            .into_iter()
+            .take_while(|(_, entry)| {
+                let over = total_bytes > max_bytes;
+                total_bytes -= entry.bytes;
+                over
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+impl DiskCache {
+    /// Opens the cache in the configured directory, indexing the entries left by previous runs
+    pub async fn open(config: &DiskCacheConfig) -> std::io::Result<Arc<Self>> {
+        tokio::fs::create_dir_all(&config.dir).await?;
+        let dir = tokio::fs::canonicalize(&config.dir).await?;
+
+        let mut open_caches = OPEN_CACHES.lock().await;
+        if let Some(cache) = open_caches.get(&dir).and_then(Weak::upgrade) {
+            return Ok(cache);
+        }
+
+        // Entries are ordered by modification time, which is refreshed on every hit
+        let mut found = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(&dir).await?;
+        while let Some(file) = read_dir.next_entry().await? {
+            let path = file.path();
+
+            // Writes interrupted by a previous run are dropped
+            if path.extension().is_some_and(|extension| extension == PARTIAL_EXTENSION) {
+                tokio::fs::remove_file(&path).await?;
+                continue;
+            }
+            let Some(key) = entry_key(&path) else {
+                continue;
+            };
+            let metadata = file.metadata().await?;
+            found.push((key, metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+        }
+        found.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut index = DiskIndex::default();
+        for (key, bytes, _) in found {
+            index.insert(key, bytes);
+        }
+
+        let cache = Arc::new(Self { dir: dir.clone(), max_bytes: config.max_bytes, index: Mutex::new(index) });
+        cache.evict(&mut *cache.index.lock().await).await;
+        open_caches.insert(dir, Arc::downgrade(&cache));
+        Ok(cache)
+    }
+
+    /// Reads the cached embeddings of `keys`, skipping the ones missing or unreadable
+    pub async fn get(&self, keys: &[String]) -> HashMap<String, Vec<f32>> {
+        let mut index = self.index.lock().await;
+        let mut found = HashMap::new();
+        for key in keys {
+            if found.contains_key(key) || !index.entries.contains_key(key) {
+                continue;
+            }
+
+            let path = self.entry_path(key);
+            match tokio::fs::read(&path).await {
+                Ok(bytes) if bytes.len() % 4 == 0 => {
+                    let embedding =
+                        bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+                    found.insert(key.clone(), embedding);
+                    index.touch(key);
+                    if let Err(e) = touch_file(&path).await {
+                        warn!("Failed to refresh cached embedding {}: {}", path.display(), e);
+                    }
+                }
+                result => {
+                    if let Err(e) = result {
+                        warn!("Failed to read cached embedding {}: {}", path.display(), e);
+                    }
+                    let _ = tokio::fs::remove_file(&path).await;
+                    index.remove(key);
+                }
+            }
+        }
+        found
+    }
+
+    /// Stores embeddings under their keys, then evicts the least recently used beyond the size cap
+    pub async fn insert(&self, entries: &[(&str, &[f32])]) -> std::io::Result<()> {
+        let mut index = self.index.lock().await;
+        for (key, embedding) in entries {
+            let bytes: Vec<u8> = embedding.iter().flat_map(|value| value.to_le_bytes()).collect();
+
+            // Written aside first, so a reader never sees a partial entry
+            let path = self.entry_path(key);
+            let partial = path.with_extension(PARTIAL_EXTENSION);
+            tokio::fs::write(&partial, &bytes).await?;
+            tokio::fs::rename(&partial, &path).await?;
+            index.insert(key.to_string(), bytes.len() as u64);
+        }
+        self.evict(&mut index).await;
+        Ok(())
+    }
+
+    async fn evict(&self, index: &mut DiskIndex) {
+        for key in index.overflow(self.max_bytes) {
+            let path = self.entry_path(&key);
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to evict cached embedding {}: {}", path.display(), e);
+            }
+            index.remove(&key);
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension(ENTRY_EXTENSION)
+    }
+}
+
+fn entry_key(path: &Path) -> Option<String> {
+    if path.extension()? != ENTRY_EXTENSION {
+        return None;
+    }
+    path.file_stem()?.to_str().map(str::to_string)
+}
+
+async fn touch_file(path: &Path) -> std::io::Result<()> {
+    let file = tokio::fs::OpenOptions::new().write(true).open(path).await?.into_std().await;
+    tokio::task::spawn_blocking(move || file.set_modified(SystemTime::now())).await?
+}
@@ -1,24 +1,37 @@
+use crate::disk_cache::DiskCache;
 use crate::indexer::ContentSource;
 use base64::Engine as _;
 use bioma_actor::prelude::*;
+use bioma_llm::metrics::{self, Counter, Histogram};
 use bon::Builder;
 use derive_more::{Deref, Display};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
+use surrealdb::engine::any::Any;
 use surrealdb::value::RecordId;
+use surrealdb::Surreal;
 use tempfile::Builder as TempBuilder;
 use tokio::sync::Mutex;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
+/// Default cap on the number of embeddings returned by a threshold search
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+/// Default size cap of the on-disk embeddings cache
+const DEFAULT_DISK_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
 lazy_static! {
-    static ref SHARED_EMBEDDINGS: Arc<Mutex<HashMap<Model, Weak<SharedEmbedding>>>> =
+    /// Embedding pools shared by the actors using the same model and number of workers
+    static ref SHARED_EMBEDDINGS: Arc<Mutex<HashMap<(Model, usize), Weak<SharedEmbedding>>>> =
         Arc::new(Mutex::new(HashMap::new()));
 }
 
@@ -50,13 +63,34 @@ pub enum EmbeddingsError {
     Io(#[from] std::io::Error),
     #[error("Image format error: {0}")]
     ImageFormat(String),
+    #[error("Unsupported image format: {0}")]
+    UnsupportedImageFormat(String),
+    #[error("Truncated {0} image: {1} bytes")]
+    TruncatedImage(&'static str, usize),
     #[error("Persist error: {0}")]
     Persist(#[from] tempfile::PersistError),
     #[error("Input size too large: {0} tokens (max: {1})")]
     InputSizeTooLarge(usize, usize),
+    #[error("Embeddings count mismatch: {0} inputs but {1} embeddings")]
+    EmbeddingsCountMismatch(usize, usize),
+    #[error("Invalid embeddings configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Embedding dimension mismatch: {0} dimensions (model: {1})")]
+    DimensionMismatch(usize, usize),
+    #[error("Model does not output token embeddings")]
+    TokenEmbeddingsUnsupported,
 }
 
-impl ActorError for EmbeddingsError {}
+impl ActorError for EmbeddingsError {
+    fn is_backend_failure(&self) -> bool {
+        matches!(
+            self,
+            EmbeddingsError::TextEmbeddingNotInitialized
+                | EmbeddingsError::SendTextEmbeddings(_)
+                | EmbeddingsError::RecvEmbeddings(_)
+        )
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ImageData {
@@ -64,7 +98,89 @@ pub enum ImageData {
     Base64(String),
 }
 
+/// Image formats accepted for embedding, recognized by their magic bytes
+#[derive(utoipa::ToSchema, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Gif,
+}
+
+impl ImageFormat {
+    /// Detects the format from the signature at the start of the data
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Some(ImageFormat::WebP)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Gif => "image/gif",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => ".png",
+            ImageFormat::Jpeg => ".jpg",
+            ImageFormat::WebP => ".webp",
+            ImageFormat::Gif => ".gif",
+        }
+    }
+
+    /// Whether the data runs up to the end marker of the format, or the length declared in its header
+    fn is_complete(&self, data: &[u8]) -> bool {
+        match self {
+            ImageFormat::Png => data.ends_with(&[0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82]),
+            ImageFormat::Jpeg => data.len() > 4 && data.ends_with(&[0xFF, 0xD9]),
+            ImageFormat::WebP => {
+                let size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+                data.len() >= size + 8
+            }
+            ImageFormat::Gif => data.len() > 13 && data.ends_with(&[0x3B]),
+        }
+    }
+
+    /// Detects the format of a complete image
+    pub fn validate(data: &[u8]) -> Result<Self, EmbeddingsError> {
+        let format = Self::detect(data).ok_or_else(|| {
+            EmbeddingsError::UnsupportedImageFormat("expected a PNG, JPEG, WebP or GIF signature".to_string())
+        })?;
+        if !format.is_complete(data) {
+            return Err(EmbeddingsError::TruncatedImage(format.mime_type(), data.len()));
+        }
+        Ok(format)
+    }
+}
+
 impl ImageData {
+    /// Wraps image bytes in a data URL carrying their MIME type, after checking they hold a complete image
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, EmbeddingsError> {
+        let format = ImageFormat::validate(&bytes)?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+        Ok(ImageData::Base64(format!("data:{};base64,{}", format.mime_type(), encoded)))
+    }
+
+    /// Refers to an image file, after reading it to check it holds a complete image
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, EmbeddingsError> {
+        let path = path.as_ref();
+        ImageFormat::validate(&std::fs::read(path)?)?;
+        Ok(ImageData::Path(path.to_string_lossy().into_owned()))
+    }
+
     fn parse_base64_image(base64_str: &str) -> Result<(Vec<u8>, Option<String>), EmbeddingsError> {
         // Handle both raw base64 and data URLs
         let (base64_data, format) = if let Some(idx) = base64_str.find("data:image/") {
@@ -100,20 +216,15 @@ impl ImageData {
                 "jpeg" | "jpg" => return Ok(".jpg"),
                 "png" => return Ok(".png"),
                 "webp" => return Ok(".webp"),
+                "gif" => return Ok(".gif"),
                 _ => {} // Fall through to signature detection
             }
         }
 
         // Check file signature
-        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
-            Ok(".jpg")
-        } else if data.starts_with(&[0x89, 0x50, 0x4E, 0x47]) {
-            Ok(".png")
-        } else if data.starts_with(&[0x52, 0x49, 0x46, 0x46]) && data[8..12] == [0x57, 0x45, 0x42, 0x50] {
-            Ok(".webp")
-        } else {
-            Err(EmbeddingsError::ImageFormat("Unsupported image format".into()))
-        }
+        ImageFormat::detect(data)
+            .map(|format| format.extension())
+            .ok_or_else(|| EmbeddingsError::UnsupportedImageFormat("unrecognized image signature".into()))
     }
 }
 
@@ -156,6 +267,8 @@ impl EmbeddingContent {
 #[derive(Debug, Clone)]
 pub enum EmbeddingRequestContent {
     Content(EmbeddingContent),
+    /// A text embedded as one vector per token
+    Tokens(String),
     Heartbeat,
 }
 
@@ -164,6 +277,22 @@ pub struct EmbeddingRequest {
     content: EmbeddingRequestContent,
 }
 
+/// Computes the vectors of a request in place of the embedding models, see `Embeddings::with_stub`
+#[derive(Clone)]
+pub struct StubEmbedder(Arc<dyn Fn(EmbeddingRequestContent) -> Vec<Vec<f32>> + Send + Sync>);
+
+impl StubEmbedder {
+    pub fn new(embed: impl Fn(EmbeddingRequestContent) -> Vec<Vec<f32>> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(embed))
+    }
+}
+
+impl std::fmt::Debug for StubEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StubEmbedder")
+    }
+}
+
 /// Store embeddings for texts or images
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoreEmbeddings {
@@ -171,6 +300,9 @@ pub struct StoreEmbeddings {
     pub content: EmbeddingContent,
     /// Metadata to store with the embeddings
     pub metadata: Option<Vec<Value>>,
+    /// Embeddings already generated for the content, in the same order; skips generation when present
+    #[serde(default)]
+    pub embeddings: Option<Vec<Vec<f32>>>,
 }
 
 /// Generate embeddings for texts or images
@@ -178,6 +310,44 @@ pub struct StoreEmbeddings {
 pub struct GenerateEmbeddings {
     /// The content to embed (either texts or images)
     pub content: EmbeddingContent,
+    /// Whether the texts are queries or passages, selecting the instruction prefix to apply
+    #[serde(default)]
+    pub input: InputKind,
+    /// Scale the embeddings to unit length, so that their dot product is their cosine similarity
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+/// The role of a text input, used to pick its instruction prefix
+#[derive(utoipa::ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputKind {
+    Query,
+    #[default]
+    Passage,
+}
+
+/// Prefixes expected by instruction-tuned models (e.g. "query: " and "passage: " for e5)
+#[derive(bon::Builder, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstructionTemplate {
+    /// Prefix prepended to query inputs
+    #[builder(default, into)]
+    #[serde(default)]
+    pub query_prefix: String,
+    /// Prefix prepended to passage inputs
+    #[builder(default, into)]
+    #[serde(default)]
+    pub passage_prefix: String,
+}
+
+impl InstructionTemplate {
+    /// Prepends the prefix for the given input kind to each text
+    pub fn apply(&self, input: InputKind, texts: &[String]) -> Vec<String> {
+        let prefix = match input {
+            InputKind::Query => &self.query_prefix,
+            InputKind::Passage => &self.passage_prefix,
+        };
+        texts.iter().map(|text| format!("{}{}", prefix, text)).collect()
+    }
 }
 
 /// The generated embeddings
@@ -186,6 +356,77 @@ pub struct GeneratedEmbeddings {
     pub embeddings: Vec<Vec<f32>>,
 }
 
+/// Generate embeddings for many contents, each embedded separately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateEmbeddingsBatch {
+    /// The contents to embed, each generating its own result
+    pub contents: Vec<EmbeddingContent>,
+    /// Whether the texts are queries or passages, selecting the instruction prefix to apply
+    #[serde(default)]
+    pub input: InputKind,
+    /// How many contents are handed to the embedding workers at once, defaulting to the number of workers
+    ///
+    /// Only as many contents as there are `workers` are embedded in parallel, the others wait for a free worker.
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+}
+
+/// Generate one embedding per token of each text, for late interaction scoring with [`max_sim`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateMultiVectorEmbeddings {
+    /// The texts to embed
+    pub texts: Vec<String>,
+    /// Whether the texts are queries or passages, selecting the instruction prefix to apply
+    #[serde(default)]
+    pub input: InputKind,
+    /// Scale each token embedding to unit length, so that MaxSim sums cosine similarities
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+/// The generated token embeddings
+#[derive(utoipa::ToSchema, Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedMultiVectorEmbeddings {
+    /// The token embeddings of each text, in input order
+    pub embeddings: Vec<Vec<Vec<f32>>>,
+}
+
+/// The embeddings of a batch, one result per content in input order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedEmbeddingsBatch {
+    /// The embeddings of each content, or the error that content failed with
+    pub results: Vec<Result<GeneratedEmbeddings, BatchEmbeddingError>>,
+}
+
+/// Why a content of a batch failed to embed
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BatchEmbeddingError {
+    #[error("Input size too large: {0} tokens (max: {1})")]
+    InputSizeTooLarge(usize, usize),
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+    #[error("Embedding failed: {0}")]
+    Embedding(String),
+}
+
+impl From<EmbeddingsError> for BatchEmbeddingError {
+    fn from(error: EmbeddingsError) -> Self {
+        match error {
+            // Errors raised by the embedding workers are carried through fastembed's error
+            EmbeddingsError::Fastembed(error) => match error.downcast::<EmbeddingsError>() {
+                Ok(error) => error.into(),
+                Err(error) => BatchEmbeddingError::Embedding(error.to_string()),
+            },
+            EmbeddingsError::InputSizeTooLarge(length, max) => BatchEmbeddingError::InputSizeTooLarge(length, max),
+            error @ (EmbeddingsError::ImageFormat(_)
+            | EmbeddingsError::UnsupportedImageFormat(_)
+            | EmbeddingsError::TruncatedImage(..)
+            | EmbeddingsError::Base64Decode(_)) => BatchEmbeddingError::InvalidImage(error.to_string()),
+            error => BatchEmbeddingError::Embedding(error.to_string()),
+        }
+    }
+}
+
 /// Check if the embedding task is alive
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Health;
@@ -223,22 +464,86 @@ pub struct TopK {
     pub k: usize,
     /// The threshold for the similarity score
     pub threshold: f32,
+    /// Only consider embeddings with an id up to this ULID, hiding ones stored afterwards
+    pub snapshot: Option<String>,
 }
 
 fn default_sources() -> Vec<String> {
     vec!["/global".to_string()]
 }
 
+/// Get every embedding at least as similar to a query as the threshold
+#[derive(Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct WithinThreshold {
+    /// The query to search for
+    pub query: Query,
+    /// A list of sources to filter the search
+    #[builder(default = default_sources())]
+    #[serde(default = "default_sources")]
+    pub sources: Vec<String>,
+    /// The minimum similarity score of the embeddings to return
+    pub threshold: f32,
+    /// Safety cap on the number of embeddings returned
+    #[builder(default = default_max_results())]
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+    /// Only consider embeddings with an id up to this ULID, hiding ones stored afterwards
+    pub snapshot: Option<String>,
+}
+
+fn default_max_results() -> usize {
+    DEFAULT_MAX_RESULTS
+}
+
 /// The similarity between a query and an embedding
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Similarity {
+    /// The embedding's record, ordering embeddings of equal similarity
+    #[serde(default)]
+    pub id: Option<RecordId>,
     pub text: Option<String>,
     pub similarity: f32,
     pub source: Option<ContentSource>,
     pub metadata: Option<Value>,
 }
 
+/// Late interaction score of a document against a query, both embedded as one vector per token.
+///
+/// Each query token is matched with its most similar document token, and the similarities are summed (MaxSim, as in
+/// ColBERT). A document without tokens scores 0.
+pub fn max_sim(query: &[Vec<f32>], document: &[Vec<f32>]) -> f32 {
+    query
+        .iter()
+        .filter_map(|query_token| {
+            document.iter().map(|document_token| dot(query_token, document_token)).max_by(|a, b| a.total_cmp(b))
+        })
+        .sum()
+}
+
+/// Dot product of two vectors, over the length of the shorter one
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Cosine similarity of two vectors, 0 if either is a zero vector
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let magnitudes = dot(a, a).sqrt() * dot(b, b).sqrt();
+    if magnitudes == 0.0 {
+        return 0.0;
+    }
+    dot(a, b) / magnitudes
+}
+
+/// Scales a vector to unit length, leaving a zero vector unchanged
+pub fn normalize(vector: &mut [f32]) {
+    let magnitude = dot(vector, vector).sqrt();
+    if magnitude > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= magnitude);
+    }
+}
+
 #[derive(bon::Builder, Debug, Serialize, Deserialize)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct Embeddings {
     pub table_name_prefix: Option<String>,
     #[builder(default = default_model())]
@@ -247,12 +552,93 @@ pub struct Embeddings {
     pub image_model: ImageModel,
     #[builder(default = default_max_total_input_length())]
     max_total_input_length: usize,
+    /// Number of model instances embedding in parallel, each holding its own copy of the text and image models
+    #[builder(default = default_workers())]
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Instruction prefixes applied to text inputs before embedding
+    #[serde(default)]
+    pub instruction: Option<InstructionTemplate>,
+    /// Reuse the embeddings of texts already embedded by the same model, cached in the store
+    #[builder(default)]
+    #[serde(default)]
+    pub cache: bool,
+    #[serde(skip)]
+    cache_db: Option<Arc<Mutex<Surreal<Any>>>>,
+    /// Keep the embeddings of texts on disk, reused across restarts
+    #[serde(default)]
+    pub disk_cache: Option<DiskCacheConfig>,
+    #[serde(skip)]
+    #[builder(skip)]
+    disk: Option<Arc<DiskCache>>,
     #[serde(skip)]
     embedding_tx: Option<mpsc::Sender<EmbeddingRequest>>,
     #[serde(skip)]
     shared_embedding: Option<StrongSharedEmbedding>,
     #[serde(skip)]
     embedding_task: Option<JoinHandle<Result<(), fastembed::Error>>>,
+    #[serde(skip)]
+    text_metrics: Option<EmbeddingsMetrics>,
+    #[serde(skip)]
+    image_metrics: Option<EmbeddingsMetrics>,
+    /// Serves the requests in place of the models, which are then never loaded
+    #[serde(skip)]
+    #[builder(skip)]
+    stub: Option<StubEmbedder>,
+}
+
+/// An embeddings cache kept in a directory, with a size cap beyond which the least recently used are evicted
+#[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct DiskCacheConfig {
+    pub dir: PathBuf,
+    #[builder(default = default_disk_cache_max_bytes())]
+    #[serde(default = "default_disk_cache_max_bytes")]
+    pub max_bytes: u64,
+}
+
+fn default_disk_cache_max_bytes() -> u64 {
+    DEFAULT_DISK_CACHE_MAX_BYTES
+}
+
+/// Throughput metrics of an embedding model
+#[derive(Debug, Clone)]
+struct EmbeddingsMetrics {
+    generated: Counter,
+    duration: Histogram,
+    store_cache_hits: Counter,
+    disk_cache_hits: Counter,
+}
+
+impl EmbeddingsMetrics {
+    fn new(model: &str) -> Self {
+        let registry = metrics::registry();
+        let labels = [("model", model)];
+        let cache_hits = |cache: &str| {
+            registry.counter(
+                "bioma_embeddings_cache_hits_total",
+                "Embeddings served from the cache",
+                &[("model", model), ("cache", cache)],
+            )
+        };
+        Self {
+            generated: registry.counter("bioma_embeddings_generated_total", "Embeddings generated", &labels),
+            duration: registry.histogram(
+                "bioma_embeddings_duration_seconds",
+                "Time to generate a batch of embeddings",
+                &labels,
+                metrics::DURATION_BUCKETS,
+            ),
+            store_cache_hits: cache_hits("store"),
+            disk_cache_hits: cache_hits("disk"),
+        }
+    }
+}
+
+/// An embedding cached under the hash of its text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEmbedding {
+    key: String,
+    embedding: Vec<f32>,
 }
 
 fn default_model() -> Model {
@@ -267,6 +653,10 @@ fn default_max_total_input_length() -> usize {
     81_920
 }
 
+fn default_workers() -> usize {
+    1
+}
+
 #[derive(Deref)]
 struct StrongSharedEmbedding(Arc<SharedEmbedding>);
 
@@ -283,16 +673,34 @@ impl Clone for Embeddings {
             model: self.model.clone(),
             image_model: self.image_model.clone(),
             max_total_input_length: self.max_total_input_length,
+            workers: self.workers,
+            instruction: self.instruction.clone(),
+            cache: self.cache,
+            cache_db: None,
+            disk_cache: self.disk_cache.clone(),
+            disk: None,
             embedding_tx: None,
             shared_embedding: None,
             embedding_task: None,
+            text_metrics: None,
+            image_metrics: None,
+            stub: self.stub.clone(),
         }
     }
 }
 
 impl Default for Embeddings {
     fn default() -> Self {
-        Self::builder().build()
+        Self::builder().build_unchecked()
+    }
+}
+
+impl<S: embeddings_builder::IsComplete> EmbeddingsBuilder<S> {
+    /// Builds the embeddings actor, failing if the configuration is invalid
+    pub fn build(self) -> Result<Embeddings, EmbeddingsError> {
+        let embeddings = self.build_unchecked();
+        embeddings.validate()?;
+        Ok(embeddings)
     }
 }
 
@@ -300,37 +708,7 @@ impl Message<TopK> for Embeddings {
     type Response = Vec<Similarity>;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, message: &TopK) -> Result<(), EmbeddingsError> {
-        let query_embedding = match &message.query {
-            Query::Embedding(embedding) => embedding.clone(),
-            Query::Text(text) => {
-                match self.send_embedding_request(&EmbeddingContent::Text(vec![text.to_string()])).await {
-                    Ok(embeddings) => embeddings.first().cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)?,
-                    Err(EmbeddingsError::SendTextEmbeddings(_)) => {
-                        warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
-                        self.reinitialize(ctx).await?;
-
-                        let embeddings =
-                            self.send_embedding_request(&EmbeddingContent::Text(vec![text.to_string()])).await?;
-                        embeddings.first().cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)?
-                    }
-                    Err(e) => return Err(e),
-                }
-            }
-            Query::Image(image_data) => {
-                match self.send_embedding_request(&EmbeddingContent::Image(vec![image_data.clone()])).await {
-                    Ok(embeddings) => embeddings.first().cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)?,
-                    Err(EmbeddingsError::SendTextEmbeddings(_)) => {
-                        warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
-                        self.reinitialize(ctx).await?;
-
-                        let embeddings =
-                            self.send_embedding_request(&EmbeddingContent::Image(vec![image_data.clone()])).await?;
-                        embeddings.first().cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)?
-                    }
-                    Err(e) => return Err(e),
-                }
-            }
-        };
+        let query_embedding = self.query_embedding(ctx, &message.query).await?;
 
         let db = ctx.engine().db();
         let query_sql = include_str!("../sql/similarities.surql")
@@ -344,6 +722,34 @@ impl Message<TopK> for Embeddings {
             .bind(("query", query_embedding))
             .bind(("threshold", message.threshold))
             .bind(("sources", message.sources.clone()))
+            .bind(("snapshot", message.snapshot.clone()))
+            .bind(("prefix", self.table_prefix()))
+            .await
+            .map_err(SystemActorError::from)?;
+        let results: Vec<Similarity> = results.take(0).map_err(SystemActorError::from)?;
+        ctx.reply(results).await?;
+        Ok(())
+    }
+}
+
+impl Message<WithinThreshold> for Embeddings {
+    type Response = Vec<Similarity>;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, message: &WithinThreshold) -> Result<(), EmbeddingsError> {
+        let query_embedding = self.query_embedding(ctx, &message.query).await?;
+
+        let db = ctx.engine().db();
+        let query_sql = include_str!("../sql/within_threshold.surql");
+
+        let mut results = db
+            .lock()
+            .await
+            .query(query_sql)
+            .bind(("query", query_embedding))
+            .bind(("threshold", message.threshold))
+            .bind(("sources", message.sources.clone()))
+            .bind(("snapshot", message.snapshot.clone()))
+            .bind(("max_results", message.max_results))
             .bind(("prefix", self.table_prefix()))
             .await
             .map_err(SystemActorError::from)?;
@@ -357,15 +763,31 @@ impl Message<StoreEmbeddings> for Embeddings {
     type Response = StoredEmbeddings;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, message: &StoreEmbeddings) -> Result<(), EmbeddingsError> {
-        let embeddings = match self.send_embedding_request(&message.content).await {
-            Ok(embeddings) => embeddings,
-            Err(EmbeddingsError::SendTextEmbeddings(_)) => {
-                warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
-                self.reinitialize(ctx).await?;
+        let embeddings = match &message.embeddings {
+            Some(embeddings) => {
+                let inputs = match &message.content {
+                    EmbeddingContent::Text(texts) => texts.len(),
+                    EmbeddingContent::Image(images) => images.len(),
+                };
+                if embeddings.len() != inputs {
+                    return Err(EmbeddingsError::EmbeddingsCountMismatch(inputs, embeddings.len()));
+                }
+                embeddings.clone()
+            }
+            None => {
+                // Stored texts are passages, the original text is kept without the prefix
+                let content = self.instruct(&message.content, InputKind::Passage);
+                match self.embed(&content).await {
+                    Ok(embeddings) => embeddings,
+                    Err(EmbeddingsError::SendTextEmbeddings(_)) => {
+                        warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
+                        self.reinitialize(ctx).await?;
 
-                self.send_embedding_request(&message.content).await?
+                        self.embed(&content).await?
+                    }
+                    Err(e) => return Err(e),
+                }
             }
-            Err(e) => return Err(e),
         };
 
         let db = ctx.engine().db();
@@ -420,22 +842,87 @@ impl Message<GenerateEmbeddings> for Embeddings {
         ctx: &mut ActorContext<Self>,
         message: &GenerateEmbeddings,
     ) -> Result<(), EmbeddingsError> {
-        let embeddings = match self.send_embedding_request(&message.content).await {
+        let content = self.instruct(&message.content, message.input);
+        let mut embeddings = match self.embed(&content).await {
             Ok(embeddings) => embeddings,
             Err(EmbeddingsError::SendTextEmbeddings(_)) => {
                 warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
                 self.reinitialize(ctx).await?;
 
-                self.send_embedding_request(&message.content).await?
+                self.embed(&content).await?
             }
             Err(e) => return Err(e),
         };
+        if message.normalize {
+            embeddings.iter_mut().for_each(|embedding| normalize(embedding));
+        }
 
         ctx.reply(GeneratedEmbeddings { embeddings }).await?;
         Ok(())
     }
 }
 
+impl Message<GenerateEmbeddingsBatch> for Embeddings {
+    type Response = GeneratedEmbeddingsBatch;
+
+    async fn handle(
+        &mut self,
+        ctx: &mut ActorContext<Self>,
+        message: &GenerateEmbeddingsBatch,
+    ) -> Result<(), EmbeddingsError> {
+        // Check the embedding task up front rather than retrying each content
+        if let Err(EmbeddingsError::SendTextEmbeddings(_)) = self.send_heartbeat().await {
+            warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
+            self.reinitialize(ctx).await?;
+        }
+
+        let max_concurrency = message.max_concurrency.unwrap_or(self.workers);
+        let results = self.generate_batch(&message.contents, message.input, max_concurrency).await;
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        if failed > 0 {
+            warn!("{} {} of {} batch contents failed to embed", ctx.id(), failed, results.len());
+        }
+
+        let results = results.into_iter().map(|result| result.map_err(BatchEmbeddingError::from)).collect();
+        ctx.reply(GeneratedEmbeddingsBatch { results }).await?;
+        Ok(())
+    }
+}
+
+impl Message<GenerateMultiVectorEmbeddings> for Embeddings {
+    type Response = GeneratedMultiVectorEmbeddings;
+
+    async fn handle(
+        &mut self,
+        ctx: &mut ActorContext<Self>,
+        message: &GenerateMultiVectorEmbeddings,
+    ) -> Result<(), EmbeddingsError> {
+        if let Err(EmbeddingsError::SendTextEmbeddings(_)) = self.send_heartbeat().await {
+            warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
+            self.reinitialize(ctx).await?;
+        }
+
+        let texts = match &self.instruction {
+            Some(instruction) => instruction.apply(message.input, &message.texts),
+            None => message.texts.clone(),
+        };
+
+        // Each text is embedded on its own, so the workers can share them
+        let results: Vec<Result<Vec<Vec<f32>>, EmbeddingsError>> = futures::stream::iter(texts)
+            .map(|text| self.send_request(EmbeddingRequestContent::Tokens(text)))
+            .buffered(self.workers.max(1))
+            .collect()
+            .await;
+        let mut embeddings = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+        if message.normalize {
+            embeddings.iter_mut().flatten().for_each(|embedding| normalize(embedding));
+        }
+
+        ctx.reply(GeneratedMultiVectorEmbeddings { embeddings }).await?;
+        Ok(())
+    }
+}
+
 impl Message<Health> for Embeddings {
     type Response = Status;
 
@@ -450,6 +937,22 @@ impl Message<Health> for Embeddings {
     }
 }
 
+impl Message<HealthCheck> for Embeddings {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _message: &HealthCheck) -> Result<(), EmbeddingsError> {
+        let start = std::time::Instant::now();
+        let result = self.send_heartbeat().await;
+        let latency = start.elapsed();
+        let status = match result {
+            Ok(()) => HealthStatus::healthy(latency, format!("Embedding task for {} is alive", self.model)),
+            Err(e) => HealthStatus::unhealthy(latency, format!("Embedding task for {} failed: {}", self.model, e)),
+        };
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Display, Eq, Hash, PartialEq)]
 pub enum Model {
     NomicEmbedTextV15,
@@ -498,6 +1001,10 @@ pub struct ImageModelInfo {
 impl Actor for Embeddings {
     type Error = EmbeddingsError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), EmbeddingsError> {
         info!("{} Started", ctx.id());
 
@@ -516,16 +1023,36 @@ impl Actor for Embeddings {
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<GenerateMultiVectorEmbeddings>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
+            } else if let Some(input) = frame.is::<GenerateEmbeddingsBatch>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             } else if let Some(input) = frame.is::<TopK>() {
                 let response = self.reply(ctx, &input, &frame).await;
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<WithinThreshold>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             } else if let Some(input) = frame.is::<Health>() {
                 let response = self.reply(ctx, &input, &frame).await;
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
         info!("{} Finished", ctx.id());
@@ -533,20 +1060,62 @@ impl Actor for Embeddings {
     }
 }
 
+impl Supervised for Embeddings {
+    fn spawn_supervised(self, engine: Engine, id: ActorId, options: SpawnOptions, link: SupervisorLink) -> ActorHandle {
+        tokio::spawn(run_supervised(engine, id, self, options, link))
+    }
+}
+
 impl Embeddings {
     const MAX_TEXT_LENGTH: usize = 8192;
 
+    fn validate(&self) -> Result<(), EmbeddingsError> {
+        if let Some(prefix) = &self.table_name_prefix {
+            // The prefix becomes part of the table names
+            if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(EmbeddingsError::InvalidConfig(format!(
+                    "table name prefix {:?} must be non-empty and contain only letters, digits and underscores",
+                    prefix
+                )));
+            }
+        }
+        if self.max_total_input_length == 0 {
+            return Err(EmbeddingsError::InvalidConfig("max total input length must be at least 1".to_string()));
+        }
+        if self.workers == 0 {
+            return Err(EmbeddingsError::InvalidConfig("embedding workers must be at least 1".to_string()));
+        }
+        if self.disk_cache.as_ref().is_some_and(|config| config.max_bytes == 0) {
+            return Err(EmbeddingsError::InvalidConfig("disk cache size cap must be at least 1 byte".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Serves the embedding requests with `stub` instead of the models, for tests that need no real embeddings
+    ///
+    /// The embeddings tables are not defined, so only the generation messages can be used.
+    #[cfg(feature = "testing")]
+    pub fn with_stub(mut self, stub: StubEmbedder) -> Self {
+        self.stub = Some(stub);
+        self
+    }
+
     pub async fn init(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), EmbeddingsError> {
+        if let Some(stub) = self.stub.clone() {
+            return self.init_stub(ctx, stub).await;
+        }
+
         // Manage a shared embedding task
         let shared_embedding = {
             let mut embeddings_map = SHARED_EMBEDDINGS.lock().await;
-            let existing_embedding = if let Some(weak_ref) = embeddings_map.get(&self.model) {
+            let key = (self.model.clone(), self.workers);
+            let existing_embedding = if let Some(weak_ref) = embeddings_map.get(&key) {
                 if let Some(strong_ref) = weak_ref.upgrade() {
                     // Return the existing shared embedding
                     Some(strong_ref)
                 } else {
                     // Remove the expired weak reference
-                    embeddings_map.remove(&self.model);
+                    embeddings_map.remove(&key);
                     None
                 }
             } else {
@@ -626,136 +1195,100 @@ impl Embeddings {
                     info!("Model {:?} stored with id: {}", self.image_model, model.id);
                 }
 
-                // Create a new shared embedding
-                let (embedding_tx, mut embedding_rx) = mpsc::channel::<EmbeddingRequest>(100);
-                let text_model = self.model.clone();
-                let image_model = self.image_model.clone();
+                // Create a new shared embedding, served by a pool of workers that each load their own models
+                let (embedding_tx, embedding_rx) = mpsc::channel::<EmbeddingRequest>(100);
+                let embedding_rx = Arc::new(std::sync::Mutex::new(embedding_rx));
+                let in_flight = Arc::new(AtomicUsize::new(0));
+                let concurrent_calls = metrics::registry().histogram(
+                    "bioma_embeddings_concurrent_calls",
+                    "Embedding calls running when a call starts, itself included",
+                    &[("model", &self.model.to_string())],
+                    metrics::SIZE_BUCKETS,
+                );
                 let cache_dir = ctx.engine().huggingface_cache_dir().clone();
-                let max_total_input_length = self.max_total_input_length;
-
-                let embedding_task: JoinHandle<Result<(), fastembed::Error>> = tokio::task::spawn_blocking(move || {
-                    // Initialize both text and image embeddings
-                    let mut text_options =
-                        fastembed::InitOptions::new(get_fastembed_model(&text_model)).with_cache_dir(cache_dir.clone());
-                    let mut image_options = fastembed::ImageInitOptions::new(get_fastembed_image_model(&image_model))
-                        .with_cache_dir(cache_dir);
-
-                    #[cfg(target_os = "macos")]
-                    {
-                        text_options = text_options.with_execution_providers(vec![
-                            ort::execution_providers::CoreMLExecutionProvider::default().build(),
-                        ]);
-
-                        image_options = image_options.with_execution_providers(vec![
-                            ort::execution_providers::CoreMLExecutionProvider::default().build(),
-                        ]);
-                    }
 
-                    #[cfg(target_os = "linux")]
-                    {
-                        text_options = text_options.with_execution_providers(vec![
-                            ort::execution_providers::CUDAExecutionProvider::default().build(),
-                        ]);
-
-                        image_options = image_options.with_execution_providers(vec![
-                            ort::execution_providers::CUDAExecutionProvider::default().build(),
-                        ]);
-                    }
+                let workers: Vec<JoinHandle<Result<(), fastembed::Error>>> = (0..self.workers)
+                    .map(|_| {
+                        let text_model = self.model.clone();
+                        let image_model = self.image_model.clone();
+                        let cache_dir = cache_dir.clone();
+                        let max_total_input_length = self.max_total_input_length;
+                        let embedding_rx = embedding_rx.clone();
+                        let in_flight = in_flight.clone();
+                        let concurrent_calls = concurrent_calls.clone();
+
+                        tokio::task::spawn_blocking(move || {
+                            // Initialize both text and image embeddings
+                            let mut text_options = fastembed::InitOptions::new(get_fastembed_model(&text_model))
+                                .with_cache_dir(cache_dir.clone());
+                            let mut image_options =
+                                fastembed::ImageInitOptions::new(get_fastembed_image_model(&image_model))
+                                    .with_cache_dir(cache_dir);
+
+                            #[cfg(target_os = "macos")]
+                            {
+                                text_options = text_options.with_execution_providers(vec![
+                                    ort::execution_providers::CoreMLExecutionProvider::default().build(),
+                                ]);
+
+                                image_options = image_options.with_execution_providers(vec![
+                                    ort::execution_providers::CoreMLExecutionProvider::default().build(),
+                                ]);
+                            }
 
-                    let text_embedding = fastembed::TextEmbedding::try_new(text_options)?;
-                    let image_embedding = fastembed::ImageEmbedding::try_new(image_options)?;
+                            #[cfg(target_os = "linux")]
+                            {
+                                text_options = text_options.with_execution_providers(vec![
+                                    ort::execution_providers::CUDAExecutionProvider::default().build(),
+                                ]);
 
-                    while let Some(request) = embedding_rx.blocking_recv() {
-                        match request.content {
-                            EmbeddingRequestContent::Heartbeat => {
-                                let _ = request.response_tx.send(Ok(vec![]));
+                                image_options = image_options.with_execution_providers(vec![
+                                    ort::execution_providers::CUDAExecutionProvider::default().build(),
+                                ]);
                             }
-                            EmbeddingRequestContent::Content(content) => {
-                                let start = std::time::Instant::now();
-
-                                match content {
-                                    EmbeddingContent::Text(texts) => {
-                                        // Truncate each text to a maximum of 8192 characters
-                                        let truncated_texts: Vec<String> = texts
-                                            .into_iter()
-                                            .map(|text| {
-                                                if text.len() > Self::MAX_TEXT_LENGTH {
-                                                    text.chars().take(Self::MAX_TEXT_LENGTH).collect()
-                                                } else {
-                                                    text
-                                                }
-                                            })
-                                            .collect();
-
-                                        let total_length: usize = truncated_texts.iter().map(|text| text.len()).sum();
-
-                                        // Prevent GPU memory overload by limiting the total size of text that can be processed at once
-                                        if total_length > max_total_input_length {
-                                            error!(
-                                                "Total text input size too large: {} characters (max: {})",
-                                                total_length, max_total_input_length
-                                            );
-
-                                            let error = EmbeddingsError::InputSizeTooLarge(
-                                                total_length,
-                                                max_total_input_length,
-                                            );
-
-                                            let fastembed_error = fastembed::Error::msg(error.to_string());
-
-                                            // Send error response
-                                            let _ = request.response_tx.send(Err(fastembed_error));
-
-                                            continue;
-                                        }
-
-                                        let text_count = truncated_texts.len();
-                                        let avg_text_len = total_length as f32 / text_count as f32;
-
-                                        match text_embedding.embed(truncated_texts, None) {
-                                            Ok(embeddings) => {
-                                                info!(
-                                                    "Generated {} text embeddings (avg. {:.1} chars) in {:?}",
-                                                    text_count,
-                                                    avg_text_len,
-                                                    start.elapsed()
-                                                );
-                                                let _ = request.response_tx.send(Ok(embeddings));
-                                            }
-                                            Err(err) => {
-                                                error!("Failed to generate text embeddings: {}", err);
-                                                let _ = request.response_tx.send(Err(err));
-                                            }
-                                        }
+
+                            let text_embedding = fastembed::TextEmbedding::try_new(text_options)?;
+                            let image_embedding = fastembed::ImageEmbedding::try_new(image_options)?;
+
+                            // Counts a call in flight, until its result is sent
+                            let enter =
+                                || concurrent_calls.observe((in_flight.fetch_add(1, Ordering::SeqCst) + 1) as f64);
+                            loop {
+                                // Idle workers take turns waiting on the queue
+                                let Some(request) = embedding_rx.lock().unwrap().blocking_recv() else {
+                                    break;
+                                };
+                                let result = match request.content {
+                                    EmbeddingRequestContent::Heartbeat => {
+                                        let _ = request.response_tx.send(Ok(vec![]));
+                                        continue;
+                                    }
+                                    EmbeddingRequestContent::Content(content) => {
+                                        enter();
+                                        Self::embed_content(
+                                            &text_embedding,
+                                            &image_embedding,
+                                            content,
+                                            max_total_input_length,
+                                        )
                                     }
-                                    EmbeddingContent::Image(images) => {
-                                        let image_count = images.len();
-                                        match EmbeddingContent::process_image_data(&images) {
-                                            Ok(paths) => match image_embedding.embed(paths, None) {
-                                                Ok(embeddings) => {
-                                                    info!(
-                                                        "Generated {} image embeddings in {:?}",
-                                                        image_count,
-                                                        start.elapsed()
-                                                    );
-                                                    let _ = request.response_tx.send(Ok(embeddings));
-                                                }
-                                                Err(err) => {
-                                                    error!("Failed to generate image embeddings: {}", err);
-                                                    let _ = request.response_tx.send(Err(err));
-                                                }
-                                            },
-                                            Err(err) => {
-                                                error!("Failed to process image data: {}", err);
-                                                let _ = request.response_tx.send(Err(err.into()));
-                                            }
-                                        }
+                                    EmbeddingRequestContent::Tokens(text) => {
+                                        enter();
+                                        Self::embed_tokens(&text_embedding, text, max_total_input_length)
                                     }
-                                }
+                                };
+                                in_flight.fetch_sub(1, Ordering::SeqCst);
+                                let _ = request.response_tx.send(result);
                             }
-                        }
+                            Ok(())
+                        })
+                    })
+                    .collect();
+
+                let embedding_task = tokio::spawn(async move {
+                    for worker in workers {
+                        worker.await??;
                     }
-
                     info!("{} embedding task finished", ctx_id);
                     Ok(())
                 });
@@ -764,7 +1297,7 @@ impl Embeddings {
 
                 // Store the shared embedding
                 let shared_embedding = Arc::new(SharedEmbedding { embedding_tx });
-                embeddings_map.insert(self.model.clone(), Arc::downgrade(&shared_embedding));
+                embeddings_map.insert(key, Arc::downgrade(&shared_embedding));
                 shared_embedding
             }
         };
@@ -772,11 +1305,38 @@ impl Embeddings {
         let shared_embedding = Some(shared_embedding); // Wrap in Option first
         self.shared_embedding = shared_embedding.map(StrongSharedEmbedding);
         self.embedding_tx = self.shared_embedding.as_ref().map(|se| se.embedding_tx.clone());
+        self.text_metrics = Some(EmbeddingsMetrics::new(&self.model.to_string()));
+        self.image_metrics = Some(EmbeddingsMetrics::new(&self.image_model.to_string()));
+        self.cache_db = self.cache.then(|| ctx.engine().db());
+        if let Some(config) = &self.disk_cache {
+            self.disk = Some(DiskCache::open(config).await?);
+        }
 
         info!("{} Initialization complete", ctx.id());
         Ok(())
     }
 
+    /// Starts a task answering the embedding requests with the stub, on its own rather than shared
+    async fn init_stub(&mut self, ctx: &mut ActorContext<Self>, stub: StubEmbedder) -> Result<(), EmbeddingsError> {
+        let (embedding_tx, mut embedding_rx) = mpsc::channel::<EmbeddingRequest>(100);
+        self.embedding_task = Some(tokio::spawn(async move {
+            while let Some(request) = embedding_rx.recv().await {
+                let embeddings = match request.content {
+                    EmbeddingRequestContent::Heartbeat => vec![],
+                    content => (stub.0)(content),
+                };
+                let _ = request.response_tx.send(Ok(embeddings));
+            }
+            Ok(())
+        }));
+        self.embedding_tx = Some(embedding_tx);
+        self.text_metrics = Some(EmbeddingsMetrics::new(&self.model.to_string()));
+        self.image_metrics = Some(EmbeddingsMetrics::new(&self.image_model.to_string()));
+
+        info!("{} Initialization complete, with a stub embedder", ctx.id());
+        Ok(())
+    }
+
     /// Reinitialize the embedding infrastructure
     async fn reinitialize(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), EmbeddingsError> {
         info!("{} Reinitializing embedding infrastructure", ctx.id());
@@ -796,26 +1356,288 @@ impl Embeddings {
         Ok(())
     }
 
+    /// Embeds one request's content with a worker's models
+    fn embed_content(
+        text_embedding: &fastembed::TextEmbedding,
+        image_embedding: &fastembed::ImageEmbedding,
+        content: EmbeddingContent,
+        max_total_input_length: usize,
+    ) -> Result<Vec<Vec<f32>>, fastembed::Error> {
+        let start = std::time::Instant::now();
+
+        match content {
+            EmbeddingContent::Text(texts) => {
+                // Truncate each text to a maximum of 8192 characters
+                let truncated_texts: Vec<String> = texts
+                    .into_iter()
+                    .map(|text| {
+                        if text.len() > Self::MAX_TEXT_LENGTH {
+                            text.chars().take(Self::MAX_TEXT_LENGTH).collect()
+                        } else {
+                            text
+                        }
+                    })
+                    .collect();
+
+                let total_length: usize = truncated_texts.iter().map(|text| text.len()).sum();
+
+                // Prevent GPU memory overload by limiting the total size of text that can be processed at once
+                if total_length > max_total_input_length {
+                    error!(
+                        "Total text input size too large: {} characters (max: {})",
+                        total_length, max_total_input_length
+                    );
+
+                    let error = EmbeddingsError::InputSizeTooLarge(total_length, max_total_input_length);
+                    return Err(fastembed::Error::new(error));
+                }
+
+                let text_count = truncated_texts.len();
+                let avg_text_len = total_length as f32 / text_count as f32;
+
+                text_embedding
+                    .embed(truncated_texts, None)
+                    .inspect(|_| {
+                        info!(
+                            "Generated {} text embeddings (avg. {:.1} chars) in {:?}",
+                            text_count,
+                            avg_text_len,
+                            start.elapsed()
+                        )
+                    })
+                    .inspect_err(|err| error!("Failed to generate text embeddings: {}", err))
+            }
+            EmbeddingContent::Image(images) => {
+                let image_count = images.len();
+                let paths = EmbeddingContent::process_image_data(&images)
+                    .inspect_err(|err| error!("Failed to process image data: {}", err))?;
+                image_embedding
+                    .embed(paths, None)
+                    .inspect(|_| info!("Generated {} image embeddings in {:?}", image_count, start.elapsed()))
+                    .inspect_err(|err| error!("Failed to generate image embeddings: {}", err))
+            }
+        }
+    }
+
+    /// Embeds a text as one vector per token, taken from the model's last hidden state before pooling
+    fn embed_tokens(
+        text_embedding: &fastembed::TextEmbedding,
+        text: String,
+        max_total_input_length: usize,
+    ) -> Result<Vec<Vec<f32>>, fastembed::Error> {
+        let start = std::time::Instant::now();
+
+        let text: String = text.chars().take(Self::MAX_TEXT_LENGTH).collect();
+        if text.len() > max_total_input_length {
+            let error = EmbeddingsError::InputSizeTooLarge(text.len(), max_total_input_length);
+            return Err(fastembed::Error::new(error));
+        }
+
+        let mut tokens = Vec::new();
+        for batch in text_embedding.transform(vec![text], None)?.into_raw() {
+            let hidden_states = batch.select_output(&[fastembed::OutputKey::ByName("last_hidden_state")])?;
+            if hidden_states.ndim() != 3 {
+                return Err(fastembed::Error::new(EmbeddingsError::TokenEmbeddingsUnsupported));
+            }
+
+            // Padding tokens are masked out
+            for (sequence, mask) in hidden_states.outer_iter().zip(batch.attention_mask_array.outer_iter()) {
+                for (token, &attended) in sequence.outer_iter().zip(mask.iter()) {
+                    if attended == 1 {
+                        tokens.push(token.iter().copied().collect());
+                    }
+                }
+            }
+        }
+        info!("Generated {} token embeddings in {:?}", tokens.len(), start.elapsed());
+        Ok(tokens)
+    }
+
+    /// Applies the instruction template, if any, to text content
+    fn instruct<'a>(&self, content: &'a EmbeddingContent, input: InputKind) -> Cow<'a, EmbeddingContent> {
+        match (&self.instruction, content) {
+            (Some(instruction), EmbeddingContent::Text(texts)) => {
+                Cow::Owned(EmbeddingContent::Text(instruction.apply(input, texts)))
+            }
+            _ => Cow::Borrowed(content),
+        }
+    }
+
+    /// Embeds each content separately, handing up to `max_concurrency` of them to the embedding workers at once
+    ///
+    /// Results are aligned with `contents`, and a failed content does not stop the others.
+    pub async fn generate_batch(
+        &self,
+        contents: &[EmbeddingContent],
+        input: InputKind,
+        max_concurrency: usize,
+    ) -> Vec<Result<GeneratedEmbeddings, EmbeddingsError>> {
+        // `buffered` yields the results back in input order
+        futures::stream::iter(contents)
+            .map(|content| async move {
+                let content = self.instruct(content, input);
+                let embeddings = self.embed(&content).await?;
+                Ok(GeneratedEmbeddings { embeddings })
+            })
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Enables or disables the embeddings cache
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.cache = enabled;
+        self
+    }
+
+    /// Key of a text in the embeddings cache, which changes with the model
+    fn cache_key(&self, text: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.model.to_string().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(text.trim().as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Generates embeddings, serving texts from the caches when enabled and caching the ones generated
+    ///
+    /// The disk cache is read first, then the store for the texts it misses.
+    async fn embed(&self, content: &EmbeddingContent) -> Result<Vec<Vec<f32>>, EmbeddingsError> {
+        let EmbeddingContent::Text(texts) = content else {
+            return self.send_embedding_request(content).await;
+        };
+        if self.cache_db.is_none() && self.disk.is_none() {
+            return self.send_embedding_request(content).await;
+        }
+
+        let keys: Vec<String> = texts.iter().map(|text| self.cache_key(text)).collect();
+        let hits = |cached: &HashMap<String, Vec<f32>>| keys.iter().filter(|key| cached.contains_key(*key)).count();
+        let mut cached = match &self.disk {
+            Some(disk) => disk.get(&keys).await,
+            None => HashMap::new(),
+        };
+        let disk_hits = hits(&cached);
+
+        if let Some(db) = &self.cache_db {
+            let unseen: Vec<String> = keys.iter().filter(|key| !cached.contains_key(*key)).cloned().collect();
+            if !unseen.is_empty() {
+                let stored: Vec<CachedEmbedding> = db
+                    .lock()
+                    .await
+                    .query(include_str!("../sql/cached_embeddings.surql"))
+                    .bind(("prefix", self.table_prefix()))
+                    .bind(("keys", unseen))
+                    .await
+                    .map_err(SystemActorError::from)?
+                    .take(0)
+                    .map_err(SystemActorError::from)?;
+                cached.extend(stored.into_iter().map(|cached| (cached.key, cached.embedding)));
+            }
+        }
+        let store_hits = hits(&cached) - disk_hits;
+
+        // Generate the texts missing from the caches in one request
+        let missing: Vec<usize> = (0..texts.len()).filter(|&i| !cached.contains_key(&keys[i])).collect();
+        if !missing.is_empty() {
+            let missing_texts = missing.iter().map(|&i| texts[i].clone()).collect();
+            let generated = self.send_embedding_request(&EmbeddingContent::Text(missing_texts)).await?;
+            if generated.len() != missing.len() {
+                return Err(EmbeddingsError::EmbeddingsCountMismatch(missing.len(), generated.len()));
+            }
+
+            let entries: Vec<CachedEmbedding> = missing
+                .iter()
+                .zip(generated)
+                .map(|(&i, embedding)| CachedEmbedding { key: keys[i].clone(), embedding })
+                .collect();
+            if let Some(db) = &self.cache_db {
+                db.lock()
+                    .await
+                    .query(include_str!("../sql/cache_embeddings.surql"))
+                    .bind(("prefix", self.table_prefix()))
+                    .bind(("entries", entries.clone()))
+                    .await
+                    .map_err(SystemActorError::from)?;
+            }
+            if let Some(disk) = &self.disk {
+                let disk_entries: Vec<(&str, &[f32])> =
+                    entries.iter().map(|entry| (entry.key.as_str(), entry.embedding.as_slice())).collect();
+                if let Err(e) = disk.insert(&disk_entries).await {
+                    warn!("Failed to cache embeddings on disk: {}", e);
+                }
+            }
+            cached.extend(entries.into_iter().map(|entry| (entry.key, entry.embedding)));
+        }
+
+        if let Some(metrics) = &self.text_metrics {
+            metrics.disk_cache_hits.inc_by(disk_hits as u64);
+            metrics.store_cache_hits.inc_by(store_hits as u64);
+        }
+        keys.iter().map(|key| cached.get(key).cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)).collect()
+    }
+
     /// Helper method to send embedding requests
     async fn send_embedding_request(&self, content: &EmbeddingContent) -> Result<Vec<Vec<f32>>, EmbeddingsError> {
+        let start = std::time::Instant::now();
+        let embeddings = self.send_request(EmbeddingRequestContent::Content(content.clone())).await?;
+
+        let metrics = match content {
+            EmbeddingContent::Text(_) => &self.text_metrics,
+            EmbeddingContent::Image(_) => &self.image_metrics,
+        };
+        if let Some(metrics) = metrics {
+            metrics.generated.inc_by(embeddings.len() as u64);
+            metrics.duration.observe_duration(start.elapsed());
+        }
+        Ok(embeddings)
+    }
+
+    /// Hands a request to the embedding workers and waits for its result
+    async fn send_request(&self, content: EmbeddingRequestContent) -> Result<Vec<Vec<f32>>, EmbeddingsError> {
         let Some(embedding_tx) = self.embedding_tx.as_ref() else {
             return Err(EmbeddingsError::TextEmbeddingNotInitialized);
         };
 
         let (tx, rx) = oneshot::channel();
-        embedding_tx
-            .send(EmbeddingRequest { response_tx: tx, content: EmbeddingRequestContent::Content(content.clone()) })
-            .await?;
+        embedding_tx.send(EmbeddingRequest { response_tx: tx, content }).await?;
 
         match rx.await {
-            Ok(result) => match result {
-                Ok(embeddings) => Ok(embeddings),
-                Err(err) => Err(EmbeddingsError::Fastembed(err)),
-            },
+            Ok(result) => result.map_err(EmbeddingsError::Fastembed),
             Err(err) => Err(EmbeddingsError::RecvEmbeddings(err)),
         }
     }
 
+    /// Computes the embedding of a search query, reinitializing the embedding task if it died
+    async fn query_embedding(
+        &mut self,
+        ctx: &mut ActorContext<Self>,
+        query: &Query,
+    ) -> Result<Vec<f32>, EmbeddingsError> {
+        let content = match query {
+            Query::Embedding(embedding) => {
+                self.check_dimension(embedding)?;
+                return Ok(embedding.clone());
+            }
+            Query::Text(text) => {
+                let content = EmbeddingContent::Text(vec![text.to_string()]);
+                self.instruct(&content, InputKind::Query).into_owned()
+            }
+            Query::Image(image_data) => EmbeddingContent::Image(vec![image_data.clone()]),
+        };
+
+        let embeddings = match self.embed(&content).await {
+            Ok(embeddings) => embeddings,
+            Err(EmbeddingsError::SendTextEmbeddings(_)) => {
+                warn!("{} Embedding task appears to have died, reinitializing...", ctx.id());
+                self.reinitialize(ctx).await?;
+
+                self.embed(&content).await?
+            }
+            Err(e) => return Err(e),
+        };
+        embeddings.first().cloned().ok_or(EmbeddingsError::NoEmbeddingsGenerated)
+    }
+
     /// Send a heartbeat to check if the embedding task is still alive
     async fn send_heartbeat(&self) -> Result<(), EmbeddingsError> {
         let Some(embedding_tx) = self.embedding_tx.as_ref() else {
@@ -832,6 +1654,20 @@ impl Embeddings {
         }
     }
 
+    /// The number of dimensions of the embeddings generated by the text model
+    pub fn dimension(&self) -> Result<usize, EmbeddingsError> {
+        Ok(fastembed::TextEmbedding::get_model_info(&get_fastembed_model(&self.model))?.dim)
+    }
+
+    /// Checks that a precomputed embedding can be compared against the stored ones
+    pub fn check_dimension(&self, embedding: &[f32]) -> Result<(), EmbeddingsError> {
+        let dimension = self.dimension()?;
+        if embedding.len() != dimension {
+            return Err(EmbeddingsError::DimensionMismatch(embedding.len(), dimension));
+        }
+        Ok(())
+    }
+
     pub fn table_prefix(&self) -> String {
         self.table_name_prefix.as_ref().unwrap_or(&self.model.to_string()).clone()
     }
@@ -0,0 +1,36 @@
+use bioma_actor::prelude::*;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How long an HTTP service has to answer a health probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks that the HTTP service at `url` answers.
+///
+/// Any response short of a server error counts as healthy, since the conversion services have no dedicated
+/// health route and reply 404 or 405 to a plain `GET`.
+pub(crate) async fn probe_http(service: &str, url: &Url) -> HealthStatus {
+    let start = Instant::now();
+    let result = reqwest::Client::new().get(url.clone()).timeout(PROBE_TIMEOUT).send().await;
+    let latency = start.elapsed();
+    match result {
+        Ok(response) if response.status().is_server_error() => HealthStatus::unhealthy(
+            latency,
+            format!("{} at {} returned status {}", service, url, response.status()),
+        ),
+        Ok(response) => {
+            HealthStatus::healthy(latency, format!("{} at {} returned status {}", service, url, response.status()))
+        }
+        Err(e) => HealthStatus::unhealthy(latency, format!("{} at {} {}: {}", service, url, error_class(&e), e)),
+    }
+}
+
+fn error_class(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timed out"
+    } else if error.is_connect() {
+        "refused the connection"
+    } else {
+        "failed"
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    embeddings::{Embeddings, EmbeddingsError, ImageData, StoreEmbeddings},
+    embeddings::{Embeddings, EmbeddingsError, GenerateEmbeddings, ImageData, InputKind, StoreEmbeddings},
     markitdown::{AnalyzeMCFile, MarkitDown, MarkitDownError},
     pdf_analyzer::{AnalyzePdf, PdfAnalyzer, PdfAnalyzerError},
     prelude::{Summary, SummaryError},
@@ -7,13 +7,16 @@ use crate::{
 };
 use base64::Engine;
 use bioma_actor::prelude::*;
+use bioma_llm::metrics::{self, Counter};
 use derive_more::Display;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use surrealdb::RecordId;
 use text_splitter::{ChunkConfig, CodeSplitter, MarkdownSplitter, TextSplitter};
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 use walkdir::WalkDir;
 
@@ -22,8 +25,42 @@ use crate::embeddings::EmbeddingContent;
 pub const DEFAULT_CHUNK_CAPACITY: std::ops::Range<usize> = 500..2000;
 const DEFAULT_CHUNK_OVERLAP: usize = 200;
 const DEFAULT_CHUNK_BATCH_SIZE: usize = 50;
+const DEFAULT_EMBEDDING_CONCURRENCY: usize = 1;
+const DEFAULT_LANGUAGE_MIN_CONFIDENCE: f64 = 0.5;
+pub const UNKNOWN_LANGUAGE: &str = "unknown";
 const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp"];
 
+lazy_static! {
+    static ref INDEXER_METRICS: IndexerMetrics = IndexerMetrics::new();
+}
+
+/// Throughput metrics of the indexer
+struct IndexerMetrics {
+    indexed: Counter,
+    cached: Counter,
+    failed: Counter,
+    chunks: Counter,
+}
+
+impl IndexerMetrics {
+    fn new() -> Self {
+        let registry = metrics::registry();
+        let files = |status: &str| {
+            registry.counter(
+                "bioma_indexer_files_total",
+                "Files and texts processed, by outcome",
+                &[("status", status)],
+            )
+        };
+        Self {
+            indexed: files("indexed"),
+            cached: files("cached"),
+            failed: files("failed"),
+            chunks: registry.counter("bioma_indexer_chunks_total", "Chunks embedded and stored", &[]),
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum IndexerError {
     #[error("System error: {0}")]
@@ -58,6 +95,8 @@ pub enum IndexerError {
     Summary(#[from] SummaryError),
     #[error("Summary actor not initialized")]
     SummaryActorNotInitialized,
+    #[error("Invalid indexer configuration: {0}")]
+    InvalidConfig(String),
 }
 
 impl ActorError for IndexerError {}
@@ -77,6 +116,35 @@ pub struct Index {
     #[builder(default)]
     #[serde(default)]
     pub summarize: bool,
+
+    /// Detect the language of each text chunk and store it in the chunk metadata
+    #[serde(default)]
+    pub language_detection: Option<LanguageDetection>,
+}
+
+/// Language detection settings for text chunks
+#[derive(utoipa::ToSchema, bon::Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageDetection {
+    /// Minimum confidence of a detection, below which the chunk is tagged as unknown
+    #[builder(default = default_language_min_confidence())]
+    #[serde(default = "default_language_min_confidence")]
+    pub min_confidence: f64,
+}
+
+impl Default for LanguageDetection {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl LanguageDetection {
+    /// Returns the ISO 639-3 code of the text's language, or `unknown` when not confident enough
+    pub fn detect(&self, text: &str) -> String {
+        match whatlang::detect(text) {
+            Some(info) if info.confidence() >= self.min_confidence => info.lang().code().to_string(),
+            _ => UNKNOWN_LANGUAGE.to_string(),
+        }
+    }
 }
 
 #[derive(utoipa::ToSchema, bon::Builder, Debug, Clone, Serialize, Deserialize)]
@@ -102,6 +170,10 @@ pub struct TextChunkConfig {
     #[builder(default = default_chunk_batch_size())]
     #[serde(default = "default_chunk_batch_size")]
     pub chunk_batch_size: usize,
+
+    /// How to split the text, instead of the splitter picked from the file type
+    #[serde(default)]
+    pub chunk_strategy: Option<ChunkStrategy>,
 }
 
 impl Default for TextChunkConfig {
@@ -110,6 +182,25 @@ impl Default for TextChunkConfig {
     }
 }
 
+#[derive(utoipa::ToSchema, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChunkStrategy {
+    /// Windows of a fixed number of tokens, counted as whitespace-separated words
+    FixedSize {
+        tokens: usize,
+        /// Tokens repeated from the previous chunk, clamped below the chunk size
+        #[serde(default)]
+        overlap: usize,
+    },
+
+    /// Whole sentences, grouped up to the end of the chunk capacity
+    Sentence,
+
+    /// One chunk per markdown section, tagged with its headings
+    ///
+    /// Sections longer than the chunk capacity are split further, keeping their headings.
+    Markdown,
+}
+
 #[derive(utoipa::ToSchema, bon::Builder, Debug, Clone, Serialize, Deserialize)]
 pub struct GlobsContent {
     /// List of glob patterns
@@ -120,6 +211,37 @@ pub struct GlobsContent {
     #[serde(default)]
     #[serde(flatten)]
     pub config: TextChunkConfig,
+
+    /// How symlinks found while expanding the globs are handled
+    #[builder(default)]
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+
+    /// Remove the stored sources under the glob roots whose files no longer exist
+    #[builder(default)]
+    #[serde(default)]
+    pub prune_missing: bool,
+
+    /// Skip the files unchanged since they were indexed, reindex the changed ones and remove the missing ones
+    ///
+    /// Files are compared by modification time, then by content hash when it differs. Files indexed
+    /// without this option have no stored fingerprint, so they are reindexed once.
+    #[builder(default)]
+    #[serde(default)]
+    pub incremental: bool,
+}
+
+#[derive(utoipa::ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymlinkPolicy {
+    /// Ignore symlinks to files and directories
+    Skip,
+
+    /// Follow symlinks, indexing a file once for every link that reaches it
+    #[default]
+    Follow,
+
+    /// Follow symlinks, tracking visited real paths so each file is indexed once
+    FollowNoRepeat,
 }
 
 #[derive(utoipa::ToSchema, bon::Builder, Debug, Clone, Serialize, Deserialize)]
@@ -185,6 +307,10 @@ pub fn default_chunk_batch_size() -> usize {
     DEFAULT_CHUNK_BATCH_SIZE
 }
 
+pub fn default_language_min_confidence() -> f64 {
+    DEFAULT_LANGUAGE_MIN_CONFIDENCE
+}
+
 #[derive(utoipa::ToResponse, utoipa::ToSchema, Debug, Serialize, Deserialize, Clone)]
 pub struct IndexedSource {
     pub source: String,
@@ -212,6 +338,21 @@ pub struct Indexed {
     pub indexed: usize,
     pub cached: usize,
     pub sources: Vec<IndexedSource>,
+    /// Whether shutdown stopped indexing before every source was processed
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Sources removed because their files no longer exist
+    #[serde(default)]
+    pub deleted: Vec<ContentSource>,
+    /// Sources indexed for the first time
+    #[serde(default)]
+    pub added: usize,
+    /// Sources reindexed because their files changed, in incremental mode
+    #[serde(default)]
+    pub updated: usize,
+    /// Sources whose files are unchanged, in incremental mode; also counted as cached
+    #[serde(default)]
+    pub skipped: usize,
 }
 
 #[derive(Display, Debug, Clone, Serialize, Deserialize)]
@@ -236,6 +377,27 @@ pub enum TextType {
 pub struct TextMetadata {
     pub content: TextType,
     pub chunk_number: usize,
+    /// ISO 639-3 code of the chunk's language, when language detection is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Where the chunk sits in the file it was read from, unless the file was converted before chunking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<ChunkLocation>,
+    /// Headings enclosing the chunk, outermost first, when split with the markdown strategy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headings: Vec<String>,
+}
+
+/// The span of a chunk in its file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    pub path: PathBuf,
+    /// Byte range of the chunk in the file
+    pub byte_range: std::ops::Range<usize>,
+    /// First line of the chunk, starting from 1
+    pub start_line: usize,
+    /// Last line of the chunk, inclusive
+    pub end_line: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -266,6 +428,19 @@ pub struct ContentSource {
     pub uri: String,
 }
 
+/// Modification time and content hash of an indexed file, stored with its source for incremental indexing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileFingerprint {
+    /// Nanoseconds since the Unix epoch
+    modified: u64,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StoredFingerprint {
+    fingerprint: Option<FileFingerprint>,
+}
+
 #[derive(utoipa::ToSchema, Debug, Serialize, Deserialize, Clone)]
 pub struct DeleteSource {
     pub sources: Vec<String>,
@@ -289,10 +464,30 @@ pub struct ImageDimensions {
 
 #[derive(Debug, Clone)]
 pub enum Content {
-    Text { content: String, text_type: TextType, chunk_config: (std::ops::Range<usize>, usize, usize) },
+    Text {
+        content: String,
+        text_type: TextType,
+        chunk_config: TextChunkConfig,
+        /// The file the content was read verbatim from, used to locate the chunks
+        path: Option<PathBuf>,
+    },
     Image { data: ImageContent },
 }
 
+/// A chunk of text and the byte offset at which it starts
+#[derive(Debug)]
+struct TextChunk<'a> {
+    offset: usize,
+    text: &'a str,
+    headings: Vec<String>,
+}
+
+impl<'a> From<(usize, &'a str)> for TextChunk<'a> {
+    fn from((offset, text): (usize, &'a str)) -> Self {
+        Self { offset, text, headings: vec![] }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ImageContent {
     Path(String),
@@ -329,6 +524,73 @@ impl Indexer {
         }
     }
 
+    /// Returns the fingerprint stored with a source, `None` if the source is not stored
+    async fn stored_fingerprint(
+        &self,
+        ctx: &ActorContext<Self>,
+        source: &ContentSource,
+    ) -> Result<Option<Option<FileFingerprint>>, IndexerError> {
+        let query = "SELECT fingerprint FROM source:{source: $source, uri: $uri}";
+        let mut results = ctx
+            .engine()
+            .db()
+            .lock()
+            .await
+            .query(query)
+            .bind(("source", source.source.clone()))
+            .bind(("uri", source.uri.clone()))
+            .await
+            .map_err(SystemActorError::from)?;
+        let stored: Vec<StoredFingerprint> = results.take(0).map_err(SystemActorError::from)?;
+        Ok(stored.into_iter().next().map(|stored| stored.fingerprint))
+    }
+
+    async fn update_fingerprint(
+        &self,
+        ctx: &ActorContext<Self>,
+        source: &ContentSource,
+        fingerprint: &FileFingerprint,
+    ) -> Result<(), IndexerError> {
+        let query = "UPDATE source:{source: $source, uri: $uri} SET fingerprint = $fingerprint";
+        ctx.engine()
+            .db()
+            .lock()
+            .await
+            .query(query)
+            .bind(("source", source.source.clone()))
+            .bind(("uri", source.uri.clone()))
+            .bind(("fingerprint", fingerprint.clone()))
+            .await
+            .map_err(SystemActorError::from)?;
+        Ok(())
+    }
+
+    /// Deletes the sources with the given uris along with their embeddings
+    async fn delete_source_uris(
+        &self,
+        ctx: &ActorContext<Self>,
+        source: &str,
+        uris: Vec<String>,
+    ) -> Result<Vec<ContentSource>, IndexerError> {
+        let query = include_str!("../sql/del_source_uris.surql").replace("{prefix}", &self.embeddings.table_prefix());
+        let mut results = ctx
+            .engine()
+            .db()
+            .lock()
+            .await
+            .query(&query)
+            .bind(("source", source.to_string()))
+            .bind(("uris", uris))
+            .await
+            .map_err(SystemActorError::from)?;
+        let deleted: DeletedSource = results
+            .take::<Vec<DeletedSource>>(0)
+            .map_err(IndexerError::from)?
+            .pop()
+            .ok_or(IndexerError::Other("No delete result found".to_string()))?;
+        Ok(deleted.deleted_sources)
+    }
+
     /// Process summary generation and indexing for a text or image
     ///
     /// This function:
@@ -374,7 +636,11 @@ impl Indexer {
         // Generate embeddings for the summary
         let result = ctx
             .send_and_wait_reply::<Embeddings, StoreEmbeddings>(
-                StoreEmbeddings { content: EmbeddingContent::Text(vec![response.summary.clone()]), metadata },
+                StoreEmbeddings {
+                    content: EmbeddingContent::Text(vec![response.summary.clone()]),
+                    metadata,
+                    embeddings: None,
+                },
                 embeddings_id,
                 SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
             )
@@ -429,7 +695,7 @@ impl Indexer {
         let embeddings_future = async {
             let result = ctx
                 .send_and_wait_reply::<Embeddings, StoreEmbeddings>(
-                    StoreEmbeddings { content: embeddings_content, metadata: metadata_clone },
+                    StoreEmbeddings { content: embeddings_content, metadata: metadata_clone, embeddings: None },
                     embeddings_id,
                     SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
                 )
@@ -455,6 +721,81 @@ impl Indexer {
         Ok((embeddings_ids, summary_text))
     }
 
+    /// Embeds chunk batches in parallel while storing them in source order
+    ///
+    /// Up to `embedding_concurrency` batches are embedded at once, each on its own embeddings actor, ahead of the
+    /// one being stored. The bounded channel between the two stages applies backpressure, so memory stays bounded
+    /// regardless of the input size.
+    async fn embed_and_store_ordered<'a>(
+        &self,
+        ctx: &ActorContext<Self>,
+        source: &ContentSource,
+        embeddings_id: &ActorId,
+        batches: impl Iterator<Item = (usize, &'a [String], &'a [Value])>,
+    ) -> Result<Vec<RecordId>, IndexerError> {
+        let workers = match self.embedding_worker_ids.as_slice() {
+            [] => std::slice::from_ref(embeddings_id),
+            workers => workers,
+        };
+        let (batch_tx, mut batch_rx) = tokio::sync::mpsc::channel(workers.len());
+
+        // Embed batches in parallel, `buffered` yields them back in source order. Consecutive batches go to
+        // different actors, so the batches in flight never queue behind each other.
+        let embed_future = async {
+            let mut embedded = futures::stream::iter(batches.zip(workers.iter().cycle()))
+                .map(|((first_chunk, chunk_batch, metadata_batch), worker_id)| async move {
+                    let generated = ctx
+                        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+                            GenerateEmbeddings {
+                                content: EmbeddingContent::Text(chunk_batch.to_vec()),
+                                input: InputKind::Passage,
+                                normalize: false,
+                            },
+                            worker_id,
+                            SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+                        )
+                        .await;
+                    (first_chunk, chunk_batch, metadata_batch, generated)
+                })
+                .buffered(workers.len());
+
+            while let Some(batch) = embedded.next().await {
+                // The store stage stopped on an error
+                if batch_tx.send(batch).await.is_err() {
+                    break;
+                }
+            }
+            drop(batch_tx);
+        };
+
+        // Store embedded batches one at a time, keeping stored chunk positions monotonic
+        let store_future = async {
+            let mut embeddings_ids = Vec::new();
+            while let Some((first_chunk, chunk_batch, metadata_batch, generated)) = batch_rx.recv().await {
+                let generated = generated.inspect_err(|e| error!("Failed to generate embeddings: {}", e))?;
+                let last_chunk = first_chunk + chunk_batch.len();
+                debug!("{} Storing chunks {}..{} of {}", ctx.id(), first_chunk, last_chunk, source.uri);
+                let stored = ctx
+                    .send_and_wait_reply::<Embeddings, StoreEmbeddings>(
+                        StoreEmbeddings {
+                            content: EmbeddingContent::Text(chunk_batch.to_vec()),
+                            metadata: Some(metadata_batch.to_vec()),
+                            embeddings: Some(generated.embeddings),
+                        },
+                        embeddings_id,
+                        SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+                    )
+                    .await
+                    .inspect_err(|e| error!("Failed to store embeddings: {}", e))?;
+                embeddings_ids.extend(stored.ids);
+            }
+            Ok::<_, IndexerError>(embeddings_ids)
+        };
+
+        let ((), embeddings_ids) = tokio::join!(embed_future, store_future);
+        embeddings_ids
+    }
+
     async fn index_content(
         &self,
         ctx: &mut ActorContext<Self>,
@@ -462,6 +803,7 @@ impl Indexer {
         content: Content,
         embeddings_id: &ActorId,
         summarize: bool,
+        language_detection: Option<&LanguageDetection>,
     ) -> Result<IndexResult, IndexerError> {
         match content {
             Content::Image { data } => {
@@ -536,29 +878,33 @@ impl Indexer {
                     Ok(IndexResult::Indexed(embeddings_ids, summary_text))
                 }
             }
-            Content::Text { content, text_type, chunk_config: (chunk_capacity, chunk_overlap, chunk_batch_size) } => {
-                // Map types if needed
-                let (text_type, content) = match text_type {
-                    TextType::Code(CodeLanguage::Html) => (TextType::Markdown, mdka::from_html(&content)),
-                    TextType::Pdf => (TextType::Markdown, content),
-                    TextType::MCFile => (TextType::Markdown, content),
-                    _ => (text_type, content),
+            Content::Text { content, text_type, chunk_config, path } => {
+                let TextChunkConfig { chunk_capacity, chunk_overlap, chunk_batch_size, chunk_strategy } = &chunk_config;
+                let (chunk_overlap, chunk_batch_size) = (*chunk_overlap, *chunk_batch_size);
+
+                // Map types if needed, converted content can no longer be located in the file
+                let (text_type, content, path) = match text_type {
+                    TextType::Code(CodeLanguage::Html) => (TextType::Markdown, mdka::from_html(&content), None),
+                    TextType::Pdf => (TextType::Markdown, content, None),
+                    TextType::MCFile => (TextType::Markdown, content, None),
+                    _ => (text_type, content, path),
                 };
 
-                let chunks = match &text_type {
-                    TextType::Text => {
+                let chunks: Vec<TextChunk> = match (chunk_strategy, &text_type) {
+                    (Some(strategy), _) => split_with_strategy(strategy, &content, chunk_capacity, chunk_overlap)?,
+                    (None, TextType::Text) => {
                         let splitter = TextSplitter::new(
                             ChunkConfig::new(chunk_capacity.clone()).with_trim(false).with_overlap(chunk_overlap)?,
                         );
-                        splitter.chunks(&content).collect::<Vec<&str>>()
+                        splitter.chunk_indices(&content).map(TextChunk::from).collect()
                     }
-                    TextType::Markdown => {
+                    (None, TextType::Markdown) => {
                         let splitter = MarkdownSplitter::new(
                             ChunkConfig::new(chunk_capacity.clone()).with_trim(false).with_overlap(chunk_overlap)?,
                         );
-                        splitter.chunks(&content).collect::<Vec<&str>>()
+                        splitter.chunk_indices(&content).map(TextChunk::from).collect()
                     }
-                    TextType::Code(language) => {
+                    (None, TextType::Code(language)) => {
                         let language = match language {
                             CodeLanguage::Rust => tree_sitter_rust::LANGUAGE,
                             CodeLanguage::Python => tree_sitter_python::LANGUAGE,
@@ -571,47 +917,68 @@ impl Indexer {
                             ChunkConfig::new(chunk_capacity.clone()).with_trim(false).with_overlap(chunk_overlap)?,
                         )
                         .expect("Invalid tree-sitter language");
-                        splitter.chunks(&content).collect::<Vec<&str>>()
+                        splitter.chunk_indices(&content).map(TextChunk::from).collect()
                     }
                     _ => panic!("Invalid text type"),
                 };
 
-                let chunks = chunks.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+                let line_starts = line_starts(&content);
                 let metadata = chunks
                     .iter()
                     .enumerate()
-                    .map(|(i, _)| Metadata::Text(TextMetadata { content: text_type.clone(), chunk_number: i }))
+                    .map(|(i, chunk)| {
+                        Metadata::Text(TextMetadata {
+                            content: text_type.clone(),
+                            chunk_number: i,
+                            language: language_detection.map(|detection| detection.detect(chunk.text)),
+                            location: path
+                                .as_ref()
+                                .map(|path| locate_chunk(path, &line_starts, chunk.offset, chunk.text)),
+                            headings: chunk.headings.clone(),
+                        })
+                    })
                     .map(|metadata| serde_json::to_value(metadata).unwrap_or_default())
                     .collect::<Vec<Value>>();
+                let chunks = chunks.iter().map(|chunk| chunk.text.to_string()).collect::<Vec<String>>();
 
-                let chunk_batches = chunks.chunks(chunk_batch_size);
-                let metadata_batches = metadata.chunks(chunk_batch_size);
+                let mut batches = chunks
+                    .chunks(chunk_batch_size)
+                    .zip(metadata.chunks(chunk_batch_size))
+                    .enumerate()
+                    .map(|(i, (chunk_batch, metadata_batch))| (i * chunk_batch_size, chunk_batch, metadata_batch));
 
                 let mut all_embeddings_ids = Vec::new();
-                for (chunk_batch, metadata_batch) in chunk_batches.zip(metadata_batches) {
-                    let (embeddings_ids, summary_text) = self
-                        .process_embeddings_and_summary(
-                            ctx,
-                            &source,
-                            &Content::Text {
-                                content: content.clone(),
-                                text_type: text_type.clone(),
-                                chunk_config: (chunk_capacity.clone(), chunk_overlap, chunk_batch_size),
-                            },
-                            embeddings_id,
-                            summarize && all_embeddings_ids.is_empty(), // Only summarize on first batch
-                            EmbeddingContent::Text(chunk_batch.to_vec()),
-                            Some(metadata_batch.to_vec()),
-                        )
-                        .await?;
 
-                    all_embeddings_ids.extend(embeddings_ids);
+                // Only the first batch is summarized
+                if summarize {
+                    if let Some((_, chunk_batch, metadata_batch)) = batches.next() {
+                        let (embeddings_ids, summary_text) = self
+                            .process_embeddings_and_summary(
+                                ctx,
+                                &source,
+                                &Content::Text {
+                                    content: content.clone(),
+                                    text_type: text_type.clone(),
+                                    chunk_config: chunk_config.clone(),
+                                    path: None,
+                                },
+                                embeddings_id,
+                                true,
+                                EmbeddingContent::Text(chunk_batch.to_vec()),
+                                Some(metadata_batch.to_vec()),
+                            )
+                            .await?;
+
+                        all_embeddings_ids.extend(embeddings_ids);
 
-                    if let Some(text) = summary_text {
-                        return Ok(IndexResult::Indexed(all_embeddings_ids, Some(text)));
+                        if let Some(text) = summary_text {
+                            return Ok(IndexResult::Indexed(all_embeddings_ids, Some(text)));
+                        }
                     }
                 }
 
+                all_embeddings_ids.extend(self.embed_and_store_ordered(ctx, &source, embeddings_id, batches).await?);
+
                 if all_embeddings_ids.is_empty() {
                     Ok(IndexResult::Failed)
                 } else {
@@ -621,24 +988,84 @@ impl Indexer {
         }
     }
 
+    /// Bumps the version of the index, so cached retrievals made before the change are no longer served
+    async fn bump_index_version(&self, ctx: &ActorContext<Self>) -> Result<(), IndexerError> {
+        let query = include_str!("../sql/bump_index_version.surql");
+        ctx.engine().db().lock().await.query(query).await.map_err(SystemActorError::from)?;
+        Ok(())
+    }
+
+    /// Deletes the stored sources of `source` under the roots of `globs` whose files no longer exist
+    async fn prune_missing_sources(
+        &self,
+        ctx: &ActorContext<Self>,
+        source: &str,
+        globs: &[String],
+    ) -> Result<Vec<ContentSource>, IndexerError> {
+        let local_store_dir = ctx.engine().local_store_dir();
+        let roots: Vec<PathBuf> = globs
+            .iter()
+            .filter_map(|pattern| {
+                let full_pattern = local_store_dir.join(pattern);
+                pathdiff::diff_paths(glob_root(&full_pattern), local_store_dir)
+            })
+            .collect();
+
+        let db = ctx.engine().db();
+        let mut results = db
+            .lock()
+            .await
+            .query("SELECT id.source AS source, id.uri AS uri FROM source WHERE id.source = $source")
+            .bind(("source", source.to_string()))
+            .await
+            .map_err(SystemActorError::from)?;
+        let stored: Vec<ContentSource> = results.take(0).map_err(SystemActorError::from)?;
+
+        let mut missing = vec![];
+        for stored_source in stored {
+            let uri = Path::new(&stored_source.uri);
+            if !roots.iter().any(|root| uri.starts_with(root)) {
+                continue;
+            }
+            // An unreadable path is kept, only files known to be gone are removed
+            if !tokio::fs::try_exists(local_store_dir.join(uri)).await.unwrap_or(true) {
+                missing.push(stored_source.uri);
+            }
+        }
+        if missing.is_empty() {
+            return Ok(vec![]);
+        }
+        self.delete_source_uris(ctx, source, missing).await
+    }
+
     /// Handles the result of indexing a source, updating the sources vector and storing the source in the database if needed
+    ///
+    /// When `replace` is set, the stored source and its previous embeddings are removed in the same transaction as
+    /// the new ones are linked, so a failed reindex leaves the previous version in place.
     async fn handle_index_result(
         &self,
         ctx: &ActorContext<Self>,
         source: &ContentSource,
         result: Result<IndexResult, IndexerError>,
         sources: &mut Vec<IndexedSource>,
+        fingerprint: Option<&FileFingerprint>,
+        replace: bool,
     ) -> Result<bool, IndexerError> {
         match result {
             Ok(IndexResult::Indexed(ids, summary_text)) => {
                 if !ids.is_empty() {
+                    INDEXER_METRICS.chunks.inc_by(ids.len() as u64);
                     sources.push(IndexedSource {
                         source: source.source.clone(),
                         uri: source.uri.clone(),
                         status: IndexStatus::Indexed,
                     });
 
-                    let source_query = include_str!("../sql/source.surql");
+                    let source_query = if replace {
+                        include_str!("../sql/replace_source.surql").replace("{prefix}", &self.embeddings.table_prefix())
+                    } else {
+                        include_str!("../sql/source.surql").to_string()
+                    };
                     ctx.engine()
                         .db()
                         .lock()
@@ -647,6 +1074,7 @@ impl Indexer {
                         .bind(("source", source.source.clone()))
                         .bind(("uri", source.uri.clone()))
                         .bind(("summary", summary_text))
+                        .bind(("fingerprint", fingerprint.cloned()))
                         .bind(("emb_ids", ids))
                         .bind(("prefix", self.embeddings.table_prefix()))
                         .await
@@ -733,6 +1161,220 @@ impl Indexer {
     }
 }
 
+/// Expands a glob pattern into the files it covers, walking matched directories
+fn glob_paths(pattern: &str, symlinks: SymlinkPolicy) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut visited = HashSet::new();
+
+    // Only the first path resolving to a given real file is kept when not repeating
+    let mut is_new = |path: &Path| match symlinks {
+        SymlinkPolicy::FollowNoRepeat => path.canonicalize().map(|real| visited.insert(real)).unwrap_or(false),
+        SymlinkPolicy::Skip | SymlinkPolicy::Follow => true,
+    };
+
+    for entry in glob::glob(pattern).unwrap().flatten() {
+        if symlinks == SymlinkPolicy::Skip && entry.is_symlink() {
+            continue;
+        }
+
+        if entry.is_file() {
+            if is_new(&entry) {
+                paths.push(entry);
+            }
+        } else if entry.is_dir() {
+            for entry in WalkDir::new(entry)
+                .follow_links(symlinks != SymlinkPolicy::Skip)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+            {
+                if is_new(entry.path()) {
+                    paths.push(entry.path().to_path_buf());
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// The leading part of a glob pattern without wildcards, under which every match lies
+fn glob_root(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Splits `text` into chunks with the requested strategy
+fn split_with_strategy<'a>(
+    strategy: &ChunkStrategy,
+    text: &'a str,
+    capacity: &std::ops::Range<usize>,
+    overlap: usize,
+) -> Result<Vec<TextChunk<'a>>, IndexerError> {
+    match strategy {
+        ChunkStrategy::FixedSize { tokens: 0, .. } => {
+            Err(IndexerError::InvalidConfig("fixed size chunks must hold at least 1 token".to_string()))
+        }
+        ChunkStrategy::FixedSize { tokens, overlap } => Ok(fixed_size_chunks(text, *tokens, *overlap)),
+        ChunkStrategy::Sentence => Ok(sentence_chunks(text, capacity.end)),
+        ChunkStrategy::Markdown => {
+            let splitter =
+                MarkdownSplitter::new(ChunkConfig::new(capacity.clone()).with_trim(false).with_overlap(overlap)?);
+            let mut chunks = Vec::new();
+            for section in markdown_sections(text) {
+                if section.text.len() <= capacity.end {
+                    chunks.push(section);
+                    continue;
+                }
+                chunks.extend(splitter.chunk_indices(section.text).map(|(offset, text)| TextChunk {
+                    offset: section.offset + offset,
+                    text,
+                    headings: section.headings.clone(),
+                }));
+            }
+            Ok(chunks)
+        }
+    }
+}
+
+/// Byte ranges of the whitespace-separated words of `text`
+fn word_ranges(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(first)) => {
+                words.push(first..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(first) = start {
+        words.push(first..text.len());
+    }
+    words
+}
+
+/// Windows of `tokens` words, each starting `tokens - overlap` words after the previous one
+fn fixed_size_chunks(text: &str, tokens: usize, overlap: usize) -> Vec<TextChunk<'_>> {
+    let words = word_ranges(text);
+    let step = tokens - overlap.min(tokens - 1);
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < words.len() {
+        let last = (first + tokens).min(words.len()) - 1;
+        let range = words[first].start..words[last].end;
+        chunks.push(TextChunk::from((range.start, &text[range])));
+        if last + 1 == words.len() {
+            break;
+        }
+        first += step;
+    }
+    chunks
+}
+
+/// Groups consecutive sentences into chunks of at most `max_len` bytes, unless a sentence is longer on its own
+fn sentence_chunks(text: &str, max_len: usize) -> Vec<TextChunk<'_>> {
+    let mut chunks: Vec<TextChunk> = Vec::new();
+    let mut start = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if start.is_none() {
+            if c.is_whitespace() {
+                continue;
+            }
+            start = Some(i);
+        }
+        let next = chars.peek().map(|(_, next)| *next);
+        let sentence_end = matches!(c, '.' | '!' | '?') && next.is_none_or(char::is_whitespace);
+        let paragraph_end = c == '\n' && next == Some('\n');
+        if !(sentence_end || paragraph_end || next.is_none()) {
+            continue;
+        }
+
+        let Some(offset) = start.take() else { continue };
+        let end = offset + text[offset..i + c.len_utf8()].trim_end().len();
+        match chunks.last_mut() {
+            Some(chunk) if end - chunk.offset <= max_len => chunk.text = &text[chunk.offset..end],
+            _ => chunks.push(TextChunk::from((offset, &text[offset..end]))),
+        }
+    }
+    chunks
+}
+
+/// Splits markdown on its headings, tagging each section with the headings enclosing it
+fn markdown_sections(text: &str) -> Vec<TextChunk<'_>> {
+    let mut sections = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut section_start = 0;
+    let mut in_code_block = false;
+
+    let mut push_section = |start: usize, end: usize, headings: &[(usize, String)]| {
+        let section = text[start..end].trim_end();
+        if !section.trim_start().is_empty() {
+            let headings = headings.iter().map(|(_, title)| title.clone()).collect();
+            sections.push(TextChunk { offset: start, text: section, headings });
+        }
+    };
+
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block || line.len() - trimmed.len() > 3 {
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let title = &trimmed[level..];
+        if !(1..=6).contains(&level) || !title.starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        push_section(section_start, line_start, &headings);
+        section_start = line_start;
+        headings.retain(|(outer, _)| *outer < level);
+        headings.push((level, title.trim().trim_end_matches('#').trim_end().to_string()));
+    }
+    push_section(section_start, text.len(), &headings);
+    sections
+}
+
+/// Fingerprints the file at `path`, hashing its content unless its modification time matches `stored`
+async fn file_fingerprint(path: &Path, stored: Option<&FileFingerprint>) -> std::io::Result<FileFingerprint> {
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+    let modified = modified.duration_since(std::time::UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+    if let Some(stored) = stored.filter(|stored| stored.modified == modified) {
+        return Ok(stored.clone());
+    }
+    let hash = blake3::hash(&tokio::fs::read(path).await?).to_hex().to_string();
+    Ok(FileFingerprint { modified, hash })
+}
+
+/// Byte offsets at which each line of `content` starts
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// Locates a chunk starting at byte `offset` of the file at `path`
+fn locate_chunk(path: &Path, line_starts: &[usize], offset: usize, chunk: &str) -> ChunkLocation {
+    let end = offset + chunk.len();
+    let line_of = |byte: usize| line_starts.partition_point(|&start| start <= byte);
+    ChunkLocation {
+        path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        byte_range: offset..end,
+        start_line: line_of(offset),
+        end_line: line_of(end.saturating_sub(1).max(offset)),
+    }
+}
+
 impl Message<Index> for Indexer {
     type Response = Indexed;
 
@@ -750,11 +1392,15 @@ impl Message<Index> for Indexer {
         let total_index_time = std::time::Instant::now();
         let mut indexed = 0;
         let mut cached = 0;
+        let mut updated = 0;
+        let mut skipped = 0;
         let mut sources = Vec::new();
+        // Set when shutting down, the source being indexed is finished and the remaining ones are left out
+        let mut interrupted = false;
 
         match &message.content {
-            IndexContent::Globs(GlobsContent { globs, config }) => {
-                for pattern in globs {
+            IndexContent::Globs(GlobsContent { globs, config, symlinks, incremental, .. }) => {
+                'globs: for pattern in globs {
                     let local_store_dir = ctx.engine().local_store_dir();
                     let full_pattern = if std::path::Path::new(pattern).is_absolute() {
                         pattern.clone()
@@ -763,25 +1409,8 @@ impl Message<Index> for Indexer {
                     };
 
                     info!("Indexing glob: {}", &full_pattern);
-                    let paths = tokio::task::spawn_blocking(move || {
-                        let mut paths = Vec::new();
-                        for entry in glob::glob(&full_pattern).unwrap().flatten() {
-                            if entry.is_file() {
-                                paths.push(entry);
-                            } else if entry.is_dir() {
-                                for entry in WalkDir::new(entry)
-                                    .follow_links(true)
-                                    .into_iter()
-                                    .filter_map(|e| e.ok())
-                                    .filter(|e| e.file_type().is_file())
-                                {
-                                    paths.push(entry.path().to_path_buf());
-                                }
-                            }
-                        }
-                        paths
-                    })
-                    .await;
+                    let symlinks = *symlinks;
+                    let paths = tokio::task::spawn_blocking(move || glob_paths(&full_pattern, symlinks)).await;
 
                     let Ok(paths) = paths else {
                         warn!("Skipping glob: {}", &pattern);
@@ -794,6 +1423,11 @@ impl Message<Index> for Indexer {
                     };
 
                     for pathbuf in paths {
+                        if ctx.engine().shutdown_signal().is_draining() {
+                            interrupted = true;
+                            break 'globs;
+                        }
+
                         // Convert the full path to a path relative to the local store directory
                         let local_store_dir = ctx.engine().local_store_dir();
                         let relative_path = pathdiff::diff_paths(&pathbuf, local_store_dir)
@@ -801,8 +1435,43 @@ impl Message<Index> for Indexer {
                         let uri = relative_path.to_string_lossy().to_string();
                         let source = ContentSource { source: message.source.clone(), uri: uri.clone() };
 
-                        // Check if source already exists
-                        if let Some(indexed_source) = self.check_source_exists(ctx, &source).await? {
+                        // Check if source already exists, or in incremental mode whether its file changed since
+                        let mut fingerprint = None;
+                        let mut changed = false;
+                        if *incremental {
+                            let stored = self.stored_fingerprint(ctx, &source).await?;
+                            let current = file_fingerprint(&pathbuf, stored.as_ref().and_then(Option::as_ref)).await;
+                            let current = match current {
+                                Ok(current) => current,
+                                Err(e) => {
+                                    sources.push(IndexedSource {
+                                        source: message.source.clone(),
+                                        uri: uri.clone(),
+                                        status: IndexStatus::Failed(format!("Failed to read file: {}", e)),
+                                    });
+                                    continue;
+                                }
+                            };
+                            match stored {
+                                Some(Some(stored)) if stored.hash == current.hash => {
+                                    if stored.modified != current.modified {
+                                        self.update_fingerprint(ctx, &source, &current).await?;
+                                    }
+                                    skipped += 1;
+                                    cached += 1;
+                                    sources.push(IndexedSource {
+                                        source: message.source.clone(),
+                                        uri: uri.clone(),
+                                        status: IndexStatus::Cached,
+                                    });
+                                    continue;
+                                }
+                                // The previous embeddings are replaced once the new ones are stored
+                                Some(_) => changed = true,
+                                None => {}
+                            }
+                            fingerprint = Some(current);
+                        } else if let Some(indexed_source) = self.check_source_exists(ctx, &source).await? {
                             cached += 1;
                             sources.push(indexed_source);
                             continue;
@@ -815,8 +1484,7 @@ impl Message<Index> for Indexer {
                             if IMAGE_EXTENSIONS.iter().any(|&img_ext| img_ext.eq_ignore_ascii_case(ext)) {
                                 Content::Image { data: ImageContent::Path(pathbuf.to_string_lossy().into_owned()) }
                             } else {
-                                let chunk_config =
-                                    (config.chunk_capacity.clone(), config.chunk_overlap, config.chunk_batch_size);
+                                let chunk_config = config.clone();
 
                                 // Special handling for PDF
                                 if ext == "pdf" {
@@ -829,7 +1497,12 @@ impl Message<Index> for Indexer {
                                         .await
                                     {
                                         Ok(content) => {
-                                            Content::Text { content, text_type: TextType::Pdf, chunk_config }
+                                            Content::Text {
+                                                content,
+                                                text_type: TextType::Pdf,
+                                                chunk_config,
+                                                path: None,
+                                            }
                                         }
                                         Err(e) => {
                                             error!("Failed to convert pdf to md: {}. Error: {}", pathbuf.display(), e);
@@ -853,7 +1526,12 @@ impl Message<Index> for Indexer {
                                         .await
                                     {
                                         Ok(content) => {
-                                            Content::Text { content, text_type: TextType::MCFile, chunk_config }
+                                            Content::Text {
+                                                content,
+                                                text_type: TextType::MCFile,
+                                                chunk_config,
+                                                path: None,
+                                            }
                                         }
                                         Err(e) => {
                                             error!(
@@ -878,26 +1556,49 @@ impl Message<Index> for Indexer {
                                     };
 
                                     match tokio::fs::read_to_string(&pathbuf).await {
-                                        Ok(content) => Content::Text { content, text_type, chunk_config },
+                                        Ok(content) => Content::Text {
+                                            content,
+                                            text_type,
+                                            chunk_config,
+                                            path: Some(pathbuf.clone()),
+                                        },
                                         Err(_) => continue,
                                     }
                                 }
                             }
                         } else {
                             // Handle files without extensions as text
-                            let chunk_config =
-                                (config.chunk_capacity.clone(), config.chunk_overlap, config.chunk_batch_size);
+                            let chunk_config = config.clone();
                             match tokio::fs::read_to_string(&pathbuf).await {
-                                Ok(content) => Content::Text { content, text_type: TextType::Text, chunk_config },
+                                Ok(content) => Content::Text {
+                                    content,
+                                    text_type: TextType::Text,
+                                    chunk_config,
+                                    path: Some(pathbuf.clone()),
+                                },
                                 Err(_) => continue,
                             }
                         };
 
                         // Process content
-                        let result =
-                            self.index_content(ctx, source.clone(), content, embeddings_id, message.summarize).await;
-                        if self.handle_index_result(ctx, &source, result, &mut sources).await? {
+                        let result = self
+                            .index_content(
+                                ctx,
+                                source.clone(),
+                                content,
+                                embeddings_id,
+                                message.summarize,
+                                message.language_detection.as_ref(),
+                            )
+                            .await;
+                        if self
+                            .handle_index_result(ctx, &source, result, &mut sources, fingerprint.as_ref(), changed)
+                            .await?
+                        {
                             indexed += 1;
+                            if changed {
+                                updated += 1;
+                            }
                         }
                     }
                 }
@@ -917,6 +1618,11 @@ impl Message<Index> for Indexer {
                 .to_string();
 
                 for text in texts {
+                    if ctx.engine().shutdown_signal().is_draining() {
+                        interrupted = true;
+                        break;
+                    }
+
                     let (uri, filepath, _) = self.generate_file_path(ctx, &prefix, &extension).await?;
                     let source = ContentSource { source: message.source.clone(), uri };
 
@@ -934,13 +1640,22 @@ impl Message<Index> for Indexer {
                     let content = Content::Text {
                         content: text.clone(),
                         text_type: TextType::Text,
-                        chunk_config: (config.chunk_capacity.clone(), config.chunk_overlap, config.chunk_batch_size),
+                        chunk_config: config.clone(),
+                        path: Some(filepath.clone()),
                     };
 
                     // Process content
-                    let result =
-                        self.index_content(ctx, source.clone(), content, embeddings_id, message.summarize).await;
-                    if self.handle_index_result(ctx, &source, result, &mut sources).await? {
+                    let result = self
+                        .index_content(
+                            ctx,
+                            source.clone(),
+                            content,
+                            embeddings_id,
+                            message.summarize,
+                            message.language_detection.as_ref(),
+                        )
+                        .await;
+                    if self.handle_index_result(ctx, &source, result, &mut sources, None, false).await? {
                         indexed += 1;
                     }
                 }
@@ -957,6 +1672,11 @@ impl Message<Index> for Indexer {
                 });
 
                 for image in images {
+                    if ctx.engine().shutdown_signal().is_draining() {
+                        interrupted = true;
+                        break;
+                    }
+
                     // Gotta get metadata early because we need to know the extension to save the file
                     let (extension, image_metadata) = tokio::task::spawn_blocking({
                         let image_clone = image.clone();
@@ -1031,17 +1751,50 @@ impl Message<Index> for Indexer {
                     let content = Content::Image { data: ImageContent::Base64(image.clone(), image_metadata) };
 
                     // Process content
-                    let result =
-                        self.index_content(ctx, source.clone(), content, embeddings_id, message.summarize).await;
-                    if self.handle_index_result(ctx, &source, result, &mut sources).await? {
+                    let result = self
+                        .index_content(
+                            ctx,
+                            source.clone(),
+                            content,
+                            embeddings_id,
+                            message.summarize,
+                            message.language_detection.as_ref(),
+                        )
+                        .await;
+                    if self.handle_index_result(ctx, &source, result, &mut sources, None, false).await? {
                         indexed += 1;
                     }
                 }
             }
         }
 
+        // Pruning needs the full listing of the globs, so an interrupted run leaves the store as is
+        let mut deleted = vec![];
+        if let IndexContent::Globs(GlobsContent { globs, prune_missing, incremental, .. }) = &message.content {
+            if (*prune_missing || *incremental) && !interrupted {
+                deleted = self.prune_missing_sources(ctx, &message.source, globs).await?;
+            }
+        }
+
         info!("Indexed {} paths, cached {} paths, in {:?}", indexed, cached, total_index_time.elapsed());
-        ctx.reply(Indexed { indexed, cached, sources }).await?;
+        if !deleted.is_empty() {
+            info!("Removed {} sources no longer on disk", deleted.len());
+        }
+        if interrupted {
+            warn!("Indexing of {} interrupted by shutdown", message.source);
+        }
+        for source in &sources {
+            match source.status {
+                IndexStatus::Indexed => INDEXER_METRICS.indexed.inc(),
+                IndexStatus::Cached => INDEXER_METRICS.cached.inc(),
+                IndexStatus::Failed(_) => INDEXER_METRICS.failed.inc(),
+            }
+        }
+        if indexed > 0 || !deleted.is_empty() {
+            self.bump_index_version(ctx).await?;
+        }
+        let added = indexed - updated;
+        ctx.reply(Indexed { indexed, cached, sources, interrupted, deleted, added, updated, skipped }).await?;
         Ok(())
     }
 }
@@ -1082,18 +1835,37 @@ impl Message<DeleteSource> for Indexer {
             }
         }
 
+        if !delete_result.deleted_sources.is_empty() {
+            self.bump_index_version(ctx).await?;
+        }
         ctx.reply(delete_result).await?;
         Ok(())
     }
 }
 
 #[derive(bon::Builder, Debug, Serialize, Deserialize, Default)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct Indexer {
+    #[builder(default)]
     pub embeddings: Embeddings,
+    #[builder(default)]
     pub pdf_analyzer: PdfAnalyzer,
+    #[builder(default)]
     pub markitdown: MarkitDown,
+    #[builder(default)]
     pub summary: Summary,
+    /// Number of chunk batches of a source embedded in parallel, defaults to 1
+    ///
+    /// The batches in flight go to as many embeddings actors, round robin, and their embeddings are stored in the
+    /// order of the chunks as they come back, see `embed_and_store_ordered`. The embeddings pool is given at least as
+    /// many workers, each loading its own copy of the models.
+    #[serde(default)]
+    pub embedding_concurrency: Option<usize>,
     embeddings_id: Option<ActorId>,
+    /// The actors embedding chunk batches, starting with the main embeddings actor
+    #[serde(skip)]
+    #[builder(skip)]
+    embedding_worker_ids: Vec<ActorId>,
     pdf_analyzer_id: Option<ActorId>,
     markitdown_id: Option<ActorId>,
     summary_id: Option<ActorId>,
@@ -1102,11 +1874,41 @@ pub struct Indexer {
     #[serde(skip)]
     embeddings_handle: Option<tokio::task::JoinHandle<()>>,
     #[serde(skip)]
+    #[builder(skip)]
+    embedding_worker_handles: Vec<tokio::task::JoinHandle<()>>,
+    #[serde(skip)]
     markitdown_handle: Option<tokio::task::JoinHandle<()>>,
     #[serde(skip)]
     summary_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
+impl<S: indexer_builder::IsComplete> IndexerBuilder<S> {
+    /// Builds the indexer actor, failing if the configuration is invalid
+    pub fn build(self) -> Result<Indexer, IndexerError> {
+        let indexer = self.build_unchecked();
+        if indexer.embedding_concurrency == Some(0) {
+            return Err(IndexerError::InvalidConfig("embedding concurrency must be at least 1".to_string()));
+        }
+        Ok(indexer)
+    }
+}
+
+impl Message<HealthCheck> for Indexer {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _message: &HealthCheck) -> Result<(), IndexerError> {
+        let start = std::time::Instant::now();
+        let result = ctx.engine().db().lock().await.query("RETURN true").await.and_then(|response| response.check());
+        let latency = start.elapsed();
+        let status = match result {
+            Ok(_) => HealthStatus::healthy(latency, "Store connection answers queries"),
+            Err(e) => HealthStatus::unhealthy(latency, format!("Store connection failed: {}", e)),
+        };
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
 impl Actor for Indexer {
     type Error = IndexerError;
 
@@ -1126,6 +1928,11 @@ impl Actor for Indexer {
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
 
@@ -1158,6 +1965,11 @@ impl Indexer {
             }
         });
 
+        // Give the embeddings pool a worker for every batch embedded in parallel
+        let concurrency = self.embedding_concurrency.unwrap_or(DEFAULT_EMBEDDING_CONCURRENCY).max(1);
+        let mut embeddings = self.embeddings.clone();
+        embeddings.workers = embeddings.workers.max(concurrency);
+
         // Generate child id for embeddings
         let embeddings_id = ActorId::of::<Embeddings>(format!("{}/embeddings", self_id.name()));
         self.embeddings_id = Some(embeddings_id.clone());
@@ -1166,7 +1978,7 @@ impl Indexer {
         let (mut embeddings_ctx, mut embeddings_actor) = Actor::spawn(
             ctx.engine().clone(),
             embeddings_id.clone(),
-            self.embeddings.clone(),
+            embeddings.clone(),
             SpawnOptions::builder().exists(SpawnExistsOptions::Reset).build(),
         )
         .await?;
@@ -1178,6 +1990,25 @@ impl Indexer {
             }
         });
 
+        // Spawn an embeddings actor for each further batch embedded in parallel, sharing the same pool
+        self.embedding_worker_ids = vec![embeddings_id.clone()];
+        for worker in 1..concurrency {
+            let worker_id = ActorId::of::<Embeddings>(format!("{}/embeddings/{}", self_id.name(), worker));
+            let (mut worker_ctx, mut worker_actor) = Actor::spawn(
+                ctx.engine().clone(),
+                worker_id.clone(),
+                embeddings.clone(),
+                SpawnOptions::builder().exists(SpawnExistsOptions::Reset).build(),
+            )
+            .await?;
+            self.embedding_worker_handles.push(tokio::spawn(async move {
+                if let Err(e) = worker_actor.start(&mut worker_ctx).await {
+                    error!("Embeddings worker actor error: {}", e);
+                }
+            }));
+            self.embedding_worker_ids.push(worker_id);
+        }
+
         // Generate child id for markitdown
         let markitdown_id = ActorId::of::<MarkitDown>(format!("{}/markitdown", self_id.name()));
         self.markitdown_id = Some(markitdown_id.clone());
@@ -1,25 +1,33 @@
+pub mod config;
+mod disk_cache;
 pub mod embeddings;
+mod health;
 pub mod indexer;
 pub mod markitdown;
 pub mod pdf_analyzer;
+pub mod pipeline;
 pub mod rerank;
 pub mod retriever;
 pub mod summary;
 
 pub mod prelude {
+    pub use crate::config::{self, BiomaConfig, ConfigError};
     pub use crate::embeddings::{
-        self, EmbeddingContent, Embeddings, EmbeddingsError, GenerateEmbeddings, GeneratedEmbeddings, ImageData,
-        StoreEmbeddings,
+        self, BatchEmbeddingError, DiskCacheConfig, EmbeddingContent, Embeddings, EmbeddingsError, GenerateEmbeddings,
+        GenerateEmbeddingsBatch, GenerateMultiVectorEmbeddings, GeneratedEmbeddings, GeneratedEmbeddingsBatch,
+        GeneratedMultiVectorEmbeddings, ImageData, InputKind, InstructionTemplate, StoreEmbeddings, StubEmbedder,
     };
     pub use crate::indexer::{
-        self, DeleteSource, DeletedSource, GlobsContent, Index, IndexContent, Indexed, Indexer, IndexerError,
-        TextChunkConfig,
+        self, ChunkStrategy, DeleteSource, DeletedSource, GlobsContent, Index, IndexContent, Indexed, Indexer,
+        IndexerError, LanguageDetection, SymlinkPolicy, TextChunkConfig,
     };
-    pub use crate::markitdown::{self, MarkitDown, MarkitDownError};
-    pub use crate::pdf_analyzer::{self, PdfAnalyzer, PdfAnalyzerError};
+    pub use crate::markitdown::{self, AnalyzeMCFile, AnalyzeMCFileTree, DocumentNode, MarkitDown, MarkitDownError};
+    pub use crate::pdf_analyzer::{self, AnalyzePdf, PdfAnalyzer, PdfAnalyzerError};
+    pub use crate::pipeline::{self, Answer, Ask, Citation, ContextTemplate, RagPipeline, RagPipelineError};
     pub use crate::rerank::{self, RankTexts, RankedText, RankedTexts, Rerank, RerankError};
     pub use crate::retriever::{
-        self, ListSources, ListedSources, RetrieveContext, RetrieveQuery, Retriever, RetrieverError,
+        self, EmptyQueryPolicy, ListSources, ListedSources, QueryCacheConfig, RetrieveContext, RetrieveQuery, Retriever,
+        RetrieverError,
     };
     pub use crate::summary::{self, Summarize, Summary, SummaryError, SummaryResponse};
 }
@@ -1,6 +1,6 @@
 use bioma_actor::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::{error, info};
 use url::Url;
 
@@ -14,6 +14,12 @@ pub struct AnalyzeMCFile {
     pub file_path: PathBuf,
 }
 
+/// Converts a file like [`AnalyzeMCFile`], replying with its document tree instead of the markdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzeMCFileTree {
+    pub file_path: PathBuf,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum MarkitDownError {
     #[error("System error: {0}")]
@@ -24,11 +30,218 @@ pub enum MarkitDownError {
     ErrorPostFile(#[from] reqwest::Error),
     #[error("Error serde data")]
     SerdeJson(#[from] serde_json::Error),
+    #[error("Invalid MarkItDown configuration: {0}")]
+    InvalidConfig(String),
 }
 
 impl ActorError for MarkitDownError {}
 
+/// A block-level node of a converted document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentNode {
+    Document { children: Vec<DocumentNode> },
+    Heading { level: u8, text: String },
+    Paragraph { text: String },
+    List { ordered: bool, items: Vec<String> },
+    CodeBlock { language: Option<String>, code: String },
+    Table { header: Vec<String>, rows: Vec<Vec<String>> },
+    Quote { text: String },
+    Rule,
+}
+
+impl DocumentNode {
+    /// The child nodes of a document, empty for any other node.
+    pub fn children(&self) -> &[DocumentNode] {
+        match self {
+            DocumentNode::Document { children } => children,
+            _ => &[],
+        }
+    }
+
+    /// Serializes the node back to markdown.
+    ///
+    /// The result is normalized markdown, not the converter output: details the tree does not keep,
+    /// such as list numbering, table alignment and inline HTML blocks, are not reproduced.
+    pub fn to_markdown(&self) -> String {
+        match self {
+            DocumentNode::Document { children } => {
+                children.iter().map(|child| child.to_markdown()).collect::<Vec<_>>().join("\n\n")
+            }
+            DocumentNode::Heading { level, text } => format!("{} {}", "#".repeat(*level as usize), text),
+            DocumentNode::Paragraph { text } => text.clone(),
+            DocumentNode::List { ordered, items } => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| if *ordered { format!("{}. {}", i + 1, item) } else { format!("- {}", item) })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            DocumentNode::CodeBlock { language, code } => {
+                format!("```{}\n{}\n```", language.as_deref().unwrap_or_default(), code)
+            }
+            DocumentNode::Table { header, rows } => {
+                let row = |cells: &[String]| format!("| {} |", cells.join(" | "));
+                let mut lines = vec![row(header), format!("|{}", " --- |".repeat(header.len()))];
+                lines.extend(rows.iter().map(|cells| row(cells)));
+                lines.join("\n")
+            }
+            DocumentNode::Quote { text } => {
+                text.lines().map(|line| format!("> {}", line).trim_end().to_string()).collect::<Vec<_>>().join("\n")
+            }
+            DocumentNode::Rule => "---".to_string(),
+        }
+    }
+}
+
+/// Parses the markdown produced by MarkItDown into a document tree.
+pub fn parse_to_tree(input: &str) -> Result<DocumentNode, MarkitDownError> {
+    let mut parser = TreeParser::default();
+    let mut lines = input.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            parser.flush();
+        } else if let Some(fence) = code_fence(trimmed) {
+            parser.flush();
+            let language = trimmed[fence.len()..].trim();
+            let mut code = vec![];
+            for line in lines.by_ref() {
+                if line.trim().starts_with(fence) {
+                    break;
+                }
+                code.push(line);
+            }
+            parser.children.push(DocumentNode::CodeBlock {
+                language: (!language.is_empty()).then(|| language.to_string()),
+                code: code.join("\n"),
+            });
+        } else if let Some((level, text)) = atx_heading(trimmed) {
+            parser.flush();
+            parser.children.push(DocumentNode::Heading { level, text });
+        } else if let Some(level) = setext_underline(trimmed).filter(|_| !parser.paragraph.is_empty()) {
+            let text = parser.paragraph.drain(..).collect::<Vec<_>>().join(" ");
+            parser.children.push(DocumentNode::Heading { level, text });
+        } else if is_rule(trimmed) {
+            parser.flush();
+            parser.children.push(DocumentNode::Rule);
+        } else if trimmed.starts_with('|') && lines.peek().is_some_and(|next| is_table_separator(next.trim())) {
+            parser.flush();
+            lines.next();
+            let header = table_cells(trimmed);
+            let mut rows = vec![];
+            while let Some(row) = lines.next_if(|next| next.trim().starts_with('|')) {
+                rows.push(table_cells(row.trim()));
+            }
+            parser.children.push(DocumentNode::Table { header, rows });
+        } else if let Some(text) = trimmed.strip_prefix('>') {
+            if parser.quote.is_empty() {
+                parser.flush();
+            }
+            parser.quote.push(text.trim().to_string());
+        } else if let Some((ordered, item)) = list_item(line) {
+            if !parser.list.as_ref().is_some_and(|(list_ordered, _)| *list_ordered == ordered) {
+                parser.flush();
+            }
+            parser.list.get_or_insert((ordered, vec![])).1.push(item);
+        } else if let Some((_, items)) = parser.list.as_mut() {
+            // Indented and lazy continuation lines belong to the last item
+            if let Some(last) = items.last_mut() {
+                last.push('\n');
+                last.push_str(line);
+            }
+        } else if !parser.quote.is_empty() {
+            parser.quote.push(trimmed.to_string());
+        } else {
+            parser.paragraph.push(trimmed.to_string());
+        }
+    }
+    parser.flush();
+
+    Ok(DocumentNode::Document { children: parser.children })
+}
+
+#[derive(Default)]
+struct TreeParser {
+    children: Vec<DocumentNode>,
+    paragraph: Vec<String>,
+    list: Option<(bool, Vec<String>)>,
+    quote: Vec<String>,
+}
+
+impl TreeParser {
+    fn flush(&mut self) {
+        if !self.paragraph.is_empty() {
+            let text = self.paragraph.drain(..).collect::<Vec<_>>().join("\n");
+            self.children.push(DocumentNode::Paragraph { text });
+        }
+        if let Some((ordered, items)) = self.list.take() {
+            self.children.push(DocumentNode::List { ordered, items });
+        }
+        if !self.quote.is_empty() {
+            let text = self.quote.drain(..).collect::<Vec<_>>().join("\n");
+            self.children.push(DocumentNode::Quote { text });
+        }
+    }
+}
+
+fn code_fence(line: &str) -> Option<&'static str> {
+    ["```", "~~~"].into_iter().find(|fence| line.starts_with(fence))
+}
+
+fn atx_heading(line: &str) -> Option<(u8, String)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some((level as u8, rest.trim().trim_end_matches('#').trim_end().to_string()))
+}
+
+fn setext_underline(line: &str) -> Option<u8> {
+    if line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ['-', '*', '_'].into_iter().any(|mark| marks.chars().all(|c| c == mark))
+}
+
+fn is_table_separator(line: &str) -> bool {
+    line.starts_with('|') && line.contains('-') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+fn list_item(line: &str) -> Option<(bool, String)> {
+    // Indented markers are nested items, kept as part of their parent
+    if line.starts_with([' ', '\t']) {
+        return None;
+    }
+    if let Some(item) = ["- ", "* ", "+ "].into_iter().find_map(|marker| line.strip_prefix(marker)) {
+        return Some((false, item.trim().to_string()));
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    let rest = &line[digits..];
+    if digits > 0 && (rest.starts_with(". ") || rest.starts_with(") ")) {
+        return Some((true, rest[2..].trim().to_string()));
+    }
+    None
+}
+
 #[derive(bon::Builder, Debug, Serialize, Deserialize, Clone)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct MarkitDown {
     #[builder(default = Url::parse("http://localhost:5001").unwrap())]
     pub markitdown_url: Url,
@@ -36,13 +249,25 @@ pub struct MarkitDown {
 
 impl Default for MarkitDown {
     fn default() -> Self {
-        Self::builder().build()
+        Self::builder().build_unchecked()
+    }
+}
+
+impl<S: markit_down_builder::IsComplete> MarkitDownBuilder<S> {
+    /// Builds the MarkItDown actor, failing if the URL is not an HTTP URL
+    pub fn build(self) -> Result<MarkitDown, MarkitDownError> {
+        let markitdown = self.build_unchecked();
+        let url = &markitdown.markitdown_url;
+        if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+            return Err(MarkitDownError::InvalidConfig(format!("{} is not an HTTP URL", url)));
+        }
+        Ok(markitdown)
     }
 }
 
 impl MarkitDown {
-    async fn post_markitdown(&self, req: &AnalyzeMCFile) -> Result<String, MarkitDownError> {
-        let form_result = reqwest::multipart::Form::new().file("file", &req.file_path).await;
+    async fn post_markitdown(&self, file_path: &Path) -> Result<String, MarkitDownError> {
+        let form_result = reqwest::multipart::Form::new().file("file", file_path).await;
 
         match form_result {
             Ok(form) => {
@@ -69,15 +294,41 @@ impl Message<AnalyzeMCFile> for MarkitDown {
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, msg: &AnalyzeMCFile) -> Result<(), MarkitDownError> {
         info!("path {:?}", msg.file_path);
-        let markdown = self.post_markitdown(msg).await?;
+        let markdown = self.post_markitdown(&msg.file_path).await?;
         ctx.reply(markdown).await?;
         Ok(())
     }
 }
 
+impl Message<AnalyzeMCFileTree> for MarkitDown {
+    type Response = DocumentNode;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, msg: &AnalyzeMCFileTree) -> Result<(), MarkitDownError> {
+        info!("path {:?}", msg.file_path);
+        let markdown = self.post_markitdown(&msg.file_path).await?;
+        let tree = parse_to_tree(&markdown)?;
+        ctx.reply(tree).await?;
+        Ok(())
+    }
+}
+
+impl Message<HealthCheck> for MarkitDown {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &HealthCheck) -> Result<(), MarkitDownError> {
+        let status = crate::health::probe_http("MarkItDown", &self.markitdown_url).await;
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
 impl Actor for MarkitDown {
     type Error = MarkitDownError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
         info!("{} Started", ctx.id());
 
@@ -89,6 +340,16 @@ impl Actor for MarkitDown {
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<AnalyzeMCFileTree>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
+            } else if let Some(input) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
 
@@ -43,11 +43,16 @@ pub enum PdfAnalyzerError {
     ErrorPostFile(#[from] reqwest::Error),
     #[error("Error serde data")]
     SerdeJson(#[from] serde_json::Error),
+    #[error("Invalid PDF analyzer configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Error reading PDF: {0}")]
+    Pdf(#[from] lopdf::Error),
 }
 
 impl ActorError for PdfAnalyzerError {}
 
 #[derive(bon::Builder, Debug, Serialize, Deserialize, Clone)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct PdfAnalyzer {
     #[builder(default = Url::parse("http://localhost:5060").unwrap())]
     pub pdf_analyzer_url: Url,
@@ -55,7 +60,19 @@ pub struct PdfAnalyzer {
 
 impl Default for PdfAnalyzer {
     fn default() -> Self {
-        Self::builder().build()
+        Self::builder().build_unchecked()
+    }
+}
+
+impl<S: pdf_analyzer_builder::IsComplete> PdfAnalyzerBuilder<S> {
+    /// Builds the PDF analyzer actor, failing if the URL is not an HTTP URL
+    pub fn build(self) -> Result<PdfAnalyzer, PdfAnalyzerError> {
+        let pdf_analyzer = self.build_unchecked();
+        let url = &pdf_analyzer.pdf_analyzer_url;
+        if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+            return Err(PdfAnalyzerError::InvalidConfig(format!("{} is not an HTTP URL", url)));
+        }
+        Ok(pdf_analyzer)
     }
 }
 
@@ -83,6 +100,157 @@ impl PdfAnalyzer {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormFieldType {
+    Text,
+    Checkbox,
+    Radio,
+    Choice,
+    Signature,
+}
+
+/// A field of a fillable PDF form
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormField {
+    /// Fully qualified name, the names of the parent fields joined with dots
+    pub name: String,
+    pub field_type: FormFieldType,
+    /// The current value, the name of the selected state for checkboxes and radio buttons, `Off` if none is
+    pub value: Option<String>,
+}
+
+/// Button field flag marking a radio button group
+const FIELD_FLAG_RADIO: i64 = 1 << 15;
+/// Button field flag marking a push button, which has no value
+const FIELD_FLAG_PUSH_BUTTON: i64 = 1 << 16;
+/// Limit on nested fields, guarding against reference cycles
+const MAX_FIELD_DEPTH: usize = 32;
+
+/// Reads the AcroForm fields of a PDF and their current values, empty if the PDF has no form.
+pub fn form_fields(path: impl AsRef<Path>) -> Result<Vec<FormField>, PdfAnalyzerError> {
+    let document = lopdf::Document::load(path)?;
+    let Ok(acro_form) = document.catalog()?.get(b"AcroForm") else {
+        return Ok(vec![]);
+    };
+    let (_, acro_form) = document.dereference(acro_form)?;
+    let Ok(roots) = acro_form.as_dict()?.get(b"Fields") else {
+        return Ok(vec![]);
+    };
+
+    let mut fields = vec![];
+    for root in document.dereference(roots)?.1.as_array()? {
+        collect_form_fields(&document, root, &InheritedField::default(), 0, &mut fields)?;
+    }
+    Ok(fields)
+}
+
+/// Attributes a field passes down to its kids
+#[derive(Default, Clone)]
+struct InheritedField<'a> {
+    name: Option<String>,
+    field_type: Option<&'a [u8]>,
+    flags: i64,
+    value: Option<&'a lopdf::Object>,
+}
+
+fn collect_form_fields<'a>(
+    document: &'a lopdf::Document,
+    node: &'a lopdf::Object,
+    parent: &InheritedField<'a>,
+    depth: usize,
+    fields: &mut Vec<FormField>,
+) -> Result<(), PdfAnalyzerError> {
+    if depth > MAX_FIELD_DEPTH {
+        return Ok(());
+    }
+    let node = document.dereference(node)?.1.as_dict()?;
+
+    let mut field = parent.clone();
+    let partial_name = node.get(b"T").and_then(|name| document.dereference(name)).and_then(|(_, name)| name.as_str());
+    if let Ok(partial_name) = partial_name {
+        let partial_name = decode_pdf_text(partial_name);
+        field.name = Some(match &parent.name {
+            Some(parent_name) => format!("{}.{}", parent_name, partial_name),
+            None => partial_name,
+        });
+    }
+    if let Ok(field_type) = node.get(b"FT").and_then(|field_type| field_type.as_name()) {
+        field.field_type = Some(field_type);
+    }
+    if let Ok(flags) = node.get(b"Ff").and_then(|flags| flags.as_i64()) {
+        field.flags = flags;
+    }
+    if let Ok(value) = node.get(b"V") {
+        field.value = Some(document.dereference(value)?.1);
+    }
+
+    // Kids without a name of their own are the widgets of this field rather than fields
+    let kids: &[lopdf::Object] = match node.get(b"Kids") {
+        Ok(kids) => document.dereference(kids)?.1.as_array()?.as_slice(),
+        Err(_) => &[],
+    };
+    let has_field_kids = kids.iter().any(|kid| {
+        document.dereference(kid).and_then(|(_, kid)| kid.as_dict()).is_ok_and(|kid| kid.has(b"T"))
+    });
+    if has_field_kids {
+        for kid in kids {
+            collect_form_fields(document, kid, &field, depth + 1, fields)?;
+        }
+        return Ok(());
+    }
+
+    let (Some(name), Some(field_type)) = (field.name, field.field_type) else {
+        return Ok(());
+    };
+    let field_type = match field_type {
+        b"Tx" => FormFieldType::Text,
+        b"Btn" if field.flags & FIELD_FLAG_PUSH_BUTTON != 0 => return Ok(()),
+        b"Btn" if field.flags & FIELD_FLAG_RADIO != 0 => FormFieldType::Radio,
+        b"Btn" => FormFieldType::Checkbox,
+        b"Ch" => FormFieldType::Choice,
+        b"Sig" => FormFieldType::Signature,
+        _ => return Ok(()),
+    };
+    let value = match (field.value, field_type) {
+        (_, FormFieldType::Signature) => None,
+        (Some(value), _) => form_value(document, value),
+        (None, FormFieldType::Checkbox | FormFieldType::Radio) => Some("Off".to_string()),
+        (None, _) => None,
+    };
+    fields.push(FormField { name, field_type, value });
+    Ok(())
+}
+
+fn form_value(document: &lopdf::Document, value: &lopdf::Object) -> Option<String> {
+    match value {
+        lopdf::Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        lopdf::Object::String(text, _) => Some(decode_pdf_text(text)),
+        // Choice fields with several selected options
+        lopdf::Object::Array(values) => {
+            let values: Vec<String> = values
+                .iter()
+                .filter_map(|value| document.dereference(value).ok())
+                .filter_map(|(_, value)| form_value(document, value))
+                .collect();
+            (!values.is_empty()).then(|| values.join(", "))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a PDF text string, UTF-16 when it starts with a byte order mark and PDFDocEncoding otherwise
+fn decode_pdf_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        // PDFDocEncoding matches Latin-1 for the printable characters
+        None => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
 impl Message<AnalyzePdf> for PdfAnalyzer {
     type Response = String;
 
@@ -94,9 +262,23 @@ impl Message<AnalyzePdf> for PdfAnalyzer {
     }
 }
 
+impl Message<HealthCheck> for PdfAnalyzer {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _msg: &HealthCheck) -> Result<(), PdfAnalyzerError> {
+        let status = crate::health::probe_http("PDF analyzer", &self.pdf_analyzer_url).await;
+        ctx.reply(status).await?;
+        Ok(())
+    }
+}
+
 impl Actor for PdfAnalyzer {
     type Error = PdfAnalyzerError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
         info!("{} Started", ctx.id());
 
@@ -108,6 +290,11 @@ impl Actor for PdfAnalyzer {
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(input) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
 
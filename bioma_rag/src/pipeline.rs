@@ -0,0 +1,358 @@
+use crate::indexer::ContentSource;
+use crate::rerank::{RankTexts, Rerank};
+use crate::retriever::{default_retriever_sources, Context, RetrieveContext, RetrieveQuery, Retriever};
+use bioma_actor::prelude::*;
+use bioma_llm::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+const DEFAULT_PIPELINE_LIMIT: usize = 5;
+const DEFAULT_STAGE_TIMEOUT: Duration = Duration::from_secs(200);
+const DEFAULT_SESSION_TURNS: usize = 4;
+/// Placeholder of the prompt template replaced by the numbered context
+pub const CONTEXT_PLACEHOLDER: &str = "{context}";
+
+#[derive(thiserror::Error, Debug)]
+pub enum RagPipelineError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("Retrieval failed: {0}")]
+    Retrieval(String),
+    #[error("Chat failed: {0}")]
+    Chat(String),
+}
+
+impl ActorError for RagPipelineError {}
+
+/// Answers a question from the retrieved context
+#[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct Ask {
+    pub question: String,
+    /// Conversation the question belongs to, whose previous turns are sent along with it
+    pub session_id: Option<String>,
+    /// Sources the context is retrieved from, `/global` when empty
+    #[builder(default)]
+    #[serde(default)]
+    pub filters: Vec<String>,
+}
+
+/// A context sent to the model, referred to in the answer by its `[n]` marker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    /// Number of the `[n]` marker, starting from 1
+    pub marker: usize,
+    pub source: Option<ContentSource>,
+    pub score: Option<f32>,
+    pub text: Option<String>,
+}
+
+/// Time spent in each stage of the pipeline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StageLatencies {
+    pub retrieve: Duration,
+    /// Not set when no rerank actor is configured
+    pub rerank: Option<Duration>,
+    pub chat: Duration,
+    pub total: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Answer {
+    pub answer: String,
+    pub citations: Vec<Citation>,
+    pub usage: Option<Usage>,
+    pub latencies: StageLatencies,
+    /// Stages that failed without failing the question, such as a rerank falling back to the retrieval order
+    pub warnings: Vec<String>,
+}
+
+/// Runs a question through retrieval, optional reranking and chat.
+///
+/// The retrieved contexts are formatted by `context_template` and substituted for `{context}` in `prompt_template`,
+/// which is sent as the system message followed by the previous turns of the session and the question. A failed
+/// rerank keeps the retrieval order, and a failed retrieval answers without context when `answer_without_context` is
+/// set.
+#[derive(bon::Builder, Debug, Serialize, Deserialize)]
+pub struct RagPipeline {
+    pub retriever: ActorId,
+    pub rerank: Option<ActorId>,
+    pub chat: ActorId,
+    #[builder(default = default_prompt_template())]
+    #[serde(default = "default_prompt_template")]
+    pub prompt_template: String,
+    /// How the contexts substituted for `{context}` are formatted
+    #[builder(default)]
+    #[serde(default)]
+    pub context_template: ContextTemplate,
+    /// Maximum number of contexts sent to the model
+    #[builder(default = DEFAULT_PIPELINE_LIMIT)]
+    #[serde(default = "default_pipeline_limit")]
+    pub limit: usize,
+    #[builder(default)]
+    #[serde(default)]
+    pub answer_without_context: bool,
+    #[builder(default = DEFAULT_STAGE_TIMEOUT)]
+    #[serde(default = "default_stage_timeout")]
+    pub stage_timeout: Duration,
+    /// Number of past question and answer pairs sent along, which with the system message and the question must fit
+    /// in the chat's `messages_number_limit`
+    #[builder(default = DEFAULT_SESSION_TURNS)]
+    #[serde(default = "default_session_turns")]
+    pub session_turns: usize,
+    #[builder(skip)]
+    #[serde(skip)]
+    sessions: HashMap<String, Vec<ChatMessage>>,
+}
+
+pub fn default_prompt_template() -> String {
+    format!(
+        "Answer the question using only the context below. Cite the context supporting each statement with its \
+         marker, such as [1]. If the context does not contain the answer, say so.\n\n{}",
+        CONTEXT_PLACEHOLDER
+    )
+}
+
+fn default_pipeline_limit() -> usize {
+    DEFAULT_PIPELINE_LIMIT
+}
+
+fn default_stage_timeout() -> Duration {
+    DEFAULT_STAGE_TIMEOUT
+}
+
+fn default_session_turns() -> usize {
+    DEFAULT_SESSION_TURNS
+}
+
+/// How the retrieved contexts are formatted into the prompt.
+///
+/// Each context is rendered as its marker, its source if known, a newline and its text, followed by the separator.
+/// Contexts are numbered from 1 in relevance order, matching the markers of the `Citation`s.
+#[derive(bon::Builder, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextTemplate {
+    /// Marker the model cites a context by, where `{n}` is replaced by the context number
+    #[builder(default = default_context_marker(), into)]
+    #[serde(default = "default_context_marker")]
+    pub marker: String,
+    /// Appended to the marker of a context with a source, where `{source}` and `{uri}` are replaced by its fields
+    #[builder(default = default_context_source(), into)]
+    #[serde(default = "default_context_source")]
+    pub source: String,
+    #[builder(default = default_context_separator(), into)]
+    #[serde(default = "default_context_separator")]
+    pub separator: String,
+    /// List the contexts from the least to the most relevant, so the best one is closest to the question
+    #[builder(default)]
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+fn default_context_marker() -> String {
+    "[{n}]".to_string()
+}
+
+fn default_context_source() -> String {
+    " (source: {uri})".to_string()
+}
+
+fn default_context_separator() -> String {
+    "\n\n".to_string()
+}
+
+impl Default for ContextTemplate {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl ContextTemplate {
+    /// Formats the contexts, numbered in the given order
+    pub fn render(&self, contexts: &[Context]) -> String {
+        let mut entries: Vec<String> = contexts
+            .iter()
+            .enumerate()
+            .map(|(i, context)| {
+                let mut entry = self.marker.replace("{n}", &(i + 1).to_string());
+                if let Some(source) = &context.source {
+                    entry.push_str(&self.source.replace("{source}", &source.source).replace("{uri}", &source.uri));
+                }
+                entry.push('\n');
+                entry.push_str(context.text.as_deref().unwrap_or_default());
+                entry.push_str(&self.separator);
+                entry
+            })
+            .collect();
+        if self.reverse {
+            entries.reverse();
+        }
+        entries.concat()
+    }
+}
+
+/// Numbers the contexts with the `[n]` markers the model cites them by
+pub fn format_context(contexts: &[Context]) -> String {
+    ContextTemplate::default().render(contexts)
+}
+
+impl RagPipeline {
+    fn options(&self) -> SendOptions {
+        SendOptions::builder().timeout(self.stage_timeout).build()
+    }
+
+    async fn retrieve(&self, ctx: &ActorContext<Self>, message: &Ask) -> Result<Vec<Context>, RagPipelineError> {
+        let sources = if message.filters.is_empty() { default_retriever_sources() } else { message.filters.clone() };
+        let retrieve = RetrieveContext::builder()
+            .query(RetrieveQuery::Text(message.question.clone()))
+            .limit(self.limit)
+            .sources(sources)
+            .build();
+        let retrieved = ctx
+            .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve, &self.retriever, self.options())
+            .await
+            .map_err(|e| RagPipelineError::Retrieval(e.to_string()))?;
+        Ok(retrieved.context)
+    }
+
+    /// Reorders the text contexts by their rerank score, keeping the other contexts after them
+    async fn rerank(
+        &self,
+        ctx: &ActorContext<Self>,
+        rerank_id: &ActorId,
+        question: &str,
+        contexts: Vec<Context>,
+    ) -> Result<Vec<Context>, SystemActorError> {
+        let (texts, others): (Vec<_>, Vec<_>) = contexts.into_iter().partition(|context| context.text.is_some());
+        if texts.is_empty() {
+            return Ok(others);
+        }
+
+        let rank_texts = RankTexts::builder()
+            .query(question.to_string())
+            .texts(texts.iter().filter_map(|context| context.text.clone()).collect())
+            .raw_scores(true)
+            .return_text(false)
+            .build();
+        let ranked = ctx.send_and_wait_reply::<Rerank, RankTexts>(rank_texts, rerank_id, self.options()).await?;
+
+        let mut reranked: Vec<Context> = ranked
+            .texts
+            .into_iter()
+            .filter_map(|ranked| {
+                texts.get(ranked.index).map(|context| Context { score: Some(ranked.score), ..context.clone() })
+            })
+            .collect();
+        reranked.extend(others);
+        Ok(reranked)
+    }
+
+    fn record_turn(&mut self, session_id: &str, question: &str, answer: &str) {
+        let turns = self.sessions.entry(session_id.to_string()).or_default();
+        turns.push(ChatMessage::user(question.to_string()));
+        turns.push(ChatMessage::assistant(answer.to_string()));
+        let excess = turns.len().saturating_sub(self.session_turns * 2);
+        turns.drain(..excess);
+    }
+}
+
+impl Message<Ask> for RagPipeline {
+    type Response = Answer;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, message: &Ask) -> Result<(), RagPipelineError> {
+        let start = Instant::now();
+        let mut latencies = StageLatencies::default();
+        let mut warnings = Vec::new();
+
+        // Retrieve the context, answering without it if allowed
+        let stage = Instant::now();
+        let contexts = match self.retrieve(ctx, message).await {
+            Ok(contexts) => contexts,
+            Err(e) if self.answer_without_context => {
+                warn!("{} {}, answering without context", ctx.id(), e);
+                warnings.push(format!("{}, answered without context", e));
+                vec![]
+            }
+            Err(e) => return Err(e),
+        };
+        latencies.retrieve = stage.elapsed();
+
+        // Rerank the context, keeping the retrieval order if it fails
+        let contexts = match &self.rerank {
+            Some(rerank_id) if !contexts.is_empty() => {
+                let stage = Instant::now();
+                let reranked = self.rerank(ctx, rerank_id, &message.question, contexts.clone()).await;
+                latencies.rerank = Some(stage.elapsed());
+                match reranked {
+                    Ok(reranked) => reranked,
+                    Err(e) => {
+                        warn!("{} Rerank failed, keeping the retrieval order: {}", ctx.id(), e);
+                        warnings.push(format!("Rerank failed, kept the retrieval order: {}", e));
+                        contexts
+                    }
+                }
+            }
+            _ => contexts,
+        };
+        let contexts: Vec<Context> = contexts.into_iter().take(self.limit).collect();
+
+        // Assemble the prompt
+        let system = self.prompt_template.replace(CONTEXT_PLACEHOLDER, &self.context_template.render(&contexts));
+        let mut messages = vec![ChatMessage::system(system)];
+        if let Some(turns) = message.session_id.as_ref().and_then(|session_id| self.sessions.get(session_id)) {
+            messages.extend(turns.iter().cloned());
+        }
+        messages.push(ChatMessage::user(message.question.clone()));
+
+        let stage = Instant::now();
+        let chat_messages = ChatMessages::builder().messages(messages).restart(true).build();
+        let response = ctx
+            .send_and_wait_reply::<Chat, ChatMessages>(chat_messages, &self.chat, self.options())
+            .await
+            .map_err(|e| RagPipelineError::Chat(e.to_string()))?;
+        latencies.chat = stage.elapsed();
+
+        let answer = response.response.message.content;
+        if let Some(session_id) = &message.session_id {
+            self.record_turn(session_id, &message.question, &answer);
+        }
+
+        let usage = response.usage;
+        let citations = contexts
+            .into_iter()
+            .enumerate()
+            .map(|(i, context)| Citation {
+                marker: i + 1,
+                source: context.source,
+                score: context.score,
+                text: context.text,
+            })
+            .collect();
+        latencies.total = start.elapsed();
+        info!("{} Answered in {:?}", ctx.id(), latencies);
+
+        ctx.reply(Answer { answer, citations, usage, latencies, warnings }).await?;
+        Ok(())
+    }
+}
+
+impl Actor for RagPipeline {
+    type Error = RagPipelineError;
+
+    async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), RagPipelineError> {
+        info!("{} Started", ctx.id());
+
+        let mut stream = ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(input) = frame.is::<Ask>() {
+                let response = self.reply(ctx, &input, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
+            }
+        }
+
+        info!("{} Finished", ctx.id());
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 // use crate::ORT_EXIT_MUTEX;
 use bioma_actor::prelude::*;
+use bioma_llm::metrics::{self, Histogram};
 use bon::Builder;
 use derive_more::{Deref, Display};
 use lazy_static::lazy_static;
@@ -26,6 +27,10 @@ pub enum RerankError {
     SendRerankRequest(#[from] mpsc::error::SendError<RerankRequest>),
     #[error("Error receiving rerank response: {0}")]
     RecvRerankResponse(#[from] oneshot::error::RecvError),
+    #[error("Retrieval scores mismatch: {0} texts but {1} scores")]
+    RetrievalScoresMismatch(usize, usize),
+    #[error("Rerank query is empty")]
+    EmptyQuery,
 }
 
 impl ActorError for RerankError {}
@@ -69,6 +74,20 @@ pub struct RankTexts {
     #[serde(default = "default_truncation_direction")]
     #[builder(default = default_truncation_direction())]
     pub truncation_direction: TruncationDirection,
+
+    /// The original retrieval score of each text, in the same order as `texts`
+    ///
+    /// When present, each rerank score is blended with its retrieval score and the results are ordered by the
+    /// fused score.
+    #[serde(default)]
+    pub retrieval_scores: Option<Vec<f32>>,
+
+    /// The weight of the retrieval score in the fused score
+    ///
+    /// 0.0 keeps the rerank score only and 1.0 keeps the retrieval score only.
+    #[serde(default = "default_fusion_weight")]
+    #[builder(default = default_fusion_weight())]
+    pub fusion_weight: f32,
 }
 
 #[derive(utoipa::ToResponse, utoipa::ToSchema, Debug, Clone, Serialize, Deserialize)]
@@ -93,10 +112,17 @@ pub fn default_truncation_direction() -> TruncationDirection {
     TruncationDirection::Right
 }
 
+pub fn default_fusion_weight() -> f32 {
+    0.5
+}
+
 #[derive(utoipa::ToSchema, Debug, Clone, Serialize, Deserialize)]
 pub struct RankedText {
     pub index: usize,
     pub score: f32,
+    /// The rerank score blended with the retrieval score, when retrieval scores were given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fused_score: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
 }
@@ -106,24 +132,70 @@ pub struct RankedTexts {
     pub texts: Vec<RankedText>,
 }
 
+impl RankedTexts {
+    /// Blends each rerank score with the retrieval score of the same text and orders the texts by the fused score
+    pub fn fuse(&mut self, retrieval_scores: &[f32], weight: f32) {
+        for text in &mut self.texts {
+            let retrieval_score = retrieval_scores.get(text.index).copied().unwrap_or_default();
+            text.fused_score = Some((1.0 - weight) * text.score + weight * retrieval_score);
+        }
+        self.texts.sort_by(|a, b| b.fused_score.partial_cmp(&a.fused_score).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
 impl Message<RankTexts> for Rerank {
     type Response = RankedTexts;
 
     async fn handle(&mut self, ctx: &mut ActorContext<Self>, rank_texts: &RankTexts) -> Result<(), RerankError> {
+        if rank_texts.query.trim().is_empty() {
+            return Err(RerankError::EmptyQuery);
+        }
+
+        // Nothing to rank, so the backend is not called
         if rank_texts.texts.is_empty() {
             warn!("No texts to rerank");
             ctx.reply(RankedTexts { texts: vec![] }).await?;
             return Ok(());
         }
 
+        if let Some(retrieval_scores) = &rank_texts.retrieval_scores {
+            if retrieval_scores.len() != rank_texts.texts.len() {
+                return Err(RerankError::RetrievalScoresMismatch(rank_texts.texts.len(), retrieval_scores.len()));
+            }
+        }
+
         let Some(rerank_tx) = self.rerank_tx.as_ref() else {
             return Err(RerankError::RerankNotInitialized);
         };
 
+        let start = std::time::Instant::now();
         let (tx, rx) = oneshot::channel();
         rerank_tx.send(RerankRequest { sender: tx, message: rank_texts.clone() }).await?;
 
-        ctx.reply(rx.await??).await?;
+        let mut ranked_texts = rx.await??;
+        RERANK_METRICS.batch_size.observe(rank_texts.texts.len() as f64);
+        RERANK_METRICS.duration.observe_duration(start.elapsed());
+        if let Some(retrieval_scores) = &rank_texts.retrieval_scores {
+            ranked_texts.fuse(retrieval_scores, rank_texts.fusion_weight);
+        }
+
+        ctx.reply(ranked_texts).await?;
+        Ok(())
+    }
+}
+
+impl Message<HealthCheck> for Rerank {
+    type Response = HealthStatus;
+
+    async fn handle(&mut self, ctx: &mut ActorContext<Self>, _message: &HealthCheck) -> Result<(), RerankError> {
+        let start = std::time::Instant::now();
+        let result = self.probe().await;
+        let latency = start.elapsed();
+        let status = match result {
+            Ok(()) => HealthStatus::healthy(latency, format!("Rerank task for {} is alive", self.model)),
+            Err(e) => HealthStatus::unhealthy(latency, format!("Rerank task for {} failed: {}", self.model, e)),
+        };
+        ctx.reply(status).await?;
         Ok(())
     }
 }
@@ -135,6 +207,33 @@ pub struct RerankRequest {
 
 lazy_static! {
     static ref SHARED_RERANK: Arc<Mutex<Weak<SharedRerank>>> = Arc::new(Mutex::new(Weak::new()));
+    static ref RERANK_METRICS: RerankMetrics = RerankMetrics::new();
+}
+
+/// Batch metrics of the reranker
+struct RerankMetrics {
+    batch_size: Histogram,
+    duration: Histogram,
+}
+
+impl RerankMetrics {
+    fn new() -> Self {
+        let registry = metrics::registry();
+        Self {
+            batch_size: registry.histogram(
+                "bioma_rerank_batch_size",
+                "Texts ranked per request",
+                &[],
+                metrics::SIZE_BUCKETS,
+            ),
+            duration: registry.histogram(
+                "bioma_rerank_duration_seconds",
+                "Time to rank a batch of texts",
+                &[],
+                metrics::DURATION_BUCKETS,
+            ),
+        }
+    }
 }
 
 struct SharedRerank {
@@ -196,6 +295,10 @@ pub struct ModelInfo {
 impl Actor for Rerank {
     type Error = RerankError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), RerankError> {
         info!("{} Started", ctx.id());
 
@@ -209,6 +312,11 @@ impl Actor for Rerank {
                 if let Err(err) = response {
                     error!("{} {:?}", ctx.id(), err);
                 }
+            } else if let Some(health_check) = frame.is::<HealthCheck>() {
+                let response = self.reply(ctx, &health_check, &frame).await;
+                if let Err(err) = response {
+                    error!("{} {:?}", ctx.id(), err);
+                }
             }
         }
         info!("{} Finished", ctx.id());
@@ -217,6 +325,19 @@ impl Actor for Rerank {
 }
 
 impl Rerank {
+    /// Ranks a single text to check that the rerank task answers
+    async fn probe(&self) -> Result<(), RerankError> {
+        let Some(rerank_tx) = self.rerank_tx.as_ref() else {
+            return Err(RerankError::RerankNotInitialized);
+        };
+
+        let (tx, rx) = oneshot::channel();
+        let message = RankTexts::builder().query("health".to_string()).texts(vec!["health".to_string()]).build();
+        rerank_tx.send(RerankRequest { sender: tx, message }).await?;
+        rx.await??;
+        Ok(())
+    }
+
     pub async fn init(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), RerankError> {
         info!("{} Started", ctx.id());
 
@@ -278,6 +399,7 @@ impl Rerank {
                                         .map(|result| RankedText {
                                             index: result.index,
                                             score: result.score,
+                                            fused_score: None,
                                             text: match request.message.return_text {
                                                 true => Some(request.message.texts[result.index].clone()),
                                                 false => None,
@@ -1,12 +1,51 @@
-use crate::embeddings::{self, Embeddings, EmbeddingsError};
+use crate::embeddings::{self, Embeddings, EmbeddingsError, Similarity};
 use crate::indexer::{ContentSource, Metadata};
-use crate::rerank::{RankTexts, Rerank, RerankError, TruncationDirection};
+use crate::rerank::{default_fusion_weight, RankTexts, Rerank, RerankError, TruncationDirection};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use bioma_actor::prelude::*;
+use bioma_llm::metrics::{self, Counter, Histogram};
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
 
 const DEFAULT_RETRIEVER_LIMIT: usize = 10;
-const DEFAULT_RETRIEVER_THRESHOLD: f32 = 0.0;
+/// How many more candidates to fetch when contexts are capped per source, to leave room for backfilling
+const PER_SOURCE_OVERFETCH: usize = 4;
+const DEFAULT_QUERY_CACHE_TTL: Duration = Duration::from_secs(300);
+const DEFAULT_QUERY_CACHE_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref RETRIEVER_METRICS: RetrieverMetrics = RetrieverMetrics::new();
+}
+
+/// Query metrics of the retriever
+struct RetrieverMetrics {
+    queries: Counter,
+    search: Histogram,
+    rerank: Histogram,
+}
+
+impl RetrieverMetrics {
+    fn new() -> Self {
+        let registry = metrics::registry();
+        let stage = |name: &str| {
+            registry.histogram(
+                "bioma_retriever_stage_duration_seconds",
+                "Time spent in each retrieval stage",
+                &[("stage", name)],
+                metrics::DURATION_BUCKETS,
+            )
+        };
+        Self {
+            queries: registry.counter("bioma_retriever_queries_total", "Queries retrieved", &[]),
+            search: stage("search"),
+            rerank: stage("rerank"),
+        }
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum RetrieverError {
@@ -30,6 +69,12 @@ pub enum RetrieverError {
     EmbeddingsIdNotFound,
     #[error("Rerank ID not found")]
     RerankIdNotFound,
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Empty query")]
+    EmptyQuery,
+    #[error("Invalid retriever configuration: {0}")]
+    InvalidConfig(String),
 }
 
 impl ActorError for RetrieverError {}
@@ -43,28 +88,84 @@ pub struct RetrieveContext {
     #[builder(default = default_retriever_limit())]
     #[serde(default = "default_retriever_limit")]
     pub limit: usize,
-    /// The threshold for the similarity score
-    #[builder(default = default_retriever_threshold())]
-    #[serde(default = "default_retriever_threshold")]
-    pub threshold: f32,
+    /// The minimum similarity score of the contexts, none are filtered out when unset
+    #[serde(default)]
+    pub threshold: Option<f32>,
     /// A list of sources to filter the search
     #[serde(default = "default_retriever_sources")]
     #[builder(default)]
     pub sources: Vec<String>,
+    /// The minimum number of contexts to return, relaxing the threshold if fewer pass it
+    #[serde(default)]
+    pub min_results: Option<usize>,
+    /// Return a cursor to fetch the next page, pinned to the current state of the store
+    #[serde(default)]
+    #[builder(default)]
+    pub paginate: bool,
+    /// The cursor returned by a previous page of the same query
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// How an empty or whitespace-only query is handled
+    #[serde(default)]
+    #[builder(default)]
+    pub empty_query: EmptyQueryPolicy,
+    /// The maximum number of contexts from the same source document, backfilling with other sources
+    ///
+    /// When paginating, the cap holds across all the pages of the query.
+    #[serde(default)]
+    pub max_per_source: Option<usize>,
+    /// Merge retrieved chunks that follow each other in the same document into one passage, without their overlap
+    #[serde(default)]
+    #[builder(default)]
+    pub merge_adjacent: bool,
+}
+
+/// Handling of a query text that is empty or only whitespace
+#[derive(utoipa::ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmptyQueryPolicy {
+    /// Fail with `RetrieverError::EmptyQuery`
+    #[default]
+    Error,
+
+    /// Return no contexts
+    EmptyResult,
 }
 
 #[derive(utoipa::ToSchema, Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "query")]
 pub enum RetrieveQuery {
     Text(String),
+    /// A precomputed embedding, searched as is without calling the embedding model.
+    ///
+    /// Its dimension must match the embedding model. The contexts are ordered by similarity, without reranking.
+    Vector(Vec<f32>),
 }
 
-pub fn default_retriever_limit() -> usize {
-    DEFAULT_RETRIEVER_LIMIT
+impl RetrieveQuery {
+    /// Identifies the query in pagination cursors
+    fn cursor_key(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Vector(vector) => {
+                let mut hasher = DefaultHasher::new();
+                vector.iter().for_each(|value| value.to_bits().hash(&mut hasher));
+                format!("vector:{:016x}", hasher.finish())
+            }
+        }
+    }
 }
 
-pub fn default_retriever_threshold() -> f32 {
-    DEFAULT_RETRIEVER_THRESHOLD
+impl std::fmt::Display for RetrieveQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "{}", text),
+            Self::Vector(vector) => write!(f, "<vector of {} dimensions>", vector.len()),
+        }
+    }
+}
+
+pub fn default_retriever_limit() -> usize {
+    DEFAULT_RETRIEVER_LIMIT
 }
 
 pub fn default_retriever_sources() -> Vec<String> {
@@ -76,11 +177,273 @@ pub struct Context {
     pub text: Option<String>,
     pub source: Option<ContentSource>,
     pub metadata: Option<Metadata>,
+    /// Whether this context scored below the requested threshold and was only included to reach `min_results`
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub below_threshold: bool,
+    /// The rerank score of a text, or the similarity of an image, used to order the contexts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Keeps the similarities that pass the threshold, relaxing it to fill up to `min_results`.
+///
+/// Returns each similarity paired with a flag telling whether it fell below the original threshold. Without a
+/// threshold every similarity passes.
+fn relax_threshold(
+    similarities: Vec<Similarity>,
+    threshold: Option<f32>,
+    min_results: Option<usize>,
+) -> Vec<(Similarity, bool)> {
+    let (passing, mut below): (Vec<_>, Vec<_>) =
+        similarities.into_iter().partition(|s| threshold.is_none_or(|threshold| s.similarity >= threshold));
+    let missing = min_results.unwrap_or(0).saturating_sub(passing.len());
+
+    // Lower the threshold progressively by taking the best scoring similarities first
+    below.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+
+    passing.into_iter().map(|s| (s, false)).chain(below.into_iter().take(missing).map(|s| (s, true))).collect()
+}
+
+/// The id of a similarity's embedding, breaking ties between equal scores
+fn similarity_id(similarity: &Similarity) -> String {
+    similarity.id.as_ref().map(|id| id.to_string()).unwrap_or_default()
+}
+
+/// A context scored by its similarity to the query, along with its embedding id
+fn similarity_context((similarity, below_threshold): (Similarity, bool)) -> (Context, f32, String) {
+    let score = similarity.similarity;
+    let id = similarity_id(&similarity);
+    let context = Context {
+        text: similarity.text,
+        source: similarity.source,
+        metadata: similarity.metadata.and_then(|m| serde_json::from_value(m).ok()),
+        below_threshold,
+        score: Some(score),
+    };
+    (context, score, id)
+}
+
+/// Keeps at most `max_per_source` contexts from each source document, in score order.
+///
+/// `counts` holds the contexts already returned from each source by previous pages. Contexts dropped from a source
+/// leave room for the next best contexts from other sources. Contexts without a source are never dropped.
+fn cap_per_source(
+    contexts: Vec<(Context, f32, String)>,
+    max_per_source: usize,
+    counts: &HashMap<(String, String), usize>,
+) -> Vec<(Context, f32, String)> {
+    let mut counts = counts.clone();
+    contexts
+        .into_iter()
+        .filter(|(context, _, _)| match &context.source {
+            Some(source) => {
+                let count = counts.entry((source.source.clone(), source.uri.clone())).or_default();
+                *count += 1;
+                *count <= max_per_source
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Document and number of a text chunk
+fn chunk_key(context: &Context) -> Option<(&str, &str, usize)> {
+    match (&context.source, &context.metadata) {
+        (Some(source), Some(Metadata::Text(metadata))) => {
+            Some((source.source.as_str(), source.uri.as_str(), metadata.chunk_number))
+        }
+        _ => None,
+    }
+}
+
+/// Merges runs of consecutive chunks from the same document into one context, in chunk order.
+///
+/// A merged context takes the place and the best score of its chunks. Contexts other than text chunks are kept as is.
+fn merge_adjacent_chunks(contexts: Vec<Context>) -> Vec<Context> {
+    let mut order: Vec<usize> = (0..contexts.len()).collect();
+    order.sort_by(|&a, &b| chunk_key(&contexts[a]).cmp(&chunk_key(&contexts[b])).then(a.cmp(&b)));
+
+    // Group the ranks of chunks following each other
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    for index in order {
+        let follows = match (runs.last().and_then(|run| run.last()), chunk_key(&contexts[index])) {
+            (Some(&last), Some((source, uri, number))) => {
+                chunk_key(&contexts[last]).is_some_and(|(last_source, last_uri, last_number)| {
+                    (last_source, last_uri, last_number + 1) == (source, uri, number)
+                })
+            }
+            _ => false,
+        };
+        match runs.last_mut() {
+            Some(run) if follows => run.push(index),
+            _ => runs.push(vec![index]),
+        }
+    }
+    runs.sort_by_key(|run| run.iter().min().copied());
+
+    let mut contexts: Vec<Option<Context>> = contexts.into_iter().map(Some).collect();
+    runs.into_iter()
+        .filter_map(|run| run.into_iter().filter_map(|index| contexts[index].take()).reduce(merge_chunks))
+        .collect()
+}
+
+/// Appends `next` to the chunk before it, dropping the start of `next` that repeats the end of `context`
+fn merge_chunks(mut context: Context, next: Context) -> Context {
+    let (location, next_location) = match (&mut context.metadata, &next.metadata) {
+        (Some(Metadata::Text(metadata)), Some(Metadata::Text(next_metadata))) => {
+            (metadata.location.as_mut(), next_metadata.location.as_ref())
+        }
+        _ => (None, None),
+    };
+
+    // The overlap is known from the chunk offsets in their file, or found by comparing the texts
+    let overlap = match (&location, next_location) {
+        (Some(location), Some(next_location)) => {
+            Some(location.byte_range.end.saturating_sub(next_location.byte_range.start))
+        }
+        _ => None,
+    };
+    if let (Some(location), Some(next_location)) = (location, next_location) {
+        location.byte_range.end = next_location.byte_range.end;
+        location.end_line = next_location.end_line;
+    }
+
+    context.text = match (context.text, next.text) {
+        (Some(text), Some(next_text)) => {
+            let overlap = overlap
+                .filter(|&overlap| next_text.is_char_boundary(overlap))
+                .unwrap_or_else(|| text_overlap(&text, &next_text));
+            Some(text + &next_text[overlap..])
+        }
+        (text, next_text) => text.or(next_text),
+    };
+    context.below_threshold &= next.below_threshold;
+    context.score = match (context.score, next.score) {
+        (Some(score), Some(next_score)) => Some(score.max(next_score)),
+        (score, next_score) => score.or(next_score),
+    };
+    context
+}
+
+/// Length of the longest start of `next` that `text` ends with
+fn text_overlap(text: &str, next: &str) -> usize {
+    (1..=text.len().min(next.len()))
+        .rev()
+        .find(|&length| next.is_char_boundary(length) && text.ends_with(&next[..length]))
+        .unwrap_or(0)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrievedContext {
     pub context: Vec<Context>,
+    /// Cursor for the next page, when paginating and more contexts may follow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Whether the contexts were served from the query cache
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cached: bool,
+    /// Whether nothing is indexed yet, as opposed to no context matching the query
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub empty_store: bool,
+}
+
+/// Caching of the contexts retrieved for repeated identical queries.
+///
+/// Entries expire after `ttl`, and as soon as the index changes: indexing or deleting sources bumps the index
+/// version, which is stamped on every entry.
+#[derive(bon::Builder, Debug, Clone, Serialize, Deserialize)]
+pub struct QueryCacheConfig {
+    #[builder(default = DEFAULT_QUERY_CACHE_TTL)]
+    #[serde(default = "default_query_cache_ttl")]
+    pub ttl: Duration,
+    /// Maximum number of cached queries, the oldest is evicted first
+    #[builder(default = DEFAULT_QUERY_CACHE_CAPACITY)]
+    #[serde(default = "default_query_cache_capacity")]
+    pub capacity: usize,
+}
+
+fn default_query_cache_ttl() -> Duration {
+    DEFAULT_QUERY_CACHE_TTL
+}
+
+fn default_query_cache_capacity() -> usize {
+    DEFAULT_QUERY_CACHE_CAPACITY
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug)]
+struct CachedQuery {
+    version: u64,
+    cached_at: Instant,
+    retrieved: RetrievedContext,
+}
+
+/// Where a query's contexts are cached, and the index version they are valid for
+#[derive(Debug, Clone, Copy)]
+struct CacheKey {
+    hash: u64,
+    version: u64,
+}
+
+/// Position of a paginated retrieval, encoded as an opaque token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RetrieveCursor {
+    /// The query the cursor was created for
+    query: String,
+    /// Upper bound of the embedding ids visible to every page
+    snapshot: String,
+    /// Score and embedding id of the last context returned, contexts are ordered by score then id
+    after: Option<(f32, String)>,
+    /// Number of contexts returned so far
+    returned: usize,
+    /// Contexts returned so far from each source document, so `max_per_source` holds across pages
+    #[serde(default)]
+    per_source: Vec<((String, String), usize)>,
+}
+
+impl RetrieveCursor {
+    fn new(query: &str) -> Self {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        Self {
+            query: query.to_string(),
+            snapshot: ulid_upper_bound(now.as_millis() as u64),
+            after: None,
+            returned: 0,
+            per_source: Vec::new(),
+        }
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(token: &str, query: &str) -> Result<Self, RetrieverError> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|e| RetrieverError::InvalidCursor(e.to_string()))?;
+        let cursor: Self = serde_json::from_slice(&bytes).map_err(|e| RetrieverError::InvalidCursor(e.to_string()))?;
+        if cursor.query != query {
+            return Err(RetrieverError::InvalidCursor("cursor belongs to a different query".to_string()));
+        }
+        Ok(cursor)
+    }
+}
+
+/// Returns the greatest ULID that can be generated up to the given time in milliseconds.
+///
+/// Embedding ids are time ordered ULIDs, so comparing against this bound hides embeddings stored afterwards.
+fn ulid_upper_bound(millis: u64) -> String {
+    const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+    let mut ulid: String = (0..10).rev().map(|i| CROCKFORD[((millis >> (i * 5)) & 0x1f) as usize] as char).collect();
+    ulid.push_str(&"Z".repeat(16));
+    ulid
 }
 
 impl RetrievedContext {
@@ -134,110 +497,199 @@ impl Message<RetrieveContext> for Retriever {
             return Err(RetrieverError::RerankIdNotFound);
         };
 
+        // Reject empty queries before they reach the embedding model, and vectors the store cannot compare against
         match &message.query {
-            RetrieveQuery::Text(text) => {
-                info!("Fetching context for query: {}", text);
-                let embeddings_req = embeddings::TopK {
-                    query: embeddings::Query::Text(text.clone()),
-                    k: message.limit * 2,
-                    threshold: message.threshold,
-                    sources: message.sources.clone(),
+            RetrieveQuery::Text(text) if text.trim().is_empty() => {
+                return match message.empty_query {
+                    EmptyQueryPolicy::Error => Err(RetrieverError::EmptyQuery),
+                    EmptyQueryPolicy::EmptyResult => {
+                        let retrieved =
+                            RetrievedContext { context: vec![], next_cursor: None, cached: false, empty_store: false };
+                        ctx.reply(retrieved).await?;
+                        Ok(())
+                    }
                 };
+            }
+            RetrieveQuery::Text(_) => {}
+            RetrieveQuery::Vector(vector) => self.embeddings.check_dimension(vector)?,
+        }
+
+        // Serve repeated queries from the cache while the index is unchanged
+        let cache_key = self.cache_key(ctx, message).await?;
+        if let Some(retrieved) = cache_key.and_then(|key| self.cached(key)) {
+            debug!("Serving cached context for query: {}", message.query);
+            ctx.reply(retrieved).await?;
+            return Ok(());
+        }
 
-                info!("Searching for similarities");
+        info!("Fetching context for query: {}", message.query);
+        let limit = message.limit.max(message.min_results.unwrap_or(0));
+
+        // Resume from the cursor, or start a new pagination pinned to the current store
+        let cursor = match &message.cursor {
+            Some(token) => Some(RetrieveCursor::decode(token, &message.query.cursor_key())?),
+            None if message.paginate => Some(RetrieveCursor::new(&message.query.cursor_key())),
+            None => None,
+        };
+        let returned = cursor.as_ref().map_or(0, |cursor| cursor.returned);
+
+        // Fetch extra candidates when capping per source, so other sources can backfill
+        let overfetch = if message.max_per_source.is_some() { PER_SOURCE_OVERFETCH } else { 1 };
+        let embeddings_req = embeddings::TopK {
+            query: match &message.query {
+                RetrieveQuery::Text(text) => embeddings::Query::Text(text.clone()),
+                RetrieveQuery::Vector(vector) => embeddings::Query::Embedding(vector.clone()),
+            },
+            k: (returned + limit) * 2 * overfetch,
+            threshold: message.threshold.unwrap_or(-1.0),
+            sources: message.sources.clone(),
+            snapshot: cursor.as_ref().map(|cursor| cursor.snapshot.clone()),
+        };
+
+        info!("Searching for similarities");
+        let start = std::time::Instant::now();
+        let similarities = match ctx
+            .send_and_wait_reply::<Embeddings, embeddings::TopK>(
+                embeddings_req,
+                embeddings_id,
+                SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Failed to get similarities: {}", e);
+                return Err(RetrieverError::ComputingSimilarity(e.to_string()));
+            }
+        };
+        info!("Similarities: {} in {:?}", similarities.len(), start.elapsed());
+        RETRIEVER_METRICS.queries.inc();
+        RETRIEVER_METRICS.search.observe_duration(start.elapsed());
+
+        // Tell a store with nothing indexed yet apart from a query matching nothing
+        let empty_store = similarities.is_empty() && !self.has_embeddings(ctx).await?;
+        if empty_store {
+            info!("No embeddings stored, nothing to retrieve");
+        }
+
+        // Apply the threshold if requested, relaxing it if fewer than `min_results` pass
+        let similarities = relax_threshold(similarities, message.threshold, message.min_results);
+
+        // Separate text and image content based on ContentType
+        let (text_similarities, image_similarities): (Vec<_>, Vec<_>) = similarities.into_iter().partition(|(s, _)| {
+            s.metadata
+                .as_ref()
+                .and_then(|m| serde_json::from_value::<Metadata>(m.clone()).ok())
+                .is_some_and(|metadata| matches!(metadata, Metadata::Text(_)))
+        });
+
+        // Process text content with reranking
+        let mut ranked_contexts = match &message.query {
+            RetrieveQuery::Text(_) if text_similarities.is_empty() => Vec::new(),
+            RetrieveQuery::Text(text) => {
+                let texts: Vec<String> = text_similarities.iter().filter_map(|(s, _)| s.text.clone()).collect();
+
+                let rerank_req = RankTexts {
+                    query: text.clone(),
+                    texts,
+                    raw_scores: true,
+                    return_text: false,
+                    truncate: true,
+                    truncation_direction: TruncationDirection::Right,
+                    retrieval_scores: None,
+                    fusion_weight: default_fusion_weight(),
+                };
                 let start = std::time::Instant::now();
-                let similarities = match ctx
-                    .send_and_wait_reply::<Embeddings, embeddings::TopK>(
-                        embeddings_req,
-                        embeddings_id,
+                let ranked_texts = ctx
+                    .send_and_wait_reply::<Rerank, RankTexts>(
+                        rerank_req,
+                        rerank_id,
                         SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
                     )
-                    .await
-                {
-                    Ok(result) => result,
-                    Err(e) => {
-                        error!("Failed to get similarities: {}", e);
-                        return Err(RetrieverError::ComputingSimilarity(e.to_string()));
-                    }
-                };
-                info!("Similarities: {} in {:?}", similarities.len(), start.elapsed());
-
-                // Separate text and image content based on ContentType
-                let (text_similarities, image_similarities): (Vec<_>, Vec<_>) =
-                    similarities.into_iter().map(|s| (s.clone(), s.similarity)).partition(|(s, _)| {
-                        s.metadata
-                            .as_ref()
-                            .and_then(|m| serde_json::from_value::<Metadata>(m.clone()).ok())
-                            .is_some_and(|metadata| matches!(metadata, Metadata::Text(_)))
-                    });
-
-                // Process text content with reranking
-                let mut ranked_contexts = if !text_similarities.is_empty() {
-                    let texts: Vec<String> = text_similarities.iter().filter_map(|(s, _)| s.text.clone()).collect();
-
-                    let rerank_req = RankTexts {
-                        query: text.clone(),
-                        texts,
-                        raw_scores: true,
-                        return_text: false,
-                        truncate: true,
-                        truncation_direction: TruncationDirection::Right,
-                    };
-                    let ranked_texts = ctx
-                        .send_and_wait_reply::<Rerank, RankTexts>(
-                            rerank_req,
-                            rerank_id,
-                            SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+                    .await?;
+                RETRIEVER_METRICS.rerank.observe_duration(start.elapsed());
+
+                // Create contexts with rerank scores
+                ranked_texts
+                    .texts
+                    .into_iter()
+                    .map(|t| {
+                        (
+                            Context {
+                                text: text_similarities[t.index].0.text.clone(),
+                                source: text_similarities[t.index].0.source.clone(),
+                                metadata: text_similarities[t.index]
+                                    .0
+                                    .metadata
+                                    .as_ref()
+                                    .and_then(|m| serde_json::from_value(m.clone()).ok()),
+                                below_threshold: text_similarities[t.index].1,
+                                score: Some(t.score),
+                            },
+                            t.score,
+                            similarity_id(&text_similarities[t.index].0),
                         )
-                        .await?;
-
-                    // Create contexts with rerank scores
-                    ranked_texts
-                        .texts
-                        .into_iter()
-                        .map(|t| {
-                            (
-                                Context {
-                                    text: text_similarities[t.index].0.text.clone(),
-                                    source: text_similarities[t.index].0.source.clone(),
-                                    metadata: text_similarities[t.index]
-                                        .0
-                                        .metadata
-                                        .as_ref()
-                                        .and_then(|m| serde_json::from_value(m.clone()).ok()),
-                                },
-                                t.score,
-                            )
-                        })
-                        .collect::<Vec<_>>()
-                } else {
-                    Vec::new()
-                };
+                    })
+                    .collect::<Vec<_>>()
+            }
+            // There is no query text to rerank against, so texts keep their similarity scores
+            RetrieveQuery::Vector(_) => text_similarities.into_iter().map(similarity_context).collect(),
+        };
 
-                // Add image contexts with their similarity scores
-                ranked_contexts.extend(image_similarities.into_iter().map(|(s, score)| {
-                    (
-                        Context {
-                            text: s.text,
-                            source: s.source.clone(),
-                            metadata: s.metadata.and_then(|m| serde_json::from_value(m).ok()),
-                        },
-                        score,
-                    )
-                }));
+        // Add image contexts with their similarity scores
+        ranked_contexts.extend(image_similarities.into_iter().map(similarity_context));
 
-                // Sort all contexts by score in descending order
-                ranked_contexts.sort_by(|(_, a_score), (_, b_score)| {
-                    b_score.partial_cmp(a_score).unwrap_or(std::cmp::Ordering::Equal)
-                });
+        // Sort all contexts by score in descending order, then by embedding id so that pages split ties consistently
+        ranked_contexts.sort_by(|(_, a_score, a_id), (_, b_score, b_id)| {
+            b_score.partial_cmp(a_score).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a_id.cmp(b_id))
+        });
 
-                // Take only the contexts, limited by the requested amount
-                let contexts = ranked_contexts.into_iter().map(|(context, _)| context).take(message.limit).collect();
+        // Skip contexts already returned by previous pages
+        if let Some((last_score, last_id)) = cursor.as_ref().and_then(|cursor| cursor.after.as_ref()) {
+            ranked_contexts.retain(|(_, score, id)| *score < *last_score || (*score == *last_score && id > last_id));
+        }
+
+        // Cap the contexts from each source document, counting those returned by previous pages
+        let mut per_source: HashMap<(String, String), usize> =
+            cursor.as_ref().map(|cursor| cursor.per_source.iter().cloned().collect()).unwrap_or_default();
+        if let Some(max_per_source) = message.max_per_source {
+            ranked_contexts = cap_per_source(ranked_contexts, max_per_source, &per_source);
+        }
 
-                ctx.reply(RetrievedContext { context: contexts }).await?;
-                Ok(())
+        // Take only the contexts, limited by the requested amount
+        let ranked_contexts: Vec<_> = ranked_contexts.into_iter().take(limit).collect();
+
+        // A full page means more contexts may follow
+        let next_cursor = match cursor {
+            Some(cursor) if ranked_contexts.len() == limit => {
+                for source in ranked_contexts.iter().filter_map(|(context, _, _)| context.source.as_ref()) {
+                    *per_source.entry((source.source.clone(), source.uri.clone())).or_default() += 1;
+                }
+                Some(
+                    RetrieveCursor {
+                        after: ranked_contexts.last().map(|(_, score, id)| (*score, id.clone())),
+                        returned: cursor.returned + ranked_contexts.len(),
+                        per_source: per_source.into_iter().collect(),
+                        ..cursor
+                    }
+                    .encode(),
+                )
             }
+            _ => None,
+        };
+
+        let mut contexts: Vec<_> = ranked_contexts.into_iter().map(|(context, _, _)| context).collect();
+        if message.merge_adjacent {
+            contexts = merge_adjacent_chunks(contexts);
+        }
+
+        let retrieved = RetrievedContext { context: contexts, next_cursor, cached: false, empty_store };
+        if let Some(key) = cache_key {
+            self.cache(key, &retrieved);
         }
+
+        ctx.reply(retrieved).await?;
+        Ok(())
     }
 }
 
@@ -300,19 +752,39 @@ impl Message<ListUniqueSources> for Retriever {
 }
 
 #[derive(bon::Builder, Debug, Serialize, Deserialize, Default)]
+#[builder(finish_fn(name = build_unchecked, vis = ""))]
 pub struct Retriever {
     #[builder(default)]
     pub embeddings: Embeddings,
     #[builder(default)]
     pub rerank: Rerank,
+    /// Caches the contexts of repeated identical queries, disabled if not set
+    #[serde(default)]
+    pub query_cache: Option<QueryCacheConfig>,
     embeddings_id: Option<ActorId>,
     rerank_id: Option<ActorId>,
+    #[builder(skip)]
+    #[serde(skip)]
+    cached_queries: HashMap<u64, CachedQuery>,
     #[serde(skip)]
     embeddings_handle: Option<tokio::task::JoinHandle<()>>,
     #[serde(skip)]
     rerank_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
+impl<S: retriever_builder::IsComplete> RetrieverBuilder<S> {
+    /// Builds the retriever actor, failing if the configuration is invalid
+    pub fn build(self) -> Result<Retriever, RetrieverError> {
+        let retriever = self.build_unchecked();
+        if let Some(query_cache) = &retriever.query_cache {
+            if query_cache.capacity == 0 || query_cache.ttl.is_zero() {
+                return Err(RetrieverError::InvalidConfig("query cache needs a capacity and a TTL".to_string()));
+            }
+        }
+        Ok(retriever)
+    }
+}
+
 impl Actor for Retriever {
     type Error = RetrieverError;
 
@@ -391,4 +863,72 @@ impl Retriever {
 
         Ok(())
     }
+
+    /// The cache key of a query, if caching is enabled and the query can be cached.
+    ///
+    /// Paginated queries are not cached, since each page depends on the previous ones.
+    async fn cache_key(
+        &self,
+        ctx: &ActorContext<Self>,
+        message: &RetrieveContext,
+    ) -> Result<Option<CacheKey>, RetrieverError> {
+        if self.query_cache.is_none() || message.paginate || message.cursor.is_some() {
+            return Ok(None);
+        }
+
+        let query = include_str!("../sql/index_version.surql");
+        let mut results = ctx.engine().db().lock().await.query(query).await.map_err(SystemActorError::from)?;
+        let version: Option<u64> = results.take(0).map_err(SystemActorError::from)?;
+
+        // Every parameter affects the contexts, so the whole message is hashed
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(message).unwrap_or_default().hash(&mut hasher);
+        Ok(Some(CacheKey { hash: hasher.finish(), version: version.unwrap_or(0) }))
+    }
+
+    /// Whether any embedding is stored by the embeddings actor
+    async fn has_embeddings(&self, ctx: &ActorContext<Self>) -> Result<bool, RetrieverError> {
+        let query = include_str!("../sql/has_embeddings.surql");
+        let mut results = ctx
+            .engine()
+            .db()
+            .lock()
+            .await
+            .query(query)
+            .bind(("prefix", self.embeddings.table_prefix()))
+            .await
+            .map_err(SystemActorError::from)?;
+        let has_embeddings: Option<bool> = results.take(0).map_err(SystemActorError::from)?;
+        Ok(has_embeddings.unwrap_or(false))
+    }
+
+    /// The cached contexts of a query, unless they expired or the index changed since
+    fn cached(&mut self, key: CacheKey) -> Option<RetrievedContext> {
+        let ttl = self.query_cache.as_ref()?.ttl;
+        let entry = self.cached_queries.get(&key.hash)?;
+        if entry.version != key.version || entry.cached_at.elapsed() > ttl {
+            self.cached_queries.remove(&key.hash);
+            return None;
+        }
+        Some(RetrievedContext { cached: true, ..entry.retrieved.clone() })
+    }
+
+    fn cache(&mut self, key: CacheKey, retrieved: &RetrievedContext) {
+        let Some(config) = &self.query_cache else {
+            return;
+        };
+
+        // Make room by dropping stale entries first, then the oldest ones
+        self.cached_queries.retain(|_, entry| entry.version == key.version && entry.cached_at.elapsed() <= config.ttl);
+        while self.cached_queries.len() >= config.capacity.max(1) {
+            let oldest = self.cached_queries.iter().min_by_key(|(_, entry)| entry.cached_at).map(|(hash, _)| *hash);
+            let Some(oldest) = oldest else {
+                break;
+            };
+            self.cached_queries.remove(&oldest);
+        }
+
+        let entry = CachedQuery { version: key.version, cached_at: Instant::now(), retrieved: retrieved.clone() };
+        self.cached_queries.insert(key.hash, entry);
+    }
 }
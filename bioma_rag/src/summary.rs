@@ -115,7 +115,7 @@ fn default_max_text_length() -> usize {
 impl Default for Summary {
     fn default() -> Self {
         Self {
-            chat: Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build(),
+            chat: Chat::default(),
             text_prompt: default_text_prompt(),
             image_prompt: default_image_prompt(),
             max_text_length: default_max_text_length(),
@@ -141,6 +141,10 @@ impl Clone for Summary {
 impl Actor for Summary {
     type Error = SummaryError;
 
+    fn serves_while_draining() -> bool {
+        true
+    }
+
     async fn start(&mut self, ctx: &mut ActorContext<Self>) -> Result<(), Self::Error> {
         self.init(ctx).await?;
 
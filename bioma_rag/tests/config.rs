@@ -0,0 +1,83 @@
+use bioma_llm::prelude::Chat;
+use bioma_rag::prelude::*;
+use serde_json::json;
+
+#[test]
+fn test_config_from_file_with_env_overrides() -> Result<(), ConfigError> {
+    // The only test in this binary touching these variables, so setting them cannot race with another test
+    std::env::set_var("BIOMA__CHAT__MODEL", "qwen2.5:7b");
+    std::env::set_var("BIOMA__CHAT__MESSAGES_NUMBER_LIMIT", "20");
+    std::env::set_var("BIOMA__PDF_ANALYZER__URL", "http://pdf.internal:5060");
+
+    let config = BiomaConfig::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/configs/bioma_config.toml"));
+
+    std::env::remove_var("BIOMA__CHAT__MODEL");
+    std::env::remove_var("BIOMA__CHAT__MESSAGES_NUMBER_LIMIT");
+    std::env::remove_var("BIOMA__PDF_ANALYZER__URL");
+    let config = config?;
+
+    // Values from the file, overridden by the environment, with defaults for the rest
+    let chat = config.chat()?;
+    assert_eq!(chat.model, "qwen2.5:7b");
+    assert_eq!(chat.messages_number_limit, 20);
+    assert_eq!(chat.max_context_length, 8192);
+    assert_eq!(chat.max_concurrent_requests, Chat::default().max_concurrent_requests);
+
+    // The shared Ollama endpoint is used by the chat unless it sets its own
+    assert_eq!(chat.endpoint.as_str(), "http://ollama.internal:11434/");
+
+    let indexer = config.indexer()?;
+    assert_eq!(indexer.embedding_concurrency, Some(2));
+    assert_eq!(indexer.embeddings.table_name_prefix.as_deref(), Some("fixture"));
+    assert_eq!(indexer.markitdown.markitdown_url.as_str(), "http://markitdown.internal:5001/");
+    assert_eq!(indexer.pdf_analyzer.pdf_analyzer_url.as_str(), "http://pdf.internal:5060/");
+    assert_eq!(indexer.summary.chat.endpoint.as_str(), "http://ollama.internal:11434/");
+
+    Ok(())
+}
+
+#[test]
+fn test_config_invalid_key() {
+    // Errors point at the offending key, whether it comes from the document or from an override
+    let error = BiomaConfig::from_value(json!({"chat": {"max_context_length": "large"}}), vec![]).unwrap_err();
+    assert!(error.to_string().contains("`chat.max_context_length`"), "Unexpected error: {}", error);
+
+    let overrides = vec![("BIOMA__RERANK__MODEL".to_string(), "Unknown".to_string())];
+    let error = BiomaConfig::from_value(json!({}), overrides).unwrap_err();
+    assert!(error.to_string().contains("`rerank.model`"), "Unexpected error: {}", error);
+
+    let error = BiomaConfig::from_value(json!({"chat": {"modle": "llama3.2:3b"}}), vec![]).unwrap_err();
+    assert!(error.to_string().contains("`chat`"), "Unexpected error: {}", error);
+
+    // Values the actors would reject are reported at their own key, not their section
+    let error = BiomaConfig::from_value(json!({"chat": {"model": " "}}), vec![]).unwrap_err();
+    assert!(error.to_string().contains("`chat.model`"), "Unexpected error: {}", error);
+
+    let overrides = vec![("BIOMA__OLLAMA__ENDPOINT".to_string(), "file:///tmp/ollama".to_string())];
+    let error = BiomaConfig::from_value(json!({}), overrides).unwrap_err();
+    assert!(error.to_string().contains("`ollama.endpoint`"), "Unexpected error: {}", error);
+
+    let mut config = BiomaConfig::default();
+    config.indexer.embedding_concurrency = Some(0);
+    let error = config.indexer().unwrap_err();
+    assert!(error.to_string().contains("`indexer.embedding_concurrency`"), "Unexpected error: {}", error);
+}
+
+#[test]
+fn test_config_retriever() -> Result<(), ConfigError> {
+    // The query cache is disabled unless its section is set
+    let retriever = BiomaConfig::from_value(json!({}), vec![])?.retriever()?;
+    assert!(retriever.query_cache.is_none());
+
+    let overrides = vec![("BIOMA__RETRIEVER__QUERY_CACHE__CAPACITY".to_string(), "16".to_string())];
+    let retriever =
+        BiomaConfig::from_value(json!({"retriever": {"query_cache": {"ttl_secs": 60}}}), overrides)?.retriever()?;
+    let query_cache = retriever.query_cache.expect("query cache is configured");
+    assert_eq!(query_cache.ttl, std::time::Duration::from_secs(60));
+    assert_eq!(query_cache.capacity, 16);
+
+    let error = BiomaConfig::from_value(json!({"retriever": {"query_cache": {"capacity": 0}}}), vec![]).unwrap_err();
+    assert!(error.to_string().contains("`retriever.query_cache.capacity`"), "Unexpected error: {}", error);
+
+    Ok(())
+}
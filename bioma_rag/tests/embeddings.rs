@@ -1,7 +1,7 @@
 use base64::Engine as Base64Engine;
 use bioma_actor::prelude::*;
 use bioma_rag::{
-    embeddings::{ImageModel, Model},
+    embeddings::{EmbeddingRequestContent, ImageModel, Model},
     prelude::*,
 };
 use test_log::test;
@@ -48,7 +48,11 @@ async fn test_embeddings_generate_nomic_v15() -> Result<(), TestError> {
     // Generate embeddings for the Nomic v1.5 embeddings actor
     let nomic_embeddings = nomic_relay_ctx
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-            GenerateEmbeddings { content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()) },
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+                input: InputKind::Passage,
+                normalize: false,
+            },
             &embeddings_nomic_id,
             SendOptions::default(),
         )
@@ -72,6 +76,189 @@ async fn test_embeddings_generate_nomic_v15() -> Result<(), TestError> {
     Ok(())
 }
 
+#[test]
+fn test_embeddings_builder() -> Result<(), TestError> {
+    let embeddings = Embeddings::builder().build()?;
+    assert_eq!(embeddings.model, Model::NomicEmbedTextV15);
+    assert!(matches!(embeddings.image_model, ImageModel::NomicEmbedVisionV15));
+    assert_eq!(embeddings.table_prefix(), Model::NomicEmbedTextV15.to_string());
+
+    let embeddings =
+        Embeddings::builder().model(Model::ClipVitB32Text).table_name_prefix("clip_32".to_string()).build()?;
+    assert_eq!(embeddings.model, Model::ClipVitB32Text);
+    assert_eq!(embeddings.table_prefix(), "clip_32");
+
+    let result = Embeddings::builder().table_name_prefix("clip-32;".to_string()).build();
+    assert!(matches!(result, Err(EmbeddingsError::InvalidConfig(_))));
+
+    assert_eq!(Embeddings::builder().build()?.workers, 1);
+    assert!(matches!(Embeddings::builder().workers(0).build(), Err(EmbeddingsError::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_embeddings_max_sim() {
+    // Stub token vectors: the query asks about "capital" and "france"
+    let capital = vec![1.0, 0.0, 0.0, 0.0];
+    let france = vec![0.0, 1.0, 0.0, 0.0];
+    let paris = vec![0.6, 0.8, 0.0, 0.0];
+    let rust = vec![0.0, 0.0, 1.0, 0.0];
+    let language = vec![0.0, 0.0, 0.6, 0.8];
+    let query = vec![capital.clone(), france.clone()];
+
+    let matching = vec![paris, capital, france];
+    let unrelated = vec![rust, language];
+    let matching_score = embeddings::max_sim(&query, &matching);
+    let unrelated_score = embeddings::max_sim(&query, &unrelated);
+    assert!((matching_score - 2.0).abs() < 1e-6, "Each query token should find its exact match: {}", matching_score);
+    assert!(matching_score > unrelated_score);
+    assert_eq!(embeddings::max_sim(&query, &[]), 0.0);
+
+    // Token vectors are compared by dot product, so their magnitude counts, unlike with cosine similarity
+    let query = vec![vec![1.0, 1.0], vec![2.0, 0.0]];
+    let document = vec![vec![3.0, 0.0], vec![1.0, 1.0], vec![0.0, -1.0]];
+    assert_eq!(embeddings::max_sim(&query, &document), 3.0 + 6.0);
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_generate_multi_vector() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Stub token vectors: one per word, from a small fixed vocabulary, unknown words pointing elsewhere
+    let stub = StubEmbedder::new(|content| match content {
+        EmbeddingRequestContent::Tokens(text) => text
+            .split_whitespace()
+            .map(|word| match word.to_lowercase().as_str() {
+                "capital" => vec![2.0, 0.0, 0.0],
+                "france" => vec![0.0, 2.0, 0.0],
+                "paris" => vec![3.0, 4.0, 0.0],
+                _ => vec![0.0, 0.0, 3.0],
+            })
+            .collect(),
+        _ => vec![],
+    });
+    let instruction = InstructionTemplate::builder().query_prefix("query: ").build();
+    let embeddings = Embeddings::builder().instruction(instruction).build()?.with_stub(stub);
+
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings/multi_vector");
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), embeddings, SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    let relay_id = ActorId::of::<Relay>("/relay/multi_vector");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let generate = |texts: &[&str], input: InputKind| GenerateMultiVectorEmbeddings {
+        texts: texts.iter().map(|text| text.to_string()).collect(),
+        input,
+        normalize: true,
+    };
+
+    // One normalized vector per token, in the order of the texts
+    let texts = ["Paris capital France", "Rust language"];
+    let generated = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateMultiVectorEmbeddings>(
+            generate(&texts, InputKind::Passage),
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let [matching, unrelated] = &generated.embeddings[..] else {
+        panic!("Expected two texts, got {}", generated.embeddings.len());
+    };
+    assert_eq!(matching, &vec![vec![0.6, 0.8, 0.0], vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]]);
+    assert_eq!(unrelated, &vec![vec![0.0, 0.0, 1.0], vec![0.0, 0.0, 1.0]]);
+
+    // The query gets its instruction prefix, embedded as one more token
+    let generated = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateMultiVectorEmbeddings>(
+            generate(&["capital france"], InputKind::Query),
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let query = &generated.embeddings[0];
+    assert_eq!(query.len(), 3);
+
+    // Each query token finds its exact match in the matching text, the prefix token only in the unrelated one
+    let matching_score = embeddings::max_sim(query, matching);
+    let unrelated_score = embeddings::max_sim(query, unrelated);
+    assert!((matching_score - 2.0).abs() < 1e-6, "Unexpected matching score {}", matching_score);
+    assert!((unrelated_score - 1.0).abs() < 1e-6, "Unexpected unrelated score {}", unrelated_score);
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_instruction_prefixes() -> Result<(), TestError> {
+    let instruction = InstructionTemplate::builder().query_prefix("search_query: ").build();
+
+    // Prefixes are prepended to the respective inputs
+    let texts = vec!["Hello, world!".to_string()];
+    assert_eq!(instruction.apply(InputKind::Query, &texts), vec!["search_query: Hello, world!".to_string()]);
+    assert_eq!(instruction.apply(InputKind::Passage, &texts), texts);
+
+    let engine = Engine::test().await?;
+
+    // Spawn the embeddings actor with the instruction template
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings/instruction");
+    let embeddings = Embeddings::builder().instruction(instruction).build()?;
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), embeddings, SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay/instruction");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // A query is embedded with its prefix applied by the actor
+    let query = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(texts.clone()),
+                input: InputKind::Query,
+                normalize: false,
+            },
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // The same text prefixed manually as a passage, which has an empty prefix
+    let prefixed = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(vec!["search_query: Hello, world!".to_string()]),
+                input: InputKind::Passage,
+                normalize: false,
+            },
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(query.embeddings, prefixed.embeddings, "Query prefix should be applied before embedding");
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_embeddings_generate_clipvit32() -> Result<(), TestError> {
     let engine = Engine::test().await?;
@@ -85,7 +272,7 @@ async fn test_embeddings_generate_clipvit32() -> Result<(), TestError> {
             .table_name_prefix("clipvit32".to_string())
             .model(Model::ClipVitB32Text)
             .image_model(ImageModel::ClipVitB32Vision)
-            .build(),
+            .build()?,
         SpawnOptions::default(),
     )
     .await?;
@@ -109,7 +296,11 @@ async fn test_embeddings_generate_clipvit32() -> Result<(), TestError> {
     // Generate embeddings for the CLIP-ViT-32 embeddings actor
     let clipvit32_embeddings = clipvit32_relay_ctx
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-            GenerateEmbeddings { content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()) },
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+                input: InputKind::Passage,
+                normalize: false,
+            },
             &embeddings_clipvit32_id,
             SendOptions::default(),
         )
@@ -159,7 +350,7 @@ async fn test_embeddings_generate_multiple_types() -> Result<(), TestError> {
             .table_name_prefix("clipvit32".to_string())
             .model(Model::ClipVitB32Text)
             .image_model(ImageModel::ClipVitB32Vision)
-            .build(),
+            .build()?,
         SpawnOptions::default(),
     )
     .await?;
@@ -187,7 +378,11 @@ async fn test_embeddings_generate_multiple_types() -> Result<(), TestError> {
     // Generate embeddings for the Nomic v1.5 embeddings actor
     let nomic_embeddings = nomic_relay_ctx
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-            GenerateEmbeddings { content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()) },
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+                input: InputKind::Passage,
+                normalize: false,
+            },
             &embeddings_nomic_id,
             SendOptions::default(),
         )
@@ -196,7 +391,11 @@ async fn test_embeddings_generate_multiple_types() -> Result<(), TestError> {
     // Generate embeddings for the CLIP-ViT-32 embeddings actor
     let clipvit32_embeddings = clipvit32_relay_ctx
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
-            GenerateEmbeddings { content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()) },
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+                input: InputKind::Passage,
+                normalize: false,
+            },
             &embeddings_clipvit32_id,
             SendOptions::default(),
         )
@@ -270,6 +469,7 @@ async fn test_embeddings_top_k_similarities() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
                 metadata: None,
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -353,6 +553,7 @@ async fn test_embeddings_persistence() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
                 metadata: None,
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -451,6 +652,7 @@ async fn test_embeddings_with_metadata() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
                 metadata: Some(metadata),
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -565,7 +767,7 @@ async fn test_embeddings_pool() -> Result<(), TestError> {
     for (i, chunk) in chunks.iter().enumerate() {
         let embeddings_id = &embeddings_actors[i];
         let future = relay_ctx.send_and_wait_reply::<Embeddings, StoreEmbeddings>(
-            StoreEmbeddings { content: EmbeddingContent::Text(chunk.clone()), metadata: None },
+            StoreEmbeddings { content: EmbeddingContent::Text(chunk.clone()), metadata: None, embeddings: None },
             embeddings_id,
             SendOptions::default(),
         );
@@ -666,6 +868,8 @@ async fn test_image_embeddings_generate() -> Result<(), TestError> {
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
             GenerateEmbeddings {
                 content: EmbeddingContent::Image(image_paths.iter().map(|p| ImageData::Path(p.clone())).collect()),
+                input: InputKind::Passage,
+                normalize: false,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -720,6 +924,7 @@ async fn test_image_embeddings_store_and_search() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Image(image_paths.iter().map(|p| ImageData::Path(p.clone())).collect()),
                 metadata: Some(metadata),
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -776,7 +981,7 @@ async fn test_embeddings_cross_modal_search() -> Result<(), TestError> {
     let (mut embeddings_ctx, mut embeddings_actor) = Actor::spawn(
         engine.clone(),
         embeddings_id.clone(),
-        Embeddings::builder().model(Model::ClipVitB32Text).image_model(ImageModel::ClipVitB32Vision).build(),
+        Embeddings::builder().model(Model::ClipVitB32Text).image_model(ImageModel::ClipVitB32Vision).build()?,
         SpawnOptions::default(),
     )
     .await?;
@@ -803,6 +1008,7 @@ async fn test_embeddings_cross_modal_search() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Image(image_paths.iter().map(|p| ImageData::Path(p.clone())).collect()),
                 metadata: None,
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -876,6 +1082,8 @@ async fn test_embeddings_multiple_images_batch() -> Result<(), TestError> {
         .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
             GenerateEmbeddings {
                 content: EmbeddingContent::Image(image_paths.iter().map(|p| ImageData::Path(p.clone())).collect()),
+                input: InputKind::Passage,
+                normalize: false,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -900,7 +1108,7 @@ async fn test_embeddings_mixed_modal_storage() -> Result<(), TestError> {
     let (mut embeddings_ctx, mut embeddings_actor) = Actor::spawn(
         engine.clone(),
         embeddings_id.clone(),
-        Embeddings::builder().model(Model::ClipVitB32Text).image_model(ImageModel::ClipVitB32Vision).build(),
+        Embeddings::builder().model(Model::ClipVitB32Text).image_model(ImageModel::ClipVitB32Vision).build()?,
         SpawnOptions::default(),
     )
     .await?;
@@ -925,6 +1133,7 @@ async fn test_embeddings_mixed_modal_storage() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Image(vec![ImageData::Path("../assets/images/elephant.jpg".to_string())]),
                 metadata: Some(vec![serde_json::json!({"type": "image"})]),
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -952,6 +1161,7 @@ async fn test_embeddings_mixed_modal_storage() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Text(vec!["an elephant in the wild".to_string()]),
                 metadata: Some(vec![serde_json::json!({"type": "text"})]),
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -1037,6 +1247,7 @@ async fn test_embeddings_source_filtering() -> Result<(), TestError> {
                 StoreEmbeddings {
                     content: EmbeddingContent::Text(texts.iter().map(|t| t.to_string()).collect()),
                     metadata: Some(texts.iter().map(|_| serde_json::json!({"source": source})).collect()),
+                    embeddings: None,
                 },
                 &embeddings_id,
                 SendOptions::default(),
@@ -1144,6 +1355,47 @@ async fn test_embeddings_source_filtering() -> Result<(), TestError> {
     Ok(())
 }
 
+#[test]
+fn test_image_data_validation() -> Result<(), TestError> {
+    // A PNG made of its signature, a header chunk and the end chunk
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    png.extend([0, 0, 0, 13]);
+    png.extend(b"IHDR");
+    png.extend([0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+    png.extend([0x1F, 0x15, 0xC4, 0x89]);
+    png.extend([0, 0, 0, 0]);
+    png.extend(b"IEND");
+    png.extend([0xAE, 0x42, 0x60, 0x82]);
+
+    // Valid bytes become a data URL carrying the detected MIME type
+    assert_eq!(embeddings::ImageFormat::detect(&png), Some(embeddings::ImageFormat::Png));
+    let ImageData::Base64(data_url) = ImageData::from_bytes(png.clone())? else {
+        panic!("Expected the image bytes to be base64 encoded");
+    };
+    assert!(data_url.starts_with("data:image/png;base64,"), "Unexpected data URL: {}", data_url);
+
+    // A file cut off before its end chunk is rejected
+    let truncated = png[..png.len() - 10].to_vec();
+    let result = ImageData::from_bytes(truncated.clone());
+    assert!(matches!(result, Err(EmbeddingsError::TruncatedImage("image/png", _))), "Unexpected result: {:?}", result);
+
+    // A BMP is not a supported format
+    let bmp = b"BM\x36\x00\x00\x00\x00\x00\x00\x00\x36\x00\x00\x00".to_vec();
+    let result = ImageData::from_bytes(bmp);
+    assert!(matches!(result, Err(EmbeddingsError::UnsupportedImageFormat(_))), "Unexpected result: {:?}", result);
+
+    // Files are checked the same way, and referred to by path
+    let dir = tempfile::tempdir()?;
+    let valid_path = dir.path().join("valid.png");
+    std::fs::write(&valid_path, &png)?;
+    assert!(matches!(ImageData::from_path(&valid_path)?, ImageData::Path(path) if path.ends_with("valid.png")));
+    let truncated_path = dir.path().join("truncated.png");
+    std::fs::write(&truncated_path, &truncated)?;
+    assert!(matches!(ImageData::from_path(&truncated_path), Err(EmbeddingsError::TruncatedImage(..))));
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_base64_image_embeddings() -> Result<(), TestError> {
     let engine = Engine::test().await?;
@@ -1187,6 +1439,7 @@ async fn test_base64_image_embeddings() -> Result<(), TestError> {
                         "description": "Base64 elephant image",
                         "format": if i == 0 { "raw" } else { "data_url" }
                     })]),
+                    embeddings: None,
                 },
                 &embeddings_id,
                 SendOptions::default(),
@@ -1281,6 +1534,7 @@ async fn test_without_source_embeddings() -> Result<(), TestError> {
             StoreEmbeddings {
                 content: EmbeddingContent::Text(texts.iter().map(|t| t.to_string()).collect()),
                 metadata: Some(texts.iter().map(|_| serde_json::json!({"source": "/global"})).collect()),
+                embeddings: None,
             },
             &embeddings_id,
             SendOptions::default(),
@@ -1327,3 +1581,353 @@ async fn test_without_source_embeddings() -> Result<(), TestError> {
 
     Ok(())
 }
+
+#[test(tokio::test)]
+async fn test_embeddings_within_threshold() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the embeddings actor
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings");
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), Embeddings::default(), SpawnOptions::default()).await?;
+
+    let table_prefix = embeddings_actor.table_prefix();
+
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // A cluster of vectors close to the first axis, and one vector along another axis
+    let axis = |i: usize| {
+        let mut vector = vec![0.0; NOMIC_V15_EMBEDDING_LENGTH];
+        vector[i] = 1.0;
+        vector
+    };
+    let near = |i: usize| {
+        let mut vector = axis(0);
+        vector[i] = 0.1;
+        vector
+    };
+    let texts = vec!["near 1", "near 2", "near 3", "far"];
+    let vectors = vec![near(1), near(2), near(3), axis(10)];
+
+    let stored = relay_ctx
+        .send_and_wait_reply::<Embeddings, StoreEmbeddings>(
+            StoreEmbeddings {
+                content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+                metadata: None,
+                embeddings: Some(vectors),
+            },
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let source_query = include_str!("../sql/source.surql");
+    let source = "test_source.test";
+    let uri = "test_uri.test";
+
+    engine
+        .db()
+        .lock()
+        .await
+        .query(source_query)
+        .bind(("source", source))
+        .bind(("uri", uri))
+        .bind(("emb_ids", stored.ids))
+        .bind(("prefix", table_prefix))
+        .await
+        .map_err(SystemActorError::from)?;
+
+    // Only the cluster is within the threshold, most similar first
+    let within = embeddings::WithinThreshold::builder()
+        .query(embeddings::Query::Embedding(axis(0)))
+        .threshold(0.9)
+        .sources(vec![source.to_string()])
+        .build();
+
+    let similarities = relay_ctx
+        .send_and_wait_reply::<Embeddings, embeddings::WithinThreshold>(within, &embeddings_id, SendOptions::default())
+        .await?;
+
+    let mut found: Vec<_> = similarities.iter().filter_map(|s| s.text.clone()).collect();
+    found.sort();
+    assert_eq!(found, vec!["near 1", "near 2", "near 3"]);
+    assert!(similarities.iter().all(|s| s.similarity >= 0.9));
+    assert!(similarities.windows(2).all(|pair| pair[0].similarity >= pair[1].similarity));
+
+    // The safety cap bounds the results
+    let within = embeddings::WithinThreshold::builder()
+        .query(embeddings::Query::Embedding(axis(0)))
+        .threshold(0.9)
+        .max_results(2)
+        .sources(vec![source.to_string()])
+        .build();
+
+    let similarities = relay_ctx
+        .send_and_wait_reply::<Embeddings, embeddings::WithinThreshold>(within, &embeddings_id, SendOptions::default())
+        .await?;
+    assert_eq!(similarities.len(), 2);
+
+    // Terminate the actor
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_generate_batch() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings/batch");
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), Embeddings::default(), SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay/batch");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // Every tenth content exceeds the total input length and fails on its own
+    let oversized = vec!["x".repeat(8192); 11];
+    let texts: Vec<String> = (0..100).map(|i| format!("Document {} is about topic number {}.", i, i)).collect();
+    let contents: Vec<EmbeddingContent> = texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            if i % 10 == 3 {
+                EmbeddingContent::Text(oversized.clone())
+            } else {
+                EmbeddingContent::Text(vec![text.clone()])
+            }
+        })
+        .collect();
+
+    let batch = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddingsBatch>(
+            GenerateEmbeddingsBatch { contents, input: InputKind::Passage, max_concurrency: Some(8) },
+            &embeddings_id,
+            SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+        )
+        .await?;
+    assert_eq!(batch.results.len(), 100);
+
+    // The successful contents are embedded in one call to compare against
+    let expected = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(
+                    texts.iter().enumerate().filter(|(i, _)| i % 10 != 3).map(|(_, text)| text.clone()).collect(),
+                ),
+                input: InputKind::Passage,
+                normalize: false,
+            },
+            &embeddings_id,
+            SendOptions::builder().timeout(std::time::Duration::from_secs(200)).build(),
+        )
+        .await?;
+
+    let mut expected = expected.embeddings.into_iter();
+    for (i, result) in batch.results.iter().enumerate() {
+        if i % 10 == 3 {
+            let error = result.as_ref().expect_err("Expected the oversized content to fail");
+            assert!(matches!(error, BatchEmbeddingError::InputSizeTooLarge(_, _)), "Unexpected error: {}", error);
+            continue;
+        }
+        let generated = result.as_ref().unwrap_or_else(|e| panic!("Content {} failed: {}", i, e));
+        assert_eq!(generated.embeddings.len(), 1);
+        let expected = expected.next().unwrap();
+        let distance: f32 = generated.embeddings[0].iter().zip(&expected).map(|(a, b)| (a - b).abs()).sum();
+        assert!(distance < 1e-2, "Content {} is out of order (distance {})", i, distance);
+    }
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_normalize() -> Result<(), TestError> {
+    // A zero vector is left as is rather than turning into NaNs
+    let mut zero = vec![0.0; 4];
+    embeddings::normalize(&mut zero);
+    assert_eq!(zero, vec![0.0; 4]);
+    assert_eq!(embeddings::cosine_similarity(&zero, &[1.0, 0.0, 0.0, 0.0]), 0.0);
+    assert_eq!(embeddings::dot(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+
+    let engine = Engine::test().await?;
+
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings/normalize");
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), Embeddings::default(), SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay/normalize");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let texts = vec!["Hello, world!".to_string(), "The quick brown fox jumps over the lazy dog.".to_string()];
+    let generated = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            GenerateEmbeddings { content: EmbeddingContent::Text(texts), input: InputKind::Passage, normalize: true },
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    for embedding in &generated.embeddings {
+        let magnitude = embeddings::dot(embedding, embedding).sqrt();
+        assert!((magnitude - 1.0).abs() < 1e-4, "Expected a unit vector, got magnitude {}", magnitude);
+        let similarity = embeddings::cosine_similarity(embedding, embedding);
+        assert!((similarity - 1.0).abs() < 1e-4, "Expected a similarity of 1, got {}", similarity);
+    }
+
+    // Unit vectors compare the same by dot product and cosine similarity
+    let (a, b) = (&generated.embeddings[0], &generated.embeddings[1]);
+    assert!((embeddings::dot(a, b) - embeddings::cosine_similarity(a, b)).abs() < 1e-4);
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
+/// The number of embeddings served from a cache so far, across models
+fn cache_hits(cache: &str) -> f64 {
+    let label = format!("cache=\"{}\"", cache);
+    bioma_llm::metrics::render()
+        .lines()
+        .filter(|line| line.starts_with("bioma_embeddings_cache_hits_total{") && line.contains(&label))
+        .filter_map(|line| line.rsplit(' ').next()?.parse::<f64>().ok())
+        .sum()
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_cache() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    let embeddings_id = ActorId::of::<Embeddings>("/embeddings/cache");
+    let embeddings = Embeddings::builder().table_name_prefix("cache".to_string()).build()?.with_cache(true);
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), embeddings, SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay/cache");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+    let generate = |texts: &[&str]| GenerateEmbeddings {
+        content: EmbeddingContent::Text(texts.iter().map(|text| text.to_string()).collect()),
+        input: InputKind::Passage,
+        normalize: false,
+    };
+
+    // The first call generates the embedding, the second one reads it back from the cache
+    let before = cache_hits("store");
+    let first = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            generate(&["Caching spares the embedding model."]),
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(cache_hits("store"), before);
+
+    let second = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            generate(&["Caching spares the embedding model.", "A text seen for the first time."]),
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(cache_hits("store"), before + 1.0, "Expected the repeated text to hit the cache");
+    assert_eq!(second.embeddings.len(), 2);
+    assert_eq!(second.embeddings[0], first.embeddings[0]);
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_embeddings_disk_cache() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+    let cache_dir = tempfile::tempdir()?;
+
+    // Spawns an embeddings actor caching on disk, in the same directory every time
+    let spawn_embeddings = |name: &str| {
+        let engine = engine.clone();
+        let embeddings_id = ActorId::of::<Embeddings>(name);
+        let embeddings = Embeddings::builder()
+            .disk_cache(DiskCacheConfig::builder().dir(cache_dir.path().to_path_buf()).build())
+            .build();
+        async move {
+            let (mut embeddings_ctx, mut embeddings_actor) =
+                Actor::spawn(engine, embeddings_id.clone(), embeddings?, SpawnOptions::default()).await?;
+            let embeddings_handle = tokio::spawn(async move {
+                if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+                    error!("Embeddings actor error: {}", e);
+                }
+            });
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            Ok::<_, TestError>((embeddings_id, embeddings_handle))
+        }
+    };
+
+    let relay_id = ActorId::of::<Relay>("/relay/disk_cache");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+    let generate = || GenerateEmbeddings {
+        content: EmbeddingContent::Text(vec!["Embeddings cached on disk outlive their actor.".to_string()]),
+        input: InputKind::Passage,
+        normalize: false,
+    };
+
+    // The first actor generates the embedding and writes it to the cache directory
+    let before = cache_hits("disk");
+    let (embeddings_id, embeddings_handle) = spawn_embeddings("/embeddings/disk_cache/first").await?;
+    let first = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(generate(), &embeddings_id, SendOptions::default())
+        .await?;
+    assert_eq!(cache_hits("disk"), before);
+    assert_eq!(std::fs::read_dir(cache_dir.path())?.count(), 1);
+
+    // Once the actor is dropped, a new one reads the embedding back from the directory
+    embeddings_handle.abort();
+    let _ = embeddings_handle.await;
+    let (embeddings_id, embeddings_handle) = spawn_embeddings("/embeddings/disk_cache/second").await?;
+    let second = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(generate(), &embeddings_id, SendOptions::default())
+        .await?;
+    assert_eq!(cache_hits("disk"), before + 1.0, "Expected the embedding to be served from the disk cache");
+    assert_eq!(second.embeddings, first.embeddings);
+
+    embeddings_handle.abort();
+
+    Ok(())
+}
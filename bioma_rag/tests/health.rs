@@ -0,0 +1,90 @@
+use bioma_actor::prelude::*;
+use bioma_rag::prelude::*;
+use std::time::Duration;
+use test_log::test;
+use tracing::error;
+use url::Url;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("MarkItDown error: {0}")]
+    MarkitDown(#[from] MarkitDownError),
+    #[error("PDF analyzer error: {0}")]
+    PdfAnalyzer(#[from] PdfAnalyzerError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("URL error: {0}")]
+    Url(#[from] url::ParseError),
+}
+
+#[test(tokio::test)]
+async fn test_health_report() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // A healthy MarkItDown stub
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server.mock("GET", "/").with_status(200).create_async().await;
+    let markitdown_url = Url::parse(&server.url())?;
+
+    // A PDF analyzer pointing at a port nothing listens on
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let pdf_analyzer_url = Url::parse(&format!("http://{}", listener.local_addr()?))?;
+    drop(listener);
+
+    let markitdown_id = ActorId::of::<MarkitDown>("/health/markitdown");
+    let (mut markitdown_ctx, mut markitdown_actor) = Actor::spawn(
+        engine.clone(),
+        markitdown_id.clone(),
+        MarkitDown::builder().markitdown_url(markitdown_url.clone()).build()?,
+        SpawnOptions::default(),
+    )
+    .await?;
+    let markitdown_handle = tokio::spawn(async move {
+        if let Err(e) = markitdown_actor.start(&mut markitdown_ctx).await {
+            error!("MarkitDown actor error: {}", e);
+        }
+    });
+
+    let pdf_analyzer_id = ActorId::of::<PdfAnalyzer>("/health/pdf-analyzer");
+    let (mut pdf_analyzer_ctx, mut pdf_analyzer_actor) = Actor::spawn(
+        engine.clone(),
+        pdf_analyzer_id.clone(),
+        PdfAnalyzer::builder().pdf_analyzer_url(pdf_analyzer_url.clone()).build()?,
+        SpawnOptions::default(),
+    )
+    .await?;
+    let pdf_analyzer_handle = tokio::spawn(async move {
+        if let Err(e) = pdf_analyzer_actor.start(&mut pdf_analyzer_ctx).await {
+            error!("PdfAnalyzer actor error: {}", e);
+        }
+    });
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let report =
+        check_health(&relay_ctx, &[markitdown_id.clone(), pdf_analyzer_id.clone()], Duration::from_secs(10)).await;
+
+    // The statuses come back in the order the actors were given
+    assert!(!report.ok());
+    assert_eq!(report.statuses.len(), 2);
+
+    let (id, markitdown_status) = &report.statuses[0];
+    assert_eq!(id, &markitdown_id);
+    assert!(markitdown_status.ok, "Unexpected status: {:?}", markitdown_status);
+
+    // The failure names the endpoint and what went wrong
+    let (id, pdf_analyzer_status) = &report.statuses[1];
+    assert_eq!(id, &pdf_analyzer_id);
+    assert!(!pdf_analyzer_status.ok);
+    assert!(pdf_analyzer_status.detail.contains(pdf_analyzer_url.as_str()), "{}", pdf_analyzer_status.detail);
+    assert!(pdf_analyzer_status.detail.contains("refused the connection"), "{}", pdf_analyzer_status.detail);
+    assert_eq!(report.failures().count(), 1);
+
+    markitdown_handle.abort();
+    pdf_analyzer_handle.abort();
+    Ok(())
+}
@@ -1,9 +1,9 @@
 use base64::Engine as _;
 use bioma_actor::prelude::Engine as ActorEngine;
 use bioma_actor::prelude::*;
-use bioma_llm::chat::Chat;
+use bioma_llm::chat::{Chat, ChatError};
 use bioma_rag::{
-    indexer::{GlobsContent, ImagesContent, TextsContent},
+    indexer::{GlobsContent, ImagesContent, IndexStatus, IndexedSource, Metadata, TextsContent},
     prelude::*,
     retriever::{ListSources, ListUniqueSources},
 };
@@ -17,6 +17,8 @@ use tracing::error;
 enum TestError {
     #[error("System error: {0}")]
     System(#[from] SystemActorError),
+    #[error("Chat error: {0}")]
+    Chat(#[from] ChatError),
     #[error("Indexer error: {0}")]
     Indexer(#[from] IndexerError),
     #[error("Retriever error: {0}")]
@@ -27,6 +29,26 @@ enum TestError {
     Json(#[from] serde_json::Error),
 }
 
+#[test]
+fn test_indexer_builder() -> Result<(), TestError> {
+    let indexer = Indexer::builder().build()?;
+    assert_eq!(indexer.embedding_concurrency, None);
+    assert_eq!(indexer.markitdown.markitdown_url, MarkitDown::default().markitdown_url);
+    assert_eq!(indexer.pdf_analyzer.pdf_analyzer_url, PdfAnalyzer::default().pdf_analyzer_url);
+
+    let markitdown_url = url::Url::parse("http://markitdown.internal:5001").unwrap();
+    let indexer = Indexer::builder()
+        .markitdown(MarkitDown::builder().markitdown_url(markitdown_url.clone()).build().unwrap())
+        .embedding_concurrency(2)
+        .build()?;
+    assert_eq!(indexer.markitdown.markitdown_url, markitdown_url);
+    assert_eq!(indexer.embedding_concurrency, Some(2));
+
+    assert!(matches!(Indexer::builder().embedding_concurrency(0).build(), Err(IndexerError::InvalidConfig(_))));
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_indexer_basic_text() -> Result<(), TestError> {
     let engine = ActorEngine::test().await?;
@@ -83,7 +105,13 @@ async fn test_indexer_basic_text() -> Result<(), TestError> {
     let index_result = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: globs.clone(), config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: globs.clone(),
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .build(),
             &indexer_id,
             SendOptions::default(),
@@ -108,7 +136,13 @@ async fn test_indexer_basic_text() -> Result<(), TestError> {
     let reindex_result = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: globs.clone(), config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: globs.clone(),
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .build(),
             &indexer_id,
             SendOptions::default(),
@@ -126,6 +160,149 @@ async fn test_indexer_basic_text() -> Result<(), TestError> {
     Ok(())
 }
 
+#[test(tokio::test)]
+async fn test_indexer_prune_missing() -> Result<(), TestError> {
+    let engine = ActorEngine::test().await?;
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("kept.txt"), "This file stays on disk.")?;
+    fs::write(temp_dir.path().join("removed.txt"), "This file is deleted before reindexing.")?;
+
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let index = |prune_missing| {
+        let globs = vec![temp_dir.path().join("*.txt").to_string_lossy().into_owned()];
+        Index::builder()
+            .content(IndexContent::Globs(GlobsContent::builder().globs(globs).prune_missing(prune_missing).build()))
+            .build()
+    };
+
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(false), &indexer_id, SendOptions::default()).await?;
+    assert_eq!(index_result.indexed, 2);
+    assert!(index_result.deleted.is_empty());
+
+    // Reindex once one of the files is gone
+    fs::remove_file(temp_dir.path().join("removed.txt"))?;
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(true), &indexer_id, SendOptions::default()).await?;
+    assert_eq!(index_result.cached, 1);
+    assert_eq!(index_result.deleted.len(), 1);
+    assert!(index_result.deleted[0].uri.ends_with("removed.txt"));
+
+    let sources = relay_ctx
+        .send_and_wait_reply::<Retriever, ListSources>(ListSources, &retriever_id, SendOptions::default())
+        .await?;
+    assert_eq!(sources.sources.len(), 1);
+    assert!(sources.sources[0].uri.ends_with("kept.txt"));
+
+    indexer_handle.abort();
+    retriever_handle.abort();
+    temp_dir.close()?;
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_indexer_incremental() -> Result<(), TestError> {
+    let engine = ActorEngine::test().await?;
+    let temp_dir = tempfile::tempdir()?;
+    let path = |name: &str| temp_dir.path().join(name);
+    fs::write(path("unchanged.txt"), "This file is left as it is.")?;
+    fs::write(path("touched.txt"), "This file is touched without changing its content.")?;
+    fs::write(path("edited.txt"), "This file is edited between the runs.")?;
+    fs::write(path("removed.txt"), "This file is deleted between the runs.")?;
+
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let index = || {
+        let globs = vec![path("*.txt").to_string_lossy().into_owned()];
+        Index::builder()
+            .content(IndexContent::Globs(GlobsContent::builder().globs(globs).incremental(true).build()))
+            .build()
+    };
+
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(), &indexer_id, SendOptions::default()).await?;
+    assert_eq!((index_result.added, index_result.updated, index_result.skipped), (4, 0, 0));
+
+    // Only the edited file is reindexed, the touched one has the same content and the removed one is deleted
+    fs::write(path("edited.txt"), "This file was edited after it was indexed.")?;
+    let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(1);
+    fs::File::options().write(true).open(path("touched.txt"))?.set_modified(modified)?;
+    fs::remove_file(path("removed.txt"))?;
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(), &indexer_id, SendOptions::default()).await?;
+    assert_eq!((index_result.added, index_result.updated, index_result.skipped), (0, 1, 2));
+    assert_eq!(index_result.cached, 2);
+    let reindexed: Vec<&IndexedSource> =
+        index_result.sources.iter().filter(|source| matches!(source.status, IndexStatus::Indexed)).collect();
+    assert_eq!(reindexed.len(), 1);
+    assert!(reindexed[0].uri.ends_with("edited.txt"));
+    assert_eq!(index_result.deleted.len(), 1);
+    assert!(index_result.deleted[0].uri.ends_with("removed.txt"));
+
+    // Nothing changed since
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(), &indexer_id, SendOptions::default()).await?;
+    assert_eq!((index_result.indexed, index_result.skipped), (0, 3));
+    assert!(index_result.deleted.is_empty());
+
+    // A changed file that can no longer be read keeps its previous embeddings
+    fs::write(path("edited.txt"), [0xff, 0xfe, 0xfd])?;
+    let index_result =
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index(), &indexer_id, SendOptions::default()).await?;
+    assert_eq!((index_result.indexed, index_result.skipped), (0, 2));
+    let delete_result = relay_ctx
+        .send_and_wait_reply::<Indexer, DeleteSource>(
+            DeleteSource { sources: vec!["/global".to_string()], delete_from_disk: false },
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(delete_result.deleted_sources.len(), 3);
+    assert!(delete_result.deleted_sources.iter().any(|source| source.uri.ends_with("edited.txt")));
+    assert!(delete_result.deleted_embeddings >= 3);
+
+    indexer_handle.abort();
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_indexer_chunking() -> Result<(), TestError> {
     let engine = ActorEngine::test().await?;
@@ -171,6 +348,7 @@ async fn test_indexer_chunking() -> Result<(), TestError> {
         chunk_capacity: 100..200, // Small chunks for testing
         chunk_overlap: 50,
         chunk_batch_size: 10,
+        chunk_strategy: None,
     };
 
     let index_result = relay_ctx
@@ -252,6 +430,9 @@ async fn test_indexer_delete_source() -> Result<(), TestError> {
                 .content(IndexContent::Globs(GlobsContent {
                     globs: vec![glob_path.clone()],
                     config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
                 }))
                 .build(),
             &indexer_id,
@@ -352,6 +533,9 @@ async fn test_retriever_list_unique_sources() -> Result<(), TestError> {
                 .content(IndexContent::Globs(GlobsContent {
                     globs: vec![source1_path],
                     config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
                 }))
                 .source(source1.clone())
                 .build(),
@@ -370,6 +554,9 @@ async fn test_retriever_list_unique_sources() -> Result<(), TestError> {
                 .content(IndexContent::Globs(GlobsContent {
                     globs: vec![source2_path],
                     config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
                 }))
                 .source(source2.clone())
                 .build(),
@@ -388,7 +575,13 @@ async fn test_retriever_list_unique_sources() -> Result<(), TestError> {
     let index_result3 = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: source3_paths, config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: source3_paths,
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .source(source3.clone())
                 .build(),
             &indexer_id,
@@ -477,7 +670,13 @@ It provides practical examples and use cases.
     let index_result = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: globs.clone(), config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: globs.clone(),
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .summarize(true)
                 .source(source.clone())
                 .build(),
@@ -543,7 +742,13 @@ async fn test_indexer_without_summary() -> Result<(), TestError> {
     let index_result = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: globs.clone(), config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: globs.clone(),
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .summarize(false)
                 .source(source.clone())
                 .build(),
@@ -587,8 +792,7 @@ async fn test_indexer_with_images_summary() -> Result<(), TestError> {
 
     // Spawn the indexer actor with explicit summary configuration
     let mut indexer = Indexer::default();
-    indexer.summary =
-        Summary::builder().chat(Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build()).build();
+    indexer.summary = Summary::builder().chat(Chat::builder().model("llama3.2:3b").build()?).build();
 
     let indexer_id = ActorId::of::<Indexer>("/indexer");
     let (mut indexer_ctx, mut indexer_actor) =
@@ -619,7 +823,13 @@ async fn test_indexer_with_images_summary() -> Result<(), TestError> {
     let index_result = relay_ctx
         .send_and_wait_reply::<Indexer, Index>(
             Index::builder()
-                .content(IndexContent::Globs(GlobsContent { globs: globs.clone(), config: TextChunkConfig::default() }))
+                .content(IndexContent::Globs(GlobsContent {
+                    globs: globs.clone(),
+                    config: TextChunkConfig::default(),
+                    symlinks: SymlinkPolicy::default(),
+                    prune_missing: false,
+                    incremental: false,
+                }))
                 .summarize(true)
                 .source(source.clone())
                 .build(),
@@ -702,6 +912,7 @@ async fn test_indexer_direct_texts() -> Result<(), TestError> {
         chunk_capacity: 100..200, // Small chunks for testing
         chunk_overlap: 50,
         chunk_batch_size: 10,
+        chunk_strategy: None,
     };
 
     let index_result = relay_ctx
@@ -825,7 +1036,15 @@ fn test_index_json_structure() -> Result<(), TestError> {
     let globs_from_builder = Index::builder()
         .content(IndexContent::Globs(GlobsContent {
             globs: vec!["*.txt".to_string(), "*.md".to_string()],
-            config: TextChunkConfig { chunk_capacity: 500..2000, chunk_overlap: 200, chunk_batch_size: 50 },
+            config: TextChunkConfig {
+                chunk_capacity: 500..2000,
+                chunk_overlap: 200,
+                chunk_batch_size: 50,
+                chunk_strategy: None,
+            },
+            symlinks: SymlinkPolicy::default(),
+            prune_missing: false,
+            incremental: false,
         }))
         .source("/test/source".to_string())
         .summarize(true)
@@ -835,7 +1054,12 @@ fn test_index_json_structure() -> Result<(), TestError> {
         .content(IndexContent::Texts(TextsContent {
             texts: vec!["Test content 1".to_string(), "Test content 2".to_string()],
             mime_type: "text/markdown".to_string(),
-            config: TextChunkConfig { chunk_capacity: 500..2000, chunk_overlap: 200, chunk_batch_size: 50 },
+            config: TextChunkConfig {
+                chunk_capacity: 500..2000,
+                chunk_overlap: 200,
+                chunk_batch_size: 50,
+                chunk_strategy: None,
+            },
         }))
         .source("/test/source".to_string())
         .summarize(false)
@@ -896,3 +1120,241 @@ fn test_index_json_structure() -> Result<(), TestError> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+#[test(tokio::test)]
+async fn test_indexer_symlink_loop_follow_no_repeat() -> Result<(), TestError> {
+    let engine = ActorEngine::test().await?;
+    let temp_dir = tempfile::tempdir()?;
+
+    // Create test files, a symlink to one of them and a symlink looping back to the root
+    let root = temp_dir.path().join("docs");
+    fs::create_dir_all(root.join("nested"))?;
+    fs::write(root.join("first.txt"), "This is the first file.")?;
+    fs::write(root.join("nested").join("second.txt"), "This is the second file.")?;
+    std::os::unix::fs::symlink(root.join("first.txt"), root.join("nested").join("first_link.txt"))?;
+    std::os::unix::fs::symlink(&root, root.join("nested").join("loop"))?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // Index the directory, following symlinks without repeating real files
+    let index_result = relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Globs(
+                    GlobsContent::builder()
+                        .globs(vec![root.to_string_lossy().into_owned()])
+                        .symlinks(SymlinkPolicy::FollowNoRepeat)
+                        .build(),
+                ))
+                .build(),
+            &indexer_id,
+            SendOptions::builder().timeout(std::time::Duration::from_secs(60)).build(),
+        )
+        .await?;
+
+    // Each real file is indexed exactly once
+    assert_eq!(index_result.indexed, 2, "Expected each real file to be indexed once");
+    assert_eq!(index_result.cached, 0, "Expected no cached files");
+    assert_eq!(index_result.sources.len(), 2, "Expected 2 sources");
+
+    // Cleanup
+    indexer_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_indexer_parallel_embedding_ordered_store() -> Result<(), TestError> {
+    let engine = ActorEngine::test().await?;
+    let temp_dir = tempfile::tempdir()?;
+
+    // Create a large file that splits into many chunk batches
+    let large_content = (0..400).map(|i| format!("Paragraph {} of a large file.\n\n", i)).collect::<String>();
+    fs::write(temp_dir.path().join("large.md"), large_content)?;
+
+    // Spawn the indexer actor with two batches embedded in parallel
+    let mut indexer = Indexer::default();
+    indexer.embedding_concurrency = Some(2);
+    let model = indexer.embeddings.model.to_string();
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), indexer, SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let globs = vec![temp_dir.path().join("*.md").to_string_lossy().into_owned()];
+    let chunk_config =
+        TextChunkConfig { chunk_capacity: 50..100, chunk_overlap: 0, chunk_batch_size: 4, chunk_strategy: None };
+
+    let (overlapping_before, _) = concurrent_embedding_calls(&model);
+    let index_result = relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Globs(GlobsContent::builder().globs(globs).config(chunk_config).build()))
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(index_result.indexed, 1, "Expected 1 file to be indexed");
+
+    // Every chunk position was stored once, and batches were stored in source order: ids are ULIDs, whose
+    // timestamps never go back from one batch to the next
+    #[derive(serde::Deserialize)]
+    struct StoredChunk {
+        chunk: usize,
+        stored: i64,
+    }
+    let mut stored: Vec<StoredChunk> = engine
+        .db()
+        .lock()
+        .await
+        .query("SELECT metadata.chunk_number AS chunk, time::nano(time::from::ulid(record::id(id))) AS stored FROM type::table($table)")
+        .bind(("table", format!("{}_embedding", model)))
+        .await
+        .map_err(SystemActorError::from)?
+        .take(0)
+        .map_err(SystemActorError::from)?;
+    stored.sort_by_key(|chunk| chunk.chunk);
+    assert!(stored.len() > 8, "Expected many chunks, got {}", stored.len());
+    assert!(stored.iter().enumerate().all(|(position, chunk)| chunk.chunk == position));
+    for (batch, next) in stored.chunks(4).zip(stored.chunks(4).skip(1)) {
+        let batch_stored = batch.iter().map(|chunk| chunk.stored).max().unwrap();
+        assert!(next.iter().all(|chunk| chunk.stored >= batch_stored), "Batches stored out of order");
+    }
+
+    // Embedding calls overlapped, and never more than the two workers ran at once
+    let (overlapping_after, peak) = concurrent_embedding_calls(&model);
+    assert!(overlapping_after > overlapping_before, "Expected embedding calls to overlap");
+    assert!(peak <= 2.0, "Expected at most 2 concurrent embedding calls, got {}", peak);
+
+    // Cleanup
+    indexer_handle.abort();
+    temp_dir.close()?;
+
+    Ok(())
+}
+
+/// Embedding calls of a model that started while another was running, and the most that ran at once
+fn concurrent_embedding_calls(model: &str) -> (f64, f64) {
+    let label = format!("model=\"{}\"", model);
+    let output = bioma_llm::metrics::render();
+    let buckets: Vec<(f64, f64)> = output
+        .lines()
+        .filter(|line| line.starts_with("bioma_embeddings_concurrent_calls_bucket{") && line.contains(&label))
+        .filter_map(|line| {
+            let (_, bound) = line.split_once("le=\"")?;
+            let bound = bound.split('"').next()?.parse::<f64>().ok()?;
+            Some((bound, line.rsplit(' ').next()?.parse::<f64>().ok()?))
+        })
+        .collect();
+    let count = buckets.last().map_or(0.0, |(_, count)| *count);
+    let alone = buckets.first().map_or(0.0, |(_, count)| *count);
+    let peak = buckets.iter().find(|(_, cumulative)| *cumulative == count).map_or(0.0, |(bound, _)| *bound);
+    (count - alone, peak)
+}
+
+#[test(tokio::test)]
+async fn test_indexer_language_detection() -> Result<(), TestError> {
+    let engine = ActorEngine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let documents = [
+        ("/test/language/en", "eng", "The weather is lovely today, so we are going for a long walk in the park."),
+        ("/test/language/fr", "fra", "Il fait très beau aujourd'hui, nous allons faire une longue promenade au parc."),
+    ];
+
+    for (source, expected_language, text) in documents {
+        relay_ctx
+            .send_and_wait_reply::<Indexer, Index>(
+                Index::builder()
+                    .content(IndexContent::Texts(TextsContent::builder().texts(vec![text.to_string()]).build()))
+                    .source(source.to_string())
+                    .language_detection(LanguageDetection::default())
+                    .build(),
+                &indexer_id,
+                SendOptions::default(),
+            )
+            .await?;
+
+        let retrieved = relay_ctx
+            .send_and_wait_reply::<Retriever, RetrieveContext>(
+                RetrieveContext::builder()
+                    .query(RetrieveQuery::Text(text.to_string()))
+                    .sources(vec![source.to_string()])
+                    .build(),
+                &retriever_id,
+                SendOptions::default(),
+            )
+            .await?;
+
+        assert_eq!(retrieved.context.len(), 1, "Expected the chunk of {}", source);
+        let Some(Metadata::Text(metadata)) = &retrieved.context[0].metadata else {
+            panic!("Expected text metadata for {}", source);
+        };
+        assert_eq!(metadata.language.as_deref(), Some(expected_language));
+    }
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use bioma_actor::prelude::*;
+use bioma_rag::prelude::*;
+use tracing::error;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("MarkItDown error: {0}")]
+    MarkitDown(#[from] MarkitDownError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("URL error: {0}")]
+    Url(#[from] url::ParseError),
+}
+
+#[test]
+fn test_parse_to_tree_heading_and_list() -> Result<(), MarkitDownError> {
+    let input = "# Migration report\n\nObserved species:\n\n- Swallow\n- Stork\n- Crane\n";
+
+    let tree = markitdown::parse_to_tree(input)?;
+    let children = tree.children();
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0], DocumentNode::Heading { level: 1, text: "Migration report".to_string() });
+    assert_eq!(children[1], DocumentNode::Paragraph { text: "Observed species:".to_string() });
+    assert_eq!(
+        children[2],
+        DocumentNode::List {
+            ordered: false,
+            items: vec!["Swallow".to_string(), "Stork".to_string(), "Crane".to_string()]
+        }
+    );
+
+    // The string output is a serialization of the tree
+    assert_eq!(tree.to_markdown(), input.trim_end());
+    assert_eq!(markitdown::parse_to_tree(&tree.to_markdown())?, tree);
+
+    Ok(())
+}
+
+#[test]
+fn test_markitdown_builder() -> Result<(), MarkitDownError> {
+    let markitdown = MarkitDown::builder().build()?;
+    assert_eq!(markitdown.markitdown_url.as_str(), "http://localhost:5001/");
+
+    let url = url::Url::parse("https://markitdown.internal").unwrap();
+    let markitdown = MarkitDown::builder().markitdown_url(url.clone()).build()?;
+    assert_eq!(markitdown.markitdown_url, url);
+
+    let url = url::Url::parse("file:///tmp/markitdown").unwrap();
+    assert!(matches!(MarkitDown::builder().markitdown_url(url).build(), Err(MarkitDownError::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markitdown_returns_converter_output() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Details the tree does not keep must survive in the markdown reply
+    let text_content = "Steps:\n\n3. Ring\n4. Release\n\n| Species | Count |\n|:--------|------:|\n| Stork | 12 |\n\n```rust\nlet x = 1;\n```\n\n<div align=\"center\">Map</div>\n";
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/convert")
+        .with_status(200)
+        .with_body(serde_json::json!({ "text_content": text_content }).to_string())
+        .create_async()
+        .await;
+
+    let temp_dir = tempfile::tempdir()?;
+    let file_path = temp_dir.path().join("report.docx");
+    std::fs::write(&file_path, b"report")?;
+
+    let markitdown_id = ActorId::of::<MarkitDown>("/markitdown");
+    let (mut markitdown_ctx, mut markitdown_actor) = Actor::spawn(
+        engine.clone(),
+        markitdown_id.clone(),
+        MarkitDown::builder().markitdown_url(url::Url::parse(&server.url())?).build()?,
+        SpawnOptions::default(),
+    )
+    .await?;
+    let markitdown_handle = tokio::spawn(async move {
+        if let Err(e) = markitdown_actor.start(&mut markitdown_ctx).await {
+            error!("MarkitDown actor error: {}", e);
+        }
+    });
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let markdown = relay_ctx
+        .send_and_wait_reply::<MarkitDown, AnalyzeMCFile>(
+            AnalyzeMCFile { file_path: file_path.clone() },
+            &markitdown_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(markdown, text_content);
+
+    // The tree is available on its own message
+    let tree = relay_ctx
+        .send_and_wait_reply::<MarkitDown, AnalyzeMCFileTree>(
+            AnalyzeMCFileTree { file_path },
+            &markitdown_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let children = tree.children();
+    assert_eq!(children.len(), 5);
+    assert_eq!(
+        children[1],
+        DocumentNode::List { ordered: true, items: vec!["Ring".to_string(), "Release".to_string()] }
+    );
+    assert!(matches!(&children[2], DocumentNode::Table { header, rows } if header.len() == 2 && rows.len() == 1));
+    assert_eq!(
+        children[3],
+        DocumentNode::CodeBlock { language: Some("rust".to_string()), code: "let x = 1;".to_string() }
+    );
+
+    markitdown_handle.abort();
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use bioma_actor::prelude::*;
+use bioma_llm::prelude::*;
+use bioma_rag::indexer::TextsContent;
+use bioma_rag::prelude::*;
+use std::pin::Pin;
+use std::sync::Arc;
+use test_log::test;
+use tracing::error;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("Chat error: {0}")]
+    Chat(#[from] ChatError),
+}
+
+/// Answers every request with the same response
+#[derive(Debug)]
+struct StubBackend {
+    response: ChatMessageResponse,
+}
+
+impl ChatBackend for StubBackend {
+    fn chat<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        Box::pin(async move { Ok(self.response.clone()) })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        _request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        Box::pin(async move {
+            let stream = futures::stream::iter([Ok::<_, ChatError>(self.response.clone())]);
+            Ok(Box::pin(stream) as ChatResponseStream)
+        })
+    }
+}
+
+/// The value of the first series starting with `prefix` in the exposition text
+fn value(output: &str, prefix: &str) -> f64 {
+    output
+        .lines()
+        .find(|line| line.starts_with(prefix))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| panic!("Missing series {} in:\n{}", prefix, output))
+}
+
+#[test(tokio::test)]
+async fn test_metrics_after_workload() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    let indexer_id = ActorId::of::<Indexer>("/metrics/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    let retriever_id = ActorId::of::<Retriever>("/metrics/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"Paris"},"done":true,"total_duration":1000,"load_duration":10,"prompt_eval_count":12,"prompt_eval_duration":100,"eval_count":5,"eval_duration":200}"#;
+    let backend = StubBackend { response: serde_json::from_str(body).unwrap() };
+    let chat_id = ActorId::of::<Chat>("/metrics/chat");
+    let chat = Chat::builder().model("stub").backend(Arc::new(backend)).build()?;
+    let (mut chat_ctx, mut chat_actor) =
+        Actor::spawn(engine.clone(), chat_id.clone(), chat, SpawnOptions::default()).await?;
+    let chat_handle = tokio::spawn(async move {
+        if let Err(e) = chat_actor.start(&mut chat_ctx).await {
+            error!("Chat actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // Index, retrieve and chat once each
+    let source = "/test/metrics".to_string();
+    let texts = vec!["Paris is the capital of France.".to_string(), "Rust is a systems language.".to_string()];
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+    relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("What is the capital of France?".to_string()))
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let messages = ChatMessages::builder().messages(vec![ChatMessage::user("Capital of France?".to_string())]).build();
+    relay_ctx.send_and_wait_reply::<Chat, ChatMessages>(messages, &chat_id, SendOptions::default()).await?;
+
+    let output = bioma_llm::metrics::render();
+
+    // Chat requests and tokens by model
+    assert_eq!(value(&output, "bioma_chat_requests_total{model=\"stub\"}"), 1.0);
+    assert_eq!(value(&output, "bioma_chat_prompt_tokens_total{model=\"stub\"}"), 12.0);
+    assert_eq!(value(&output, "bioma_chat_completion_tokens_total{model=\"stub\"}"), 5.0);
+    assert_eq!(value(&output, "bioma_chat_request_duration_seconds_count{model=\"stub\"}"), 1.0);
+    assert!(output.contains("# TYPE bioma_chat_request_duration_seconds histogram"));
+
+    // Indexed texts and chunks, then embeddings for the chunks and the query
+    assert!(value(&output, "bioma_indexer_files_total{status=\"indexed\"}") >= 1.0);
+    let chunks = value(&output, "bioma_indexer_chunks_total");
+    assert!(chunks >= 2.0, "Expected both texts to be chunked, got {}", chunks);
+    assert!(value(&output, "bioma_embeddings_generated_total{model=") >= chunks + 1.0);
+
+    // Retrieval stages and rerank batches
+    assert_eq!(value(&output, "bioma_retriever_queries_total"), 1.0);
+    assert_eq!(value(&output, "bioma_retriever_stage_duration_seconds_count{stage=\"search\"}"), 1.0);
+    assert_eq!(value(&output, "bioma_retriever_stage_duration_seconds_count{stage=\"rerank\"}"), 1.0);
+    assert_eq!(value(&output, "bioma_rerank_batch_size_count"), 1.0);
+    assert!(value(&output, "bioma_rerank_batch_size_sum") >= 1.0);
+    assert_eq!(value(&output, "bioma_rerank_batch_size_bucket{le=\"+Inf\"}"), 1.0);
+
+    indexer_handle.abort();
+    retriever_handle.abort();
+    chat_handle.abort();
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use bioma_rag::pdf_analyzer::{FormField, FormFieldType};
+use bioma_rag::prelude::*;
+
+#[test]
+fn test_pdf_analyzer_builder() -> Result<(), PdfAnalyzerError> {
+    let pdf_analyzer = PdfAnalyzer::builder().build()?;
+    assert_eq!(pdf_analyzer.pdf_analyzer_url.as_str(), "http://localhost:5060/");
+
+    let url = url::Url::parse("http://pdf.internal:5060").unwrap();
+    let pdf_analyzer = PdfAnalyzer::builder().pdf_analyzer_url(url.clone()).build()?;
+    assert_eq!(pdf_analyzer.pdf_analyzer_url, url);
+
+    let url = url::Url::parse("ftp://pdf.internal").unwrap();
+    assert!(matches!(PdfAnalyzer::builder().pdf_analyzer_url(url).build(), Err(PdfAnalyzerError::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_pdf_form_fields() -> Result<(), PdfAnalyzerError> {
+    let form = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/test_files/filled_form.pdf");
+    let fields = pdf_analyzer::form_fields(form)?;
+
+    let field = |name: &str, field_type, value: &str| FormField {
+        name: name.to_string(),
+        field_type,
+        value: Some(value.to_string()),
+    };
+    assert_eq!(
+        fields,
+        vec![
+            field("applicant.name", FormFieldType::Text, "Ada Lovelace"),
+            field("applicant.email", FormFieldType::Text, "ada@example.com"),
+            field("subscribe", FormFieldType::Checkbox, "Yes"),
+            field("newsletter", FormFieldType::Checkbox, "Off"),
+            field("contact", FormFieldType::Radio, "Phone"),
+        ]
+    );
+
+    // A PDF without a form has no fields
+    let fields = pdf_analyzer::form_fields(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/files/README.pdf"))?;
+    assert!(fields.is_empty());
+
+    Ok(())
+}
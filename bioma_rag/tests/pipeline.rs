@@ -0,0 +1,273 @@
+use bioma_actor::prelude::*;
+use bioma_llm::prelude::*;
+use bioma_rag::indexer::TextsContent;
+use bioma_rag::prelude::*;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use test_log::test;
+use tracing::error;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("Chat error: {0}")]
+    Chat(#[from] ChatError),
+}
+
+/// Answers every request with the same response, keeping the last request sent
+#[derive(Debug)]
+struct StubBackend {
+    response: ChatMessageResponse,
+    request: Arc<Mutex<Option<ChatRequest>>>,
+}
+
+impl ChatBackend for StubBackend {
+    fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatMessageResponse, ChatError>> + Send + 'a>> {
+        *self.request.lock().unwrap() = Some(request);
+        Box::pin(async move { Ok(self.response.clone()) })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: ChatRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<ChatResponseStream, ChatError>> + Send + 'a>> {
+        *self.request.lock().unwrap() = Some(request);
+        Box::pin(async move {
+            let stream = futures::stream::iter([Ok::<_, ChatError>(self.response.clone())]);
+            Ok(Box::pin(stream) as ChatResponseStream)
+        })
+    }
+}
+
+#[test(tokio::test)]
+async fn test_rag_pipeline_ask() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer and retriever actors
+    let indexer_id = ActorId::of::<Indexer>("/pipeline/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    let retriever_id = ActorId::of::<Retriever>("/pipeline/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    // Spawn a chat actor with a stubbed backend
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"The Eiffel Tower was built for the 1889 World's Fair [1]."},"done":true,"total_duration":2000000,"load_duration":1000,"prompt_eval_count":120,"prompt_eval_duration":100000,"eval_count":14,"eval_duration":500000}"#;
+    let request = Arc::new(Mutex::new(None));
+    let backend = StubBackend { response: serde_json::from_str(body).unwrap(), request: request.clone() };
+    let chat_id = ActorId::of::<Chat>("/pipeline/chat");
+    let (mut chat_ctx, mut chat_actor) = Actor::spawn(
+        engine.clone(),
+        chat_id.clone(),
+        Chat::builder().backend(Arc::new(backend)).build()?,
+        SpawnOptions::default(),
+    )
+    .await?;
+    let chat_handle = tokio::spawn(async move {
+        if let Err(e) = chat_actor.start(&mut chat_ctx).await {
+            error!("Chat actor error: {}", e);
+        }
+    });
+
+    // Spawn the pipeline, with a rerank actor that does not exist to exercise the fallback
+    let pipeline_id = ActorId::of::<RagPipeline>("/pipeline");
+    let pipeline = RagPipeline::builder()
+        .retriever(retriever_id.clone())
+        .rerank(ActorId::of::<Rerank>("/pipeline/missing-rerank"))
+        .chat(chat_id.clone())
+        .stage_timeout(std::time::Duration::from_secs(30))
+        .build();
+    let (mut pipeline_ctx, mut pipeline_actor) =
+        Actor::spawn(engine.clone(), pipeline_id.clone(), pipeline, SpawnOptions::default()).await?;
+    let pipeline_handle = tokio::spawn(async move {
+        if let Err(e) = pipeline_actor.start(&mut pipeline_ctx).await {
+            error!("RagPipeline actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/pipeline".to_string();
+    let texts = vec![
+        "The Eiffel Tower in Paris was built for the 1889 World's Fair.".to_string(),
+        "Rust is a systems programming language.".to_string(),
+    ];
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let ask = Ask::builder()
+        .question("When was the Eiffel Tower built?".to_string())
+        .session_id("session".to_string())
+        .filters(vec![source])
+        .build();
+    let answer = relay_ctx
+        .send_and_wait_reply::<RagPipeline, Ask>(
+            ask,
+            &pipeline_id,
+            SendOptions::builder().timeout(std::time::Duration::from_secs(120)).build(),
+        )
+        .await?;
+
+    assert_eq!(answer.answer, "The Eiffel Tower was built for the 1889 World's Fair [1].");
+    let usage = answer.usage.expect("Expected the usage of the chat reply");
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (120, 14));
+
+    // The retrieved chunks are cited by their markers, with their scores
+    assert!(!answer.citations.is_empty());
+    assert_eq!(answer.citations[0].marker, 1);
+    assert!(answer.citations[0].text.as_deref().is_some_and(|text| text.contains("Eiffel Tower")));
+    assert!(answer.citations.iter().all(|citation| citation.score.is_some()));
+
+    // The missing rerank actor falls back to the retrieval order
+    assert_eq!(answer.warnings.len(), 1, "Unexpected warnings: {:?}", answer.warnings);
+    assert!(answer.warnings[0].starts_with("Rerank failed"));
+    assert!(answer.latencies.rerank.is_some());
+    assert!(answer.latencies.total >= answer.latencies.retrieve + answer.latencies.chat);
+
+    // The prompt holds the numbered chunks followed by the question
+    let request = request.lock().unwrap().take().expect("Expected a chat request");
+    let system = &request.messages[0];
+    assert_eq!(system.role, MessageRole::System);
+    assert!(system.content.contains("[1]"), "Missing citation marker in {}", system.content);
+    assert!(system.content.contains("The Eiffel Tower in Paris was built"), "Missing chunk in {}", system.content);
+    let question = request.messages.last().unwrap();
+    assert_eq!(question.role, MessageRole::User);
+    assert_eq!(question.content, "When was the Eiffel Tower built?");
+
+    indexer_handle.abort();
+    retriever_handle.abort();
+    chat_handle.abort();
+    pipeline_handle.abort();
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_rag_pipeline_context_template() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer and retriever actors
+    let indexer_id = ActorId::of::<Indexer>("/pipeline/template/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    let retriever_id = ActorId::of::<Retriever>("/pipeline/template/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    // Spawn a chat actor with a stubbed backend
+    let body = r#"{"model":"stub","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"In 1889 (doc 1)."},"done":true}"#;
+    let request = Arc::new(Mutex::new(None));
+    let backend = StubBackend { response: serde_json::from_str(body).unwrap(), request: request.clone() };
+    let chat_id = ActorId::of::<Chat>("/pipeline/template/chat");
+    let (mut chat_ctx, mut chat_actor) = Actor::spawn(
+        engine.clone(),
+        chat_id.clone(),
+        Chat::builder().backend(Arc::new(backend)).build()?,
+        SpawnOptions::default(),
+    )
+    .await?;
+    let chat_handle = tokio::spawn(async move {
+        if let Err(e) = chat_actor.start(&mut chat_ctx).await {
+            error!("Chat actor error: {}", e);
+        }
+    });
+
+    // Spawn the pipeline with a custom citation format
+    let context_template =
+        ContextTemplate::builder().marker("<doc {n}>").source(" from {source}").separator("\n</doc>\n").build();
+    let pipeline_id = ActorId::of::<RagPipeline>("/pipeline/template");
+    let pipeline = RagPipeline::builder()
+        .retriever(retriever_id.clone())
+        .chat(chat_id.clone())
+        .prompt_template("Cite documents as (doc n).\n{context}".to_string())
+        .context_template(context_template)
+        .stage_timeout(std::time::Duration::from_secs(30))
+        .build();
+    let (mut pipeline_ctx, mut pipeline_actor) =
+        Actor::spawn(engine.clone(), pipeline_id.clone(), pipeline, SpawnOptions::default()).await?;
+    let pipeline_handle = tokio::spawn(async move {
+        if let Err(e) = pipeline_actor.start(&mut pipeline_ctx).await {
+            error!("RagPipeline actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/pipeline/template".to_string();
+    let text = "The Eiffel Tower in Paris was built for the 1889 World's Fair.".to_string();
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(vec![text.clone()]).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let ask = Ask::builder().question("When was the Eiffel Tower built?".to_string()).filters(vec![source]).build();
+    let answer = relay_ctx
+        .send_and_wait_reply::<RagPipeline, Ask>(
+            ask,
+            &pipeline_id,
+            SendOptions::builder().timeout(std::time::Duration::from_secs(120)).build(),
+        )
+        .await?;
+    assert_eq!(answer.citations.len(), 1);
+    assert_eq!(answer.citations[0].marker, 1);
+
+    // The injected context uses the custom markers, source and separator instead of the default format
+    let request = request.lock().unwrap().take().expect("Expected a chat request");
+    let system = &request.messages[0].content;
+    let expected = format!("Cite documents as (doc n).\n<doc 1> from /test/pipeline/template\n{}\n</doc>\n", text);
+    assert_eq!(system, &expected);
+    assert!(!system.contains("[1]"), "Unexpected default marker in {}", system);
+
+    indexer_handle.abort();
+    retriever_handle.abort();
+    chat_handle.abort();
+    pipeline_handle.abort();
+    Ok(())
+}
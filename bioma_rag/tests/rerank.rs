@@ -10,6 +10,69 @@ enum TestError {
     Rerank(#[from] RerankError),
 }
 
+#[test]
+fn test_rerank_builder() {
+    assert!(matches!(Rerank::builder().build().model, rerank::Model::BGERerankerV2M3));
+    let model = Rerank::builder().model(rerank::Model::BGERerankerBase).build().model;
+    assert!(matches!(model, rerank::Model::BGERerankerBase));
+}
+
+#[test(tokio::test)]
+async fn test_rerank_empty_candidates() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Serve requests without initializing the rerank task, so any backend call fails
+    let rerank_id = ActorId::of::<Rerank>("/rerank/empty");
+    let (mut rerank_ctx, mut rerank_actor) =
+        Actor::spawn(engine.clone(), rerank_id.clone(), Rerank::default(), SpawnOptions::default()).await?;
+    let rerank_handle = tokio::spawn(async move {
+        let mut stream = rerank_ctx.recv().await?;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(rank_texts) = frame.is::<RankTexts>() {
+                let _ = rerank_actor.reply(&mut rerank_ctx, &rank_texts, &frame).await;
+            }
+        }
+        Ok::<_, SystemActorError>(())
+    });
+
+    let relay_id = ActorId::of::<Relay>("/relay/rerank/empty");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+    let rank = |query: &str, texts: Vec<String>| RankTexts::builder().query(query.to_string()).texts(texts).build();
+
+    // No candidates rank to nothing without reaching the backend
+    let ranked = relay_ctx
+        .send_and_wait_reply::<Rerank, RankTexts>(rank("What is Rust?", vec![]), &rerank_id, SendOptions::default())
+        .await?;
+    assert!(ranked.texts.is_empty());
+
+    // Candidates do need the backend
+    let result = relay_ctx
+        .send_and_wait_reply::<Rerank, RankTexts>(
+            rank("What is Rust?", vec!["Rust is a language".to_string()]),
+            &rerank_id,
+            SendOptions::default(),
+        )
+        .await;
+    let error = result.expect_err("Expected the uninitialized backend to fail");
+    assert!(error.to_string().contains("not initialized"), "Unexpected error: {}", error);
+
+    // An empty query is rejected up front
+    let result = relay_ctx
+        .send_and_wait_reply::<Rerank, RankTexts>(
+            rank("  ", vec!["Rust is a language".to_string()]),
+            &rerank_id,
+            SendOptions::default(),
+        )
+        .await;
+    let error = result.expect_err("Expected an empty query to be rejected");
+    assert!(error.to_string().contains("query is empty"), "Unexpected error: {}", error);
+
+    rerank_handle.abort();
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_rerank_basic() -> Result<(), TestError> {
     let engine = Engine::test().await?;
@@ -184,3 +247,65 @@ async fn test_rerank_pool() -> Result<(), TestError> {
 
     Ok(())
 }
+
+#[test(tokio::test)]
+async fn test_rerank_fused_scores() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the rerank actor
+    let rerank_id = ActorId::of::<Rerank>("/rerank");
+    let (mut rerank_ctx, mut rerank_actor) =
+        Actor::spawn(engine.clone(), rerank_id.clone(), Rerank::default(), SpawnOptions::default()).await?;
+
+    let rerank_handle = tokio::spawn(async move {
+        if let Err(e) = rerank_actor.start(&mut rerank_ctx).await {
+            eprintln!("Rerank actor error: {}", e);
+        }
+    });
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let query = "What is the capital of France?";
+    let texts = vec!["Paris is the capital of France.".to_string(), "Bananas are rich in potassium.".to_string()];
+
+    // Pure rerank puts the relevant text first
+    let ranked_texts = relay_ctx
+        .send_and_wait_reply::<Rerank, RankTexts>(
+            RankTexts::builder().query(query.to_string()).texts(texts.clone()).build(),
+            &rerank_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(ranked_texts.texts[0].index, 0);
+    assert!(ranked_texts.texts.iter().all(|text| text.fused_score.is_none()));
+
+    // A retrieval signal that strongly favors the other text overrides it with a high fusion weight
+    let fused_texts = relay_ctx
+        .send_and_wait_reply::<Rerank, RankTexts>(
+            RankTexts::builder()
+                .query(query.to_string())
+                .texts(texts.clone())
+                .retrieval_scores(vec![0.0, 1.0])
+                .fusion_weight(0.9)
+                .build(),
+            &rerank_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(fused_texts.texts[0].index, 1, "Expected the fused ordering to differ from pure rerank");
+    for text in &fused_texts.texts {
+        let retrieval_score = [0.0, 1.0][text.index];
+        let fused_score = text.fused_score.expect("Expected a fused score");
+        assert!((fused_score - (0.1 * text.score + 0.9 * retrieval_score)).abs() < 1e-5);
+    }
+
+    // Terminate the actor
+    rerank_handle.abort();
+
+    Ok(())
+}
@@ -2,7 +2,9 @@ use bioma_actor::prelude::*;
 use bioma_rag::prelude::*;
 use bioma_rag::{
     embeddings::EmbeddingsError,
-    indexer::{CodeLanguage, ContentSource, ImageDimensions, ImageMetadata, Metadata, TextMetadata, TextType},
+    indexer::{
+        CodeLanguage, ContentSource, ImageDimensions, ImageMetadata, Metadata, TextMetadata, TextType, TextsContent,
+    },
     retriever::{Context, RetrievedContext},
 };
 use test_log::test;
@@ -12,12 +14,45 @@ use tracing::error;
 enum TestError {
     #[error("System error: {0}")]
     System(#[from] SystemActorError),
+    #[error("Indexer error: {0}")]
+    Indexer(#[from] IndexerError),
     #[error("Retriever error: {0}")]
     Retriever(#[from] RetrieverError),
     #[error("Embeddings error: {0}")]
     Embeddings(#[from] EmbeddingsError),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Db(#[from] surrealdb::Error),
+}
+
+/// Number of requests the embeddings actor `embeddings_id` was sent, a query being embedded by its search
+async fn embedding_requests(engine: &Engine, embeddings_id: &ActorId) -> Result<usize, TestError> {
+    let mut results = engine
+        .db()
+        .lock()
+        .await
+        .query("SELECT VALUE name FROM message WHERE rx = $rx")
+        .bind(("rx", embeddings_id.record_id()))
+        .await?;
+    let names: Vec<String> = results.take(0)?;
+    Ok(names.iter().filter(|name| !name.contains("Health")).count())
+}
+
+#[test]
+fn test_retriever_builder() -> Result<(), TestError> {
+    let retriever = Retriever::builder().build()?;
+    assert!(retriever.query_cache.is_none());
+    assert_eq!(retriever.embeddings.model, Embeddings::default().model);
+
+    let query_cache = QueryCacheConfig::builder().capacity(16).build();
+    let retriever = Retriever::builder().query_cache(query_cache).build()?;
+    assert_eq!(retriever.query_cache.map(|config| config.capacity), Some(16));
+
+    let query_cache = QueryCacheConfig::builder().capacity(0).build();
+    assert!(matches!(Retriever::builder().query_cache(query_cache).build(), Err(RetrieverError::InvalidConfig(_))));
+
+    Ok(())
 }
 
 #[test(tokio::test)]
@@ -34,7 +69,12 @@ async fn test_retrieved_context_formatting() -> Result<(), TestError> {
                 metadata: Some(Metadata::Text(TextMetadata {
                     content: TextType::Code(CodeLanguage::Rust),
                     chunk_number: 1,
+                    language: None,
+                    location: None,
+                    headings: vec![],
                 })),
+                below_threshold: false,
+                score: None,
             },
             Context {
                 text: None,
@@ -46,8 +86,13 @@ async fn test_retrieved_context_formatting() -> Result<(), TestError> {
                     modified: 1234567890,
                     created: 1234567800,
                 })),
+                below_threshold: false,
+                score: None,
             },
         ],
+        next_cursor: None,
+        cached: false,
+        empty_store: false,
     };
 
     // Test to_markdown format
@@ -111,7 +156,7 @@ async fn test_retrieved_context_formatting() -> Result<(), TestError> {
     assert_eq!(parsed_json, expected_json, "JSON structure mismatch");
 
     // Test empty context
-    let empty_context = RetrievedContext { context: vec![] };
+    let empty_context = RetrievedContext { context: vec![], next_cursor: None, cached: false, empty_store: false };
     let empty_json = empty_context.to_json();
     let parsed_empty: serde_json::Value =
         serde_json::from_str(&empty_json).expect("Failed to parse empty context JSON");
@@ -120,3 +165,1011 @@ async fn test_retrieved_context_formatting() -> Result<(), TestError> {
 
     Ok(())
 }
+
+#[test(tokio::test)]
+async fn test_retriever_min_results_relaxation() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/min_results".to_string();
+    let query = "The capital of France is Paris.".to_string();
+
+    // Only the first text is close enough to the query to pass a strict threshold
+    let texts = vec![
+        query.clone(),
+        "Rust is a systems programming language.".to_string(),
+        "Photosynthesis converts light into chemical energy.".to_string(),
+    ];
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // Without a threshold nothing is filtered out
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder().query(RetrieveQuery::Text(query.clone())).sources(vec![source.clone()]).build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(retrieved.context.len(), 3, "Expected every context without a threshold");
+    assert!(retrieved.context.iter().all(|c| !c.below_threshold));
+
+    // Without min_results only the passing context is returned
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text(query.clone()))
+                .threshold(0.95)
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(retrieved.context.len(), 1, "Expected only the passing context");
+    assert!(!retrieved.context[0].below_threshold);
+
+    // With min_results the threshold is relaxed and the extra contexts are flagged
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text(query))
+                .threshold(0.95)
+                .min_results(3)
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(retrieved.context.len(), 3, "Expected the threshold to be relaxed up to min_results");
+    assert_eq!(
+        retrieved.context.iter().filter(|c| c.below_threshold).count(),
+        2,
+        "Expected the relaxed contexts to be flagged"
+    );
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_cursor_pagination() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/pagination".to_string();
+    let query = "Which city is the capital of France?".to_string();
+
+    let texts = vec![
+        "Paris is the capital of France.".to_string(),
+        "France is a country in western Europe.".to_string(),
+        "The Eiffel Tower is in Paris.".to_string(),
+    ];
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts.clone()).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // Fetch the first page
+    let mut page = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text(query.clone()))
+                .limit(1)
+                .paginate(true)
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let mut retrieved: Vec<String> = page.context.iter().filter_map(|c| c.text.clone()).collect();
+
+    // Write a better match for the query while paginating
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(vec![query.clone()]).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // Follow the cursor until the last page
+    while let Some(cursor) = page.next_cursor.clone() {
+        page = relay_ctx
+            .send_and_wait_reply::<Retriever, RetrieveContext>(
+                RetrieveContext::builder()
+                    .query(RetrieveQuery::Text(query.clone()))
+                    .limit(1)
+                    .cursor(cursor)
+                    .sources(vec![source.clone()])
+                    .build(),
+                &retriever_id,
+                SendOptions::default(),
+            )
+            .await?;
+        retrieved.extend(page.context.iter().filter_map(|c| c.text.clone()));
+    }
+
+    // Every original text is returned exactly once and the later write is not visible
+    assert_eq!(retrieved.len(), texts.len(), "Expected no duplicate or skipped results");
+    for text in &texts {
+        assert_eq!(retrieved.iter().filter(|t| *t == text).count(), 1, "Expected {} exactly once", text);
+    }
+    assert!(!retrieved.contains(&query), "Expected writes after the first page to be hidden");
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_cursor_pagination_ties() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // Identical texts, each in its own document, all score the same
+    let source = "/test/retriever/pagination_ties".to_string();
+    let query = "Where do storks go in autumn?".to_string();
+    let texts = vec!["Storks migrate to Africa in autumn.".to_string(); 5];
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts.clone()).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // Pages of two split the ties
+    let mut uris = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = relay_ctx
+            .send_and_wait_reply::<Retriever, RetrieveContext>(
+                RetrieveContext::builder()
+                    .query(RetrieveQuery::Text(query.clone()))
+                    .limit(2)
+                    .paginate(true)
+                    .maybe_cursor(cursor)
+                    .sources(vec![source.clone()])
+                    .build(),
+                &retriever_id,
+                SendOptions::default(),
+            )
+            .await?;
+        let scores: Vec<Option<f32>> = page.context.iter().map(|context| context.score).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] == pair[1]), "Expected equal scores, got {:?}", scores);
+        uris.extend(page.context.iter().filter_map(|context| context.source.as_ref()).map(|source| source.uri.clone()));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    // Every document is returned exactly once, none is lost at a page boundary
+    let mut unique = uris.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(uris.len(), texts.len(), "Expected every tied context once, got {:?}", uris);
+    assert_eq!(unique.len(), texts.len(), "Expected no duplicate contexts, got {:?}", uris);
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_empty_query() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/empty_query".to_string();
+    let texts = vec!["Paris is the capital of France.".to_string(), "The Eiffel Tower is in Paris.".to_string()];
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // By default an empty query is rejected
+    let result = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("".to_string()))
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await;
+
+    let error = result.expect_err("Expected an empty query to be rejected");
+    assert!(error.to_string().contains("Empty query"), "Unexpected error: {}", error);
+    let embeddings_id = ActorId::of::<Embeddings>("/retriever/embeddings");
+    assert_eq!(embedding_requests(&engine, &embeddings_id).await?, 0, "Expected no query embedding");
+
+    // With EmptyResult no contexts are returned, even though a search would have forced some through min_results
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("   ".to_string()))
+                .min_results(2)
+                .empty_query(EmptyQueryPolicy::EmptyResult)
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert!(retrieved.context.is_empty(), "Expected no contexts for an empty query");
+    assert!(retrieved.next_cursor.is_none());
+    assert_eq!(embedding_requests(&engine, &embeddings_id).await?, 0, "Expected no query embedding");
+
+    // A non-empty query is embedded
+    relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("Where is the Eiffel Tower?".to_string()))
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(embedding_requests(&engine, &embeddings_id).await?, 1);
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_max_per_source() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/max_per_source".to_string();
+    let query = "What is Paris, the capital of France, known for?".to_string();
+
+    // One document about Paris split into many chunks, and several shorter documents on other topics
+    let texts = vec![
+        [
+            "Paris is the capital of France and its largest city.",
+            "Paris is known for the Eiffel Tower, built for the 1889 World's Fair.",
+            "Paris is home to the Louvre, the most visited museum in the world.",
+            "Paris is famous for its cafes, bakeries and French cuisine.",
+            "Paris is crossed by the Seine, lined with bridges and bookstalls.",
+            "Paris hosts the cathedral of Notre-Dame on the Ile de la Cite.",
+        ]
+        .join("\n\n"),
+        "Berlin is the capital of Germany.".to_string(),
+        "Rome is the capital of Italy.".to_string(),
+        "Madrid is the capital of Spain.".to_string(),
+        "Rust is a systems programming language.".to_string(),
+        "Photosynthesis converts light into chemical energy.".to_string(),
+    ];
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(
+                    TextsContent::builder()
+                        .texts(texts)
+                        .config(TextChunkConfig::builder().chunk_capacity(40..80).chunk_overlap(0).build())
+                        .build(),
+                ))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let distinct_uris = |retrieved: &RetrievedContext| {
+        let mut uris: Vec<_> = retrieved.context.iter().filter_map(|c| c.source.as_ref().map(|s| &s.uri)).collect();
+        uris.sort();
+        uris.dedup();
+        uris.len()
+    };
+
+    // Without a cap the top chunks all come from the Paris document
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text(query.clone()))
+                .limit(5)
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(retrieved.context.len(), 5);
+    assert_eq!(distinct_uris(&retrieved), 1, "Expected the top chunks to come from a single document");
+
+    // With a cap of one per source, other documents backfill the results
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text(query))
+                .limit(5)
+                .max_per_source(1)
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    assert_eq!(retrieved.context.len(), 5);
+    assert_eq!(distinct_uris(&retrieved), 5, "Expected one chunk from each of 5 distinct documents");
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_merge_adjacent() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // A document split into chunks sharing part of their text
+    let source = "/test/retriever/merge_adjacent".to_string();
+    let text = "The Eiffel Tower was built for the 1889 World's Fair. It was designed by the engineering company \
+                of Gustave Eiffel. It was the tallest structure in the world until 1930."
+        .to_string();
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(
+                    TextsContent::builder()
+                        .texts(vec![text.clone()])
+                        .config(TextChunkConfig::builder().chunk_capacity(40..60).chunk_overlap(20).build())
+                        .build(),
+                ))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let retrieve = |merge_adjacent: bool| {
+        RetrieveContext::builder()
+            .query(RetrieveQuery::Text("Who built the Eiffel Tower?".to_string()))
+            .threshold(-1.0)
+            .sources(vec![source.clone()])
+            .merge_adjacent(merge_adjacent)
+            .build()
+    };
+
+    // Every chunk is retrieved, repeating the overlapping text
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve(false), &retriever_id, SendOptions::default())
+        .await?;
+    assert!(retrieved.context.len() > 1, "Expected the document to be split into several chunks");
+    let total: usize = retrieved.context.iter().filter_map(|context| context.text.as_ref()).map(String::len).sum();
+    assert!(total > text.len(), "Expected the chunks to overlap");
+
+    // Merged, they read as the document itself
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve(true), &retriever_id, SendOptions::default())
+        .await?;
+    assert_eq!(retrieved.context.len(), 1);
+    assert_eq!(retrieved.context[0].text.as_deref(), Some(text.as_str()));
+    let Some(Metadata::Text(TextMetadata { chunk_number, location: Some(location), .. })) =
+        &retrieved.context[0].metadata
+    else {
+        panic!("Expected the location of the passage in {:?}", retrieved.context[0].metadata);
+    };
+    assert_eq!(*chunk_number, 0);
+    assert_eq!(location.byte_range, 0..text.len());
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_chunk_locations() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // A file split into several chunks, with a multi-byte character before the one retrieved
+    let temp_dir = tempfile::tempdir()?;
+    let path = temp_dir.path().join("cities.txt");
+    let content = [
+        "Zürich is the largest city in Switzerland.",
+        "Berlin is the capital of Germany.",
+        "Rome is the capital of Italy.",
+        "The Eiffel Tower in Paris was built for the 1889 World's Fair.",
+        "Madrid is the capital of Spain.",
+    ]
+    .join("\n\n");
+    std::fs::write(&path, &content)?;
+
+    let source = "/test/retriever/chunk_locations".to_string();
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Globs(
+                    GlobsContent::builder()
+                        .globs(vec![path.to_string_lossy().into_owned()])
+                        .config(TextChunkConfig::builder().chunk_capacity(20..70).chunk_overlap(0).build())
+                        .build(),
+                ))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("When was the Eiffel Tower built?".to_string()))
+                .limit(1)
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let context = retrieved.context.first().expect("Expected a retrieved chunk");
+    let text = context.text.as_deref().expect("Expected a text chunk");
+    assert!(text.contains("Eiffel Tower"), "Unexpected chunk: {}", text);
+
+    let Some(Metadata::Text(TextMetadata { location: Some(location), .. })) = &context.metadata else {
+        panic!("Expected the chunk location in {:?}", context.metadata);
+    };
+
+    // The offsets slice the file back to the exact chunk
+    let file = std::fs::read(&location.path)?;
+    assert_eq!(location.path, std::path::absolute(&path)?);
+    assert_eq!(&file[location.byte_range.clone()], text.as_bytes());
+
+    // The lines span the chunk, starting from 1
+    let line_of = |byte: usize| content[..byte].matches('\n').count() + 1;
+    assert_eq!(location.start_line, line_of(location.byte_range.start));
+    assert_eq!(location.end_line, line_of(location.byte_range.end - 1));
+    assert!(location.start_line <= 7 && location.end_line >= 7);
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_markdown_chunk_strategy() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // A document with nested headings, and a code block that looks like one
+    let temp_dir = tempfile::tempdir()?;
+    let path = temp_dir.path().join("guide.md");
+    let sections = [
+        "Bioma builds agents out of actors.",
+        "# Guide\n\nHow to get started.",
+        "## Install\n\nAdd the crate.\n\n```sh\n# cargo add bioma_actor\n```",
+        "### Linux\n\nInstall SurrealDB first.",
+        "## Usage\n\nSpawn an actor.",
+        "# Reference\n\nEvery message type.",
+    ];
+    let content = sections.join("\n\n");
+    std::fs::write(&path, &content)?;
+
+    let source = "/test/retriever/markdown_chunks".to_string();
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Globs(
+                    GlobsContent::builder()
+                        .globs(vec![path.to_string_lossy().into_owned()])
+                        .config(TextChunkConfig::builder().chunk_strategy(ChunkStrategy::Markdown).build())
+                        .build(),
+                ))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Text("How do I install Bioma?".to_string()))
+                .threshold(-1.0)
+                .limit(10)
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    let mut chunks = retrieved
+        .context
+        .iter()
+        .map(|context| match &context.metadata {
+            Some(Metadata::Text(metadata)) => (metadata.clone(), context.text.clone().unwrap_or_default()),
+            other => panic!("Expected text metadata, got {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    chunks.sort_by_key(|(metadata, _)| metadata.chunk_number);
+
+    // Every section is its own chunk, located in the file
+    let texts = chunks.iter().map(|(_, text)| text.as_str()).collect::<Vec<_>>();
+    assert_eq!(texts, sections);
+    for (metadata, text) in &chunks {
+        let location = metadata.location.as_ref().expect("Expected the chunk location");
+        assert_eq!(&content[location.byte_range.clone()], text.as_str());
+    }
+
+    // Each chunk carries the headings enclosing it, outermost first
+    let headings = chunks.iter().map(|(metadata, _)| metadata.headings.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        headings,
+        vec![
+            vec![],
+            vec!["Guide"],
+            vec!["Guide", "Install"],
+            vec!["Guide", "Install", "Linux"],
+            vec!["Guide", "Usage"],
+            vec!["Reference"],
+        ]
+    );
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_query_cache() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor with query caching
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let retriever = Retriever::builder().query_cache(QueryCacheConfig::default()).build()?;
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), retriever, SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/cache".to_string();
+    let index = |texts: Vec<String>| {
+        Index::builder()
+            .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+            .source(source.clone())
+            .build()
+    };
+    let retrieve = RetrieveContext::builder()
+        .query(RetrieveQuery::Text("Which city is the capital of France?".to_string()))
+        .sources(vec![source.clone()])
+        .build();
+
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            index(vec!["Paris is the capital of France.".to_string()]),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // The first query is retrieved, the identical one after it is served from the cache
+    let first = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve.clone(), &retriever_id, SendOptions::default())
+        .await?;
+    assert!(!first.cached);
+    let second = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve.clone(), &retriever_id, SendOptions::default())
+        .await?;
+    assert!(second.cached);
+    let texts = |retrieved: &RetrievedContext| retrieved.context.iter().map(|c| c.text.clone()).collect::<Vec<_>>();
+    assert_eq!(texts(&second), texts(&first));
+
+    // Changing the index invalidates the cached query
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            index(vec!["Lyon is the third largest city of France.".to_string()]),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let third = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(retrieve.clone(), &retriever_id, SendOptions::default())
+        .await?;
+    assert!(!third.cached, "Expected the query to miss the cache after the index changed");
+    assert_eq!(third.context.len(), 2);
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_retriever_empty_store() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // Spawn a relay actor
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let retrieve = |source: &str| {
+        RetrieveContext::builder()
+            .query(RetrieveQuery::Text("What is the capital of France?".to_string()))
+            .min_results(1)
+            .sources(vec![source.to_string()])
+            .build()
+    };
+
+    // Nothing is indexed yet, which is not an error
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            retrieve("/test/retriever/empty_store"),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert!(retrieved.context.is_empty());
+    assert!(retrieved.empty_store, "Expected the store to be reported as empty");
+    assert!(retrieved.next_cursor.is_none());
+
+    // Once something is indexed, a source without matches is no longer an empty store
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(
+                    TextsContent::builder().texts(vec!["Paris is the capital of France.".to_string()]).build(),
+                ))
+                .source("/test/retriever/empty_store/other".to_string())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            retrieve("/test/retriever/empty_store"),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert!(retrieved.context.is_empty());
+    assert!(!retrieved.empty_store, "Expected no matches rather than an empty store");
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use bioma_actor::prelude::*;
+use bioma_rag::indexer::{IndexStatus, TextsContent};
+use bioma_rag::prelude::*;
+use std::time::Duration;
+use test_log::test;
+use tracing::error;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+    #[error("Database error: {0}")]
+    Db(#[from] surrealdb::Error),
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StoredSource {
+    uri: String,
+}
+
+async fn stored_sources(engine: &Engine) -> Result<Vec<StoredSource>, TestError> {
+    let mut results = engine.db().lock().await.query("SELECT id.uri AS uri FROM source;").await?;
+    Ok(results.take(0)?)
+}
+
+#[test(tokio::test)]
+async fn test_shutdown_during_indexing() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    let indexer_id = ActorId::of::<Indexer>("/shutdown/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    let mut coordinator = ShutdownCoordinator::new(engine.shutdown_signal().clone());
+    coordinator.track("indexer", indexer_handle);
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    // Index enough texts for shutdown to land in the middle of the run
+    let total = 50;
+    let texts: Vec<String> =
+        (0..total).map(|i| format!("Document {} describes the migration patterns of bird species {}.", i, i)).collect();
+    let index = Index::builder()
+        .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+        .source("/test/shutdown".to_string())
+        .build();
+    let indexing = tokio::spawn(async move {
+        relay_ctx.send_and_wait_reply::<Indexer, Index>(index, &indexer_id, SendOptions::default()).await
+    });
+
+    // Wait until indexing is under way
+    while stored_sources(&engine).await?.is_empty() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let deadline = Duration::from_secs(30);
+    let report = coordinator.shutdown(deadline).await;
+    assert!(report.clean(), "Indexer should drain before the deadline: {:?}", report);
+    assert!(report.elapsed < deadline);
+
+    // The run stopped early and replied with what it indexed
+    let indexed = indexing.await.unwrap()?;
+    assert!(indexed.interrupted);
+    assert!(indexed.indexed > 0 && indexed.indexed < total, "Indexed {} of {}", indexed.indexed, total);
+    assert!(indexed.sources.iter().all(|source| matches!(source.status, IndexStatus::Indexed)));
+
+    // Every source in the store was fully indexed, and nothing else
+    let mut stored: Vec<String> = stored_sources(&engine).await?.into_iter().map(|source| source.uri).collect();
+    let mut replied: Vec<String> = indexed.sources.iter().map(|source| source.uri.clone()).collect();
+    stored.sort();
+    replied.sort();
+    assert_eq!(stored, replied);
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use base64::Engine as _;
 use bioma_actor::prelude::*;
-use bioma_llm::chat::Chat;
+use bioma_llm::chat::{Chat, ChatError};
+use bioma_llm::testing::{MockOllama, MockResponse};
 use bioma_rag::{prelude::*, summary::SummarizeContent};
 use std::fs;
 use tempfile;
@@ -11,6 +12,8 @@ use tracing::error;
 enum TestError {
     #[error("System error: {0}")]
     System(#[from] SystemActorError),
+    #[error("Chat error: {0}")]
+    Chat(#[from] ChatError),
     #[error("Summary error: {0}")]
     Summary(#[from] SummaryError),
     #[error("IO error: {0}")]
@@ -41,7 +44,7 @@ It provides practical examples and use cases.
     let (mut summary_ctx, mut summary_actor) = Actor::spawn(
         engine.clone(),
         summary_id.clone(),
-        Summary::builder().chat(Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build()).build(),
+        Summary::builder().chat(Chat::builder().model("llama3.2:3b").build()?).build(),
         SpawnOptions::default(),
     )
     .await?;
@@ -98,7 +101,7 @@ async fn test_image_summarization() -> Result<(), TestError> {
     let (mut summary_ctx, mut summary_actor) = Actor::spawn(
         engine.clone(),
         summary_id.clone(),
-        Summary::builder().chat(Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build()).build(),
+        Summary::builder().chat(Chat::builder().model("llama3.2:3b").build()?).build(),
         SpawnOptions::default(),
     )
     .await?;
@@ -146,7 +149,7 @@ async fn test_summary_error_handling() -> Result<(), TestError> {
     let (mut summary_ctx, mut summary_actor) = Actor::spawn(
         engine.clone(),
         summary_id.clone(),
-        Summary::builder().chat(Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build()).build(),
+        Summary::builder().chat(Chat::builder().model("llama3.2:3b").build()?).build(),
         SpawnOptions::default(),
     )
     .await?;
@@ -227,7 +230,7 @@ async fn test_long_text_truncation() -> Result<(), TestError> {
     let (mut summary_ctx, mut summary_actor) = Actor::spawn(
         engine.clone(),
         summary_id.clone(),
-        Summary::builder().chat(Chat::builder().model(std::borrow::Cow::Borrowed("llama3.2:3b")).build()).build(),
+        Summary::builder().chat(Chat::builder().model("llama3.2:3b").build()?).build(),
         SpawnOptions::default(),
     )
     .await?;
@@ -266,3 +269,49 @@ async fn test_long_text_truncation() -> Result<(), TestError> {
     summary_handle.abort();
     Ok(())
 }
+
+#[test(tokio::test)]
+async fn test_summary_mock_ollama() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+    let ollama = MockOllama::start().await?;
+    ollama.set_default("/api/chat", MockResponse::chat("A short note about mock servers."));
+
+    let summary_id = ActorId::of::<Summary>("/summary");
+    let chat = Chat::builder().model("mock").endpoint(ollama.url().clone()).build()?;
+    let (mut summary_ctx, mut summary_actor) =
+        Actor::spawn(engine.clone(), summary_id.clone(), Summary::builder().chat(chat).build(), SpawnOptions::default())
+            .await?;
+
+    let summary_handle = tokio::spawn(async move {
+        if let Err(e) = summary_actor.start(&mut summary_ctx).await {
+            error!("Summary actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let response = relay_ctx
+        .send_and_wait_reply::<Summary, Summarize>(
+            Summarize {
+                content: SummarizeContent::Text("Mock servers stand in for real ones in tests.".to_string()),
+                uri: "mock.md".to_string(),
+            },
+            &summary_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // The summary wraps the model's answer, and the text reached the model
+    assert!(response.summary.contains("A short note about mock servers."));
+    assert!(response.summary.contains("mock.md"));
+    let requests = ollama.requests_to("/api/chat");
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].body["messages"].to_string().contains("Mock servers stand in for real ones in tests."));
+
+    summary_handle.abort();
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use bioma_actor::prelude::*;
+use bioma_rag::indexer::TextsContent;
+use bioma_rag::prelude::*;
+use test_log::test;
+use tracing::error;
+
+#[derive(thiserror::Error, Debug)]
+enum TestError {
+    #[error("System error: {0}")]
+    System(#[from] SystemActorError),
+}
+
+/// The number of embeddings generated so far, across models
+fn generated_embeddings() -> f64 {
+    bioma_llm::metrics::render()
+        .lines()
+        .filter(|line| line.starts_with("bioma_embeddings_generated_total{"))
+        .filter_map(|line| line.rsplit(' ').next()?.parse::<f64>().ok())
+        .sum()
+}
+
+#[test(tokio::test)]
+async fn test_retriever_vector_query() -> Result<(), TestError> {
+    let engine = Engine::test().await?;
+
+    // Spawn the indexer actor
+    let indexer_id = ActorId::of::<Indexer>("/vector/indexer");
+    let (mut indexer_ctx, mut indexer_actor) =
+        Actor::spawn(engine.clone(), indexer_id.clone(), Indexer::default(), SpawnOptions::default()).await?;
+    let indexer_handle = tokio::spawn(async move {
+        if let Err(e) = indexer_actor.start(&mut indexer_ctx).await {
+            error!("Indexer actor error: {}", e);
+        }
+    });
+
+    // Spawn the retriever actor
+    let retriever_id = ActorId::of::<Retriever>("/vector/retriever");
+    let (mut retriever_ctx, mut retriever_actor) =
+        Actor::spawn(engine.clone(), retriever_id.clone(), Retriever::default(), SpawnOptions::default()).await?;
+    let retriever_handle = tokio::spawn(async move {
+        if let Err(e) = retriever_actor.start(&mut retriever_ctx).await {
+            error!("Retriever actor error: {}", e);
+        }
+    });
+
+    // Spawn an embeddings actor to compute the query vector
+    let embeddings_id = ActorId::of::<Embeddings>("/vector/embeddings");
+    let (mut embeddings_ctx, mut embeddings_actor) =
+        Actor::spawn(engine.clone(), embeddings_id.clone(), Embeddings::default(), SpawnOptions::default()).await?;
+    let embeddings_handle = tokio::spawn(async move {
+        if let Err(e) = embeddings_actor.start(&mut embeddings_ctx).await {
+            error!("Embeddings actor error: {}", e);
+        }
+    });
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let relay_id = ActorId::of::<Relay>("/relay");
+    let (relay_ctx, _relay_actor) =
+        Actor::spawn(engine.clone(), relay_id.clone(), Relay, SpawnOptions::default()).await?;
+
+    let source = "/test/retriever/vector".to_string();
+    let expected = "The Eiffel Tower is a wrought-iron lattice tower in Paris.".to_string();
+    let texts = vec![
+        "Rust is a systems programming language focused on safety.".to_string(),
+        expected.clone(),
+        "Photosynthesis converts light energy into chemical energy.".to_string(),
+    ];
+    relay_ctx
+        .send_and_wait_reply::<Indexer, Index>(
+            Index::builder()
+                .content(IndexContent::Texts(TextsContent::builder().texts(texts).build()))
+                .source(source.clone())
+                .build(),
+            &indexer_id,
+            SendOptions::default(),
+        )
+        .await?;
+
+    // Embed the expected chunk the same way it was stored
+    let generated = relay_ctx
+        .send_and_wait_reply::<Embeddings, GenerateEmbeddings>(
+            GenerateEmbeddings {
+                content: EmbeddingContent::Text(vec![expected.clone()]),
+                input: InputKind::Passage,
+                normalize: false,
+            },
+            &embeddings_id,
+            SendOptions::default(),
+        )
+        .await?;
+    let vector = generated.embeddings[0].clone();
+
+    // The vector is searched as is, so the embedding model is not called
+    let before = generated_embeddings();
+    let retrieved = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Vector(vector.clone()))
+                .threshold(0.0)
+                .sources(vec![source.clone()])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await?;
+    assert_eq!(generated_embeddings(), before, "Expected no embeddings to be generated for a vector query");
+
+    assert_eq!(retrieved.context.len(), 3);
+    assert_eq!(retrieved.context[0].text.as_deref(), Some(expected.as_str()));
+    let scores: Vec<f32> = retrieved.context.iter().filter_map(|context| context.score).collect();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]), "Expected contexts ordered by similarity: {:?}", scores);
+
+    // A vector of another dimension cannot be compared against the store
+    let result = relay_ctx
+        .send_and_wait_reply::<Retriever, RetrieveContext>(
+            RetrieveContext::builder()
+                .query(RetrieveQuery::Vector(vector[..vector.len() / 2].to_vec()))
+                .sources(vec![source])
+                .build(),
+            &retriever_id,
+            SendOptions::default(),
+        )
+        .await;
+    let error = result.expect_err("Expected a vector of the wrong dimension to be rejected");
+    assert!(error.to_string().contains("dimension mismatch"), "Unexpected error: {}", error);
+
+    // Cleanup
+    indexer_handle.abort();
+    retriever_handle.abort();
+    embeddings_handle.abort();
+
+    Ok(())
+}